@@ -0,0 +1,132 @@
+// Latency the audio preprocessing adds between releasing the hotkey and the
+// model starting, stage by stage and composed.
+//
+//   cargo bench --bench preprocessing
+//
+// The stages, in the order a recording goes through them: downmixing the
+// interleaved input to mono (in the audio callback), the ring hand-off from the
+// callback to the recording buffer, and resampling to the model's 16 kHz at
+// either quality. Inputs are 5, 30 and 120 s of synthetic stereo audio at 44.1
+// and 48 kHz, the usual rates of microphones that can't record at 16 kHz.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use esponquen::audio::{MODEL_SAMPLE_RATE, ResampleQuality, mono_samples, resample_with};
+use esponquen::capture::SampleRing;
+use std::hint::black_box;
+
+const RATES: [u32; 2] = [44_100, 48_000];
+const LENGTHS_SECS: [u32; 3] = [5, 30, 120];
+
+// Frames per audio callback, a typical buffer size
+const CALLBACK_FRAMES: usize = 512;
+
+/// Interleaved stereo: a voice-like harmonic series over a little noise
+fn synthetic(rate: u32, secs: u32) -> Vec<f32> {
+    let mut noise: u32 = 0x2545_f491;
+    (0..rate * secs)
+        .flat_map(|i| {
+            let t = i as f32 / rate as f32;
+            let voice: f32 = (1..=8)
+                .map(|harmonic| {
+                    let frequency = 140.0 * harmonic as f32;
+                    (t * frequency * std::f32::consts::TAU).sin() * 0.3 / harmonic as f32
+                })
+                .sum();
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            let hiss = (noise as f32 / u32::MAX as f32 - 0.5) * 0.02;
+            [voice + hiss, voice - hiss]
+        })
+        .collect()
+}
+
+/// The recording a callback-sized stream of `interleaved` ends up as
+fn record(interleaved: &[f32], ring: &SampleRing) -> Vec<f32> {
+    let mut recording = Vec::with_capacity(interleaved.len() / 2);
+    for buffer in interleaved.chunks(CALLBACK_FRAMES * 2) {
+        ring.push(mono_samples(buffer, 2, None));
+        ring.drain_into(&mut recording);
+    }
+    recording
+}
+
+fn inputs() -> impl Iterator<Item = (BenchmarkId, u32, Vec<f32>)> {
+    RATES.into_iter().flat_map(|rate| {
+        LENGTHS_SECS.into_iter().map(move |secs| {
+            let id = BenchmarkId::new(format!("{} Hz", rate), format!("{} s", secs));
+            (id, rate, synthetic(rate, secs))
+        })
+    })
+}
+
+fn downmix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("downmix");
+    for (id, _, input) in inputs() {
+        group.throughput(Throughput::Elements(input.len() as u64 / 2));
+        group.bench_with_input(id, &input, |b, input| {
+            b.iter(|| mono_samples(black_box(input), 2, None).collect::<Vec<f32>>())
+        });
+    }
+    group.finish();
+}
+
+fn ring_hand_off(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ring hand-off");
+    for (id, rate, input) in inputs() {
+        let mono: Vec<f32> = mono_samples(&input, 2, None).collect();
+        let ring = SampleRing::new(rate as usize);
+        group.throughput(Throughput::Elements(mono.len() as u64));
+        group.bench_with_input(id, &mono, |b, mono| {
+            b.iter(|| {
+                let mut recording = Vec::with_capacity(mono.len());
+                for buffer in black_box(mono).chunks(CALLBACK_FRAMES) {
+                    ring.push(buffer.iter().copied());
+                    ring.drain_into(&mut recording);
+                }
+                recording
+            })
+        });
+    }
+    group.finish();
+}
+
+fn resampling(c: &mut Criterion) {
+    for (name, quality) in [
+        ("resample high", ResampleQuality::High),
+        ("resample fast", ResampleQuality::Fast),
+    ] {
+        let mut group = c.benchmark_group(name);
+        for (id, rate, input) in inputs() {
+            let mono: Vec<f32> = mono_samples(&input, 2, None).collect();
+            group.throughput(Throughput::Elements(mono.len() as u64));
+            group.bench_with_input(id, &mono, |b, mono| {
+                b.iter(|| resample_with(black_box(mono), rate, MODEL_SAMPLE_RATE, quality))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    for (id, rate, input) in inputs() {
+        let ring = SampleRing::new(rate as usize);
+        group.throughput(Throughput::Elements(input.len() as u64 / 2));
+        group.bench_with_input(id, &input, |b, input| {
+            b.iter(|| {
+                let recording = record(black_box(input), &ring);
+                resample_with(&recording, rate, MODEL_SAMPLE_RATE, ResampleQuality::High)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // The 120 s inputs take about a second per run with the sinc resampler
+    config = Criterion::default().sample_size(10);
+    targets = downmix, ring_hand_off, resampling, pipeline
+}
+criterion_main!(benches);
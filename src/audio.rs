@@ -0,0 +1,425 @@
+// Raw PCM decoding and sample rate conversion for the recognizer
+
+use crate::config::{
+    DEFAULT_NORMALIZE_MAX_GAIN_DB, DEFAULT_NORMALIZE_MIN_GAIN_DB, DEFAULT_NORMALIZE_TARGET_DB,
+};
+use crate::get_resample_quality;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Sample rate the model expects
+pub const MODEL_SAMPLE_RATE: u32 = 16_000;
+
+// Zero crossings of the sinc kernel on each side; more is sharper but slower.
+// The cost is linear in it: 16 takes about 10 ms per second of 48 kHz audio on
+// a laptop CPU (benches/preprocessing.rs), a tenth of typical inference. 8
+// halves that but lets 9 kHz fold back to 7 kHz at only -17 dB, against -39 dB.
+const SINC_ZERO_CROSSINGS: usize = 16;
+
+// Kernel table entries per zero crossing, interpolated in between
+const SINC_TABLE_RESOLUTION: usize = 256;
+
+// Blackman-windowed sinc from 0 to SINC_ZERO_CROSSINGS, computed once
+static SINC_TABLE: Lazy<Vec<f32>> = Lazy::new(|| {
+    (0..=SINC_ZERO_CROSSINGS * SINC_TABLE_RESOLUTION + 1)
+        .map(|i| {
+            let x = i as f64 / SINC_TABLE_RESOLUTION as f64;
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let r = (x / SINC_ZERO_CROSSINGS as f64).min(1.0);
+            let window = 0.42 + 0.5 * (PI * r).cos() + 0.08 * (2.0 * PI * r).cos();
+            (sinc * window) as f32
+        })
+        .collect()
+});
+
+/// How audio is converted to the model's sample rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleQuality {
+    /// Windowed-sinc filtering, which keeps frequencies above the new rate from
+    /// folding back into speech
+    ///
+    /// The default: about 1.2 s for a 2-minute recording at 48 kHz, small next to
+    /// the model, and the rest of the preprocessing takes milliseconds.
+    #[default]
+    High,
+    /// Linear interpolation, about a hundred times cheaper, for low-power devices
+    Fast,
+}
+
+/// Bringing a recording's peak to a target level before transcription
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalization {
+    pub enabled: bool,
+    /// Level the loudest sample is brought to, in dBFS
+    pub target_db: f32,
+    /// Most the recording is turned down, as a negative gain in dB
+    pub min_gain_db: f32,
+    /// Most the recording is turned up, so near-silence isn't blown up into noise
+    pub max_gain_db: f32,
+}
+
+impl Normalization {
+    /// Linear gain for a recording whose loudest sample is `peak`, within the bounds
+    pub fn gain(&self, peak: f32) -> f32 {
+        let needed_db = if peak > 0.0 {
+            self.target_db - 20.0 * peak.log10()
+        } else {
+            self.max_gain_db
+        };
+        // max/min rather than clamp, which panics on bounds in the wrong order
+        let gain_db = needed_db.max(self.min_gain_db).min(self.max_gain_db);
+        10f32.powf(gain_db / 20.0)
+    }
+}
+
+impl Default for Normalization {
+    /// Off
+    fn default() -> Self {
+        Normalization {
+            enabled: false,
+            target_db: DEFAULT_NORMALIZE_TARGET_DB,
+            min_gain_db: DEFAULT_NORMALIZE_MIN_GAIN_DB,
+            max_gain_db: DEFAULT_NORMALIZE_MAX_GAIN_DB,
+        }
+    }
+}
+
+/// Scale mono audio so its peak reaches `normalization`'s target, within its
+/// gain bounds, returning the linear gain applied
+pub fn normalize(samples: &mut [f32], normalization: &Normalization) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let gain = normalization.gain(peak);
+    for sample in samples.iter_mut() {
+        *sample = (*sample * gain).clamp(-1.0, 1.0);
+    }
+    gain
+}
+
+/// Encoding of raw PCM samples (mono, little-endian)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcmFormat {
+    /// 32-bit float in -1.0..1.0
+    F32,
+    /// 16-bit signed integer
+    I16,
+}
+
+impl PcmFormat {
+    /// Look up a format by name ("f32" or "i16")
+    pub fn parse(name: &str) -> Option<PcmFormat> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "f32" => Some(PcmFormat::F32),
+            "i16" => Some(PcmFormat::I16),
+            _ => None,
+        }
+    }
+
+    /// Size of one sample in bytes
+    pub fn sample_size(self) -> usize {
+        match self {
+            PcmFormat::F32 => 4,
+            PcmFormat::I16 => 2,
+        }
+    }
+}
+
+/// Decode raw PCM bytes into f32 samples, rejecting a trailing partial sample
+pub fn decode_pcm(bytes: &[u8], format: PcmFormat) -> Result<Vec<f32>, String> {
+    let sample_size = format.sample_size();
+    if !bytes.len().is_multiple_of(sample_size) {
+        return Err(format!(
+            "Input is {} bytes, which is not a whole number of {}-byte samples",
+            bytes.len(),
+            sample_size
+        ));
+    }
+
+    let samples = bytes.chunks_exact(sample_size).map(|chunk| match format {
+        PcmFormat::F32 => f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+        PcmFormat::I16 => i16::from_le_bytes([chunk[0], chunk[1]]) as f32 / 32768.0,
+    });
+
+    Ok(samples.collect())
+}
+
+/// Append interleaved input audio to `out` as mono
+///
+/// `channel` picks one input channel (numbered from 1); `None`, or a channel the
+/// input doesn't have, averages all channels instead.
+pub fn append_mono(out: &mut Vec<f32>, data: &[f32], channels: usize, channel: Option<usize>) {
+    out.extend(mono_samples(data, channels, channel));
+}
+
+/// Interleaved input audio as mono samples, like `append_mono` but without a buffer
+pub fn mono_samples(
+    data: &[f32],
+    channels: usize,
+    channel: Option<usize>,
+) -> impl Iterator<Item = f32> + '_ {
+    let channels = channels.max(1);
+    let channel = channel.filter(|channel| (1..=channels).contains(channel));
+    data.chunks_exact(channels).map(move |frame| match channel {
+        Some(channel) => frame[channel - 1],
+        None => frame.iter().sum::<f32>() / channels as f32,
+    })
+}
+
+/// Resample mono audio at the configured quality (see `set_resample_quality`)
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    resample_with(samples, from_rate, to_rate, get_resample_quality())
+}
+
+/// Resample mono audio at the given quality
+///
+/// Both qualities produce the same number of samples: the input length scaled by
+/// the ratio of the rates, rounded.
+pub fn resample_with(
+    samples: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    match quality {
+        ResampleQuality::High => resample_sinc(samples, from_rate, to_rate),
+        ResampleQuality::Fast => resample_linear(samples, from_rate, to_rate),
+    }
+}
+
+fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let ratio = from_rate as f64 / to_rate as f64;
+    let output_len = (samples.len() as f64 / ratio).round() as usize;
+
+    (0..output_len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index.min(samples.len() - 1)];
+            let next = samples[(index + 1).min(samples.len() - 1)];
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+/// Band-limited resampling: each output sample is the input convolved with a
+/// windowed sinc centred on its position
+fn resample_sinc(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let ratio = from_rate as f64 / to_rate as f64;
+    let output_len = (samples.len() as f64 / ratio).round() as usize;
+    // When downsampling, the filter cuts at the new Nyquist frequency instead
+    let cutoff = (1.0 / ratio).min(1.0);
+    // Input samples on each side of a position that the kernel reaches
+    let reach = SINC_ZERO_CROSSINGS as f64 / cutoff;
+    let last_index = samples.len() - 1;
+
+    (0..output_len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let first = (position - reach).ceil().max(0.0) as usize;
+            let last = ((position + reach).floor().max(0.0) as usize).min(last_index);
+
+            let mut sum = 0.0;
+            let mut weights = 0.0;
+            for (index, &sample) in samples.iter().enumerate().take(last + 1).skip(first) {
+                let weight = sinc_kernel((position - index as f64).abs() * cutoff);
+                sum += weight * sample as f64;
+                weights += weight;
+            }
+            // Dividing by the weights keeps the gain at 1, also where the kernel
+            // runs past either end of the audio
+            if weights > 0.0 {
+                (sum / weights) as f32
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Windowed sinc at `x` zero crossings from the centre, looked up in `SINC_TABLE`
+fn sinc_kernel(x: f64) -> f64 {
+    let position = x * SINC_TABLE_RESOLUTION as f64;
+    let index = position as usize;
+    if index >= SINC_ZERO_CROSSINGS * SINC_TABLE_RESOLUTION {
+        return 0.0;
+    }
+    let fraction = position - index as f64;
+    let current = SINC_TABLE[index] as f64;
+    let next = SINC_TABLE[index + 1] as f64;
+    current + (next - current) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, rate: u32, secs: f64) -> Vec<f32> {
+        let len = (rate as f64 * secs) as usize;
+        (0..len)
+            .map(|i| (2.0 * PI * frequency * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Frequency of a sine from its rising zero crossings, away from the edges
+    fn measured_frequency(samples: &[f32], rate: u32) -> f64 {
+        let middle = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        let crossings: Vec<usize> = middle
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, _)| i)
+            .collect();
+        let (first, last) = (crossings[0], *crossings.last().unwrap());
+        (crossings.len() - 1) as f64 * rate as f64 / (last - first) as f64
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        let middle = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        (middle.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / middle.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn output_length_follows_the_rate_ratio() {
+        for quality in [ResampleQuality::High, ResampleQuality::Fast] {
+            assert_eq!(
+                resample_with(&[0.0; 48_000], 48_000, 16_000, quality).len(),
+                16_000
+            );
+            assert_eq!(
+                resample_with(&[0.0; 44_100], 44_100, 16_000, quality).len(),
+                16_000
+            );
+            assert_eq!(
+                resample_with(&[0.0; 1_000], 8_000, 16_000, quality).len(),
+                2_000
+            );
+            assert_eq!(
+                resample_with(&[0.0; 1_001], 22_050, 16_000, quality).len(),
+                726
+            );
+        }
+    }
+
+    #[test]
+    fn same_rate_and_empty_input_are_unchanged() {
+        let samples = [0.1, -0.2, 0.3];
+        assert_eq!(
+            resample_with(&samples, 16_000, 16_000, ResampleQuality::High),
+            samples
+        );
+        assert!(resample_with(&[], 48_000, 16_000, ResampleQuality::High).is_empty());
+    }
+
+    #[test]
+    fn sine_frequency_is_preserved() {
+        for (from_rate, quality) in [
+            (48_000, ResampleQuality::High),
+            (44_100, ResampleQuality::High),
+            (8_000, ResampleQuality::High),
+            (48_000, ResampleQuality::Fast),
+        ] {
+            let input = sine(440.0, from_rate, 1.0);
+            let output = resample_with(&input, from_rate, MODEL_SAMPLE_RATE, quality);
+            let frequency = measured_frequency(&output, MODEL_SAMPLE_RATE);
+            assert!(
+                (frequency - 440.0).abs() < 1.0,
+                "{} Hz at {:?} from {} Hz",
+                frequency,
+                quality,
+                from_rate
+            );
+        }
+    }
+
+    #[test]
+    fn high_quality_matches_the_ideal_output_in_the_passband() {
+        let output = resample_with(
+            &sine(1_000.0, 48_000, 0.5),
+            48_000,
+            16_000,
+            ResampleQuality::High,
+        );
+        let expected = sine(1_000.0, 16_000, 0.5);
+        let worst = output[800..7_200]
+            .iter()
+            .zip(&expected[800..7_200])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(worst < 0.01, "off by up to {}", worst);
+    }
+
+    #[test]
+    fn high_quality_filters_out_frequencies_above_the_new_nyquist() {
+        // 12 kHz can't be represented at 16 kHz; linear interpolation folds it
+        // back to 4 kHz, right in the speech band
+        let input = sine(12_000.0, 48_000, 0.5);
+        let high = resample_with(&input, 48_000, 16_000, ResampleQuality::High);
+        let fast = resample_with(&input, 48_000, 16_000, ResampleQuality::Fast);
+        assert!(rms(&high) < 0.01, "high quality left {}", rms(&high));
+        assert!(rms(&fast) > 0.3, "fast left {}", rms(&fast));
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    fn scaled_sine(amplitude: f32) -> Vec<f32> {
+        sine(440.0, 16_000, 0.1)
+            .iter()
+            .map(|s| s * amplitude)
+            .collect()
+    }
+
+    fn db(gain: f32) -> f32 {
+        20.0 * gain.log10()
+    }
+
+    #[test]
+    fn normalize_brings_the_peak_to_the_target_within_the_bounds() {
+        let normalization = Normalization {
+            enabled: true,
+            target_db: -3.0,
+            min_gain_db: -6.0,
+            max_gain_db: 20.0,
+        };
+
+        // -12 dBFS needs +9 dB, inside the bounds
+        let mut samples = scaled_sine(0.251);
+        let gain = normalize(&mut samples, &normalization);
+        assert!((db(gain) - 9.0).abs() < 0.1, "gain {} dB", db(gain));
+        assert!((db(peak(&samples)) + 3.0).abs() < 0.1);
+
+        // Near-silence (-60 dBFS) would need +57 dB: held at the ceiling
+        let mut samples = scaled_sine(0.001);
+        let gain = normalize(&mut samples, &normalization);
+        assert!((db(gain) - 20.0).abs() < 0.01, "gain {} dB", db(gain));
+        assert!((db(peak(&samples)) + 40.0).abs() < 0.1);
+
+        // Clipping at 0 dBFS would need -3 dB, and more over it; held at the floor
+        let mut samples = scaled_sine(4.0);
+        let gain = normalize(&mut samples, &normalization);
+        assert!((db(gain) + 6.0).abs() < 0.01, "gain {} dB", db(gain));
+    }
+
+    #[test]
+    fn silence_gets_the_ceiling_and_stays_silent() {
+        let normalization = Normalization {
+            enabled: true,
+            ..Normalization::default()
+        };
+        let mut samples = vec![0.0; 160];
+        let gain = normalize(&mut samples, &normalization);
+        assert!((db(gain) - DEFAULT_NORMALIZE_MAX_GAIN_DB).abs() < 0.01);
+        assert!(samples.iter().all(|s| *s == 0.0));
+    }
+}
@@ -0,0 +1,157 @@
+// Short tones played on the default output device for audio feedback
+
+use crate::get_sound_cues;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use log::warn;
+use std::thread;
+use std::time::Duration;
+
+// Peak amplitude of the tone (0.0-1.0)
+const BEEP_VOLUME: f32 = 0.2;
+
+// Length of the fade in/out that avoids clicks at the edges of the tone
+const FADE: Duration = Duration::from_millis(5);
+
+/// How a dictation ended, each outcome with its own tone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cue {
+    /// The text was typed or saved
+    Success,
+    /// Nothing was recognized
+    Empty,
+    /// No microphone, no audio, a failed transcription or text that couldn't be typed
+    Error,
+}
+
+/// A cue's tone and whether it's played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub enabled: bool,
+    pub frequency_hz: f32,
+    pub duration: Duration,
+}
+
+impl Tone {
+    fn off(frequency_hz: f32, duration_ms: u64) -> Self {
+        Tone {
+            enabled: false,
+            frequency_hz,
+            duration: Duration::from_millis(duration_ms),
+        }
+    }
+}
+
+/// Tones of the three cues: a short high one, a low one and a long lower one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundCues {
+    pub success: Tone,
+    pub empty: Tone,
+    pub error: Tone,
+}
+
+impl SoundCues {
+    pub fn tone(&self, cue: Cue) -> Tone {
+        match cue {
+            Cue::Success => self.success,
+            Cue::Empty => self.empty,
+            Cue::Error => self.error,
+        }
+    }
+
+    pub fn tone_mut(&mut self, cue: Cue) -> &mut Tone {
+        match cue {
+            Cue::Success => &mut self.success,
+            Cue::Empty => &mut self.empty,
+            Cue::Error => &mut self.error,
+        }
+    }
+}
+
+impl Default for SoundCues {
+    /// All off
+    fn default() -> Self {
+        SoundCues {
+            success: Tone::off(880.0, 80),
+            empty: Tone::off(330.0, 200),
+            error: Tone::off(220.0, 450),
+        }
+    }
+}
+
+/// Play the tone of `cue` in the background, if that cue is enabled
+pub fn play_cue(cue: Cue) {
+    let tone = get_sound_cues().tone(cue);
+    if tone.enabled {
+        beep(tone.frequency_hz, tone.duration);
+    }
+}
+
+/// Play a sine tone in the background without blocking the caller
+pub fn beep(frequency_hz: f32, duration: Duration) {
+    thread::spawn(move || {
+        if let Err(e) = play_tone(frequency_hz, duration) {
+            warn!("Failed to play beep: {}", e);
+        }
+    });
+}
+
+/// Play a sine tone, blocking until it has finished
+pub fn play_tone(frequency_hz: f32, duration: Duration) -> Result<(), String> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or("No output device available")?;
+    let config = device.default_output_config().map_err(|e| e.to_string())?;
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => build_tone::<f32>(&device, &config, frequency_hz, duration),
+        cpal::SampleFormat::I16 => build_tone::<i16>(&device, &config, frequency_hz, duration),
+        cpal::SampleFormat::U16 => build_tone::<u16>(&device, &config, frequency_hz, duration),
+        format => return Err(format!("Unsupported output sample format {:?}", format)),
+    }?;
+
+    stream.play().map_err(|e| e.to_string())?;
+    thread::sleep(duration + Duration::from_millis(50));
+    Ok(())
+}
+
+fn build_tone<T>(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    frequency_hz: f32,
+    duration: Duration,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+    let total_frames = (sample_rate * duration.as_secs_f32()) as usize;
+    let fade_frames = ((sample_rate * FADE.as_secs_f32()) as usize).max(1);
+    let mut frame_index = 0usize;
+
+    device
+        .build_output_stream(
+            &config.config(),
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(channels) {
+                    let value = if frame_index < total_frames {
+                        let remaining = total_frames - frame_index;
+                        let envelope =
+                            (frame_index.min(remaining) as f32 / fade_frames as f32).min(1.0);
+                        let phase = 2.0 * std::f32::consts::PI * frequency_hz * frame_index as f32
+                            / sample_rate;
+                        BEEP_VOLUME * envelope * phase.sin()
+                    } else {
+                        0.0
+                    };
+                    frame_index += 1;
+                    frame.fill(T::from_sample(value));
+                }
+            },
+            |err| warn!("Beep stream error: {}", err),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,167 @@
+// `--bench`: load the model with each provider in turn and time the same sample
+// through it, to show which provider is fastest on this machine.
+//
+// The sample is generated (a few seconds of voiced, syllable-like tones), so the
+// numbers don't depend on a microphone and are comparable between machines. The
+// first transcription of each provider is a warm-up and isn't counted.
+
+use crate::cli::Cli;
+use esponquen::EngineConfig;
+use esponquen::audio::MODEL_SAMPLE_RATE;
+use esponquen::diagln;
+use log::error;
+use std::time::{Duration, Instant};
+
+// Length of the generated sample
+const SAMPLE_SECS: u32 = 8;
+
+// Timed transcriptions per provider, after the warm-up
+const RUNS: u32 = 5;
+
+/// Timings of one provider, or why it couldn't load the model
+struct ProviderResult {
+    /// Provider as written in the config, "cpu" for the CPU
+    provider: String,
+    outcome: Result<Measurement, String>,
+}
+
+struct Measurement {
+    load: Duration,
+    /// Average inference time over the timed runs
+    inference: Duration,
+    real_time_factor: f64,
+}
+
+/// Benchmark every provider of `engine`, returning the process exit code
+pub fn run(cli: &Cli, engine: &EngineConfig) -> i32 {
+    let samples = sample();
+    let audio = Duration::from_secs(SAMPLE_SECS.into());
+    diagln!(
+        "Benchmarking {} provider(s) on {}s of audio, {} runs each\n",
+        engine.providers.len(),
+        SAMPLE_SECS,
+        RUNS
+    );
+
+    let results: Vec<ProviderResult> = engine
+        .providers
+        .iter()
+        .map(|provider| {
+            let name = provider.clone().unwrap_or_else(|| "cpu".to_string());
+            diagln!("Testing {}...", crate::provider_display_name(&name));
+            ProviderResult {
+                outcome: measure(engine, provider.as_deref(), &samples, audio),
+                provider: name,
+            }
+        })
+        .collect();
+
+    if cli.json {
+        println!("{}", results_json(&results, audio));
+    } else {
+        println!("{}", results_table(&results));
+    }
+
+    if results.iter().any(|result| result.outcome.is_ok()) {
+        0
+    } else {
+        error!("No provider could load the model");
+        1
+    }
+}
+
+/// Load the model with `provider` and time `RUNS` transcriptions of `samples`
+fn measure(
+    engine: &EngineConfig,
+    provider: Option<&str>,
+    samples: &[f32],
+    audio: Duration,
+) -> Result<Measurement, String> {
+    let started = Instant::now();
+    let mut recognizer = crate::init_recognizer(engine, provider)?;
+    let load = started.elapsed();
+
+    crate::transcribe(&mut recognizer, MODEL_SAMPLE_RATE, samples);
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let (_, timings) = crate::transcribe(&mut recognizer, MODEL_SAMPLE_RATE, samples);
+        total += timings.inference;
+    }
+    let inference = total / RUNS;
+
+    Ok(Measurement {
+        load,
+        inference,
+        real_time_factor: inference.as_secs_f64() / audio.as_secs_f64(),
+    })
+}
+
+fn results_table(results: &[ProviderResult]) -> String {
+    let mut table = format!(
+        "\n{:<10} {:>9} {:>15} {:>6}\n",
+        "Provider", "Load", "Inference (avg)", "RTF"
+    );
+    for result in results {
+        let name = crate::provider_display_name(&result.provider);
+        match &result.outcome {
+            Ok(m) => table.push_str(&format!(
+                "{:<10} {:>8.2}s {:>13}ms {:>6.2}\n",
+                name,
+                m.load.as_secs_f64(),
+                m.inference.as_millis(),
+                m.real_time_factor
+            )),
+            Err(e) => table.push_str(&format!("{:<10} unavailable: {}\n", name, e)),
+        }
+    }
+    table.push_str("\nRTF is inference time divided by the audio length; lower is faster.");
+    table
+}
+
+fn results_json(results: &[ProviderResult], audio: Duration) -> serde_json::Value {
+    let providers: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| match &result.outcome {
+            Ok(m) => serde_json::json!({
+                "provider": result.provider,
+                "ok": true,
+                "load_ms": m.load.as_secs_f64() * 1000.0,
+                "inference_ms": m.inference.as_secs_f64() * 1000.0,
+                "real_time_factor": m.real_time_factor,
+            }),
+            Err(e) => serde_json::json!({
+                "provider": result.provider,
+                "ok": false,
+                "error": e,
+            }),
+        })
+        .collect();
+
+    serde_json::json!({
+        "audio_secs": audio.as_secs_f64(),
+        "runs": RUNS,
+        "providers": providers,
+    })
+}
+
+/// The fixed benchmark sample: voiced tones in 200 ms "syllables" with short pauses
+fn sample() -> Vec<f32> {
+    let rate = MODEL_SAMPLE_RATE as f32;
+    (0..SAMPLE_SECS * MODEL_SAMPLE_RATE)
+        .map(|i| {
+            let t = i as f32 / rate;
+            let syllable = (t / 0.25) as u32;
+            let in_syllable = t % 0.25;
+            if in_syllable > 0.2 {
+                return 0.0;
+            }
+            // Pitch and envelope vary per syllable, harmonics give it some timbre
+            let pitch = 110.0 + (syllable % 7) as f32 * 15.0;
+            let envelope = (in_syllable / 0.2 * std::f32::consts::PI).sin();
+            let voice: f32 = (1..=5)
+                .map(|h| (2.0 * std::f32::consts::PI * pitch * h as f32 * t).sin() / h as f32)
+                .sum();
+            0.2 * envelope * voice
+        })
+        .collect()
+}
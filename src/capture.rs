@@ -0,0 +1,346 @@
+// Hand-off of recorded audio from the realtime input callback.
+//
+// The audio callback has a deadline: if it waits on a lock or the allocator, the
+// driver drops the next buffer. So it only pushes samples into a `SampleRing`,
+// a fixed-size queue that never blocks or allocates, and a collector thread
+// drains the ring into the recording buffer, where locking is fine.
+//
+// The first samples of a recording can be skipped, so the click of the hotkey
+// that started it isn't recorded; the ring drops them as they're pushed.
+//
+// Recording buffers come from a `BufferPool`, reserved up front for a typical
+// dictation and reused, so a long session doesn't keep growing and freeing a
+// fresh buffer for every dictation. A longer recording grows its buffer, which
+// then keeps that size for the next ones.
+
+use crate::sync::lock;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// Lock-free single-producer, single-consumer queue of samples
+///
+/// `push` is the producer side (the audio callback), `drain_into` and `clear` the
+/// consumer side. Only one thread may push and one consume at a time; the app
+/// consumes under the recording state lock. Samples that don't fit are dropped
+/// and counted rather than waited for.
+pub struct SampleRing {
+    /// Samples as f32 bits, so the slots can be shared without unsafe code
+    slots: Box<[AtomicU32]>,
+    /// Count of samples ever pushed, wrapping; the producer's position
+    head: AtomicUsize,
+    /// Count of samples ever drained, wrapping; the consumer's position
+    tail: AtomicUsize,
+    dropped: AtomicUsize,
+    /// Samples still to be discarded from the next pushes
+    skip: AtomicUsize,
+}
+
+impl SampleRing {
+    /// Create a ring holding up to `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        SampleRing {
+            slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            skip: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue samples, returning how many were queued; never blocks or allocates
+    pub fn push(&self, samples: impl IntoIterator<Item = f32>) -> usize {
+        let capacity = self.slots.len();
+        let tail = self.tail.load(Ordering::Acquire);
+        let start = self.head.load(Ordering::Relaxed);
+        let mut head = start;
+        let mut dropped = 0;
+        let mut skipped = 0;
+        let skip = self.skip.load(Ordering::Relaxed);
+
+        for sample in samples {
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+            if head.wrapping_sub(tail) == capacity {
+                dropped += 1;
+                continue;
+            }
+            self.slots[head % capacity].store(sample.to_bits(), Ordering::Relaxed);
+            head = head.wrapping_add(1);
+        }
+
+        // Publishes the slots written above to the consumer
+        self.head.store(head, Ordering::Release);
+        if dropped > 0 {
+            self.dropped.fetch_add(dropped, Ordering::Relaxed);
+        }
+        if skipped > 0 {
+            // Saturating, in case a new recording reset the count meanwhile
+            self.skip
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    Some(left.saturating_sub(skipped))
+                })
+                .ok();
+        }
+        head.wrapping_sub(start)
+    }
+
+    /// Discard the next `count` samples pushed instead of queueing them
+    pub fn skip_next(&self, count: usize) {
+        self.skip.store(count, Ordering::Relaxed);
+    }
+
+    /// Move every queued sample to the end of `out`, returning how many there were
+    pub fn drain_into(&self, out: &mut Vec<f32>) -> usize {
+        let capacity = self.slots.len();
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        let count = head.wrapping_sub(tail);
+
+        out.reserve(count);
+        out.extend((0..count).map(|i| {
+            let slot = &self.slots[tail.wrapping_add(i) % capacity];
+            f32::from_bits(slot.load(Ordering::Relaxed))
+        }));
+
+        // Hands the drained slots back to the producer
+        self.tail.store(head, Ordering::Release);
+        count
+    }
+
+    /// Discard every queued sample
+    pub fn clear(&self) {
+        self.tail
+            .store(self.head.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    /// Samples dropped because the ring was full since the last call
+    pub fn take_dropped(&self) -> usize {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Most samples a checkout reserves up front, 30 seconds at 48 kHz (about 5.5 MiB)
+///
+/// Reserving for the longest allowed recording would keep hundreds of MB
+/// committed for dictations that mostly last seconds.
+pub const MAX_RESERVED_SAMPLES: usize = 30 * 48_000;
+
+/// Recording buffers kept for reuse
+///
+/// A recording checks a buffer out when it starts and gives it back once it's
+/// transcribed. As the transcription of one recording can still run when the
+/// next one starts, the pool can hand out more buffers than it keeps. Buffers
+/// are reserved for at most `MAX_RESERVED_SAMPLES`, and grow past that as a
+/// longer recording needs.
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<f32>>>,
+    /// Most buffers kept while none are checked out
+    max_free: usize,
+}
+
+impl BufferPool {
+    /// Create an empty pool keeping up to `max_free` buffers
+    pub const fn new(max_free: usize) -> Self {
+        BufferPool {
+            free: Mutex::new(Vec::new()),
+            max_free,
+        }
+    }
+
+    /// Take an empty buffer holding `capacity` samples without growing, or
+    /// `MAX_RESERVED_SAMPLES` if that's less
+    pub fn checkout(&self, capacity: usize) -> Vec<f32> {
+        let capacity = capacity.min(MAX_RESERVED_SAMPLES);
+        match lock(&self.free).pop() {
+            Some(mut buffer) => {
+                buffer.reserve(capacity);
+                buffer
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Return a buffer for the next checkout, dropping it if the pool is full
+    pub fn give_back(&self, mut buffer: Vec<f32>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        buffer.clear();
+        let mut free = lock(&self.free);
+        if free.len() < self.max_free {
+            free.push(buffer);
+        }
+    }
+
+    /// Keep `buffer` usable until the returned guard is dropped, then give it back
+    pub fn hold(&self, buffer: Vec<f32>) -> PooledBuffer<'_> {
+        PooledBuffer { pool: self, buffer }
+    }
+
+    /// Buffers waiting for a checkout
+    pub fn free_buffers(&self) -> usize {
+        lock(&self.free).len()
+    }
+}
+
+/// A buffer that goes back to its `BufferPool` when dropped
+pub struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buffer: Vec<f32>,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<f32>;
+
+    fn deref(&self) -> &Vec<f32> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<f32> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.give_back(std::mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_come_out_in_order_across_the_wrap() {
+        let ring = SampleRing::new(8);
+        let mut out = Vec::new();
+        for round in 0..5 {
+            let samples: Vec<f32> = (0..6).map(|i| (round * 6 + i) as f32).collect();
+            assert_eq!(ring.push(samples.iter().copied()), 6);
+            assert_eq!(ring.drain_into(&mut out), 6);
+        }
+        let expected: Vec<f32> = (0..30).map(|i| i as f32).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn a_full_ring_drops_and_counts_the_rest() {
+        let ring = SampleRing::new(4);
+        assert_eq!(ring.push([1.0, 2.0, 3.0]), 3);
+        assert_eq!(ring.push([4.0, 5.0, 6.0]), 1);
+        assert_eq!(ring.take_dropped(), 2);
+        assert_eq!(ring.take_dropped(), 0);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+        assert_eq!(out, [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn clear_discards_queued_samples() {
+        let ring = SampleRing::new(4);
+        ring.push([1.0, 2.0]);
+        ring.clear();
+        ring.push([3.0]);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+        assert_eq!(out, [3.0]);
+    }
+
+    #[test]
+    fn skipped_samples_are_never_queued() {
+        let ring = SampleRing::new(8);
+        ring.skip_next(3);
+        assert_eq!(ring.push([1.0, 2.0]), 0);
+        assert_eq!(ring.push([3.0, 4.0, 5.0]), 2);
+        assert_eq!(ring.push([6.0]), 1);
+        assert_eq!(ring.take_dropped(), 0);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+        assert_eq!(out, [4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn a_returned_buffer_is_reused_empty() {
+        let pool = BufferPool::new(2);
+        let mut buffer = pool.checkout(1000);
+        assert!(buffer.capacity() >= 1000);
+        buffer.extend([0.5; 10]);
+        let address = buffer.as_ptr();
+        pool.give_back(buffer);
+
+        let buffer = pool.checkout(1000);
+        assert_eq!(buffer.as_ptr(), address);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn checkouts_reserve_at_most_the_cap_and_keep_what_they_grew_to() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.checkout(600 * 48_000);
+        assert!(buffer.capacity() >= MAX_RESERVED_SAMPLES);
+        assert!(buffer.capacity() < 2 * MAX_RESERVED_SAMPLES);
+
+        // A long recording grows past it, and the next one starts with that room
+        buffer.resize(3 * MAX_RESERVED_SAMPLES, 0.0);
+        let grown = buffer.capacity();
+        pool.give_back(buffer);
+        assert_eq!(pool.checkout(600 * 48_000).capacity(), grown);
+    }
+
+    #[test]
+    fn a_transcription_outliving_the_next_recording_keeps_its_buffer() {
+        let pool = BufferPool::new(2);
+        let mut first = pool.checkout(100);
+        first.extend([0.25; 100]);
+        let first_address = first.as_ptr();
+
+        // The next recording starts while the first is still being transcribed
+        let second = pool.checkout(100);
+        assert_ne!(second.as_ptr(), first_address);
+        assert!(first.iter().all(|sample| *sample == 0.25));
+
+        pool.give_back(first);
+        assert_eq!(pool.free_buffers(), 1);
+
+        // The recording after that gets the first buffer back
+        let third = pool.checkout(100);
+        assert_eq!(third.as_ptr(), first_address);
+        assert!(third.is_empty());
+        drop(second);
+    }
+
+    #[test]
+    fn the_pool_keeps_at_most_max_free_buffers() {
+        let pool = BufferPool::new(1);
+        let first = pool.checkout(10);
+        let second = pool.checkout(10);
+        pool.give_back(first);
+        pool.give_back(second);
+        assert_eq!(pool.free_buffers(), 1);
+
+        // Empty, never-allocated buffers aren't worth keeping
+        let pool = BufferPool::new(1);
+        pool.give_back(Vec::new());
+        assert_eq!(pool.free_buffers(), 0);
+    }
+
+    #[test]
+    fn a_held_buffer_goes_back_when_dropped() {
+        let pool = BufferPool::new(1);
+        let mut held = pool.hold(pool.checkout(10));
+        held.push(1.0);
+        assert_eq!(held.len(), 1);
+        assert_eq!(pool.free_buffers(), 0);
+        drop(held);
+        assert_eq!(pool.free_buffers(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+// Command line interface. Flags take precedence over the config file: they are
+// folded into the loaded `Config` before it's applied.
+
+use crate::instance::ControlCommand;
+use clap::{Parser, Subcommand};
+use esponquen::audio::PcmFormat;
+use esponquen::config::Config;
+use esponquen::logging::parse_level;
+use esponquen::{HOTKEY_HINT, hotkey_name, parse_hotkey};
+use log::LevelFilter;
+use std::path::PathBuf;
+
+/// Offline speech-to-text that types what you say, from the system tray
+#[derive(Parser, Clone, Debug)]
+#[command(name = "esponquen", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+
+    /// Show the debug console (Windows) and print diagnostics
+    #[arg(long)]
+    pub console: bool,
+
+    /// Keep config, logs and data next to the executable (also enabled by a portable.flag file there)
+    #[arg(long)]
+    pub portable: bool,
+
+    /// Config file to load instead of the one in the platform config directory
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Write a commented default config file and exit
+    #[arg(long)]
+    pub write_default_config: bool,
+
+    /// Profile from the config file to apply on top of the other settings
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Directory containing the model files
+    #[arg(long, value_name = "PATH")]
+    pub model_dir: Option<String>,
+
+    /// Execution provider to load the model with (e.g. cuda, dml, cpu)
+    #[arg(long, value_name = "NAME")]
+    pub provider: Option<String>,
+
+    /// Name of the input device to record from
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+
+    /// Key that starts/stops recording (e.g. F9, F13 or PageDown)
+    #[arg(long, value_name = "KEY", value_parser = parse_hotkey_arg)]
+    pub hotkey: Option<String>,
+
+    /// Print transcriptions instead of typing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Ask an already running instance to quit and take its place
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Run without a tray icon, reporting status on the console and in the log
+    #[arg(long)]
+    pub no_tray: bool,
+
+    /// Write only transcriptions to stdout, diagnostics go to stderr
+    #[arg(long)]
+    pub emit_text: bool,
+
+    /// Print nothing on the console but errors (--console still shows diagnostics)
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Print how long each transcription took
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Log level for the console and the log file: error, warn, info, debug or trace
+    #[arg(long, value_name = "LEVEL", value_parser = parse_log_level)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Input sample rate in Hz
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    pub input_rate: Option<u32>,
+
+    /// Input buffer size in frames
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub buffer_size: Option<u32>,
+
+    /// Longest recording in seconds before the watchdog may discard it
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_recording_secs: Option<u64>,
+
+    /// Finish the current dictation on Quit instead of asking
+    #[arg(long)]
+    pub finish_on_quit: bool,
+
+    /// Re-activate the window that was focused when recording started
+    #[arg(long)]
+    pub restore_focus: bool,
+
+    /// Don't show the last transcription in the tray tooltip
+    #[arg(long)]
+    pub no_tooltip_preview: bool,
+
+    /// Don't notify when the model falls back to the CPU
+    #[arg(long)]
+    pub no_fallback_notification: bool,
+
+    /// Transcribe raw mono PCM from stdin, print the text and exit
+    #[arg(long)]
+    pub transcribe_pcm: bool,
+
+    /// Sample format of the --transcribe-pcm input (f32 or i16, little-endian)
+    #[arg(long, value_name = "FORMAT", default_value = "f32", value_parser = parse_pcm_format)]
+    pub pcm_format: PcmFormat,
+
+    /// Time the model with each provider on a fixed sample, print the results and exit
+    #[arg(long, conflicts_with = "transcribe_pcm")]
+    pub bench: bool,
+
+    /// Check the config, model, microphone, keyboard, typing and tray, print the results and exit
+    #[arg(long, conflicts_with_all = ["transcribe_pcm", "bench"])]
+    pub self_test: bool,
+
+    /// Print --transcribe-pcm and --bench results as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Run the settings window (started by the tray app)
+    #[arg(long, hide = true)]
+    pub settings_window: bool,
+
+    /// Run the history window (started by the tray app)
+    #[arg(long, hide = true)]
+    pub history_window: bool,
+
+    /// Run the first-run setup window (started by the setup wizard)
+    #[arg(long, hide = true)]
+    pub setup_window: bool,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum CliCommand {
+    /// List the input devices of every audio host and exit
+    ListDevices {
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Control the running instance and print its JSON response
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum CtlAction {
+    /// Start recording
+    StartRecording,
+    /// Stop recording and transcribe
+    Stop,
+    /// Start or stop recording, like pressing the hotkey
+    Toggle,
+    /// Print the current status, hotkey and usage counters
+    Status,
+    /// Change the recording hotkey until the next restart
+    SetHotkey {
+        #[arg(value_parser = parse_hotkey_arg)]
+        key: String,
+    },
+    /// Quit, finishing a dictation in progress first
+    Quit,
+}
+
+impl CtlAction {
+    pub fn command(&self) -> ControlCommand {
+        match self {
+            CtlAction::StartRecording => ControlCommand::StartRecording,
+            CtlAction::Stop => ControlCommand::Stop,
+            CtlAction::Toggle => ControlCommand::Toggle,
+            CtlAction::Status => ControlCommand::Status,
+            CtlAction::SetHotkey { key } => ControlCommand::SetHotkey { key: key.clone() },
+            CtlAction::Quit => ControlCommand::Quit,
+        }
+    }
+}
+
+impl Cli {
+    /// Override the config file values with the ones given on the command line
+    pub fn apply_to(&self, config: &mut Config) {
+        if let Some(dir) = &self.model_dir {
+            config.model.dir = Some(dir.clone());
+        }
+        if let Some(provider) = &self.provider {
+            config.model.providers = Some(vec![provider.clone()]);
+        }
+        if let Some(device) = &self.device {
+            config.audio.device = Some(device.clone());
+        }
+        if let Some(hotkey) = &self.hotkey {
+            config.hotkey.key = Some(hotkey.clone());
+        }
+        if let Some(rate) = self.input_rate {
+            config.audio.sample_rate = Some(rate);
+        }
+        if let Some(frames) = self.buffer_size {
+            config.audio.buffer_size = Some(frames);
+        }
+        if let Some(secs) = self.max_recording_secs {
+            config.audio.max_recording_secs = Some(secs);
+        }
+        if self.finish_on_quit {
+            config.ui.finish_on_quit = Some(true);
+        }
+        if self.restore_focus {
+            config.output.restore_focus = Some(true);
+        }
+        if self.no_tooltip_preview {
+            config.ui.tooltip_preview = Some(false);
+        }
+        if self.no_fallback_notification {
+            config.ui.fallback_notification = Some(false);
+        }
+    }
+}
+
+fn parse_hotkey_arg(value: &str) -> Result<String, String> {
+    match parse_hotkey(value) {
+        Some(key) => Ok(hotkey_name(key)),
+        None => Err(format!("expected {}", HOTKEY_HINT)),
+    }
+}
+
+fn parse_log_level(value: &str) -> Result<LevelFilter, String> {
+    parse_level(value).ok_or_else(|| "expected error, warn, info, debug, trace or off".to_string())
+}
+
+fn parse_pcm_format(value: &str) -> Result<PcmFormat, String> {
+    PcmFormat::parse(value).ok_or_else(|| "expected f32 or i16".to_string())
+}
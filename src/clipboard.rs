@@ -0,0 +1,27 @@
+// Copying text to the system clipboard, the fallback when typing fails.
+//
+// On X11 and Wayland copied text is served by the process that copied it, so
+// the clipboard handle is kept for the life of the app instead of being
+// dropped (and the text with it) after each copy.
+
+use crate::sync::lock;
+use arboard::Clipboard;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+// Opened on the first copy
+static CLIPBOARD: Lazy<Mutex<Option<Clipboard>>> = Lazy::new(|| Mutex::new(None));
+
+/// Put `text` on the clipboard
+pub fn copy_text(text: &str) -> Result<(), String> {
+    let mut handle = lock(&CLIPBOARD);
+    let mut clipboard = match handle.take() {
+        Some(clipboard) => clipboard,
+        None => Clipboard::new().map_err(|e| format!("Can't open the clipboard: {}", e))?,
+    };
+    let result = clipboard
+        .set_text(text)
+        .map_err(|e| format!("Can't copy to the clipboard: {}", e));
+    *handle = Some(clipboard);
+    result
+}
@@ -0,0 +1,498 @@
+// Commands for the app's coordinator, the event loop, and the state machine they drive.
+//
+// The keyboard hook, the tray, `esponquen ctl` and the worker threads don't
+// change the app's state themselves: they send an `AppCommand` over one channel
+// and the coordinator carries it out. Which command is allowed in which status
+// is decided by `transition`, a pure function, so the coordinator only performs
+// the `Effect` it returns and logs the commands it refuses.
+//
+// The legal transitions:
+//
+//   LoadingModel   --ModelLoaded-->          WaitingForHotkey
+//   WaitingForHotkey --ReloadModel/CycleProvider--> LoadingModel
+//   WaitingForHotkey --Toggle/Start-->       Recording(new clip)
+//   Recording(c)   --Toggle/Stop-->          Transcribing(c)
+//   Recording(c)   --Cancel-->               WaitingForHotkey
+//   Transcribing(c) --Toggle/Start-->        Recording(new clip)
+//   Transcribing(c) --TranscriptionDone(c)--> WaitingForHotkey
+//   Transcribing(c) --Abandon-->             WaitingForHotkey
+//   WaitingForHotkey --Disable-->            Disabled
+//   LoadingModel, WaitingForHotkey --Fail--> Error
+//   Disabled, Error --Recover-->             WaitingForHotkey
+//   Error          --ReloadModel/CycleProvider--> LoadingModel
+//   Error          --Toggle/Start-->         Recording(new clip)
+//
+// A transcription that finishes once the next recording has started leaves the
+// recording alone. Everything else that records is refused. The clipboard
+// hotkey's commands move the status like Toggle and Start. An error only stops
+// the hotkeys, so the tray and `esponquen ctl` still record from it; a failure
+// during a dictation waits for the dictation to finish.
+
+use crate::context::{AppStatus, ClipId};
+use crate::key::Key;
+use std::fmt;
+
+/// Something for the coordinator to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum AppCommand {
+    /// The model finished loading, or a reload failed and the previous one stays
+    ModelLoaded,
+    /// Load the model from disk again (the tray's Reload model)
+    ReloadModel,
+    /// Start recording, or stop and transcribe (the hotkey, a tray click)
+    Toggle,
+    StartRecording,
+    /// Toggle, for a recording that goes to the clipboard (the clipboard hotkey)
+    ToggleToClipboard,
+    /// StartRecording, for a recording that goes to the clipboard
+    StartRecordingToClipboard,
+    /// Stop recording and transcribe
+    StopRecording,
+    /// Stop recording and discard it
+    Cancel,
+    /// The transcription of `clip` finished (or failed), from the worker that ran it
+    TranscriptionDone {
+        clip: ClipId,
+    },
+    /// Give up on a transcription hung in the model
+    Abandon,
+    /// Transcribe the recording so far as a preview
+    Snapshot,
+    SetHotkey(Key),
+    OpenHistory,
+    /// Load the model again on the next provider (the provider hotkey)
+    CycleProvider,
+    /// Stop dictating until the missing permissions are granted
+    Disable,
+    /// Something dictation needs stopped working, e.g. the keyboard grab gave up
+    Fail {
+        reason: String,
+    },
+    /// What disabled the app or failed works again
+    Recover,
+    /// Shut down, finishing a dictation in progress
+    Quit,
+}
+
+impl AppCommand {
+    /// Whether the command moves the status at all; the others are always allowed
+    pub fn changes_status(&self) -> bool {
+        !matches!(
+            self,
+            AppCommand::Snapshot
+                | AppCommand::SetHotkey(_)
+                | AppCommand::OpenHistory
+                | AppCommand::Quit
+        )
+    }
+
+    /// Whether a recording this command starts goes to the clipboard instead of
+    /// the configured output
+    pub fn to_clipboard(&self) -> bool {
+        matches!(
+            self,
+            AppCommand::ToggleToClipboard | AppCommand::StartRecordingToClipboard
+        )
+    }
+}
+
+/// What the coordinator does to carry out a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Load the model again, keeping the current one until the new one is ready
+    LoadModel,
+    StartRecording,
+    /// Stop recording and hand the audio to a transcription worker
+    Transcribe,
+    /// Stop recording and drop the audio
+    Discard,
+    /// Only the status changes
+    Nothing,
+}
+
+/// A command refused in the status it arrived in
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidTransition {
+    pub from: AppStatus,
+    pub command: AppCommand,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} refused while {}: {}",
+            self.command,
+            self.from.name(),
+            self.reason
+        )
+    }
+}
+
+/// The status `command` leads to from `status` and what it takes to get there,
+/// or why it's refused
+///
+/// A recording started by this command gets `next_clip` as its id.
+pub fn transition(
+    status: &AppStatus,
+    command: &AppCommand,
+    next_clip: ClipId,
+) -> Result<(AppStatus, Effect), InvalidTransition> {
+    use AppCommand::*;
+    use AppStatus::*;
+
+    let refuse = |reason| {
+        Err(InvalidTransition {
+            from: status.clone(),
+            command: command.clone(),
+            reason,
+        })
+    };
+
+    match (status, command) {
+        (_, Snapshot | SetHotkey(_) | OpenHistory | Quit) => Ok((status.clone(), Effect::Nothing)),
+
+        (LoadingModel, ModelLoaded) => Ok((WaitingForHotkey, Effect::Nothing)),
+        (LoadingModel | WaitingForHotkey, Fail { reason }) => Ok((
+            Error {
+                reason: reason.clone(),
+            },
+            Effect::Nothing,
+        )),
+        (LoadingModel, _) => refuse("The model is still loading"),
+        (WaitingForHotkey, Disable) => Ok((Disabled, Effect::Nothing)),
+        (Disabled | Error { .. }, Recover) => Ok((WaitingForHotkey, Effect::Nothing)),
+        (Recording { .. } | Transcribing { .. }, Disable | Fail { .. }) => {
+            refuse("Finish the current dictation first")
+        }
+        (_, Disable | Fail { .. }) => refuse("Dictation is already stopped"),
+        (_, Recover) => refuse("Nothing to recover from"),
+        (Disabled, _) => refuse("Permissions required"),
+        (_, ModelLoaded) => refuse("The model is already loaded"),
+        (WaitingForHotkey | Error { .. }, ReloadModel | CycleProvider) => {
+            Ok((LoadingModel, Effect::LoadModel))
+        }
+        (_, ReloadModel | CycleProvider) => refuse("Finish the current dictation first"),
+
+        // A new recording can start while the previous one is being transcribed
+        (
+            WaitingForHotkey | Transcribing { .. } | Error { .. },
+            Toggle | StartRecording | ToggleToClipboard | StartRecordingToClipboard,
+        ) => Ok((Recording { clip: next_clip }, Effect::StartRecording)),
+        (WaitingForHotkey | Transcribing { .. } | Error { .. }, StopRecording | Cancel) => {
+            refuse("Not recording")
+        }
+        (Recording { clip }, Toggle | ToggleToClipboard | StopRecording) => {
+            Ok((Transcribing { clip: *clip }, Effect::Transcribe))
+        }
+        (Recording { .. }, StartRecording | StartRecordingToClipboard) => {
+            refuse("Already recording")
+        }
+        (Recording { .. }, Cancel) => Ok((WaitingForHotkey, Effect::Discard)),
+
+        (Transcribing { clip }, TranscriptionDone { clip: done }) if done == clip => {
+            Ok((WaitingForHotkey, Effect::Nothing))
+        }
+        // The previous clip finished while the next one records
+        (Recording { clip }, TranscriptionDone { clip: done }) if done < clip => {
+            Ok((status.clone(), Effect::Nothing))
+        }
+        (_, TranscriptionDone { .. }) => refuse("That transcription isn't the one running"),
+        (Transcribing { .. }, Abandon) => Ok((WaitingForHotkey, Effect::Nothing)),
+        (_, Abandon) => refuse("Not transcribing"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEXT: ClipId = 9;
+
+    fn statuses() -> Vec<AppStatus> {
+        vec![
+            AppStatus::LoadingModel,
+            AppStatus::WaitingForHotkey,
+            AppStatus::Recording { clip: 3 },
+            AppStatus::Transcribing { clip: 3 },
+            AppStatus::Disabled,
+            failed(),
+        ]
+    }
+
+    fn failed() -> AppStatus {
+        AppStatus::Error {
+            reason: "Hotkeys unavailable".to_string(),
+        }
+    }
+
+    fn commands() -> Vec<AppCommand> {
+        vec![
+            AppCommand::ModelLoaded,
+            AppCommand::ReloadModel,
+            AppCommand::Toggle,
+            AppCommand::StartRecording,
+            AppCommand::ToggleToClipboard,
+            AppCommand::StartRecordingToClipboard,
+            AppCommand::StopRecording,
+            AppCommand::Cancel,
+            AppCommand::TranscriptionDone { clip: 2 },
+            AppCommand::TranscriptionDone { clip: 3 },
+            AppCommand::Abandon,
+            AppCommand::Snapshot,
+            AppCommand::SetHotkey(Key::F8),
+            AppCommand::OpenHistory,
+            AppCommand::CycleProvider,
+            AppCommand::Disable,
+            fail(),
+            AppCommand::Recover,
+            AppCommand::Quit,
+        ]
+    }
+
+    /// Every transition that moves the status, with its effect
+    fn legal() -> Vec<(AppStatus, AppCommand, AppStatus, Effect)> {
+        use AppCommand::*;
+        use AppStatus::*;
+        vec![
+            (LoadingModel, ModelLoaded, WaitingForHotkey, Effect::Nothing),
+            (
+                WaitingForHotkey,
+                ReloadModel,
+                LoadingModel,
+                Effect::LoadModel,
+            ),
+            (
+                WaitingForHotkey,
+                Toggle,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                WaitingForHotkey,
+                StartRecording,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                WaitingForHotkey,
+                ToggleToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                WaitingForHotkey,
+                StartRecordingToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                Recording { clip: 3 },
+                Toggle,
+                Transcribing { clip: 3 },
+                Effect::Transcribe,
+            ),
+            (
+                Recording { clip: 3 },
+                StopRecording,
+                Transcribing { clip: 3 },
+                Effect::Transcribe,
+            ),
+            (
+                Recording { clip: 3 },
+                ToggleToClipboard,
+                Transcribing { clip: 3 },
+                Effect::Transcribe,
+            ),
+            (
+                Recording { clip: 3 },
+                Cancel,
+                WaitingForHotkey,
+                Effect::Discard,
+            ),
+            (
+                Recording { clip: 3 },
+                TranscriptionDone { clip: 2 },
+                Recording { clip: 3 },
+                Effect::Nothing,
+            ),
+            (
+                Transcribing { clip: 3 },
+                Toggle,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                Transcribing { clip: 3 },
+                StartRecording,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                Transcribing { clip: 3 },
+                ToggleToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                Transcribing { clip: 3 },
+                StartRecordingToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                Transcribing { clip: 3 },
+                TranscriptionDone { clip: 3 },
+                WaitingForHotkey,
+                Effect::Nothing,
+            ),
+            (
+                Transcribing { clip: 3 },
+                Abandon,
+                WaitingForHotkey,
+                Effect::Nothing,
+            ),
+            (WaitingForHotkey, Disable, Disabled, Effect::Nothing),
+            (Disabled, Recover, WaitingForHotkey, Effect::Nothing),
+            (LoadingModel, fail(), failed(), Effect::Nothing),
+            (WaitingForHotkey, fail(), failed(), Effect::Nothing),
+            (failed(), Recover, WaitingForHotkey, Effect::Nothing),
+            (failed(), ReloadModel, LoadingModel, Effect::LoadModel),
+            (
+                WaitingForHotkey,
+                CycleProvider,
+                LoadingModel,
+                Effect::LoadModel,
+            ),
+            (failed(), CycleProvider, LoadingModel, Effect::LoadModel),
+            (
+                failed(),
+                Toggle,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                failed(),
+                StartRecording,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                failed(),
+                ToggleToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+            (
+                failed(),
+                StartRecordingToClipboard,
+                Recording { clip: NEXT },
+                Effect::StartRecording,
+            ),
+        ]
+    }
+
+    fn fail() -> AppCommand {
+        AppCommand::Fail {
+            reason: "Hotkeys unavailable".to_string(),
+        }
+    }
+
+    #[test]
+    fn only_the_legal_transitions_are_allowed() {
+        let legal = legal();
+        for status in statuses() {
+            for command in commands() {
+                let result = transition(&status, &command, NEXT);
+                let expected = legal
+                    .iter()
+                    .find(|(from, with, _, _)| *from == status && *with == command);
+
+                match expected {
+                    Some((_, _, to, effect)) => {
+                        assert_eq!(result, Ok((to.clone(), *effect)), "{status:?} {command:?}")
+                    }
+                    None if !command.changes_status() => assert_eq!(
+                        result,
+                        Ok((status.clone(), Effect::Nothing)),
+                        "{status:?} {command:?}"
+                    ),
+                    None => {
+                        let refused = result.expect_err(&format!("{status:?} {command:?}"));
+                        assert_eq!(refused.from, status);
+                        assert_eq!(refused.command, command);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn recordings_never_overlap() {
+        for status in statuses() {
+            for command in commands() {
+                if let (AppStatus::Recording { clip }, Ok((AppStatus::Recording { clip: to }, _))) =
+                    (&status, transition(&status, &command, NEXT))
+                {
+                    assert_eq!(clip, &to, "{command:?} started a second recording");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_a_reload_when_idle_goes_back_to_loading_the_model() {
+        for status in statuses().into_iter().skip(1) {
+            for command in commands() {
+                let result = transition(&status, &command, NEXT);
+                let reloading =
+                    matches!(
+                        status,
+                        AppStatus::WaitingForHotkey | AppStatus::Error { .. }
+                    ) && matches!(command, AppCommand::ReloadModel | AppCommand::CycleProvider);
+                assert_eq!(
+                    matches!(result, Ok((AppStatus::LoadingModel, _))),
+                    reloading,
+                    "{status:?} {command:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_records_while_disabled() {
+        for command in commands() {
+            let result = transition(&AppStatus::Disabled, &command, NEXT);
+            assert!(
+                !matches!(result, Ok((AppStatus::Recording { .. }, _))),
+                "{command:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn providers_dont_change_under_a_transcription() {
+        let refused = transition(
+            &AppStatus::Transcribing { clip: 3 },
+            &AppCommand::CycleProvider,
+            NEXT,
+        )
+        .unwrap_err();
+        assert_eq!(
+            refused.to_string(),
+            "CycleProvider refused while transcribing: Finish the current dictation first"
+        );
+        assert!(transition(&AppStatus::LoadingModel, &AppCommand::CycleProvider, NEXT).is_err());
+    }
+
+    #[test]
+    fn a_refusal_says_why() {
+        let refused =
+            transition(&AppStatus::WaitingForHotkey, &AppCommand::Cancel, NEXT).unwrap_err();
+        assert_eq!(
+            refused.to_string(),
+            "Cancel refused while ready: Not recording"
+        );
+    }
+}
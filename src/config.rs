@@ -0,0 +1,838 @@
+// Configuration file (TOML) stored in the platform config directory
+
+use crate::audio::ResampleQuality;
+use crate::webhook::WebhookMode;
+use crate::{HOTKEY_HINT, HotkeyMode, OnEmpty, parse_hotkey};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file inside the app's config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Model directory used when the config doesn't set one
+pub const DEFAULT_MODEL_DIR: &str = "./model";
+
+/// Recognizer threads when running on the CPU
+pub const DEFAULT_CPU_THREADS: i32 = 4;
+
+/// Recognizer threads when running on a GPU provider
+pub const DEFAULT_GPU_THREADS: i32 = 1;
+
+/// Delay before typing, giving the target window time to take focus
+pub const DEFAULT_TYPING_DELAY_MS: u64 = 100;
+
+/// Seconds a transcription waits for a window it can be typed into (Windows)
+pub const DEFAULT_UNREACHABLE_HOLD_SECS: u64 = 60;
+
+/// Longest recording in seconds before the watchdog may step in
+pub const DEFAULT_MAX_RECORDING_SECS: u64 = 600;
+
+/// Milliseconds of audio dropped when a recording starts, where the hotkey's click is
+pub const DEFAULT_START_COOLDOWN_MS: u64 = 60;
+
+/// Level, in dBFS, a recording's peak is brought to when normalizing
+pub const DEFAULT_NORMALIZE_TARGET_DB: f32 = -3.0;
+
+/// Most a loud recording is turned down when normalizing, in dB
+pub const DEFAULT_NORMALIZE_MIN_GAIN_DB: f32 = -6.0;
+
+/// Most a quiet recording is turned up when normalizing, in dB
+pub const DEFAULT_NORMALIZE_MAX_GAIN_DB: f32 = 20.0;
+
+/// Times the preferred GPU provider is tried again before moving on to the next
+pub const DEFAULT_LOAD_RETRIES: u32 = 2;
+
+/// Wait before the first retry of the preferred GPU provider, doubling after each
+pub const DEFAULT_LOAD_RETRY_DELAY_MS: u64 = 500;
+
+/// Seconds a transcription may take before the watchdog abandons it
+pub const DEFAULT_TRANSCRIPTION_TIMEOUT_SECS: u64 = 120;
+
+/// Size in MiB at which a day's event log is rotated
+pub const DEFAULT_EVENTS_MAX_MB: u64 = 10;
+
+/// Settings loaded from the configuration file
+///
+/// Every field is optional; missing values keep the built-in defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Profile applied at startup unless --profile picks another
+    pub profile: Option<String>,
+    pub model: ModelConfig,
+    pub audio: AudioConfig,
+    pub hotkey: HotkeyConfig,
+    pub output: OutputConfig,
+    pub text: TextConfig,
+    pub ui: UiConfig,
+    pub log: LogConfig,
+    pub history: HistoryConfig,
+    pub overlay: OverlayConfig,
+    pub sounds: SoundsConfig,
+    pub webhook: WebhookConfig,
+    pub metrics: MetricsConfig,
+    pub dbus: DbusConfig,
+    /// Canned text shown in the tray's Phrases submenu, keyed by label
+    pub phrases: BTreeMap<String, String>,
+    /// Named sets of overrides for any of the sections above, e.g. `[profiles.meetings.output]`
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// Directory containing the encoder, decoder, joiner and tokens files
+    pub dir: Option<String>,
+    /// Model files, relative to `dir` unless absolute
+    pub encoder: Option<String>,
+    pub decoder: Option<String>,
+    pub joiner: Option<String>,
+    pub tokens: Option<String>,
+    /// Execution providers to try in order, e.g. ["cuda", "cpu"]
+    pub providers: Option<Vec<String>>,
+    /// Recognizer threads on the CPU and on GPU providers
+    pub cpu_threads: Option<i32>,
+    pub gpu_threads: Option<i32>,
+    /// Seconds a transcription may take before it's abandoned as hung
+    pub transcription_timeout_secs: Option<u64>,
+    /// Times the first GPU provider is tried again when it fails to load (e.g. a
+    /// driver that isn't ready yet after boot)
+    pub load_retries: Option<u32>,
+    /// Wait before the first retry in milliseconds, doubled for each one after
+    pub load_retry_delay_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Name of the input device; the system default is used when unset
+    pub device: Option<String>,
+    /// Input sample rate in Hz, like --input-rate
+    pub sample_rate: Option<u32>,
+    /// Input buffer size in frames, like --buffer-size
+    pub buffer_size: Option<u32>,
+    /// Input channel to record (from 1); all channels are mixed down when unset
+    pub channel: Option<usize>,
+    /// Longest recording in seconds before the watchdog may discard it
+    pub max_recording_secs: Option<u64>,
+    /// Milliseconds of audio ignored after a recording starts (the hotkey's click)
+    pub start_cooldown_ms: Option<u64>,
+    /// "high" or "fast" conversion of the input to the model's 16 kHz
+    pub resample_quality: Option<ResampleQuality>,
+    /// Bring each recording's peak to `normalize_target_db` before transcribing
+    pub normalize: Option<bool>,
+    /// Peak level in dBFS (0 or below) that normalization aims for
+    pub normalize_target_db: Option<f32>,
+    /// Most normalization turns a recording down, in dB (0 or below)
+    pub normalize_min_gain_db: Option<f32>,
+    /// Most normalization turns a recording up, in dB
+    pub normalize_max_gain_db: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Key that starts/stops recording (e.g. "F9")
+    pub key: Option<String>,
+    /// Key that opens the history window (e.g. "F7"); disabled when unset
+    pub history: Option<String>,
+    /// Key that quits the app (e.g. "F12"); disabled when unset
+    pub quit: Option<String>,
+    /// Key that reloads the model with the next provider (e.g. "F10"); disabled when unset
+    pub provider: Option<String>,
+    /// Key that transcribes the recording so far as a preview (e.g. "F11"); disabled when unset
+    pub snapshot: Option<String>,
+    /// Key that records like `key` but copies the text instead (e.g. "F8"); disabled when unset
+    pub clipboard: Option<String>,
+    /// Keep hotkey presses from the focused app; by default only function keys are
+    pub block: Option<bool>,
+    /// "toggle" recording with each press, or "hold" to record while the key is down
+    pub mode: Option<HotkeyMode>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Where transcriptions go: "type" or "file"
+    pub mode: Option<OutputTarget>,
+    /// Text file that transcriptions are appended to with mode = "file"
+    pub file: Option<String>,
+    /// Line written before each appended transcription, with `{timestamp}`,
+    /// `{secs}` and `{provider}` filled in; none when empty
+    pub file_header: Option<String>,
+    /// What to do when a transcription is empty: "ignore", "beep", "notify" or "retry"
+    pub on_empty: Option<OnEmpty>,
+    /// Milliseconds to wait before typing
+    pub typing_delay_ms: Option<u64>,
+    /// Re-activate the window that was focused when recording started
+    pub restore_focus: Option<bool>,
+    /// Check that input can be sent to the focused window before typing into it
+    pub typing_probe: Option<bool>,
+    /// Apps never typed into (process names or window classes); the text is copied instead
+    pub blocked_apps: Option<Vec<String>>,
+    /// Seconds a transcription is held while a UAC prompt or elevated window has
+    /// focus, before it's copied instead; 0 copies it right away
+    pub unreachable_hold_secs: Option<u64>,
+}
+
+/// Clean-ups applied to transcriptions before they're output
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextConfig {
+    /// Remove spaces before punctuation and collapse repeated spaces
+    pub tidy: Option<bool>,
+    /// Text typed before every transcription
+    pub prefix: Option<String>,
+    /// Text typed after every transcription
+    pub suffix: Option<String>,
+}
+
+/// What goes into the log file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// "error", "warn", "info", "debug", "trace" or "off"
+    pub level: Option<String>,
+    /// Write transcriptions to the log file even below debug level
+    pub transcripts: Option<bool>,
+    /// Also write a JSONL event log, one file per day
+    pub events: Option<bool>,
+    /// Size in MiB at which a day's event log is rotated
+    pub events_max_mb: Option<u64>,
+}
+
+/// Where transcriptions go, as written in the config file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputTarget {
+    /// Type into the focused window
+    #[default]
+    Type,
+    /// Append to `output.file`
+    File,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Show the last transcription in the tray tooltip
+    pub tooltip_preview: Option<bool>,
+    /// Notify when GPU providers fail and the model runs on the CPU
+    pub fallback_notification: Option<bool>,
+    /// Finish the current dictation on Quit instead of asking
+    pub finish_on_quit: Option<bool>,
+    /// What a left click on the tray icon does
+    pub left_click: Option<TrayClick>,
+    /// What a double click on the tray icon does, see `double_click_action`
+    pub double_click: Option<TrayClick>,
+    pub icons: IconsConfig,
+    pub status_display: StatusDisplayConfig,
+}
+
+impl UiConfig {
+    /// Action of a double click, Nothing while a left click opens the menu (which
+    /// the first click of the two already did)
+    pub fn double_click_action(&self) -> TrayClick {
+        if self.left_click.unwrap_or_default() == TrayClick::Menu {
+            return TrayClick::Nothing;
+        }
+        self.double_click.unwrap_or(TrayClick::Settings)
+    }
+}
+
+/// Action of a click on the tray icon
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrayClick {
+    /// Open the tray menu, as a right click does (left click only)
+    #[default]
+    Menu,
+    /// Start or stop recording, like the hotkey
+    Toggle,
+    /// Open the settings window
+    Settings,
+    /// Open the history window
+    History,
+    /// Do nothing
+    Nothing,
+}
+
+/// Tray icon files; a generated icon is used when a file can't be loaded
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconsConfig {
+    pub loading: Option<String>,
+    pub idle: Option<String>,
+    pub recording: Option<String>,
+    pub transcribing: Option<String>,
+}
+
+/// Per status, whether entering it changes the tray icon, the tooltip, both or
+/// neither; see `StatusDisplay`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusDisplayConfig {
+    pub loading: Option<StatusDisplay>,
+    pub idle: Option<StatusDisplay>,
+    pub recording: Option<StatusDisplay>,
+    pub transcribing: Option<StatusDisplay>,
+}
+
+/// What a status shows in the tray. Without the icon the idle icon stays, and
+/// without the tooltip it only says "Esponquen".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusDisplay {
+    #[default]
+    Both,
+    Icon,
+    Tooltip,
+    None,
+}
+
+impl StatusDisplay {
+    pub fn shows_icon(self) -> bool {
+        matches!(self, StatusDisplay::Both | StatusDisplay::Icon)
+    }
+
+    pub fn shows_tooltip(self) -> bool {
+        matches!(self, StatusDisplay::Both | StatusDisplay::Tooltip)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Number of recent transcriptions listed in the history window
+    pub window_entries: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayConfig {
+    /// Show a status dot on screen while recording or transcribing
+    pub enabled: Option<bool>,
+    /// Screen corner of the dot
+    pub corner: Option<OverlayCorner>,
+}
+
+/// Tones played when a dictation ends, by outcome
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundsConfig {
+    pub success: ToneConfig,
+    pub empty: ToneConfig,
+    pub error: ToneConfig,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneConfig {
+    pub enabled: Option<bool>,
+    /// Pitch in Hz
+    pub frequency_hz: Option<f32>,
+    /// Length in milliseconds
+    pub duration_ms: Option<u64>,
+}
+
+/// Posting transcriptions to a URL
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Post transcriptions while a URL is set, unless this is false
+    pub enabled: Option<bool>,
+    pub url: Option<String>,
+    /// Post on top of typing, or instead of it
+    pub mode: Option<WebhookMode>,
+    /// Seconds a request may take
+    pub timeout_secs: Option<u64>,
+}
+
+/// Serving the status and counters over HTTP for monitoring
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Port on 127.0.0.1 for `/status` and `/metrics`; off when unset
+    pub port: Option<u16>,
+}
+
+/// The D-Bus service on Linux, see `dbus`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbusConfig {
+    /// Offer `dev.esponquen.Esponquen1` on the session bus
+    pub enabled: Option<bool>,
+    /// Put the text in TranscriptionCompleted signals, which any app on the bus sees
+    pub emit_text: Option<bool>,
+}
+
+/// Corner of the primary display where the status overlay is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ModelConfig {
+    /// Whether `other` loads the same model the same way, ignoring the settings
+    /// that apply without reloading it
+    pub fn loads_same_model(&self, other: &ModelConfig) -> bool {
+        let loaded = |model: &ModelConfig| ModelConfig {
+            transcription_timeout_secs: None,
+            load_retries: None,
+            load_retry_delay_ms: None,
+            ..model.clone()
+        };
+        loaded(self) == loaded(other)
+    }
+}
+
+impl Config {
+    /// Model directory, falling back to the default, resolved like every config path
+    pub fn model_dir(&self) -> PathBuf {
+        crate::app_paths().resolve(self.model.dir.as_deref().unwrap_or(DEFAULT_MODEL_DIR))
+    }
+
+    /// These settings with the overrides of profile `name` applied on top
+    pub fn with_profile(&self, name: &str) -> Result<Config, String> {
+        let overrides = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| format!("Unknown profile '{}'", name))?;
+
+        let mut merged = toml::Value::try_from(self).map_err(|e| e.to_string())?;
+        if let Some(table) = merged.as_table_mut() {
+            merge_tables(table, overrides);
+        }
+        merged
+            .try_into()
+            .map_err(|e| format!("Invalid profile '{}': {}", name, e))
+    }
+}
+
+/// Copy the values of `overrides` into `base`, descending into tables they share
+fn merge_tables(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_tables(base, overrides);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Commented template written by --write-default-config; every value is the default
+pub const DEFAULT_CONFIG_TEMPLATE: &str = r###"# Esponquen configuration. Every setting is optional; remove the leading # to
+# change one. Command line flags take precedence over this file.
+
+# Profile from [profiles] applied at startup; --profile and the tray pick another
+# profile = "dictation"
+
+[model]
+# Directory with the model files
+# dir = "./model"
+# Model files, relative to dir unless absolute
+# encoder = "encoder.int8.onnx"
+# decoder = "decoder.int8.onnx"
+# joiner = "joiner.int8.onnx"
+# tokens = "tokens.txt"
+# Execution providers tried in order; "cpu" always works
+# (default: ["dml", "cuda", "cpu"] on Windows, ["coreml", "cpu"] on macOS,
+# ["cuda", "rocm", "cpu"] on Linux)
+# providers = ["cuda", "cpu"]
+# cpu_threads = 4
+# gpu_threads = 1
+# Give up on a transcription that takes longer than this, as the model has hung
+# transcription_timeout_secs = 120
+# Try the first GPU provider this many more times when it fails to load, as the
+# driver may not be ready right after boot, waiting load_retry_delay_ms and then
+# twice as long each time; 0 moves on to the next provider right away
+# load_retries = 2
+# load_retry_delay_ms = 500
+
+[audio]
+# Input device name; the system default when unset
+# device = "USB Microphone"
+# sample_rate = 48000
+# buffer_size = 1024
+# Record one channel of a multi-channel interface (from 1); all are mixed when unset
+# channel = 1
+# max_recording_secs = 600
+# Ignore the first milliseconds of each recording, where the hotkey's click is;
+# 0 keeps everything
+# start_cooldown_ms = 60
+# "high" filters the input when converting it to 16 kHz; "fast" interpolates
+# linearly, which is cheaper on low-power devices but can cost accuracy
+# resample_quality = "high"
+# Scale each recording so its loudest sample reaches normalize_target_db (dBFS),
+# turning it up by at most normalize_max_gain_db, so a near-silent one isn't
+# blown up into noise, and down by at most normalize_min_gain_db
+# normalize = false
+# normalize_target_db = -3.0
+# normalize_min_gain_db = -6.0
+# normalize_max_gain_db = 20.0
+
+[hotkey]
+# F1-F24, PageUp, PageDown, Home, End, Insert, Pause, ScrollLock, or Unknown(<code>)
+# for a key without a name (the log shows the code at debug level when it's pressed)
+# key = "F6"
+# "toggle" starts and stops recording with each press; "hold" records while the
+# key is held down, e.g. on a foot pedal
+# mode = "toggle"
+# history = "F7"
+# quit = "F12"
+# Reload the model with the next provider (CPU, then each GPU one), to compare them
+# provider = "F10"
+# Show a preview of the recording so far without stopping; each press transcribes
+# everything recorded until then, so it costs CPU (or GPU) time on long dictations
+# snapshot = "F11"
+# Record like the hotkey, but copy the transcription to the clipboard whatever
+# the output mode
+# clipboard = "F8"
+# block = true
+
+[output]
+# "type" into the focused window, or append each transcription to a text "file"
+# mode = "type"
+# file = "./dictations.txt"
+# Line written before each transcription appended to the file: {timestamp} is when
+# it finished (UTC), {secs} how long the recording was and {provider} what
+# transcribed it, e.g. "## {timestamp} ({secs} s)"; empty for plain appends
+# file_header = ""
+# "ignore", "beep", "notify" or "retry"
+# on_empty = "ignore"
+# typing_delay_ms = 100
+# restore_focus = false
+# Check that the focused window accepts input before typing, so text isn't lost
+# half-typed (a no-op key event, refused by elevated windows and the secure desktop)
+# typing_probe = true
+# Never type into these apps, matched against the focused window's process name
+# (e.g. "KeePassXC.exe", "keepassxc") or window class; the text is copied instead
+# blocked_apps = ["KeePassXC", "WindowsTerminal"]
+# Windows: while a UAC prompt or an app running as administrator has focus, the
+# text can't be typed; it's held until a window it can reach has focus, for up to
+# this many seconds, and then copied to the clipboard (0 copies it right away)
+# unreachable_hold_secs = 60
+
+[text]
+# Remove spaces before punctuation and collapse repeated spaces
+# tidy = true
+# Put around every transcription, after tidying, e.g. "// " to dictate code comments
+# prefix = ""
+# suffix = ""
+
+[ui]
+# tooltip_preview = true
+# fallback_notification = true
+# finish_on_quit = false
+# "menu", "toggle" (start or stop recording), "settings", "history" or "nothing"
+# left_click = "menu"
+# The same but "menu"; ignored with left_click = "menu", the first click opens the menu
+# double_click = "settings"
+
+[log]
+# "error", "warn", "info", "debug" or "trace"; --log-level overrides it
+# level = "info"
+# Also write transcriptions to the log file (always written at debug level)
+# transcripts = false
+# Machine-readable JSONL event log (logs/events/events-<date>.jsonl)
+# events = false
+# Rotate a day's event log once it reaches this many MiB
+# events_max_mb = 10
+
+[ui.icons]
+# loading = "./icons/loading.png"
+# idle = "./icons/not-recording.png"
+# recording = "./icons/recording.png"
+# transcribing = "./icons/not-recording.png"
+
+# Whether each status changes the tray icon, the tooltip, "both" or "none"; a
+# status without the icon shows the idle one, e.g. recording = "tooltip"
+[ui.status_display]
+# loading = "both"
+# idle = "both"
+# recording = "both"
+# transcribing = "both"
+
+[history]
+# window_entries = 20
+
+[overlay]
+# enabled = false
+# "top-left", "top-right", "bottom-left" or "bottom-right"
+# corner = "top-right"
+
+# Tones played when a dictation ends, to follow along by ear
+[sounds.success]
+# enabled = false
+# frequency_hz = 880
+# duration_ms = 80
+
+# Nothing recognized (on_empty = "beep" plays this tone too)
+[sounds.empty]
+# enabled = false
+# frequency_hz = 330
+# duration_ms = 200
+
+# No microphone, no audio, a failed transcription or text that couldn't be typed
+[sounds.error]
+# enabled = false
+# frequency_hz = 220
+# duration_ms = 450
+
+# POST every transcription as JSON: text, timestamp, duration_secs and provider
+[webhook]
+# url = "http://localhost:8080/dictations"
+# Set to false to stop posting without removing the URL
+# enabled = true
+# "also" to type as well, or "instead" to only post
+# mode = "also"
+# timeout_secs = 5
+
+[metrics]
+# Serve GET /status (JSON) and GET /metrics (Prometheus) on 127.0.0.1 at this port
+# port = 9464
+
+[dbus]
+# Linux: offer dev.esponquen.Esponquen1 on the session bus, for shell extensions
+# and scripts (StartRecording, StopRecording, Toggle, GetStatus, SetHotkey)
+# enabled = true
+# Include the text in TranscriptionCompleted signals; any app on the session bus
+# can read them, so it's off and the signal carries an empty string
+# emit_text = false
+
+[phrases]
+# "Thanks" = "Thanks, I'll take a look and get back to you."
+
+# Profiles override any of the settings above while they're active
+# [profiles.meetings.audio]
+# device = "Stereo Mix"
+# [profiles.meetings.output]
+# mode = "file"
+# file = "./meetings.txt"
+#
+# [profiles.dictation.output]
+# mode = "type"
+"###;
+
+/// Location of the configuration file, or None if there is no config directory
+pub fn config_path() -> Option<PathBuf> {
+    crate::app_paths().config_file
+}
+
+/// Config file to use instead of the default location
+pub const ENV_CONFIG: &str = "ESPONQUEN_CONFIG";
+/// Overrides `model.dir`
+pub const ENV_MODEL_DIR: &str = "ESPONQUEN_MODEL_DIR";
+/// Overrides `model.providers`; one provider or a comma-separated list
+pub const ENV_PROVIDER: &str = "ESPONQUEN_PROVIDER";
+/// Overrides `hotkey.key`
+pub const ENV_HOTKEY: &str = "ESPONQUEN_HOTKEY";
+
+/// Value of an environment variable; unset and empty are the same
+fn env_var(name: &str) -> Result<Option<String>, String> {
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(format!("{} is not valid UTF-8", name)),
+    }
+}
+
+/// Config file named by ESPONQUEN_CONFIG, if set
+pub fn env_config_path() -> Result<Option<PathBuf>, String> {
+    let path = env_var(ENV_CONFIG)?.map(PathBuf::from);
+    if let Some(path) = &path {
+        debug!("Config file {} from {}", path.display(), ENV_CONFIG);
+    }
+    Ok(path)
+}
+
+/// Override config file values with the ESPONQUEN_* environment variables
+///
+/// Command line flags are applied afterwards, so they win over both.
+pub fn apply_env(config: &mut Config) -> Result<(), String> {
+    if let Some(dir) = env_var(ENV_MODEL_DIR)? {
+        debug!("model.dir = {} from {}", dir, ENV_MODEL_DIR);
+        config.model.dir = Some(dir);
+    }
+
+    if let Some(value) = env_var(ENV_PROVIDER)? {
+        let providers: Vec<String> = value
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if providers.is_empty() {
+            return Err(format!("{}='{}' names no provider", ENV_PROVIDER, value));
+        }
+        debug!("model.providers = {:?} from {}", providers, ENV_PROVIDER);
+        config.model.providers = Some(providers);
+    }
+
+    if let Some(key) = env_var(ENV_HOTKEY)? {
+        if parse_hotkey(&key).is_none() {
+            return Err(format!(
+                "{}='{}' is not a supported hotkey, expected {}",
+                ENV_HOTKEY, key, HOTKEY_HINT
+            ));
+        }
+        debug!("hotkey.key = {} from {}", key, ENV_HOTKEY);
+        config.hotkey.key = Some(key);
+    }
+
+    Ok(())
+}
+
+/// Load the configuration file at `path`, warning about keys it doesn't know
+pub fn load(path: &Path) -> Result<Config, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut unknown_keys = Vec::new();
+    let config = serde_ignored::deserialize(toml::Deserializer::new(&contents), |key| {
+        unknown_keys.push(key.to_string())
+    })
+    .map_err(|e| parse_error(path, &contents, &e))?;
+
+    for key in unknown_keys {
+        warn!(
+            "Unknown setting '{}' in {}, ignoring it",
+            key,
+            path.display()
+        );
+    }
+
+    Ok(config)
+}
+
+/// Describe a parse error with the file and line it happened on
+fn parse_error(path: &Path, contents: &str, error: &toml::de::Error) -> String {
+    match error.span() {
+        Some(span) => {
+            let line = contents[..span.start].matches('\n').count() + 1;
+            format!(
+                "Failed to parse {}, line {}: {}",
+                path.display(),
+                line,
+                error.message()
+            )
+        }
+        None => format!("Failed to parse {}: {}", path.display(), error.message()),
+    }
+}
+
+/// Write the commented default template to `path`, refusing to overwrite a file
+pub fn write_default(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+
+    std::fs::write(path, DEFAULT_CONFIG_TEMPLATE)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Set `key` under `[section]` in the file at `path`, leaving the rest of it as written
+///
+/// Used to remember changes made from the tray. Comments and the order of the
+/// file are kept; the section is added at the end if it's missing.
+pub fn update_setting(
+    path: &Path,
+    section: &str,
+    key: &str,
+    value: &toml::Value,
+) -> Result<(), String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    let setting = format!("{} = {}", key, value);
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    match lines.iter().position(|line| line.trim() == header) {
+        Some(start) => {
+            // The section runs until the next header
+            let end = lines[start + 1..]
+                .iter()
+                .position(|line| line.trim_start().starts_with('['))
+                .map_or(lines.len(), |offset| start + 1 + offset);
+            let existing = (start + 1..end).find(|&i| {
+                lines[i]
+                    .split_once('=')
+                    .is_some_and(|(name, _)| name.trim() == key)
+            });
+            match existing {
+                Some(i) => lines[i] = setting,
+                None => {
+                    // After the section's last line, before the blank lines that separate it
+                    let at = (start + 1..end)
+                        .rev()
+                        .find(|&i| !lines[i].trim().is_empty())
+                        .map_or(start + 1, |i| i + 1);
+                    lines.insert(at, setting);
+                }
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(setting);
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(path, lines.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Write the configuration file to `path`, creating its directory if needed
+pub fn save(config: &Config, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+
+    let contents =
+        toml::to_string_pretty(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_template_parses_without_unknown_keys() {
+        let config = toml::from_str::<Config>(DEFAULT_CONFIG_TEMPLATE);
+        assert!(config.is_ok(), "{:?}", config.err());
+
+        let mut unknown_keys = Vec::new();
+        let _: Config =
+            serde_ignored::deserialize(toml::Deserializer::new(DEFAULT_CONFIG_TEMPLATE), |key| {
+                unknown_keys.push(key.to_string())
+            })
+            .unwrap();
+        assert!(unknown_keys.is_empty(), "{:?}", unknown_keys);
+    }
+}
@@ -0,0 +1,189 @@
+// Tray icon shown instead of the app when the config has fatal problems.
+//
+// Started from the tray, Esponquen has no console to print the problems to, so
+// exiting would look like it never started. Instead it shows an error icon whose
+// tooltip and menu list the problems. "Check again" re-reads the config and,
+// once it's fixed, restarts the app with the same arguments.
+
+use crate::instance::InstanceLock;
+use crate::{EVENT_LOOP_WAKER, QUIT_REQUESTED, wake_event_loop};
+use esponquen::app_context;
+use esponquen::command::{AppCommand, transition};
+use esponquen::icons::{ERROR_COLOR, ICON_SIZE, circle_rgba};
+use esponquen::notification::notify;
+use esponquen::sync::lock;
+use esponquen::text::one_line_preview;
+use esponquen::validate::{Problem, Severity, has_fatal};
+use log::{error, info, warn};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::channel;
+use tray_icon::{
+    Icon, TrayIcon, TrayIconBuilder,
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+};
+use winit::event_loop::{ControlFlow, EventLoop};
+
+// Longest problem text shown in a menu item
+const MENU_TEXT_CHARS: usize = 80;
+
+/// Show the problems in the tray until the config is fixed or the user quits
+///
+/// `recheck` loads the config again and validates it. `instance` is released
+/// before restarting so the new process can take over.
+pub fn run(
+    problems: Vec<Problem>,
+    instance: Option<InstanceLock>,
+    recheck: impl Fn() -> Result<Vec<Problem>, String> + 'static,
+) -> ! {
+    notify("Esponquen couldn't start", &summary(&problems));
+
+    // The app never gets past loading the model; its status says why
+    let ctx = app_context();
+    let failure = AppCommand::Fail {
+        reason: format!("Config error: {}", summary(&problems)),
+    };
+    match transition(&ctx.status(), &failure, ctx.next_clip()) {
+        Ok((status, _)) => ctx.set_status(status),
+        Err(invalid) => warn!("{}", invalid),
+    }
+
+    let event_loop = EventLoop::new().unwrap();
+    *lock(&EVENT_LOOP_WAKER) = Some(event_loop.create_proxy());
+    let menu = Menu::new();
+    let mut problem_items = Vec::new();
+    let check_item = MenuItem::new("Check again", true, None);
+    let quit_item = MenuItem::new("Quit", true, None);
+    menu.append(&PredefinedMenuItem::separator()).ok();
+    menu.append(&check_item).ok();
+    menu.append(&quit_item).ok();
+
+    let icon = Icon::from_rgba(circle_rgba(ICON_SIZE, ERROR_COLOR), ICON_SIZE, ICON_SIZE)
+        .expect("Generated icon has valid dimensions");
+    let tray_icon = TrayIconBuilder::new()
+        .with_menu(Box::new(menu.clone()))
+        .with_icon(icon)
+        .build()
+        .unwrap_or_else(|e| {
+            error!("Failed to create tray icon: {}", e);
+            std::process::exit(2);
+        });
+    show_problems(&tray_icon, &menu, &mut problem_items, &problems);
+
+    let check_id = check_item.id().clone();
+    let quit_id = quit_item.id().clone();
+    let (menu_tx, menu_channel) = channel();
+    MenuEvent::set_event_handler(Some(move |event| {
+        menu_tx.send(event).ok();
+        wake_event_loop();
+    }));
+    let mut instance = instance;
+
+    event_loop
+        .run(move |_, elwt| {
+            // Woken by menu clicks and quit requests, nothing else needs handling
+            elwt.set_control_flow(ControlFlow::Wait);
+
+            if QUIT_REQUESTED.load(Ordering::SeqCst) {
+                std::process::exit(2);
+            }
+
+            let Ok(event) = menu_channel.try_recv() else {
+                return;
+            };
+
+            if event.id == quit_id {
+                std::process::exit(2);
+            } else if event.id == check_id {
+                match recheck() {
+                    Ok(problems) if !has_fatal(&problems) => {
+                        info!("Config fixed, restarting");
+                        drop(instance.take());
+                        restart();
+                    }
+                    Ok(problems) => {
+                        log_problems(&problems);
+                        show_problems(&tray_icon, &menu, &mut problem_items, &problems);
+                    }
+                    Err(e) => {
+                        error!("{}", e);
+                        notify("Esponquen couldn't start", &e);
+                    }
+                }
+            }
+        })
+        .unwrap();
+
+    std::process::exit(2);
+}
+
+/// Console marker for a problem
+pub fn symbol(problem: &Problem) -> &'static str {
+    match problem.severity {
+        Severity::Fatal => "✗",
+        Severity::Warning => "⚠",
+    }
+}
+
+/// Log each problem at the level matching its severity
+pub fn log_problems(problems: &[Problem]) {
+    for problem in problems {
+        match problem.severity {
+            Severity::Fatal => error!("{}", problem),
+            Severity::Warning => warn!("{}", problem),
+        }
+    }
+}
+
+/// First fatal problem, plus how many more there are
+fn summary(problems: &[Problem]) -> String {
+    let fatal: Vec<&Problem> = problems
+        .iter()
+        .filter(|p| p.severity == Severity::Fatal)
+        .collect();
+    match fatal.as_slice() {
+        [] => "No problems".to_string(),
+        [only] => only.to_string(),
+        [first, rest @ ..] => format!("{} (and {} more)", first, rest.len()),
+    }
+}
+
+/// Put the problems in the tooltip and at the top of the menu
+fn show_problems(
+    tray_icon: &TrayIcon,
+    menu: &Menu,
+    items: &mut Vec<MenuItem>,
+    problems: &[Problem],
+) {
+    tray_icon
+        .set_tooltip(Some(format!(
+            "Esponquen - Config error: {}",
+            summary(problems)
+        )))
+        .ok();
+
+    for item in items.drain(..) {
+        menu.remove(&item).ok();
+    }
+
+    for (position, problem) in problems.iter().enumerate() {
+        let text = one_line_preview(&format!("{} {}", symbol(problem), problem), MENU_TEXT_CHARS);
+        let item = MenuItem::new(text, false, None);
+        menu.insert(&item, position).ok();
+        items.push(item);
+    }
+}
+
+/// Start this executable again with the same arguments, then exit
+fn restart() -> ! {
+    let started = std::env::current_exe()
+        .and_then(|exe| Command::new(exe).args(std::env::args_os().skip(1)).spawn());
+    match started {
+        Ok(_) => std::process::exit(0),
+        Err(e) => {
+            error!("Failed to restart: {}", e);
+            notify("Esponquen couldn't restart", &e.to_string());
+            std::process::exit(2);
+        }
+    }
+}
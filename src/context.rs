@@ -0,0 +1,341 @@
+// State of one dictation pipeline: its hotkey, what it's doing, the recording in
+// progress, the provider its model runs on and how it records and transcribes
+// (`PipelineSettings`).
+//
+// The app runs a single context, `crate::app_context()`, which `set_hotkey`,
+// `set_wrap` and the other pipeline setters in lib.rs also go through. Code that
+// takes an `AppContext` can be driven against a fresh one instead, in tests or
+// for a second pipeline.
+//
+// What belongs to the app rather than to a pipeline stays in lib.rs statics:
+// the other hotkeys and how they behave, where text goes (output mode, file,
+// webhook, blocked apps), the tray and notifications, and the console.
+
+use crate::OutputMode;
+use crate::audio::{Normalization, ResampleQuality, resample_with};
+use crate::capture::{BufferPool, PooledBuffer, SampleRing};
+use crate::config;
+use crate::focus::FocusTarget;
+use crate::key::Key;
+use crate::stats::Stats;
+use crate::sync::{lock, lock_or_reset};
+use crate::text::Wrap;
+use log::warn;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Samples the ring holds, over 5 seconds of 48 kHz audio
+const AUDIO_RING_CAPACITY: usize = 1 << 18;
+
+// How often the collector moves samples from the ring to the recording buffer
+const AUDIO_COLLECT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Identifies one recording, from start to the end of its transcription
+pub type ClipId = u64;
+
+/// What the app is doing, moved on by `crate::command::transition`
+///
+/// Nothing records while `Disabled`, until the missing permissions are granted.
+/// `Error` is something dictation needs that stopped working, e.g. the hotkeys.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AppStatus {
+    LoadingModel,
+    WaitingForHotkey,
+    Recording { clip: ClipId },
+    Transcribing { clip: ClipId },
+    Disabled,
+    Error { reason: String },
+}
+
+impl AppStatus {
+    pub fn is_busy(&self) -> bool {
+        matches!(
+            self,
+            AppStatus::Recording { .. } | AppStatus::Transcribing { .. }
+        )
+    }
+
+    /// Name reported by `esponquen ctl status`
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppStatus::LoadingModel => "loading-model",
+            AppStatus::WaitingForHotkey => "ready",
+            AppStatus::Recording { .. } => "recording",
+            AppStatus::Transcribing { .. } => "transcribing",
+            AppStatus::Disabled => "disabled",
+            AppStatus::Error { .. } => "error",
+        }
+    }
+}
+
+/// How a pipeline records and transcribes
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineSettings {
+    /// Longest recording in seconds, which the recording buffer is sized for
+    pub max_recording_secs: u64,
+    /// Audio discarded when a recording starts, in milliseconds
+    pub start_cooldown_ms: u64,
+    /// How audio is converted to the model's sample rate
+    pub resample_quality: ResampleQuality,
+    pub normalization: Normalization,
+    /// Clean up spacing around punctuation (see `text::tidy`)
+    pub tidy: bool,
+    pub wrap: Wrap,
+}
+
+impl Default for PipelineSettings {
+    fn default() -> Self {
+        PipelineSettings {
+            max_recording_secs: config::DEFAULT_MAX_RECORDING_SECS,
+            start_cooldown_ms: config::DEFAULT_START_COOLDOWN_MS,
+            resample_quality: ResampleQuality::High,
+            normalization: Normalization::default(),
+            tidy: true,
+            wrap: Wrap::default(),
+        }
+    }
+}
+
+/// The recording in progress, or the last one until the next starts
+pub struct RecordingState {
+    /// Audio collected from the context's ring so far
+    pub audio_data: Vec<f32>,
+    pub sample_rate: u32,
+    /// Window that was focused when recording started (if restore focus is on)
+    pub focus_target: Option<FocusTarget>,
+    /// Where this recording's text goes instead of the configured output, if anywhere
+    pub output: Option<OutputMode>,
+    /// When the current recording started and when audio last arrived (for the watchdog)
+    pub started_at: Option<Instant>,
+    pub last_data_at: Option<Instant>,
+}
+
+/// A recording taken out of the context by `take_recording`
+pub struct TakenRecording<'a> {
+    /// Goes back to the context's pool when dropped
+    pub audio: PooledBuffer<'a>,
+    pub sample_rate: u32,
+    pub focus_target: Option<FocusTarget>,
+    pub output: Option<OutputMode>,
+    pub recorded_for: Duration,
+}
+
+/// Shared state of a dictation pipeline, see the module docs
+pub struct AppContext {
+    hotkey: Mutex<Key>,
+    settings: Mutex<PipelineSettings>,
+    /// Input channel recorded, from 1 (0: all mixed); read by the audio callback
+    input_channel: AtomicUsize,
+    /// Current status and when it was entered (for the stuck status watchdog)
+    status: Mutex<(AppStatus, Instant)>,
+    recording_state: Mutex<RecordingState>,
+    /// Set while recording; the audio callback reads it instead of locking the recording state
+    pub recording: AtomicBool,
+    /// Samples on their way from the audio callback to the recording buffer
+    pub ring: SampleRing,
+    /// Recording buffers: one being recorded into, one for a transcription still running
+    pub buffers: BufferPool,
+    /// Set while an input stream is open, recording is refused without one
+    pub input_available: AtomicBool,
+    /// Last clip id handed out by `next_clip`
+    clips: AtomicU64,
+    /// Provider the model runs on, as shown in the menu
+    pub provider_info: Mutex<String>,
+    /// Provider the model runs on, lowercase as in the config
+    pub provider_name: Mutex<String>,
+    /// Transcriptions so far, for the status and metrics
+    pub stats: Stats,
+}
+
+impl AppContext {
+    /// A pipeline loading its model, with the F6 hotkey and no input yet
+    pub fn new() -> Self {
+        AppContext {
+            hotkey: Mutex::new(Key::F6),
+            settings: Mutex::new(PipelineSettings::default()),
+            input_channel: AtomicUsize::new(0),
+            status: Mutex::new((AppStatus::LoadingModel, Instant::now())),
+            recording_state: Mutex::new(RecordingState {
+                audio_data: Vec::new(),
+                sample_rate: 16000,
+                focus_target: None,
+                output: None,
+                started_at: None,
+                last_data_at: None,
+            }),
+            recording: AtomicBool::new(false),
+            ring: SampleRing::new(AUDIO_RING_CAPACITY),
+            buffers: BufferPool::new(2),
+            input_available: AtomicBool::new(false),
+            clips: AtomicU64::new(0),
+            provider_info: Mutex::new(String::from("Initializing...")),
+            provider_name: Mutex::new(String::new()),
+            stats: Stats::new(),
+        }
+    }
+
+    pub fn hotkey(&self) -> Key {
+        *lock(&self.hotkey)
+    }
+
+    pub fn set_hotkey(&self, key: Key) {
+        *lock(&self.hotkey) = key;
+    }
+
+    /// A copy of how this pipeline records and transcribes
+    pub fn settings(&self) -> PipelineSettings {
+        lock(&self.settings).clone()
+    }
+
+    /// Change some of the settings, e.g. `ctx.update_settings(|s| s.tidy = false)`
+    pub fn update_settings(&self, update: impl FnOnce(&mut PipelineSettings)) {
+        update(&mut lock(&self.settings));
+    }
+
+    /// The input channel recorded, `None` when all channels are mixed
+    pub fn input_channel(&self) -> Option<usize> {
+        match self.input_channel.load(Ordering::Relaxed) {
+            0 => None,
+            channel => Some(channel),
+        }
+    }
+
+    /// Record one input channel (from 1), or all of them mixed with `None`
+    pub fn set_input_channel(&self, channel: Option<usize>) {
+        self.input_channel
+            .store(channel.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> AppStatus {
+        lock(&self.status).0.clone()
+    }
+
+    /// When the current status was entered
+    pub fn status_since(&self) -> Instant {
+        lock(&self.status).1
+    }
+
+    /// Change the status, restarting its clock if it's a different one
+    pub fn set_status(&self, status: AppStatus) {
+        let mut current = lock(&self.status);
+        if current.0 != status {
+            *current = (status, Instant::now());
+        }
+    }
+
+    /// An id for the next recording, unique within this context
+    pub fn next_clip(&self) -> ClipId {
+        self.clips.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    /// Lock the recording state, reset if a thread panicked in the middle of a recording
+    pub fn recording_state(&self) -> MutexGuard<'_, RecordingState> {
+        lock_or_reset(&self.recording_state, |state| self.reset_recording(state))
+    }
+
+    /// Back to not recording with nothing captured, keeping the device's sample rate
+    pub fn reset_recording(&self, state: &mut RecordingState) {
+        self.recording.store(false, Ordering::SeqCst);
+        self.ring.clear();
+        state.audio_data.clear();
+        state.focus_target = None;
+        state.output = None;
+        state.started_at = None;
+        state.last_data_at = None;
+    }
+
+    /// Start a new recording, in a buffer from the context's pool, its text
+    /// going to `output` instead of the configured output if set
+    pub fn begin_recording(&self, focus_target: Option<FocusTarget>, output: Option<OutputMode>) {
+        let settings = self.settings();
+        let mut state = self.recording_state();
+        // Late samples from the end of the last recording would otherwise start this one
+        self.ring.clear();
+        // Room for the longest allowed recording, up to the pool's cap
+        let capacity = settings.max_recording_secs as usize * state.sample_rate as usize;
+        let previous = std::mem::replace(&mut state.audio_data, self.buffers.checkout(capacity));
+        self.buffers.give_back(previous);
+        // The hotkey's click lands in the first few milliseconds of audio
+        let cooldown = settings.start_cooldown_ms as usize * state.sample_rate as usize / 1000;
+        self.ring.skip_next(cooldown);
+        state.focus_target = focus_target;
+        state.output = output;
+        state.started_at = Some(Instant::now());
+        state.last_data_at = None;
+        self.recording.store(true, Ordering::SeqCst);
+    }
+
+    /// Type the recording started at `started_at` into `target`, unless it's over
+    pub fn set_focus_target(&self, started_at: Instant, target: FocusTarget) {
+        let mut state = self.recording_state();
+        if self.is_recording() && state.started_at == Some(started_at) {
+            state.focus_target = Some(target);
+        }
+    }
+
+    /// Stop recording and take the audio, with what's still in the ring, or None
+    /// if there was no recording to stop
+    pub fn take_recording(&self) -> Option<TakenRecording<'_>> {
+        let mut state = self.recording_state();
+        if !self.recording.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        self.collect_audio(&mut state);
+        Some(TakenRecording {
+            audio: self.buffers.hold(std::mem::take(&mut state.audio_data)),
+            sample_rate: state.sample_rate,
+            focus_target: state.focus_target.take(),
+            output: state.output.take(),
+            recorded_for: state.started_at.map(|at| at.elapsed()).unwrap_or_default(),
+        })
+    }
+
+    /// Store the input's sample rate, converting audio already recorded at the old one
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        let quality = self.settings().resample_quality;
+        let mut state = self.recording_state();
+        self.collect_audio(&mut state);
+        if state.sample_rate != sample_rate && !state.audio_data.is_empty() {
+            state.audio_data =
+                resample_with(&state.audio_data, state.sample_rate, sample_rate, quality);
+        }
+        state.sample_rate = sample_rate;
+    }
+
+    /// Move what the audio callback queued into the recording buffer
+    pub fn collect_audio(&self, state: &mut RecordingState) {
+        if self.ring.drain_into(&mut state.audio_data) > 0 {
+            state.last_data_at = Some(Instant::now());
+        }
+        let dropped = self.ring.take_dropped();
+        if dropped > 0 {
+            warn!(
+                "Dropped {} samples, the recording buffer fell behind",
+                dropped
+            );
+        }
+    }
+}
+
+impl Default for AppContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collect recorded audio until the context is dropped, so the ring never fills up
+pub fn run_collector(ctx: Weak<AppContext>) {
+    loop {
+        thread::sleep(AUDIO_COLLECT_INTERVAL);
+        let Some(ctx) = ctx.upgrade() else {
+            return;
+        };
+        ctx.collect_audio(&mut ctx.recording_state());
+    }
+}
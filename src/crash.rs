@@ -0,0 +1,176 @@
+// Panics: crash reports for the ones that take the app down, containment for
+// the ones that don't have to.
+//
+// A panic on the main thread ends the event loop and with it the app. The hook
+// saves the message and a backtrace to a crash report, tells the user where it
+// is and runs the cleanups registered with `on_crash`, so the tray icon doesn't
+// linger as a dead entry. Work on other threads (keyboard hook, transcription,
+// audio callback, watchers) runs through `contain` or `spawn_supervised`, which
+// log the panic and keep the app going instead.
+
+use crate::app_paths;
+use crate::notification::notify;
+use log::{error, warn};
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Pause before a supervised thread is restarted
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// Restarts after which a supervised thread that keeps panicking is given up on
+const MAX_RESTARTS: u32 = 5;
+
+thread_local! {
+    // Whether a panic on this thread is caught by `contain`
+    static CONTAINED: Cell<bool> = const { Cell::new(false) };
+
+    // Cleanups to run if this thread panics uncontained
+    static ON_CRASH: RefCell<Vec<Box<dyn FnOnce()>>> = RefCell::new(Vec::new());
+}
+
+/// Install the panic hook; call first thing in `main`
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        let message = panic_message(info);
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        let thread = thread::current();
+        let thread_name = thread.name().unwrap_or("unnamed");
+
+        // `contain` logs it and carries on, a report per panic would only pile up
+        if CONTAINED.get() {
+            error!(
+                "Panic on the {} thread at {}: {}",
+                thread_name, location, message
+            );
+            return;
+        }
+
+        let report = write_report(thread_name, &location, &message);
+        let saved_to = report
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|e| format!("nowhere ({})", e));
+        error!(
+            "Panic on the {} thread at {}: {}\n  Crash report saved to {}",
+            thread_name, location, message, saved_to
+        );
+
+        // Only the main thread takes the whole app with it
+        if thread_name == "main" {
+            notify(
+                "Esponquen crashed",
+                &format!("Report saved to {}", saved_to),
+            );
+            for cleanup in ON_CRASH.take() {
+                cleanup();
+            }
+        }
+    }));
+}
+
+/// Run `cleanup` if the current thread panics, e.g. to remove the tray icon
+///
+/// Cleanups are kept per thread, so they can hold values that aren't `Send`.
+pub fn on_crash(cleanup: impl FnOnce() + 'static) {
+    ON_CRASH.with_borrow_mut(|cleanups| cleanups.push(Box::new(cleanup)));
+}
+
+/// Run `work`, returning None instead of unwinding if it panics
+///
+/// `what` names the work in the log. Anything `work` held a lock on is recovered
+/// by `sync::lock`; state it left half-updated is up to the caller.
+pub fn contain<R>(what: &str, work: impl FnOnce() -> R) -> Option<R> {
+    let was_contained = CONTAINED.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(work));
+    CONTAINED.set(was_contained);
+
+    match result {
+        Ok(value) => Some(value),
+        Err(_) => {
+            error!("The {} panicked, Esponquen keeps running", what);
+            None
+        }
+    }
+}
+
+/// Spawn a thread named `name` running `work`, restarting it when it panics
+///
+/// Gives up after `MAX_RESTARTS` panics so a bug doesn't turn into a busy loop.
+pub fn spawn_supervised<F>(name: &str, work: F) -> JoinHandle<()>
+where
+    F: Fn() + Send + 'static,
+{
+    let name = name.to_string();
+    thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            for restarts in 0.. {
+                if contain(&format!("{} thread", name), &work).is_some() {
+                    return;
+                }
+                if restarts == MAX_RESTARTS {
+                    error!("The {} thread keeps panicking, giving up on it", name);
+                    notify(
+                        "Esponquen is partly stopped",
+                        &format!("The {} keeps failing, restart the app.", name),
+                    );
+                    return;
+                }
+                warn!("Restarting the {} thread", name);
+                thread::sleep(RESTART_DELAY);
+            }
+        })
+        .expect("Failed to spawn thread")
+}
+
+/// Directory crash reports are written to
+pub fn crash_dir() -> PathBuf {
+    let paths = app_paths();
+    match (paths.data_dir, paths.log_file.parent()) {
+        (Some(data_dir), _) => data_dir.join("crashes"),
+        (None, Some(log_dir)) => log_dir.to_path_buf(),
+        (None, None) => PathBuf::from("."),
+    }
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    if let Some(message) = info.payload().downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = info.payload().downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Write the panic and a backtrace to a new file in `crash_dir`
+fn write_report(thread: &str, location: &str, message: &str) -> Result<PathBuf, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let report = format!(
+        "Esponquen {} crashed\n\nTime: {}\nOS: {} {}\nThread: {}\nLocation: {}\nPanic: {}\n\nBacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        timestamp,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        thread,
+        location,
+        message,
+        Backtrace::force_capture()
+    );
+
+    let dir = crash_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("crash-{}.txt", timestamp));
+    std::fs::write(&path, report).map_err(|e| e.to_string())?;
+    Ok(path)
+}
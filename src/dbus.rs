@@ -0,0 +1,193 @@
+// D-Bus service on Linux, so shell extensions and scripts can control the app
+// and follow what it's doing without polling `esponquen ctl status`:
+//
+//   name       dev.esponquen.Esponquen1 (session bus)
+//   object     /dev/esponquen/Esponquen1
+//   interface  dev.esponquen.Esponquen1
+//
+//   StartRecording(), StopRecording(), Toggle()
+//   GetStatus() -> s                 the `esponquen ctl status` fields, as JSON
+//   SetHotkey(s key)
+//   Status                           property, "loading-model", "ready", "recording",
+//                                    "transcribing", "disabled" or "error", with
+//                                    PropertiesChanged
+//   TranscriptionCompleted(s text)   signal; the text is empty unless `dbus.emit_text`
+//
+// e.g. `busctl --user call dev.esponquen.Esponquen1 /dev/esponquen/Esponquen1
+// dev.esponquen.Esponquen1 Toggle`.
+//
+// zbus runs the object server on its own executor thread, which hands method
+// calls to the handler; the app's handler only forwards them to its command
+// channel, like the `esponquen ctl` one. A second thread turns the app's
+// notices into signals.
+
+use log::warn;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use zbus::blocking::{Connection, connection};
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+/// Bus name the service is registered under, also the interface name
+pub const BUS_NAME: &str = "dev.esponquen.Esponquen1";
+
+/// Path of the service's only object
+pub const OBJECT_PATH: &str = "/dev/esponquen/Esponquen1";
+
+/// A method called over D-Bus
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    StartRecording,
+    /// Stop recording and transcribe
+    StopRecording,
+    Toggle,
+    GetStatus,
+    SetHotkey(String),
+}
+
+/// Answers a method with what it returns (only GetStatus returns anything), or
+/// why it's refused
+pub type Handler = dyn Fn(Method) -> Result<String, String> + Send + Sync;
+
+/// Something for the service to tell its listeners
+enum Notice {
+    Status(String),
+    TranscriptionCompleted(String),
+}
+
+/// The service while it's on the bus; dropping it releases the name
+pub struct Service {
+    notices: Sender<Notice>,
+}
+
+impl Service {
+    /// Change the Status property (see `AppStatus::name`), announcing it with
+    /// PropertiesChanged
+    pub fn set_status(&self, status: &str) {
+        self.notices.send(Notice::Status(status.to_string())).ok();
+    }
+
+    /// Emit TranscriptionCompleted, with an empty `text` to keep it private
+    pub fn transcription_completed(&self, text: &str) {
+        self.notices
+            .send(Notice::TranscriptionCompleted(text.to_string()))
+            .ok();
+    }
+}
+
+/// Register the service on the session bus
+///
+/// `handler` is called from zbus's thread for every method call.
+pub fn serve(
+    handler: impl Fn(Method) -> Result<String, String> + Send + Sync + 'static,
+) -> Result<Service, String> {
+    let builder = connection::Builder::session()
+        .map_err(|e| format!("Can't connect to the session bus: {}", e))?;
+    serve_on(builder, Box::new(handler))
+}
+
+/// Register the service on the bus at `address`, e.g. a private one in tests
+pub fn serve_at(
+    address: &str,
+    handler: impl Fn(Method) -> Result<String, String> + Send + Sync + 'static,
+) -> Result<Service, String> {
+    let builder = connection::Builder::address(address)
+        .map_err(|e| format!("Can't connect to the bus at {}: {}", address, e))?;
+    serve_on(builder, Box::new(handler))
+}
+
+fn serve_on(
+    builder: connection::Builder<'static>,
+    handler: Box<Handler>,
+) -> Result<Service, String> {
+    let object = Esponquen1 {
+        handler,
+        status: "loading-model".to_string(),
+    };
+    let connection = builder
+        .name(BUS_NAME)
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, object))
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Can't register {} on D-Bus: {}", BUS_NAME, e))?;
+
+    let (notices, received) = channel();
+    thread::Builder::new()
+        .name("dbus".to_string())
+        .spawn(move || emit_notices(&connection, received))
+        .map_err(|e| format!("Can't start the D-Bus thread: {}", e))?;
+    Ok(Service { notices })
+}
+
+/// Turn notices into signals until the `Service` is dropped, which closes the connection
+fn emit_notices(connection: &Connection, notices: Receiver<Notice>) {
+    for notice in notices {
+        if let Err(e) = emit(connection, notice) {
+            warn!("Couldn't emit a D-Bus signal: {}", e);
+        }
+    }
+}
+
+fn emit(connection: &Connection, notice: Notice) -> zbus::Result<()> {
+    let object = connection
+        .object_server()
+        .interface::<_, Esponquen1>(OBJECT_PATH)?;
+    match notice {
+        Notice::Status(status) => {
+            let mut esponquen = object.get_mut();
+            if esponquen.status == status {
+                return Ok(());
+            }
+            esponquen.status = status;
+            zbus::block_on(esponquen.status_changed(object.signal_emitter()))
+        }
+        Notice::TranscriptionCompleted(text) => zbus::block_on(
+            Esponquen1::transcription_completed(object.signal_emitter(), &text),
+        ),
+    }
+}
+
+/// The object at `OBJECT_PATH`
+struct Esponquen1 {
+    handler: Box<Handler>,
+    status: String,
+}
+
+impl Esponquen1 {
+    fn call(&self, method: Method) -> fdo::Result<String> {
+        (self.handler)(method).map_err(fdo::Error::Failed)
+    }
+}
+
+#[zbus::interface(name = "dev.esponquen.Esponquen1")]
+impl Esponquen1 {
+    fn start_recording(&self) -> fdo::Result<()> {
+        self.call(Method::StartRecording).map(drop)
+    }
+
+    fn stop_recording(&self) -> fdo::Result<()> {
+        self.call(Method::StopRecording).map(drop)
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        self.call(Method::Toggle).map(drop)
+    }
+
+    /// The `esponquen ctl status` fields, as a JSON object
+    fn get_status(&self) -> fdo::Result<String> {
+        self.call(Method::GetStatus)
+    }
+
+    /// Change the recording hotkey, e.g. "F9"
+    fn set_hotkey(&self, key: &str) -> fdo::Result<()> {
+        self.call(Method::SetHotkey(key.to_string())).map(drop)
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    /// A transcription finished, `text` being empty unless `dbus.emit_text` is on
+    #[zbus(signal)]
+    async fn transcription_completed(emitter: &SignalEmitter<'_>, text: &str) -> zbus::Result<()>;
+}
@@ -0,0 +1,158 @@
+// `list-devices`: print every input device cpal can see, per host, and exit.
+//
+// A device that fails to report its name or default config is listed with the
+// error instead of aborting the listing; the same goes for a host whose devices
+// can't be enumerated.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde_json::{Value, json};
+use std::fmt::Write;
+
+/// Default input config of a device, as reported by cpal
+struct DefaultConfig {
+    sample_rate: u32,
+    channels: u16,
+    format: String,
+}
+
+struct DeviceEntry {
+    name: Result<String, String>,
+    is_default: bool,
+    config: Result<DefaultConfig, String>,
+}
+
+struct HostEntry {
+    name: &'static str,
+    devices: Result<Vec<DeviceEntry>, String>,
+}
+
+/// Print the input devices of every available host, returning the process exit code
+pub fn list_devices(json: bool) -> i32 {
+    let hosts = available_hosts();
+    if json {
+        println!("{}", hosts_json(&hosts));
+    } else {
+        print!("{}", format_hosts(&hosts));
+    }
+
+    0
+}
+
+/// The `list-devices` listing as text, for the diagnostics bundle
+pub fn describe_devices() -> String {
+    format_hosts(&available_hosts())
+}
+
+fn available_hosts() -> Vec<HostEntry> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| HostEntry {
+            name: id.name(),
+            devices: cpal::host_from_id(id)
+                .map_err(|e| e.to_string())
+                .and_then(|host| host_devices(&host)),
+        })
+        .collect()
+}
+
+fn host_devices(host: &cpal::Host) -> Result<Vec<DeviceEntry>, String> {
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host.input_devices().map_err(|e| e.to_string())?;
+
+    Ok(devices
+        .map(|device| {
+            let name = device.name().map_err(|e| e.to_string());
+            let is_default = default_name.is_some() && name.as_ref().ok() == default_name.as_ref();
+            let config = device
+                .default_input_config()
+                .map(|config| DefaultConfig {
+                    sample_rate: config.sample_rate().0,
+                    channels: config.channels(),
+                    format: config.sample_format().to_string(),
+                })
+                .map_err(|e| e.to_string());
+            DeviceEntry {
+                name,
+                is_default,
+                config,
+            }
+        })
+        .collect())
+}
+
+fn format_hosts(hosts: &[HostEntry]) -> String {
+    let mut out = String::new();
+    for host in hosts {
+        writeln!(out, "{}", host.name).ok();
+
+        let devices = match &host.devices {
+            Ok(devices) => devices,
+            Err(e) => {
+                writeln!(out, "  ✗ Failed to list input devices: {}\n", e).ok();
+                continue;
+            }
+        };
+        if devices.is_empty() {
+            writeln!(out, "  (no input devices)").ok();
+        }
+
+        for device in devices {
+            let name = match &device.name {
+                Ok(name) => name.clone(),
+                Err(e) => format!("✗ <unknown name: {}>", e),
+            };
+            let marker = if device.is_default { " (default)" } else { "" };
+            writeln!(out, "  {}{}", name, marker).ok();
+
+            match &device.config {
+                Ok(config) => writeln!(
+                    out,
+                    "      {} Hz, {} channel(s), {}",
+                    config.sample_rate, config.channels, config.format
+                ),
+                Err(e) => writeln!(out, "      ✗ No default input config: {}", e),
+            }
+            .ok();
+        }
+        writeln!(out).ok();
+    }
+    out
+}
+
+fn hosts_json(hosts: &[HostEntry]) -> Value {
+    let hosts: Vec<Value> = hosts
+        .iter()
+        .map(|host| match &host.devices {
+            Ok(devices) => json!({
+                "name": host.name,
+                "devices": devices.iter().map(device_json).collect::<Vec<_>>(),
+            }),
+            Err(e) => json!({ "name": host.name, "devices": [], "error": e }),
+        })
+        .collect();
+
+    json!({ "hosts": hosts })
+}
+
+fn device_json(device: &DeviceEntry) -> Value {
+    let mut value = json!({
+        "name": device.name.as_ref().ok(),
+        "default": device.is_default,
+        "config": device.config.as_ref().ok().map(|config| json!({
+            "sample_rate": config.sample_rate,
+            "channels": config.channels,
+            "format": config.format,
+        })),
+    });
+
+    // Report whatever failed for this device
+    let errors: Vec<&str> = [device.name.as_ref().err(), device.config.as_ref().err()]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if !errors.is_empty() {
+        value["error"] = json!(errors.join("; "));
+    }
+    value
+}
@@ -0,0 +1,119 @@
+// "Export diagnostics…": a zip with what's needed to look into a bug report.
+//
+// The bundle holds the effective config (phrase texts redacted), the end of the
+// log file, system and audio device info, the providers tried when loading
+// the model and the model files with their sizes. It's written to the
+// downloads directory; callers run `export` on a background thread.
+
+use crate::devices;
+use esponquen::config::Config;
+use esponquen::{EngineConfig, app_paths};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::CompressionMethod;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+// Lines from the end of the log file included in the bundle
+const LOG_TAIL_LINES: usize = 500;
+
+/// Write a diagnostics bundle and return its path
+///
+/// `snapshot` is the app's state snapshot and `provider_attempts` one line per
+/// provider tried when loading the model.
+pub fn export(
+    config: &Config,
+    engine: &EngineConfig,
+    snapshot: &str,
+    provider_attempts: &[String],
+) -> Result<PathBuf, String> {
+    let path = bundle_path();
+    let file =
+        File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let entries = [
+        ("system.txt", system_info(snapshot, provider_attempts)),
+        ("config.toml", redacted_config(config)),
+        ("devices.txt", devices::describe_devices()),
+        ("model.txt", model_files(engine)),
+        ("esponquen.log", log_tail(&app_paths().log_file)),
+    ];
+
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(file);
+    for (name, contents) in entries {
+        zip.start_file(name, options).map_err(|e| failed(&e))?;
+        zip.write_all(contents.as_bytes()).map_err(|e| failed(&e))?;
+    }
+    zip.finish().map_err(|e| failed(&e))?;
+
+    Ok(path)
+}
+
+/// Timestamped file in the export directory (downloads, or the portable folder)
+fn bundle_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = app_paths().export_dir;
+    std::fs::create_dir_all(&dir).ok();
+    dir.join(format!("esponquen-diagnostics-{}.zip", timestamp))
+}
+
+fn system_info(snapshot: &str, provider_attempts: &[String]) -> String {
+    let mut info = format!(
+        "Esponquen {}\nOS: {} ({})\n\n{}\n\nProviders tried:\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        snapshot
+    );
+    if provider_attempts.is_empty() {
+        info.push_str("  (model not loaded yet)\n");
+    }
+    for attempt in provider_attempts {
+        info.push_str(&format!("  {}\n", attempt));
+    }
+    info
+}
+
+/// The config with phrase texts replaced, since they can hold personal text
+fn redacted_config(config: &Config) -> String {
+    let mut config = config.clone();
+    for text in config.phrases.values_mut() {
+        *text = format!("<redacted, {} chars>", text.chars().count());
+    }
+    toml::to_string_pretty(&config).unwrap_or_else(|e| format!("# Failed to serialize: {}", e))
+}
+
+fn model_files(engine: &EngineConfig) -> String {
+    let mut listing = format!("Model directory: {}\n", engine.model_dir.display());
+    for path in [
+        &engine.encoder,
+        &engine.decoder,
+        &engine.joiner,
+        &engine.tokens,
+    ] {
+        let size = match std::fs::metadata(path) {
+            Ok(metadata) => format!("{} bytes", metadata.len()),
+            Err(e) => format!("✗ {}", e),
+        };
+        listing.push_str(&format!("  {}: {}\n", path.display(), size));
+    }
+    listing
+}
+
+/// Last lines of the log, or a note when there's no log
+fn log_tail(path: &Path) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(log) => {
+            let lines: Vec<&str> = log.lines().collect();
+            let start = lines.len().saturating_sub(LOG_TAIL_LINES);
+            lines[start..].join("\n")
+        }
+        Err(e) => format!("No log at {} ({})", path.display(), e),
+    }
+}
@@ -0,0 +1,206 @@
+// The dictation pipeline without the tray and hotkeys: a context recording from
+// a `Recorder` (or fed samples directly), a recognizer and an `Output`.
+//
+// ```ignore
+// let ctx = Arc::new(AppContext::new());
+// let _recorder = Recorder::open(&ctx, None)?;
+// let recognizer = SharedRecognizer::new(Box::new(Transcriber::new(&engine, None)?));
+// let dictation = DictationEngine::new(ctx, recognizer, Box::new(ClipboardOutput))
+//     .on_result(|dictation| println!("{}", dictation.text));
+// dictation.start()?;
+// // ...
+// dictation.stop()?;
+// ```
+
+use crate::app_context;
+use crate::audio::{MODEL_SAMPLE_RATE, normalize, resample_with};
+use crate::command::{AppCommand, transition};
+use crate::context::{AppContext, AppStatus, PipelineSettings, run_collector};
+use crate::crash;
+use crate::output::Output;
+use crate::sync::lock;
+use crate::text::tidy;
+use crate::timing::TranscriptionTimings;
+use crate::transcriber::{Recognize, SharedRecognizer};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One finished dictation
+#[derive(Clone, Debug)]
+pub struct Dictation {
+    /// Transcribed text, tidied and wrapped as configured
+    pub text: String,
+    pub timings: TranscriptionTimings,
+}
+
+type ResultCallback = Box<dyn Fn(&Dictation) + Send + Sync>;
+
+/// Starts and stops recordings on a context and sends their text to an output
+pub struct DictationEngine {
+    ctx: Arc<AppContext>,
+    recognizer: SharedRecognizer,
+    output: Mutex<Box<dyn Output>>,
+    on_result: Option<ResultCallback>,
+}
+
+impl DictationEngine {
+    /// Dictate into `output`, collecting the context's audio on a thread of its own
+    pub fn new(
+        ctx: Arc<AppContext>,
+        recognizer: SharedRecognizer,
+        output: Box<dyn Output>,
+    ) -> Self {
+        let collected = Arc::downgrade(&ctx);
+        thread::spawn(move || run_collector(collected));
+        ctx.set_status(AppStatus::WaitingForHotkey);
+        DictationEngine {
+            ctx,
+            recognizer,
+            output: Mutex::new(output),
+            on_result: None,
+        }
+    }
+
+    /// Call `callback` with each dictation, after its text went to the output
+    pub fn on_result(mut self, callback: impl Fn(&Dictation) + Send + Sync + 'static) -> Self {
+        self.on_result = Some(Box::new(callback));
+        self
+    }
+
+    pub fn context(&self) -> &Arc<AppContext> {
+        &self.ctx
+    }
+
+    /// Start recording, refused without an input or while already recording
+    pub fn start(&self) -> Result<(), String> {
+        if !self.ctx.input_available.load(Ordering::SeqCst) {
+            return Err("No microphone".to_string());
+        }
+        let (recording, _) = transition(
+            &self.ctx.status(),
+            &AppCommand::StartRecording,
+            self.ctx.next_clip(),
+        )
+        .map_err(|invalid| invalid.reason.to_string())?;
+        self.ctx.begin_recording(None, None);
+        self.ctx.set_status(recording);
+        Ok(())
+    }
+
+    /// Start recording, or stop and transcribe, as the hotkey does
+    pub fn toggle(&self) -> Result<Option<Dictation>, String> {
+        if matches!(self.ctx.status(), AppStatus::Recording { .. }) {
+            self.stop()
+        } else {
+            self.start().map(|()| None)
+        }
+    }
+
+    /// Stop recording and discard it
+    pub fn cancel(&self) -> Result<(), String> {
+        let (ready, _) = transition(&self.ctx.status(), &AppCommand::Cancel, 0)
+            .map_err(|invalid| invalid.reason.to_string())?;
+        self.ctx.reset_recording(&mut self.ctx.recording_state());
+        self.ctx.set_status(ready);
+        Ok(())
+    }
+
+    /// Stop recording, transcribe it and send the text to the output
+    ///
+    /// Returns None when nothing was recognized, in which case nothing is output.
+    /// Dictations stopped while another is transcribing wait for it and are
+    /// output after it. A recognizer that panics is an error, not a crash.
+    pub fn stop(&self) -> Result<Option<Dictation>, String> {
+        let (transcribing, _) = transition(&self.ctx.status(), &AppCommand::StopRecording, 0)
+            .map_err(|invalid| invalid.reason.to_string())?;
+        let recording = self.ctx.take_recording().ok_or("Not recording")?;
+        self.ctx.set_status(transcribing.clone());
+        let result = crash::contain("transcription", || {
+            self.transcribe_and_output(&recording.audio, recording.sample_rate)
+        })
+        .unwrap_or_else(|| Err("Transcription failed".to_string()));
+
+        // Unless another thread started the next recording in the meantime
+        if let AppStatus::Transcribing { clip } = transcribing
+            && let Ok((done, _)) = transition(
+                &self.ctx.status(),
+                &AppCommand::TranscriptionDone { clip },
+                0,
+            )
+        {
+            self.ctx.set_status(done);
+        }
+        result
+    }
+
+    fn transcribe_and_output(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+    ) -> Result<Option<Dictation>, String> {
+        if audio.is_empty() {
+            return Err("No audio recorded".to_string());
+        }
+        // Held until the text is out, so queued dictations come out in order
+        let mut recognizer = self.recognizer.lease();
+        let (text, timings) =
+            transcribe_with(&mut *recognizer, sample_rate, audio, &self.ctx.settings());
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+
+        lock(&self.output).write(&text)?;
+        drop(recognizer);
+        self.ctx.stats.record(&text, &timings);
+        let dictation = Dictation { text, timings };
+        if let Some(on_result) = &self.on_result {
+            on_result(&dictation);
+        }
+        Ok(Some(dictation))
+    }
+}
+
+/// Transcribe mono audio at any sample rate with the settings of the app's context
+pub fn transcribe(
+    recognizer: &mut dyn Recognize,
+    sample_rate: u32,
+    samples: &[f32],
+) -> (String, TranscriptionTimings) {
+    transcribe_with(recognizer, sample_rate, samples, &app_context().settings())
+}
+
+/// Transcribe mono audio at any sample rate, resampling it to the model's rate,
+/// then normalizing, tidying and wrapping as `settings` say
+pub fn transcribe_with(
+    recognizer: &mut dyn Recognize,
+    sample_rate: u32,
+    samples: &[f32],
+    settings: &PipelineSettings,
+) -> (String, TranscriptionTimings) {
+    let started = Instant::now();
+    let mut samples = resample_with(
+        samples,
+        sample_rate,
+        MODEL_SAMPLE_RATE,
+        settings.resample_quality,
+    );
+    if settings.normalization.enabled {
+        normalize(&mut samples, &settings.normalization);
+    }
+    let resampled = Instant::now();
+    let mut text = recognizer.transcribe_samples(&samples, MODEL_SAMPLE_RATE);
+    if settings.tidy {
+        text = tidy(&text);
+    }
+    text = settings.wrap.apply(&text);
+
+    let timings = TranscriptionTimings {
+        audio: Duration::from_secs_f64(samples.len() as f64 / MODEL_SAMPLE_RATE as f64),
+        resample: resampled - started,
+        inference: resampled.elapsed(),
+        typing: None,
+    };
+    (text, timings)
+}
@@ -0,0 +1,970 @@
+// Validated engine settings, built with `EngineBuilder`.
+//
+// The builder holds raw values (hotkey names, paths, counts) with the defaults
+// filled in, and `build` checks them all at once. The resulting `EngineConfig`
+// is what the app runs with: `apply` pushes it into the global settings and
+// `transducer_config` describes how to load the model.
+
+#[cfg(feature = "local-model")]
+use crate::audio::MODEL_SAMPLE_RATE;
+use crate::audio::{Normalization, ResampleQuality};
+use crate::beep::{Cue, SoundCues, Tone};
+use crate::config::{self, Config, OutputTarget};
+use crate::key::Key;
+use crate::text::{FileHeader, Wrap};
+use crate::webhook::{self, Webhook, WebhookMode};
+use crate::{
+    HOTKEY_HINT, HotkeyMode, OnEmpty, OutputMode, QuitBehavior, hotkey_name, parse_hotkey,
+};
+#[cfg(feature = "local-model")]
+use sherpa_rs::transducer::TransducerConfig;
+#[cfg(feature = "local-model")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Recording hotkey unless one is configured
+const DEFAULT_HOTKEY: &str = "F6";
+
+/// Execution provider names sherpa-onnx understands, lowercase
+pub const KNOWN_PROVIDERS: [&str; 9] = [
+    "cpu", "cuda", "rocm", "dml", "directml", "coreml", "trt", "xnnpack", "nnapi",
+];
+
+/// Settings the app runs with, produced by [`EngineBuilder::build`]
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub hotkey: Key,
+    pub history_hotkey: Option<Key>,
+    pub quit_hotkey: Option<Key>,
+    pub provider_hotkey: Option<Key>,
+    pub snapshot_hotkey: Option<Key>,
+    /// Records like the hotkey but copies the text instead of typing it
+    pub clipboard_hotkey: Option<Key>,
+    /// Swallow hotkey presses, `None` blocking only keys that are safe to block
+    pub block_hotkeys: Option<bool>,
+    pub hotkey_mode: HotkeyMode,
+    pub model_dir: PathBuf,
+    pub encoder: PathBuf,
+    pub decoder: PathBuf,
+    pub joiner: PathBuf,
+    pub tokens: PathBuf,
+    /// Execution providers in the order they're tried, `None` being the CPU
+    pub providers: Vec<Option<String>>,
+    pub cpu_threads: i32,
+    pub gpu_threads: i32,
+    pub output_mode: OutputMode,
+    pub on_empty: OnEmpty,
+    pub typing_delay_ms: u64,
+    pub restore_focus: bool,
+    pub typing_probe: bool,
+    /// Apps never typed into, by process name or window class
+    pub blocked_apps: Vec<String>,
+    /// Wait for a window the text can be typed into, before copying it instead
+    pub unreachable_hold_secs: u64,
+    pub tidy: bool,
+    /// Prefix and suffix put around transcriptions
+    pub wrap: Wrap,
+    /// Line written before each transcription appended to the output file
+    pub file_header: FileHeader,
+    pub tooltip_preview: bool,
+    pub quit_behavior: QuitBehavior,
+    pub status_overlay: bool,
+    /// Put the text in D-Bus TranscriptionCompleted signals
+    pub dbus_emit_text: bool,
+    pub max_recording_secs: u64,
+    /// Audio discarded when a recording starts
+    pub start_cooldown_ms: u64,
+    pub transcription_timeout_secs: u64,
+    /// Times the first GPU provider is tried again when it fails to load
+    pub load_retries: u32,
+    /// Wait before the first of those, doubled for each one after
+    pub load_retry_delay: Duration,
+    /// Input channel to record (from 1), `None` mixing all channels
+    pub input_channel: Option<usize>,
+    pub resample_quality: ResampleQuality,
+    pub normalization: Normalization,
+    pub sound_cues: SoundCues,
+    /// Where transcriptions are posted, if anywhere
+    pub webhook: Option<Webhook>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineBuilder::new()
+            .build()
+            .expect("Default engine settings are valid")
+    }
+}
+
+impl EngineConfig {
+    /// Make these the settings of the running app
+    pub fn apply(&self) {
+        crate::set_hotkey(self.hotkey);
+        crate::set_history_hotkey(self.history_hotkey);
+        crate::set_quit_hotkey(self.quit_hotkey);
+        crate::set_provider_hotkey(self.provider_hotkey);
+        crate::set_snapshot_hotkey(self.snapshot_hotkey);
+        crate::set_clipboard_hotkey(self.clipboard_hotkey);
+        crate::set_block_hotkeys(self.block_hotkeys);
+        crate::set_hotkey_mode(self.hotkey_mode);
+        crate::set_output_mode(self.output_mode.clone());
+        crate::set_on_empty(self.on_empty);
+        crate::set_typing_delay_ms(self.typing_delay_ms);
+        crate::set_restore_focus(self.restore_focus);
+        crate::set_typing_probe(self.typing_probe);
+        crate::set_blocked_apps(self.blocked_apps.clone());
+        crate::set_unreachable_hold_secs(self.unreachable_hold_secs);
+        crate::set_tidy(self.tidy);
+        crate::set_wrap(self.wrap.clone());
+        crate::set_file_header(self.file_header.clone());
+        crate::set_tooltip_preview(self.tooltip_preview);
+        crate::set_quit_behavior(self.quit_behavior);
+        crate::set_status_overlay(self.status_overlay);
+        crate::set_dbus_emit_text(self.dbus_emit_text);
+        crate::set_max_recording_secs(self.max_recording_secs);
+        crate::set_start_cooldown_ms(self.start_cooldown_ms);
+        crate::set_transcription_timeout_secs(self.transcription_timeout_secs);
+        crate::set_input_channel(self.input_channel);
+        crate::set_resample_quality(self.resample_quality);
+        crate::set_normalization(self.normalization);
+        crate::set_sound_cues(self.sound_cues);
+        crate::set_webhook(self.webhook.clone());
+    }
+
+    /// Recognizer settings for one provider (`None` for the CPU)
+    #[cfg(feature = "local-model")]
+    pub fn transducer_config(&self, provider: Option<String>) -> TransducerConfig {
+        let path = |path: &Path| path.to_string_lossy().to_string();
+
+        TransducerConfig {
+            decoder: path(&self.decoder),
+            encoder: path(&self.encoder),
+            joiner: path(&self.joiner),
+            tokens: path(&self.tokens),
+            // Use more threads for CPU
+            num_threads: if provider.is_none() {
+                self.cpu_threads
+            } else {
+                self.gpu_threads
+            },
+            sample_rate: MODEL_SAMPLE_RATE as i32,
+            feature_dim: 80,
+            debug: false,
+            model_type: "nemo_transducer".to_string(),
+            provider,
+            ..Default::default()
+        }
+    }
+}
+
+/// Builder for [`EngineConfig`], starting from the defaults
+///
+/// ```ignore
+/// let engine = EngineBuilder::new()
+///     .hotkey("F8")
+///     .model_dir("/opt/parakeet")
+///     .provider("cuda")
+///     .provider("cpu")
+///     .build()?;
+/// engine.apply();
+/// ```
+#[derive(Clone, Debug)]
+pub struct EngineBuilder {
+    hotkey: String,
+    history_hotkey: Option<String>,
+    quit_hotkey: Option<String>,
+    provider_hotkey: Option<String>,
+    snapshot_hotkey: Option<String>,
+    clipboard_hotkey: Option<String>,
+    block_hotkeys: Option<bool>,
+    hotkey_mode: HotkeyMode,
+    model_dir: PathBuf,
+    encoder: Option<PathBuf>,
+    decoder: Option<PathBuf>,
+    joiner: Option<PathBuf>,
+    tokens: Option<PathBuf>,
+    providers: Option<Vec<String>>,
+    cpu_threads: i32,
+    gpu_threads: i32,
+    output_mode: OutputMode,
+    on_empty: OnEmpty,
+    typing_delay_ms: u64,
+    restore_focus: bool,
+    typing_probe: bool,
+    blocked_apps: Vec<String>,
+    unreachable_hold_secs: u64,
+    tidy: bool,
+    wrap: Wrap,
+    file_header: FileHeader,
+    tooltip_preview: bool,
+    quit_behavior: QuitBehavior,
+    status_overlay: bool,
+    dbus_emit_text: bool,
+    max_recording_secs: u64,
+    start_cooldown_ms: u64,
+    transcription_timeout_secs: u64,
+    load_retries: u32,
+    load_retry_delay_ms: u64,
+    input_channel: Option<usize>,
+    resample_quality: ResampleQuality,
+    normalization: Normalization,
+    sound_cues: SoundCues,
+    webhook_url: Option<String>,
+    webhook_mode: WebhookMode,
+    webhook_timeout_secs: u64,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        EngineBuilder {
+            hotkey: DEFAULT_HOTKEY.to_string(),
+            history_hotkey: None,
+            quit_hotkey: None,
+            provider_hotkey: None,
+            snapshot_hotkey: None,
+            clipboard_hotkey: None,
+            block_hotkeys: None,
+            hotkey_mode: HotkeyMode::Toggle,
+            model_dir: PathBuf::from(config::DEFAULT_MODEL_DIR),
+            encoder: None,
+            decoder: None,
+            joiner: None,
+            tokens: None,
+            providers: None,
+            cpu_threads: config::DEFAULT_CPU_THREADS,
+            gpu_threads: config::DEFAULT_GPU_THREADS,
+            output_mode: OutputMode::Type,
+            on_empty: OnEmpty::Ignore,
+            typing_delay_ms: config::DEFAULT_TYPING_DELAY_MS,
+            restore_focus: false,
+            typing_probe: true,
+            blocked_apps: Vec::new(),
+            unreachable_hold_secs: config::DEFAULT_UNREACHABLE_HOLD_SECS,
+            tidy: true,
+            wrap: Wrap::default(),
+            file_header: FileHeader::default(),
+            tooltip_preview: true,
+            quit_behavior: QuitBehavior::Prompt,
+            status_overlay: false,
+            dbus_emit_text: false,
+            max_recording_secs: config::DEFAULT_MAX_RECORDING_SECS,
+            start_cooldown_ms: config::DEFAULT_START_COOLDOWN_MS,
+            transcription_timeout_secs: config::DEFAULT_TRANSCRIPTION_TIMEOUT_SECS,
+            load_retries: config::DEFAULT_LOAD_RETRIES,
+            load_retry_delay_ms: config::DEFAULT_LOAD_RETRY_DELAY_MS,
+            input_channel: None,
+            resample_quality: ResampleQuality::High,
+            normalization: Normalization::default(),
+            sound_cues: SoundCues::default(),
+            webhook_url: None,
+            webhook_mode: WebhookMode::Also,
+            webhook_timeout_secs: webhook::DEFAULT_TIMEOUT_SECS,
+        }
+    }
+
+    /// Start from the values in a config file, keeping the defaults for the rest
+    pub fn from_config(config: &Config) -> Self {
+        let mut builder = Self::new();
+
+        if let Some(key) = &config.hotkey.key {
+            builder = builder.hotkey(key);
+        }
+        if let Some(key) = &config.hotkey.history {
+            builder = builder.history_hotkey(key);
+        }
+        if let Some(key) = &config.hotkey.quit {
+            builder = builder.quit_hotkey(key);
+        }
+        if let Some(key) = &config.hotkey.provider {
+            builder = builder.provider_hotkey(key);
+        }
+        if let Some(key) = &config.hotkey.snapshot {
+            builder = builder.snapshot_hotkey(key);
+        }
+        if let Some(key) = &config.hotkey.clipboard {
+            builder = builder.clipboard_hotkey(key);
+        }
+        if let Some(block) = config.hotkey.block {
+            builder = builder.block_hotkeys(block);
+        }
+        if let Some(mode) = config.hotkey.mode {
+            builder = builder.hotkey_mode(mode);
+        }
+        builder = builder.model_dir(config.model_dir());
+        builder.encoder = config.model.encoder.as_ref().map(PathBuf::from);
+        builder.decoder = config.model.decoder.as_ref().map(PathBuf::from);
+        builder.joiner = config.model.joiner.as_ref().map(PathBuf::from);
+        builder.tokens = config.model.tokens.as_ref().map(PathBuf::from);
+        builder.providers = config.model.providers.clone();
+        if let Some(threads) = config.model.cpu_threads {
+            builder = builder.num_threads(threads);
+        }
+        if let Some(threads) = config.model.gpu_threads {
+            builder = builder.gpu_threads(threads);
+        }
+        if let Some(secs) = config.model.transcription_timeout_secs {
+            builder = builder.transcription_timeout_secs(secs);
+        }
+        if let Some(retries) = config.model.load_retries {
+            builder = builder.load_retries(retries);
+        }
+        if let Some(ms) = config.model.load_retry_delay_ms {
+            builder = builder.load_retry_delay_ms(ms);
+        }
+        if let Some(secs) = config.audio.max_recording_secs {
+            builder = builder.max_recording_secs(secs);
+        }
+        if let Some(ms) = config.audio.start_cooldown_ms {
+            builder = builder.start_cooldown_ms(ms);
+        }
+        if let Some(channel) = config.audio.channel {
+            builder = builder.input_channel(channel);
+        }
+        if let Some(quality) = config.audio.resample_quality {
+            builder = builder.resample_quality(quality);
+        }
+        if let Some(enabled) = config.audio.normalize {
+            builder = builder.normalize(enabled);
+        }
+        if let Some(db) = config.audio.normalize_target_db {
+            builder = builder.normalize_target_db(db);
+        }
+        if config.audio.normalize_min_gain_db.is_some()
+            || config.audio.normalize_max_gain_db.is_some()
+        {
+            let defaults = Normalization::default();
+            builder = builder.normalize_gain_db(
+                config
+                    .audio
+                    .normalize_min_gain_db
+                    .unwrap_or(defaults.min_gain_db),
+                config
+                    .audio
+                    .normalize_max_gain_db
+                    .unwrap_or(defaults.max_gain_db),
+            );
+        }
+        if config.output.mode == Some(OutputTarget::File) {
+            let file = config.output.file.clone().unwrap_or_default();
+            builder = builder.output_mode(OutputMode::File(PathBuf::from(file)));
+        }
+        if let Some(template) = &config.output.file_header {
+            builder = builder.file_header(template);
+        }
+        if let Some(on_empty) = config.output.on_empty {
+            builder = builder.on_empty(on_empty);
+        }
+        if let Some(delay) = config.output.typing_delay_ms {
+            builder = builder.typing_delay_ms(delay);
+        }
+        if let Some(enabled) = config.output.restore_focus {
+            builder = builder.restore_focus(enabled);
+        }
+        if let Some(enabled) = config.output.typing_probe {
+            builder = builder.typing_probe(enabled);
+        }
+        if let Some(apps) = &config.output.blocked_apps {
+            builder = builder.blocked_apps(apps);
+        }
+        if let Some(secs) = config.output.unreachable_hold_secs {
+            builder = builder.unreachable_hold_secs(secs);
+        }
+        if let Some(enabled) = config.text.tidy {
+            builder = builder.tidy(enabled);
+        }
+        if config.text.prefix.is_some() || config.text.suffix.is_some() {
+            builder = builder.wrap(
+                config.text.prefix.as_deref().unwrap_or_default(),
+                config.text.suffix.as_deref().unwrap_or_default(),
+            );
+        }
+        if let Some(enabled) = config.ui.tooltip_preview {
+            builder = builder.tooltip_preview(enabled);
+        }
+        if let Some(finish) = config.ui.finish_on_quit {
+            builder = builder.quit_behavior(if finish {
+                QuitBehavior::FinishFirst
+            } else {
+                QuitBehavior::Prompt
+            });
+        }
+        if let Some(enabled) = config.overlay.enabled {
+            builder = builder.status_overlay(enabled);
+        }
+        for (cue, tone_config) in [
+            (Cue::Success, &config.sounds.success),
+            (Cue::Empty, &config.sounds.empty),
+            (Cue::Error, &config.sounds.error),
+        ] {
+            let mut tone = builder.sound_cues.tone(cue);
+            if let Some(enabled) = tone_config.enabled {
+                tone.enabled = enabled;
+            }
+            if let Some(frequency_hz) = tone_config.frequency_hz {
+                tone.frequency_hz = frequency_hz;
+            }
+            if let Some(ms) = tone_config.duration_ms {
+                tone.duration = Duration::from_millis(ms);
+            }
+            builder = builder.sound_cue(cue, tone);
+        }
+        if let Some(url) = &config.webhook.url
+            && config.webhook.enabled != Some(false)
+        {
+            builder = builder.webhook(url);
+        }
+        if let Some(mode) = config.webhook.mode {
+            builder = builder.webhook_mode(mode);
+        }
+        if let Some(secs) = config.webhook.timeout_secs {
+            builder = builder.webhook_timeout_secs(secs);
+        }
+        if let Some(enabled) = config.dbus.emit_text {
+            builder = builder.dbus_emit_text(enabled);
+        }
+
+        builder
+    }
+
+    /// Key that starts/stops recording (see `parse_hotkey`)
+    pub fn hotkey(mut self, name: &str) -> Self {
+        self.hotkey = name.to_string();
+        self
+    }
+
+    /// Key that opens the history window, must differ from the recording hotkey
+    pub fn history_hotkey(mut self, name: &str) -> Self {
+        self.history_hotkey = Some(name.to_string());
+        self
+    }
+
+    /// Key that quits the app, must differ from the other hotkeys
+    pub fn quit_hotkey(mut self, name: &str) -> Self {
+        self.quit_hotkey = Some(name.to_string());
+        self
+    }
+
+    /// Key that reloads the model with the next provider, must differ from the other hotkeys
+    pub fn provider_hotkey(mut self, name: &str) -> Self {
+        self.provider_hotkey = Some(name.to_string());
+        self
+    }
+
+    /// Key that previews the recording so far, must differ from the other hotkeys
+    pub fn snapshot_hotkey(mut self, name: &str) -> Self {
+        self.snapshot_hotkey = Some(name.to_string());
+        self
+    }
+
+    /// Key that records like the hotkey but puts the text on the clipboard,
+    /// must differ from the other hotkeys
+    pub fn clipboard_hotkey(mut self, name: &str) -> Self {
+        self.clipboard_hotkey = Some(name.to_string());
+        self
+    }
+
+    /// Always (or never) keep hotkey presses from the focused app
+    pub fn block_hotkeys(mut self, block: bool) -> Self {
+        self.block_hotkeys = Some(block);
+        self
+    }
+
+    /// Toggle recording with each press, or record while the hotkey is held
+    pub fn hotkey_mode(mut self, mode: HotkeyMode) -> Self {
+        self.hotkey_mode = mode;
+        self
+    }
+
+    /// Directory with the model files
+    pub fn model_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.model_dir = dir.into();
+        self
+    }
+
+    /// Encoder file, relative to the model directory unless absolute
+    pub fn encoder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.encoder = Some(path.into());
+        self
+    }
+
+    /// Decoder file, relative to the model directory unless absolute
+    pub fn decoder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.decoder = Some(path.into());
+        self
+    }
+
+    /// Joiner file, relative to the model directory unless absolute
+    pub fn joiner(mut self, path: impl Into<PathBuf>) -> Self {
+        self.joiner = Some(path.into());
+        self
+    }
+
+    /// Tokens file, relative to the model directory unless absolute
+    pub fn tokens(mut self, path: impl Into<PathBuf>) -> Self {
+        self.tokens = Some(path.into());
+        self
+    }
+
+    /// Add an execution provider to try, in call order ("cpu" always works)
+    pub fn provider(mut self, name: &str) -> Self {
+        self.providers
+            .get_or_insert_with(Vec::new)
+            .push(name.to_string());
+        self
+    }
+
+    /// Recognizer threads on the CPU
+    pub fn num_threads(mut self, threads: i32) -> Self {
+        self.cpu_threads = threads;
+        self
+    }
+
+    /// Recognizer threads on GPU providers
+    pub fn gpu_threads(mut self, threads: i32) -> Self {
+        self.gpu_threads = threads;
+        self
+    }
+
+    /// Where transcriptions go
+    pub fn output_mode(mut self, mode: OutputMode) -> Self {
+        self.output_mode = mode;
+        self
+    }
+
+    /// What happens when a transcription produces no text
+    pub fn on_empty(mut self, on_empty: OnEmpty) -> Self {
+        self.on_empty = on_empty;
+        self
+    }
+
+    /// Pause before typing, so focus can settle on the target window
+    pub fn typing_delay_ms(mut self, delay: u64) -> Self {
+        self.typing_delay_ms = delay;
+        self
+    }
+
+    /// Re-activate the window that was focused when recording started
+    pub fn restore_focus(mut self, enabled: bool) -> Self {
+        self.restore_focus = enabled;
+        self
+    }
+
+    /// Send a no-op key event before typing, to notice when input is blocked
+    pub fn typing_probe(mut self, enabled: bool) -> Self {
+        self.typing_probe = enabled;
+        self
+    }
+
+    /// Copy instead of typing when one of these apps is focused, by process name
+    /// (".exe" optional) or window class, ignoring case
+    pub fn blocked_apps<S: AsRef<str>>(mut self, apps: impl IntoIterator<Item = S>) -> Self {
+        self.blocked_apps = apps
+            .into_iter()
+            .map(|app| app.as_ref().trim().to_string())
+            .collect();
+        self
+    }
+
+    /// Hold a transcription this long while a UAC prompt or elevated window has
+    /// focus, before copying it instead (Windows)
+    pub fn unreachable_hold_secs(mut self, secs: u64) -> Self {
+        self.unreachable_hold_secs = secs;
+        self
+    }
+
+    /// Clean up spacing around punctuation in transcriptions
+    pub fn tidy(mut self, enabled: bool) -> Self {
+        self.tidy = enabled;
+        self
+    }
+
+    /// Put `prefix` and `suffix` around every transcription, after the clean-ups
+    pub fn wrap(mut self, prefix: &str, suffix: &str) -> Self {
+        self.wrap = Wrap::new(prefix, suffix);
+        self
+    }
+
+    /// Write `template` before each transcription appended to the output file,
+    /// see `text::FileHeader` for its variables
+    pub fn file_header(mut self, template: &str) -> Self {
+        self.file_header = FileHeader(template.to_string());
+        self
+    }
+
+    /// Show the last transcription in the tray tooltip
+    pub fn tooltip_preview(mut self, enabled: bool) -> Self {
+        self.tooltip_preview = enabled;
+        self
+    }
+
+    /// What Quit does while a dictation is in progress
+    pub fn quit_behavior(mut self, behavior: QuitBehavior) -> Self {
+        self.quit_behavior = behavior;
+        self
+    }
+
+    /// Show the on-screen status dot while recording or transcribing
+    pub fn status_overlay(mut self, enabled: bool) -> Self {
+        self.status_overlay = enabled;
+        self
+    }
+
+    /// Include the text in D-Bus TranscriptionCompleted signals, which any app on
+    /// the session bus can read
+    pub fn dbus_emit_text(mut self, enabled: bool) -> Self {
+        self.dbus_emit_text = enabled;
+        self
+    }
+
+    /// Longest recording before the watchdog may discard it
+    pub fn max_recording_secs(mut self, secs: u64) -> Self {
+        self.max_recording_secs = secs;
+        self
+    }
+
+    /// Audio to ignore at the start of each recording, where the hotkey's click is
+    pub fn start_cooldown_ms(mut self, ms: u64) -> Self {
+        self.start_cooldown_ms = ms;
+        self
+    }
+
+    /// Longest a transcription may take before the watchdog abandons it
+    pub fn transcription_timeout_secs(mut self, secs: u64) -> Self {
+        self.transcription_timeout_secs = secs;
+        self
+    }
+
+    /// Try the first GPU provider this many more times when it fails to load
+    pub fn load_retries(mut self, retries: u32) -> Self {
+        self.load_retries = retries;
+        self
+    }
+
+    /// Wait before the first retry of the first GPU provider, doubled for each one after
+    pub fn load_retry_delay_ms(mut self, ms: u64) -> Self {
+        self.load_retry_delay_ms = ms;
+        self
+    }
+
+    /// Record one input channel (from 1) instead of mixing them all
+    pub fn input_channel(mut self, channel: usize) -> Self {
+        self.input_channel = Some(channel);
+        self
+    }
+
+    /// How audio at other rates is converted for the model
+    pub fn resample_quality(mut self, quality: ResampleQuality) -> Self {
+        self.resample_quality = quality;
+        self
+    }
+
+    /// Bring each recording's peak to the target level before transcribing
+    pub fn normalize(mut self, enabled: bool) -> Self {
+        self.normalization.enabled = enabled;
+        self
+    }
+
+    /// Peak level in dBFS that normalization aims for
+    pub fn normalize_target_db(mut self, db: f32) -> Self {
+        self.normalization.target_db = db;
+        self
+    }
+
+    /// Most normalization may turn a recording down (`min_db`, 0 or below) and up
+    /// (`max_db`), in dB
+    pub fn normalize_gain_db(mut self, min_db: f32, max_db: f32) -> Self {
+        self.normalization.min_gain_db = min_db;
+        self.normalization.max_gain_db = max_db;
+        self
+    }
+
+    /// Tone played when a dictation ends with `cue`
+    pub fn sound_cue(mut self, cue: Cue, tone: Tone) -> Self {
+        *self.sound_cues.tone_mut(cue) = tone;
+        self
+    }
+
+    /// POST every transcription as JSON to `url`
+    pub fn webhook(mut self, url: &str) -> Self {
+        self.webhook_url = Some(url.trim().to_string());
+        self
+    }
+
+    /// Post on top of the usual output, or instead of it
+    pub fn webhook_mode(mut self, mode: WebhookMode) -> Self {
+        self.webhook_mode = mode;
+        self
+    }
+
+    /// Seconds a webhook request may take
+    pub fn webhook_timeout_secs(mut self, secs: u64) -> Self {
+        self.webhook_timeout_secs = secs;
+        self
+    }
+
+    /// Check the settings, reporting the first invalid one
+    pub fn build(self) -> Result<EngineConfig, String> {
+        let hotkey = parse_hotkey(&self.hotkey)
+            .ok_or_else(|| format!("Unknown hotkey '{}', expected {}", self.hotkey, HOTKEY_HINT))?;
+
+        let history_hotkey = match &self.history_hotkey {
+            None => None,
+            Some(name) => match parse_hotkey(name) {
+                Some(key) if key == hotkey => {
+                    return Err(format!(
+                        "History hotkey {} is already the recording hotkey",
+                        hotkey_name(key)
+                    ));
+                }
+                Some(key) => Some(key),
+                None => {
+                    return Err(format!(
+                        "Unknown history hotkey '{}', expected {}",
+                        name, HOTKEY_HINT
+                    ));
+                }
+            },
+        };
+
+        let quit_hotkey = match &self.quit_hotkey {
+            None => None,
+            Some(name) => match parse_hotkey(name) {
+                Some(key) if key == hotkey || Some(key) == history_hotkey => {
+                    return Err(format!(
+                        "Quit hotkey {} is already bound to another action",
+                        hotkey_name(key)
+                    ));
+                }
+                Some(key) => Some(key),
+                None => {
+                    return Err(format!(
+                        "Unknown quit hotkey '{}', expected {}",
+                        name, HOTKEY_HINT
+                    ));
+                }
+            },
+        };
+
+        let provider_hotkey = match &self.provider_hotkey {
+            None => None,
+            Some(name) => match parse_hotkey(name) {
+                Some(key)
+                    if key == hotkey || Some(key) == history_hotkey || Some(key) == quit_hotkey =>
+                {
+                    return Err(format!(
+                        "Provider hotkey {} is already bound to another action",
+                        hotkey_name(key)
+                    ));
+                }
+                Some(key) => Some(key),
+                None => {
+                    return Err(format!(
+                        "Unknown provider hotkey '{}', expected {}",
+                        name, HOTKEY_HINT
+                    ));
+                }
+            },
+        };
+
+        let snapshot_hotkey = match &self.snapshot_hotkey {
+            None => None,
+            Some(name) => match parse_hotkey(name) {
+                Some(key)
+                    if key == hotkey
+                        || [history_hotkey, quit_hotkey, provider_hotkey].contains(&Some(key)) =>
+                {
+                    return Err(format!(
+                        "Snapshot hotkey {} is already bound to another action",
+                        hotkey_name(key)
+                    ));
+                }
+                Some(key) => Some(key),
+                None => {
+                    return Err(format!(
+                        "Unknown snapshot hotkey '{}', expected {}",
+                        name, HOTKEY_HINT
+                    ));
+                }
+            },
+        };
+
+        let clipboard_hotkey = match &self.clipboard_hotkey {
+            None => None,
+            Some(name) => match parse_hotkey(name) {
+                Some(key)
+                    if key == hotkey
+                        || [
+                            history_hotkey,
+                            quit_hotkey,
+                            provider_hotkey,
+                            snapshot_hotkey,
+                        ]
+                        .contains(&Some(key)) =>
+                {
+                    return Err(format!(
+                        "Clipboard hotkey {} is already bound to another action",
+                        hotkey_name(key)
+                    ));
+                }
+                Some(key) => Some(key),
+                None => {
+                    return Err(format!(
+                        "Unknown clipboard hotkey '{}', expected {}",
+                        name, HOTKEY_HINT
+                    ));
+                }
+            },
+        };
+
+        if self.model_dir.as_os_str().is_empty() {
+            return Err("Model directory is empty".to_string());
+        }
+
+        let providers = match self.providers {
+            Some(names) if names.is_empty() => {
+                return Err("No execution providers listed".to_string());
+            }
+            Some(names) => names
+                .iter()
+                .map(|name| name.trim().to_lowercase())
+                .map(|name| (name != "cpu").then_some(name))
+                .collect(),
+            None => default_providers(),
+        };
+
+        if self.cpu_threads < 1 || self.gpu_threads < 1 {
+            return Err(format!(
+                "Thread counts must be at least 1 (cpu {}, gpu {})",
+                self.cpu_threads, self.gpu_threads
+            ));
+        }
+
+        if self.output_mode == OutputMode::File(PathBuf::new()) {
+            return Err("Output mode is \"file\" but no output file is set".to_string());
+        }
+
+        if self.max_recording_secs == 0 {
+            return Err("Maximum recording length must be at least 1 second".to_string());
+        }
+
+        if self.transcription_timeout_secs == 0 {
+            return Err("Transcription timeout must be at least 1 second".to_string());
+        }
+
+        if self.input_channel == Some(0) {
+            return Err("Input channels are numbered from 1".to_string());
+        }
+
+        if self.blocked_apps.iter().any(|app| app.is_empty()) {
+            return Err("Blocked app names can't be empty".to_string());
+        }
+
+        let normalization = self.normalization;
+        if !normalization.target_db.is_finite() || normalization.target_db > 0.0 {
+            return Err(format!(
+                "Normalization target must be 0 dBFS or below, not {}",
+                normalization.target_db
+            ));
+        }
+        if !(normalization.min_gain_db <= 0.0
+            && normalization.max_gain_db >= 0.0
+            && normalization.max_gain_db.is_finite())
+        {
+            return Err(format!(
+                "Normalization gain bounds must be 0 dB or below ({}) and 0 dB or above ({})",
+                normalization.min_gain_db, normalization.max_gain_db
+            ));
+        }
+
+        for cue in [Cue::Success, Cue::Empty, Cue::Error] {
+            let tone = self.sound_cues.tone(cue);
+            if tone.frequency_hz.is_nan() || tone.frequency_hz <= 0.0 || tone.duration.is_zero() {
+                return Err(format!("{:?} sound cue needs a pitch and a length", cue));
+            }
+        }
+
+        if let Some(url) = &self.webhook_url {
+            webhook::check_url(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+        }
+        if self.webhook_timeout_secs == 0 {
+            return Err("Webhook timeout must be at least 1 second".to_string());
+        }
+
+        let model_file = |configured: Option<PathBuf>, default: &str| match configured {
+            Some(path) => self.model_dir.join(path),
+            None => self.model_dir.join(default),
+        };
+
+        Ok(EngineConfig {
+            hotkey,
+            history_hotkey,
+            quit_hotkey,
+            provider_hotkey,
+            snapshot_hotkey,
+            clipboard_hotkey,
+            block_hotkeys: self.block_hotkeys,
+            hotkey_mode: self.hotkey_mode,
+            encoder: model_file(self.encoder, "encoder.int8.onnx"),
+            decoder: model_file(self.decoder, "decoder.int8.onnx"),
+            joiner: model_file(self.joiner, "joiner.int8.onnx"),
+            tokens: model_file(self.tokens, "tokens.txt"),
+            model_dir: self.model_dir,
+            providers,
+            cpu_threads: self.cpu_threads,
+            gpu_threads: self.gpu_threads,
+            output_mode: self.output_mode,
+            on_empty: self.on_empty,
+            typing_delay_ms: self.typing_delay_ms,
+            restore_focus: self.restore_focus,
+            typing_probe: self.typing_probe,
+            blocked_apps: self.blocked_apps,
+            unreachable_hold_secs: self.unreachable_hold_secs,
+            tidy: self.tidy,
+            wrap: self.wrap,
+            file_header: self.file_header,
+            tooltip_preview: self.tooltip_preview,
+            quit_behavior: self.quit_behavior,
+            status_overlay: self.status_overlay,
+            dbus_emit_text: self.dbus_emit_text,
+            max_recording_secs: self.max_recording_secs,
+            start_cooldown_ms: self.start_cooldown_ms,
+            transcription_timeout_secs: self.transcription_timeout_secs,
+            load_retries: self.load_retries,
+            load_retry_delay: Duration::from_millis(self.load_retry_delay_ms),
+            input_channel: self.input_channel,
+            resample_quality: self.resample_quality,
+            normalization: self.normalization,
+            sound_cues: self.sound_cues,
+            webhook: self.webhook_url.map(|url| Webhook {
+                url,
+                mode: self.webhook_mode,
+                timeout: Duration::from_secs(self.webhook_timeout_secs),
+            }),
+        })
+    }
+}
+
+/// GPU providers in order of preference for this platform, then the CPU
+fn default_providers() -> Vec<Option<String>> {
+    let gpu: &[&str] = if cfg!(target_os = "windows") {
+        // DirectML works with any GPU, CUDA needs NVIDIA's runtime installed
+        &["dml", "cuda"]
+    } else if cfg!(target_os = "macos") {
+        // The GPU and Neural Engine of Apple silicon
+        &["coreml"]
+    } else {
+        // NVIDIA first, as it's the common setup, then AMD
+        &["cuda", "rocm"]
+    };
+    gpu.iter()
+        .map(|provider| Some(provider.to_string()))
+        .chain([None])
+        .collect()
+}
@@ -0,0 +1,254 @@
+// What this desktop session can do, probed once at startup, and what the app
+// does instead where it can't.
+//
+// Each capability degrades along one path, and the code that needs it asks
+// `capabilities()` rather than checking the platform itself:
+//
+//   tray           tray icon        -> no icon (the hotkeys, `esponquen ctl` and the console)
+//   hotkey         keyboard grab    -> reading /dev/input (presses reach the app) -> none
+//   typing         simulated keys   -> the clipboard
+//   notifications  desktop popups   -> the log
+//
+// The probes are cheap and err on the side of "available" when they can't
+// tell, e.g. an X11 tray may be an XEmbed one that no D-Bus name announces.
+
+use crate::session::{SessionType, typing_tools};
+use once_cell::sync::Lazy;
+
+// Probed on first use; a tray or notification service started later takes a restart
+static CAPABILITIES: Lazy<Capabilities> = Lazy::new(Capabilities::probe);
+
+/// The capabilities of this session
+pub fn capabilities() -> &'static Capabilities {
+    &CAPABILITIES
+}
+
+/// How hotkey presses are seen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HotkeyCapture {
+    /// Grabbed, so a press can be kept from the focused app
+    Grab,
+    /// Read from the keyboard devices in /dev/input (Wayland)
+    Listen,
+    /// Not at all: the tray and `esponquen ctl` still work
+    Unavailable,
+}
+
+/// How transcriptions are typed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Typing {
+    /// Simulated key presses (enigo)
+    Simulated,
+    /// An external tool, see `session::typing_tools`
+    Tools,
+    /// Not at all, they're put on the clipboard instead
+    Clipboard,
+}
+
+/// What the session supports, see the module docs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub session: SessionType,
+    /// There's a tray to put the icon in
+    pub tray: bool,
+    pub hotkey: HotkeyCapture,
+    pub typing: Typing,
+    /// Desktop notifications can be shown
+    pub notifications: bool,
+}
+
+impl Capabilities {
+    /// Test what this session supports
+    pub fn probe() -> Self {
+        let session = SessionType::detect();
+        Capabilities {
+            session,
+            tray: probe_tray(session),
+            hotkey: probe_hotkey(session),
+            typing: probe_typing(session),
+            notifications: probe_notifications(),
+        }
+    }
+
+    /// Each capability that's missing and what the app does instead, one line each
+    pub fn degradations(&self) -> Vec<String> {
+        let mut degradations = Vec::new();
+        if !self.tray {
+            degradations.push(
+                "No system tray: running without an icon, use the hotkeys or esponquen ctl"
+                    .to_string(),
+            );
+        }
+        match self.hotkey {
+            HotkeyCapture::Grab => {}
+            HotkeyCapture::Listen => degradations.push(
+                "No keyboard grab: reading /dev/input, so hotkey presses also reach the focused window"
+                    .to_string(),
+            ),
+            HotkeyCapture::Unavailable => degradations.push(
+                "Can't read the keyboard: hotkeys are off (on Wayland, add your user to the 'input' group)"
+                    .to_string(),
+            ),
+        }
+        if self.typing == Typing::Clipboard {
+            degradations.push(
+                "Can't simulate typing: transcriptions are copied to the clipboard instead"
+                    .to_string(),
+            );
+        }
+        if !self.notifications {
+            degradations
+                .push("No notification service: notifications only go to the log".to_string());
+        }
+        degradations
+    }
+
+    /// How each feature works in this session, on one line
+    pub fn summary(&self) -> String {
+        let hotkey = match self.hotkey {
+            HotkeyCapture::Grab => "keyboard grab",
+            HotkeyCapture::Listen => "read from /dev/input",
+            HotkeyCapture::Unavailable => "unavailable",
+        };
+        let typing = match self.typing {
+            Typing::Simulated => "simulated keyboard (enigo)".to_string(),
+            Typing::Tools => {
+                let tools: Vec<&str> = typing_tools().iter().map(|tool| tool.program()).collect();
+                tools.join(", then ")
+            }
+            Typing::Clipboard => "clipboard only".to_string(),
+        };
+        format!(
+            "Session: {:?}; tray: {}; hotkey: {}; typing: {}; notifications: {}",
+            self.session,
+            yes_no(self.tray),
+            hotkey,
+            typing,
+            yes_no(self.notifications)
+        )
+    }
+}
+
+fn yes_no(available: bool) -> &'static str {
+    if available { "yes" } else { "no" }
+}
+
+fn probe_tray(session: SessionType) -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    match session {
+        // A text console, or a display that's not set in this environment
+        SessionType::Other => false,
+        SessionType::X11 => true,
+        // No XEmbed fallback on Wayland: the icon needs a StatusNotifier host
+        // (sway and GNOME without an extension have none)
+        SessionType::Wayland => bus_name_has_owner("org.kde.StatusNotifierWatcher").unwrap_or(true),
+    }
+}
+
+fn probe_hotkey(session: SessionType) -> HotkeyCapture {
+    if session != SessionType::Wayland {
+        return HotkeyCapture::Grab;
+    }
+    let readable = std::fs::read_dir("/dev/input").is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            entry.file_name().to_string_lossy().starts_with("event")
+                && std::fs::File::open(entry.path()).is_ok()
+        })
+    });
+    if readable {
+        HotkeyCapture::Listen
+    } else {
+        HotkeyCapture::Unavailable
+    }
+}
+
+fn probe_typing(session: SessionType) -> Typing {
+    if !cfg!(feature = "typing") {
+        return Typing::Clipboard;
+    }
+    if !typing_tools().is_empty() {
+        return Typing::Tools;
+    }
+    if session == SessionType::Wayland || is_remote_desktop() {
+        return Typing::Clipboard;
+    }
+    // Connects to the X server; elsewhere it only fails later, per window
+    #[cfg(all(feature = "typing", target_os = "linux"))]
+    if crate::output::new_enigo().is_err() {
+        return Typing::Clipboard;
+    }
+    Typing::Simulated
+}
+
+/// Whether this is a Remote Desktop session, where simulated keys get lost
+fn is_remote_desktop() -> bool {
+    cfg!(target_os = "windows")
+        && std::env::var("SESSIONNAME").is_ok_and(|name| name.starts_with("RDP-"))
+}
+
+fn probe_notifications() -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    // Notifications go over the session bus, and the daemon is often started on demand
+    has_session_bus()
+}
+
+/// Whether there's a D-Bus session bus to connect to
+pub fn has_session_bus() -> bool {
+    std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+        || std::env::var_os("XDG_RUNTIME_DIR")
+            .is_some_and(|dir| std::path::Path::new(&dir).join("bus").exists())
+}
+
+/// Whether a name is owned on the session bus, None when that can't be asked
+fn bus_name_has_owner(name: &str) -> Option<bool> {
+    let output = std::process::Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus.NameHasOwner",
+        ])
+        .arg(format!("string:{}", name))
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let reply = String::from_utf8_lossy(&output.stdout);
+    Some(reply.contains("boolean true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn everything() -> Capabilities {
+        Capabilities {
+            session: SessionType::X11,
+            tray: true,
+            hotkey: HotkeyCapture::Grab,
+            typing: Typing::Simulated,
+            notifications: true,
+        }
+    }
+
+    #[test]
+    fn each_missing_capability_is_one_degradation() {
+        assert!(everything().degradations().is_empty());
+
+        let sway = Capabilities {
+            session: SessionType::Wayland,
+            tray: false,
+            hotkey: HotkeyCapture::Listen,
+            typing: Typing::Clipboard,
+            ..everything()
+        };
+        let degradations = sway.degradations();
+        assert_eq!(degradations.len(), 3);
+        assert!(degradations[0].starts_with("No system tray"));
+        assert!(degradations[2].contains("clipboard"));
+    }
+}
@@ -0,0 +1,191 @@
+// Reading the hotkey straight from the keyboard devices, for Wayland sessions
+// where the rdev grab can't see other apps' keys.
+//
+// Every readable device in /dev/input with keys is read on a thread of its own
+// and its key events are handed to the same callback as the grab's, translated
+// to rdev keys. Reading a device doesn't take its keys from the focused app, so
+// the hotkey can't be blocked this way. Devices plugged in later (a foot pedal,
+// a second keyboard) are only picked up on the next start, or when all the
+// readers stopped (e.g. across a suspend) and the listener is started again.
+
+use evdev::{Device, InputEventKind};
+use log::{debug, info};
+use rdev::{Event, EventType, Key};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::SystemTime;
+
+/// Codes below this are keys; buttons (mouse, joystick) start here
+const BTN_MISC: u16 = 0x100;
+
+/// Key events value: released, pressed, or repeated while held
+const RELEASED: i32 = 0;
+
+/// Start reading every keyboard device, passing each key event to `callback`
+///
+/// Every reader holds a clone of `running` until it stops, so its receiver
+/// disconnects once none is left. Fails when no device could be opened, which
+/// without root is the `input` group missing.
+pub fn spawn_listener<F>(callback: F, running: Sender<String>) -> Result<(), String>
+where
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    let keyboards: Vec<(String, Device)> = evdev::enumerate()
+        .filter(|(_, device)| {
+            device
+                .supported_keys()
+                .is_some_and(|keys| keys.iter().any(|key| key.code() < BTN_MISC))
+        })
+        .map(|(path, device)| (path.display().to_string(), device))
+        .collect();
+    if keyboards.is_empty() {
+        return Err("Can't read any keyboard in /dev/input; add your user to the 'input' group (sudo usermod -aG input $USER) and log in again".to_string());
+    }
+
+    let callback = Arc::new(callback);
+    for (path, mut device) in keyboards {
+        info!(
+            "Reading keys from {} ({})",
+            path,
+            device.name().unwrap_or("unnamed")
+        );
+        let callback = Arc::clone(&callback);
+        let running = running.clone();
+        thread::spawn(move || {
+            let _running = running;
+            loop {
+                let events = match device.fetch_events() {
+                    Ok(events) => events,
+                    // Unplugged
+                    Err(e) => {
+                        debug!("Stopped reading keys from {}: {}", path, e);
+                        return;
+                    }
+                };
+                for event in events {
+                    if let InputEventKind::Key(key) = event.kind() {
+                        let key = rdev_key(key.code());
+                        callback(Event {
+                            time: SystemTime::now(),
+                            name: None,
+                            event_type: if event.value() == RELEASED {
+                                EventType::KeyRelease(key)
+                            } else {
+                                EventType::KeyPress(key)
+                            },
+                        });
+                    }
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The rdev key for an evdev key code, as the grab would report it on X11
+///
+/// Keys rdev has no name for are `Unknown` with their X11 keycode (the evdev
+/// code plus 8), so F13 and up match `esponquen::parse_hotkey`.
+fn rdev_key(code: u16) -> Key {
+    const MAIN_BLOCK: [Key; 58] = [
+        Key::Escape,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::Num0,
+        Key::Minus,
+        Key::Equal,
+        Key::Backspace,
+        Key::Tab,
+        Key::KeyQ,
+        Key::KeyW,
+        Key::KeyE,
+        Key::KeyR,
+        Key::KeyT,
+        Key::KeyY,
+        Key::KeyU,
+        Key::KeyI,
+        Key::KeyO,
+        Key::KeyP,
+        Key::LeftBracket,
+        Key::RightBracket,
+        Key::Return,
+        Key::ControlLeft,
+        Key::KeyA,
+        Key::KeyS,
+        Key::KeyD,
+        Key::KeyF,
+        Key::KeyG,
+        Key::KeyH,
+        Key::KeyJ,
+        Key::KeyK,
+        Key::KeyL,
+        Key::SemiColon,
+        Key::Quote,
+        Key::BackQuote,
+        Key::ShiftLeft,
+        Key::BackSlash,
+        Key::KeyZ,
+        Key::KeyX,
+        Key::KeyC,
+        Key::KeyV,
+        Key::KeyB,
+        Key::KeyN,
+        Key::KeyM,
+        Key::Comma,
+        Key::Dot,
+        Key::Slash,
+        Key::ShiftRight,
+        Key::KpMultiply,
+        Key::Alt,
+        Key::Space,
+        Key::CapsLock,
+    ];
+    const F1_TO_F10: [Key; 10] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+    ];
+
+    match code {
+        1..=58 => MAIN_BLOCK[usize::from(code - 1)],
+        59..=68 => F1_TO_F10[usize::from(code - 59)],
+        69 => Key::NumLock,
+        70 => Key::ScrollLock,
+        87 => Key::F11,
+        88 => Key::F12,
+        96 => Key::KpReturn,
+        97 => Key::ControlRight,
+        98 => Key::KpDivide,
+        99 => Key::PrintScreen,
+        100 => Key::AltGr,
+        102 => Key::Home,
+        103 => Key::UpArrow,
+        104 => Key::PageUp,
+        105 => Key::LeftArrow,
+        106 => Key::RightArrow,
+        107 => Key::End,
+        108 => Key::DownArrow,
+        109 => Key::PageDown,
+        110 => Key::Insert,
+        111 => Key::Delete,
+        119 => Key::Pause,
+        125 => Key::MetaLeft,
+        126 => Key::MetaRight,
+        _ => Key::Unknown(u32::from(code) + 8),
+    }
+}
@@ -0,0 +1,209 @@
+// Machine-readable event log: one JSON object per line, one file per day.
+//
+// Meant for dashboards and timing investigations, next to the human-readable
+// log. Every line carries the `schema` version, a Unix timestamp in
+// milliseconds (`ts`) and the `event` name, plus the event's own fields. Adding
+// fields keeps the schema version; renaming or removing one bumps it.
+//
+// `emit` only hands the event to a channel: a dedicated thread does the disk
+// writes, so recording and transcription never wait on it.
+
+use crate::sync::lock;
+use crate::{app_paths, get_event_log_max_bytes, is_event_log_enabled};
+use log::warn;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the line format, see the module docs
+pub const SCHEMA: u32 = 1;
+
+// Rotated files kept per day (events-<date>.1.jsonl being the newest)
+const KEPT_EVENT_FILES: usize = 3;
+
+// Channel to the writer thread, started by the first event
+static WRITER: Mutex<Option<Sender<Line>>> = Mutex::new(None);
+
+/// Something worth recording, serialized as the `event` field and its data
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    RecordingStarted,
+    RecordingStopped {
+        duration_ms: u64,
+        /// Loudest sample, from 0 to 1
+        peak_level: f32,
+    },
+    TranscriptionStarted {
+        audio_ms: u64,
+    },
+    TranscriptionFinished {
+        elapsed_ms: u64,
+        provider: String,
+        text_length: usize,
+    },
+    TypingFinished {
+        elapsed_ms: u64,
+        /// "type", "file", "print" or "webhook"
+        output: String,
+        text_length: usize,
+    },
+    Error {
+        category: ErrorCategory,
+        message: String,
+    },
+}
+
+/// What an `Event::Error` is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Microphone missing, stream failures, recordings without audio
+    Audio,
+    /// The model couldn't be loaded or crashed transcribing
+    Model,
+    /// The text couldn't be typed
+    Typing,
+    /// The output file couldn't be written
+    Output,
+}
+
+/// An event with its timestamp, taken when it happened rather than when written
+struct Line {
+    ts: u64,
+    event: Event,
+}
+
+/// Record `event`, if the event log is on
+pub fn emit(event: Event) {
+    if !is_event_log_enabled() {
+        return;
+    }
+
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let mut writer = lock(&WRITER);
+    let sender = writer.get_or_insert_with(start_writer);
+    if sender.send(Line { ts, event }).is_err() {
+        // The writer thread is gone, the next event starts a new one
+        *writer = None;
+    }
+}
+
+/// Shorthand for `emit(Event::Error { .. })`
+pub fn emit_error(category: ErrorCategory, message: impl Into<String>) {
+    emit(Event::Error {
+        category,
+        message: message.into(),
+    });
+}
+
+/// Serialize a line as written to the file
+fn to_json(line: &Line) -> String {
+    let mut value = serde_json::json!({ "schema": SCHEMA, "ts": line.ts });
+    if let (Some(object), Ok(serde_json::Value::Object(fields))) =
+        (value.as_object_mut(), serde_json::to_value(&line.event))
+    {
+        object.extend(fields);
+    }
+    value.to_string()
+}
+
+fn start_writer() -> Sender<Line> {
+    let (sender, receiver) = channel();
+    let spawned = thread::Builder::new()
+        .name("event log".to_string())
+        .spawn(move || write_lines(receiver));
+    if let Err(e) = spawned {
+        warn!("Couldn't start the event log: {}", e);
+    }
+    sender
+}
+
+/// Append every line received to the file of its day, until the app exits
+fn write_lines(receiver: Receiver<Line>) {
+    let mut open: Option<(PathBuf, File, u64)> = None;
+
+    for line in receiver {
+        let path = event_file(&app_paths().log_file, line.ts);
+        if open
+            .as_ref()
+            .is_none_or(|(open_path, ..)| *open_path != path)
+        {
+            open = open_file(&path);
+        }
+        let Some((_, file, size)) = open.as_mut() else {
+            continue;
+        };
+
+        let entry = format!("{}\n", to_json(&line));
+        if file.write_all(entry.as_bytes()).is_ok() {
+            *size += entry.len() as u64;
+        }
+
+        if *size > get_event_log_max_bytes() {
+            open = None;
+            rotate(&path);
+        }
+    }
+}
+
+fn open_file(path: &Path) -> Option<(PathBuf, File, u64)> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).ok();
+    }
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => {
+            let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+            Some((path.to_path_buf(), file, size))
+        }
+        Err(e) => {
+            warn!("Couldn't open the event log {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Shift events-<date>.jsonl to .1.jsonl, .1 to .2 and so on, dropping the oldest
+fn rotate(path: &Path) {
+    for n in (1..KEPT_EVENT_FILES).rev() {
+        std::fs::rename(rotated_path(path, n), rotated_path(path, n + 1)).ok();
+    }
+    std::fs::rename(path, rotated_path(path, 1)).ok();
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("{}.jsonl", n))
+}
+
+/// File for the events of the (UTC) day of `ts`, in an `events` folder next to the log
+fn event_file(log_file: &Path, ts: u64) -> PathBuf {
+    let (year, month, day) = civil_date(ts / 1000 / 86_400);
+    log_file
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("events")
+        .join(format!("events-{:04}-{:02}-{:02}.jsonl", year, month, day))
+}
+
+/// Year, month and day of the date `days` days after 1970-01-01
+pub(crate) fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Howard Hinnant's days-to-civil algorithm, for dates after the epoch
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,377 @@
+// Capture and restore the foreground window so transcriptions are typed into
+// the window that was active when recording started, and tell which app it
+// belongs to so `output.blocked_apps` are never typed into.
+//
+// On Windows, also tell when typed text can't reach the foreground window at all:
+// a UAC prompt or the lock screen is on the secure desktop, and a window running
+// as administrator ignores keys from a process that isn't (UIPI). Either way the
+// key presses are dropped without an error.
+
+use crate::get_blocked_apps;
+#[cfg(target_os = "linux")]
+use crate::session::SessionType;
+
+/// A window that was in the foreground and can be re-activated later
+///
+/// Holds an HWND on Windows, an X11 window id on Linux and a process id on macOS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FocusTarget(u64);
+
+/// The app owning the foreground window, as far as the platform tells
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForegroundApp {
+    /// Executable name, e.g. "KeePassXC.exe" or "keepassxc"
+    pub process: Option<String>,
+    /// Window class on Windows and X11, bundle id on macOS
+    pub class: Option<String>,
+}
+
+impl ForegroundApp {
+    /// Whether `name` is the process (".exe" optional) or class, ignoring case
+    pub fn is(&self, name: &str) -> bool {
+        let normalize = |id: &str| {
+            let id = id.trim().to_lowercase();
+            id.strip_suffix(".exe").map(str::to_string).unwrap_or(id)
+        };
+        let name = normalize(name);
+        self.process
+            .iter()
+            .chain(&self.class)
+            .any(|id| normalize(id) == name)
+    }
+
+    /// How to refer to the app in messages
+    pub fn name(&self) -> &str {
+        self.process
+            .as_deref()
+            .or(self.class.as_deref())
+            .unwrap_or("this app")
+    }
+}
+
+/// Why typed text wouldn't reach the foreground window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unreachable {
+    /// A UAC prompt, the lock screen or Ctrl+Alt+Del has the input
+    SecureDesktop,
+    /// The foreground app runs as administrator and this one doesn't
+    Elevated(ForegroundApp),
+}
+
+impl Unreachable {
+    /// What's in the way, for messages
+    pub fn describe(&self) -> String {
+        match self {
+            Unreachable::SecureDesktop => "A UAC prompt or the lock screen has focus".to_string(),
+            Unreachable::Elevated(app) => format!("{} runs as administrator", app.name()),
+        }
+    }
+}
+
+/// The focused app if it's one of `output.blocked_apps`, checked right before typing
+pub fn blocked_foreground_app() -> Option<ForegroundApp> {
+    let blocked = get_blocked_apps();
+    if blocked.is_empty() {
+        return None;
+    }
+    let app = foreground_app()?;
+    blocked.iter().any(|name| app.is(name)).then_some(app)
+}
+
+/// Get the current foreground window, or None where this isn't supported
+#[cfg(target_os = "windows")]
+pub fn capture_foreground_window() -> Option<FocusTarget> {
+    let hwnd = unsafe { winapi::um::winuser::GetForegroundWindow() };
+    if hwnd.is_null() {
+        None
+    } else {
+        Some(FocusTarget(hwnd as u64))
+    }
+}
+
+/// Bring a previously captured window back to the foreground
+#[cfg(target_os = "windows")]
+pub fn restore_focus(target: FocusTarget) -> bool {
+    let hwnd = target.0 as winapi::shared::windef::HWND;
+    unsafe {
+        if winapi::um::winuser::IsWindow(hwnd) == 0 {
+            return false;
+        }
+        if winapi::um::winuser::GetForegroundWindow() == hwnd {
+            return true;
+        }
+        winapi::um::winuser::SetForegroundWindow(hwnd) != 0
+    }
+}
+
+/// Get the app owning the foreground window
+#[cfg(target_os = "windows")]
+pub fn foreground_app() -> Option<ForegroundApp> {
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetClassNameW, GetForegroundWindow, GetWindowThreadProcessId};
+
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() {
+            return None;
+        }
+
+        let mut class = [0u16; 256];
+        let length = GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32);
+        let class = (length > 0).then(|| String::from_utf16_lossy(&class[..length as usize]));
+
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        let mut process = None;
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if !handle.is_null() {
+            let mut path = [0u16; 1024];
+            let mut length = path.len() as u32;
+            if QueryFullProcessImageNameW(handle, 0, path.as_mut_ptr(), &mut length) != 0 {
+                let path = String::from_utf16_lossy(&path[..length as usize]);
+                process = std::path::Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string());
+            }
+            CloseHandle(handle);
+        }
+
+        Some(ForegroundApp { process, class })
+    }
+}
+
+/// Why typed text wouldn't reach the foreground window, if it wouldn't
+#[cfg(target_os = "windows")]
+pub fn unreachable_foreground() -> Option<Unreachable> {
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, GetCurrentProcessId, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    if !is_input_desktop_default() {
+        return Some(Unreachable::SecureDesktop);
+    }
+
+    unsafe {
+        // An elevated process can type anywhere
+        if is_elevated(GetCurrentProcess()) != Some(false) {
+            return None;
+        }
+
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() {
+            return None;
+        }
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 || pid == GetCurrentProcessId() {
+            return None;
+        }
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+        let elevated = is_elevated(handle);
+        CloseHandle(handle);
+
+        (elevated == Some(true))
+            .then(|| Unreachable::Elevated(foreground_app().unwrap_or_default()))
+    }
+}
+
+/// Whether the desktop receiving input is the user's, not Winlogon's secure one
+#[cfg(target_os = "windows")]
+fn is_input_desktop_default() -> bool {
+    use winapi::um::winuser::{
+        CloseDesktop, DESKTOP_READOBJECTS, GetUserObjectInformationW, OpenInputDesktop, UOI_NAME,
+    };
+
+    unsafe {
+        // The secure desktop can't be opened from a user process at all
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_READOBJECTS);
+        if desktop.is_null() {
+            return false;
+        }
+        let mut name = [0u16; 64];
+        let mut needed = 0;
+        let named = GetUserObjectInformationW(
+            desktop as _,
+            UOI_NAME,
+            name.as_mut_ptr() as _,
+            (name.len() * 2) as u32,
+            &mut needed,
+        ) != 0;
+        CloseDesktop(desktop);
+        let length = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        !named || String::from_utf16_lossy(&name[..length]).eq_ignore_ascii_case("Default")
+    }
+}
+
+/// Whether a process runs elevated, None when that can't be told
+///
+/// A non-elevated process usually can't open an elevated one's token, which
+/// counts as elevated.
+#[cfg(target_os = "windows")]
+unsafe fn is_elevated(process: winapi::um::winnt::HANDLE) -> Option<bool> {
+    use winapi::shared::winerror::ERROR_ACCESS_DENIED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcessToken;
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::winnt::{TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation};
+
+    unsafe {
+        let mut token = std::ptr::null_mut();
+        if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+            return (GetLastError() == ERROR_ACCESS_DENIED).then_some(true);
+        }
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut size = 0;
+        let queried = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        ) != 0;
+        CloseHandle(token);
+        queried.then_some(elevation.TokenIsElevated != 0)
+    }
+}
+
+/// Get the current foreground window, or None where this isn't supported
+#[cfg(target_os = "linux")]
+pub fn capture_foreground_window() -> Option<FocusTarget> {
+    // Wayland doesn't let clients query or activate other windows
+    if SessionType::detect() == SessionType::Wayland {
+        return None;
+    }
+
+    let output = std::process::Command::new("xdotool")
+        .arg("getactivewindow")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()
+        .map(FocusTarget)
+}
+
+/// Bring a previously captured window back to the foreground
+#[cfg(target_os = "linux")]
+pub fn restore_focus(target: FocusTarget) -> bool {
+    std::process::Command::new("xdotool")
+        .args(["windowactivate", "--sync", &target.0.to_string()])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Get the app owning the foreground window (X11 only, like the window itself)
+#[cfg(target_os = "linux")]
+pub fn foreground_app() -> Option<ForegroundApp> {
+    let window = capture_foreground_window()?.0.to_string();
+    let xdotool = |command: &str| {
+        let output = std::process::Command::new("xdotool")
+            .args([command, &window])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    let process = xdotool("getwindowpid")
+        .and_then(|pid| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+        .map(|name| name.trim().to_string());
+    Some(ForegroundApp {
+        process,
+        class: xdotool("getwindowclassname"),
+    })
+}
+
+/// Get the current foreground window, or None where this isn't supported
+#[cfg(target_os = "macos")]
+pub fn capture_foreground_window() -> Option<FocusTarget> {
+    let output = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get unix id of first process whose frontmost is true",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()
+        .map(FocusTarget)
+}
+
+/// Bring a previously captured window back to the foreground
+#[cfg(target_os = "macos")]
+pub fn restore_focus(target: FocusTarget) -> bool {
+    let script = format!(
+        "tell application \"System Events\" to set frontmost of (first process whose unix id is {}) to true",
+        target.0
+    );
+    std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Get the app owning the foreground window
+#[cfg(target_os = "macos")]
+pub fn foreground_app() -> Option<ForegroundApp> {
+    let output = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get {name, bundle identifier} of first process whose frontmost is true",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (name, bundle) = output.trim().split_once(", ")?;
+    Some(ForegroundApp {
+        process: Some(name.to_string()),
+        class: Some(bundle.to_string()),
+    })
+}
+
+/// Get the current foreground window, or None where this isn't supported
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn capture_foreground_window() -> Option<FocusTarget> {
+    None
+}
+
+/// Bring a previously captured window back to the foreground
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn restore_focus(_target: FocusTarget) -> bool {
+    false
+}
+
+/// Why typed text wouldn't reach the foreground window: only told on Windows
+#[cfg(not(target_os = "windows"))]
+pub fn unreachable_foreground() -> Option<Unreachable> {
+    None
+}
+
+/// Get the app owning the foreground window
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn foreground_app() -> Option<ForegroundApp> {
+    None
+}
@@ -0,0 +1,72 @@
+// Headless transcription of audio piped on stdin, without the tray or hotkeys.
+//
+// `--transcribe-pcm` reads raw mono PCM until EOF: little-endian f32 samples by
+// default, or i16 with `--pcm-format i16`, at the rate given by `--input-rate`
+// (16000 Hz if omitted). The text goes to stdout, as JSON with `--json` (with
+// the stage timings when `--timings` is also given).
+
+use crate::cli::Cli;
+use esponquen::EngineConfig;
+use esponquen::audio::{MODEL_SAMPLE_RATE, decode_pcm};
+use esponquen::{diagln, is_timings_enabled};
+use log::error;
+use std::io::Read;
+
+/// Transcribe raw PCM from stdin, returning the process exit code
+pub fn transcribe_pcm(cli: &Cli, engine: &EngineConfig) -> i32 {
+    let format = cli.pcm_format;
+    let sample_rate = cli.input_rate.unwrap_or(MODEL_SAMPLE_RATE);
+
+    let mut bytes = Vec::new();
+    if let Err(e) = std::io::stdin().lock().read_to_end(&mut bytes) {
+        error!("Failed to read PCM from stdin: {}", e);
+        return 1;
+    }
+
+    let samples = match decode_pcm(&bytes, format) {
+        Ok(samples) => samples,
+        Err(e) => {
+            error!("{}", e);
+            return 1;
+        }
+    };
+
+    if samples.is_empty() {
+        error!("No audio on stdin");
+        return 1;
+    }
+
+    diagln!(
+        "Read {:.2} seconds of {:?} audio at {} Hz",
+        samples.len() as f32 / sample_rate as f32,
+        format,
+        sample_rate
+    );
+
+    let mut recognizer = match crate::load_recognizer(engine) {
+        Ok((recognizer, _, _)) => recognizer,
+        Err(e) => {
+            error!("{}", e);
+            return 1;
+        }
+    };
+    let (text, timings) = crate::transcribe(&mut recognizer, sample_rate, &samples);
+    crate::report_timings(&timings);
+
+    if cli.json {
+        let mut output = serde_json::json!({ "text": text });
+        if is_timings_enabled() {
+            output["timings"] = serde_json::json!({
+                "audio_secs": timings.audio.as_secs_f64(),
+                "resample_ms": timings.resample.as_secs_f64() * 1000.0,
+                "inference_ms": timings.inference.as_secs_f64() * 1000.0,
+                "real_time_factor": timings.real_time_factor(),
+            });
+        }
+        println!("{}", output);
+    } else {
+        println!("{}", text);
+    }
+
+    0
+}
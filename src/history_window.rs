@@ -0,0 +1,81 @@
+// History window, run in its own process (`--history-window`) like the settings
+// window. Recent transcriptions arrive as `entry=text` lines on stdin, newest
+// first, or `untyped=text` for the ones that couldn't be typed. Copying happens in the window itself; retyping writes `retype=index`
+// on stdout and closes the window so the app types into whatever had focus.
+
+use crate::settings_window::emit;
+use eframe::egui;
+use esponquen::settings;
+use log::error;
+use std::io::BufRead;
+
+/// Key of the lines carrying one history entry
+pub const ENTRY: &str = "entry";
+/// Key of the lines carrying an entry that couldn't be typed
+pub const UNTYPED_ENTRY: &str = "untyped";
+/// Key of the line asking the app to retype an entry (by index)
+pub const RETYPE: &str = "retype";
+
+struct HistoryApp {
+    /// Text of each entry and whether it couldn't be typed
+    entries: Vec<(String, bool)>,
+}
+
+impl eframe::App for HistoryApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.entries.is_empty() {
+                ui.label("No transcriptions yet.");
+                return;
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (index, (entry, untyped)) in self.entries.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Copy").clicked() {
+                            ctx.copy_text(entry.clone());
+                        }
+                        if ui.small_button("Retype").clicked() {
+                            emit(RETYPE, &index.to_string());
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                        }
+                        if *untyped {
+                            ui.label("⚠ not typed");
+                        }
+                        ui.label(entry);
+                    });
+                    ui.separator();
+                }
+            });
+        });
+    }
+}
+
+/// Read the history from stdin and show the history window
+pub fn run() {
+    let entries = std::io::stdin()
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| match settings::split_line(&line) {
+            Some((ENTRY, text)) => Some((text.to_string(), false)),
+            Some((UNTYPED_ENTRY, text)) => Some((text.to_string(), true)),
+            _ => None,
+        })
+        .collect();
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Esponquen History")
+            .with_inner_size([480.0, 360.0]),
+        ..Default::default()
+    };
+
+    if let Err(e) = eframe::run_native(
+        "Esponquen History",
+        options,
+        Box::new(|_cc| Ok(Box::new(HistoryApp { entries }))),
+    ) {
+        error!("Failed to open history window: {}", e);
+    }
+}
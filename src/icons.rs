@@ -0,0 +1,39 @@
+// Programmatically generated tray icons, used when the icon files are missing
+// or can't be decoded.
+
+/// Size in pixels of generated icons
+pub const ICON_SIZE: u32 = 32;
+
+/// Grey, shown while idle
+pub const IDLE_COLOR: [u8; 3] = [128, 128, 128];
+/// Red, shown while recording
+pub const RECORDING_COLOR: [u8; 3] = [220, 40, 40];
+/// Orange, shown while the model loads
+pub const LOADING_COLOR: [u8; 3] = [240, 150, 30];
+/// Blue, shown while transcribing
+pub const TRANSCRIBING_COLOR: [u8; 3] = [40, 110, 220];
+/// Dark red, shown when the config keeps the app from starting
+pub const ERROR_COLOR: [u8; 3] = [130, 20, 20];
+
+/// Render a filled, anti-aliased circle on a transparent background as RGBA bytes
+pub fn circle_rgba(size: u32, color: [u8; 3]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+    let center = size as f32 / 2.0;
+    let radius = center - 1.0;
+
+    for y in 0..size {
+        for x in 0..size {
+            let dx = x as f32 + 0.5 - center;
+            let dy = y as f32 + 0.5 - center;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            // Fade the outermost pixel for a smooth edge
+            let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+
+            rgba.extend_from_slice(&color);
+            rgba.push((coverage * 255.0).round() as u8);
+        }
+    }
+
+    rgba
+}
@@ -0,0 +1,341 @@
+// Single-instance enforcement and the control channel of the running instance.
+//
+// The running instance owns a local endpoint that later instances connect to: a
+// Unix socket on Linux/macOS, and on Windows a named mutex (the lock) plus a
+// loopback TCP port written to a file in the temp directory. Each connection
+// carries one JSON command line and gets one JSON response line back, e.g.
+//
+//   {"command": "set-hotkey", "key": "F9"}  ->  {"ok": true}
+//   {"command": "bogus"}                    ->  {"ok": false, "error": "..."}
+//
+// Access is limited by file permissions: the socket is only accessible to its
+// owner, and on Windows a random token stored next to the port must be sent first.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A request to the running instance (`--replace` and `esponquen ctl`)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlCommand {
+    /// Shut down cleanly, finishing a dictation in progress
+    Quit,
+    StartRecording,
+    /// Stop recording and transcribe
+    Stop,
+    Toggle,
+    Status,
+    SetHotkey {
+        key: String,
+    },
+}
+
+/// Answers a command with the fields of a successful response, or an error
+pub type Handler = dyn Fn(ControlCommand) -> Result<Value, String> + Send;
+
+// How long --replace waits for the old instance, which may finish a dictation first
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(30);
+const REPLACE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// A client that doesn't finish its command line within this is dropped, so it
+// can't hold up the commands queued behind it
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub use platform::InstanceLock;
+
+/// Become the running instance, or return None if another one already is
+///
+/// `handler` is called (from a background thread) for every command sent by a
+/// later instance.
+pub fn acquire(
+    handler: impl Fn(ControlCommand) -> Result<Value, String> + Send + 'static,
+) -> io::Result<Option<InstanceLock>> {
+    Ok(platform::try_lock()?.map(|(lock, listener)| {
+        thread::spawn(move || platform::serve(listener, &handler));
+        lock
+    }))
+}
+
+/// Ask the running instance (if any) to quit, then take its place
+pub fn replace(
+    handler: impl Fn(ControlCommand) -> Result<Value, String> + Send + 'static,
+) -> Result<InstanceLock, String> {
+    // Only fails when nothing is running, which is fine here
+    if let Ok(stream) = platform::connect() {
+        request(stream, &ControlCommand::Quit)?;
+    }
+
+    let started = Instant::now();
+    loop {
+        match platform::try_lock() {
+            Ok(Some((lock, listener))) => {
+                thread::spawn(move || platform::serve(listener, &handler));
+                return Ok(lock);
+            }
+            Ok(None) if started.elapsed() < REPLACE_TIMEOUT => thread::sleep(REPLACE_POLL_INTERVAL),
+            Ok(None) => {
+                return Err(format!(
+                    "The running instance didn't exit within {} seconds",
+                    REPLACE_TIMEOUT.as_secs()
+                ));
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Failed to take over from the running instance: {}",
+                    e
+                ));
+            }
+        }
+    }
+}
+
+/// Send one command to the running instance and return its response
+pub fn send(command: &ControlCommand) -> Result<Value, String> {
+    let stream = platform::connect().map_err(|_| "Esponquen isn't running".to_string())?;
+    request(stream, command)
+}
+
+fn request(mut stream: impl Read + Write, command: &ControlCommand) -> Result<Value, String> {
+    let failed = |e: io::Error| format!("Failed to reach the running instance: {}", e);
+    let message = serde_json::to_string(command).map_err(|e| e.to_string())?;
+    writeln!(stream, "{}", message).map_err(failed)?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(failed)?;
+    serde_json::from_str(&line)
+        .map_err(|e| format!("Invalid response from the running instance: {}", e))
+}
+
+/// Answer one command from a connecting instance
+fn handle_connection(mut reader: impl BufRead, mut writer: impl Write, handler: &Handler) {
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+
+    let result = serde_json::from_str::<ControlCommand>(line.trim())
+        .map_err(|e| format!("Invalid command: {}", e))
+        .and_then(handler);
+    let response = match result {
+        Ok(mut fields) => {
+            if !fields.is_object() {
+                fields = json!({});
+            }
+            fields["ok"] = json!(true);
+            fields
+        }
+        Err(e) => json!({ "ok": false, "error": e }),
+    };
+
+    writeln!(writer, "{}", response).ok();
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::io::{self, BufReader};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+
+    /// Removes the socket when the running instance exits
+    pub struct InstanceLock {
+        path: PathBuf,
+    }
+
+    impl Drop for InstanceLock {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.path).ok();
+        }
+    }
+
+    fn socket_path() -> PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("esponquen.sock")
+    }
+
+    pub fn try_lock() -> io::Result<Option<(InstanceLock, UnixListener)>> {
+        let path = socket_path();
+        if UnixStream::connect(&path).is_ok() {
+            return Ok(None);
+        }
+
+        // Left behind by an instance that didn't exit cleanly
+        std::fs::remove_file(&path).ok();
+        let listener = UnixListener::bind(&path)?;
+        let lock = InstanceLock { path };
+        // The temp directory fallback is shared with other users
+        std::fs::set_permissions(&lock.path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(Some((lock, listener)))
+    }
+
+    pub fn connect() -> io::Result<UnixStream> {
+        UnixStream::connect(socket_path())
+    }
+
+    pub fn serve(listener: UnixListener, handler: &super::Handler) {
+        for stream in listener.incoming().flatten() {
+            if stream.set_read_timeout(Some(super::READ_TIMEOUT)).is_err() {
+                continue;
+            }
+            super::handle_connection(BufReader::new(&stream), &stream, handler);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use winapi::shared::winerror::ERROR_ALREADY_EXISTS;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::synchapi::CreateMutexW;
+    use winapi::um::winnt::HANDLE;
+
+    /// Holds the named mutex; the port file goes away with it
+    pub struct InstanceLock {
+        mutex: HANDLE,
+        port_file: PathBuf,
+    }
+
+    /// Listener plus the token clients must send before their command
+    pub struct Listener {
+        listener: TcpListener,
+        token: String,
+    }
+
+    impl Drop for InstanceLock {
+        fn drop(&mut self) {
+            // Before releasing the mutex, so the file can't belong to a new instance yet
+            std::fs::remove_file(&self.port_file).ok();
+            unsafe {
+                CloseHandle(self.mutex);
+            }
+        }
+    }
+
+    fn port_file_path() -> PathBuf {
+        std::env::temp_dir().join("esponquen.port")
+    }
+
+    /// Random enough to keep other local users out, who can't read our temp directory
+    fn new_token() -> String {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        format!("{:016x}", hasher.finish())
+    }
+
+    pub fn try_lock() -> io::Result<Option<(InstanceLock, Listener)>> {
+        let name: Vec<u16> = "Local\\EsponquenSingleInstance\0".encode_utf16().collect();
+        let mutex = unsafe { CreateMutexW(std::ptr::null_mut(), 0, name.as_ptr()) };
+        if mutex.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            unsafe {
+                CloseHandle(mutex);
+            }
+            return Ok(None);
+        }
+
+        let port_file = port_file_path();
+        let lock = InstanceLock { mutex, port_file };
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let token = new_token();
+        let port = listener.local_addr()?.port();
+        std::fs::write(&lock.port_file, format!("{}\n{}", port, token))?;
+        Ok(Some((lock, Listener { listener, token })))
+    }
+
+    pub fn connect() -> io::Result<TcpStream> {
+        let contents = std::fs::read_to_string(port_file_path())?;
+        let mut lines = contents.lines();
+        let port: u16 = lines
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let token = lines.next().unwrap_or_default().trim();
+
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+        writeln!(stream, "{}", token)?;
+        Ok(stream)
+    }
+
+    pub fn serve(listener: Listener, handler: &super::Handler) {
+        for stream in listener.listener.incoming().flatten() {
+            if stream.set_read_timeout(Some(super::READ_TIMEOUT)).is_err() {
+                continue;
+            }
+            // Drop connections that don't know the token
+            let mut token = String::new();
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut token).is_err() || token.trim() != listener.token {
+                continue;
+            }
+            super::handle_connection(reader, &stream, handler);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(line: &str, handler: &Handler) -> Value {
+        let mut response = Vec::new();
+        handle_connection(line.as_bytes(), &mut response, handler);
+        serde_json::from_slice(&response).unwrap()
+    }
+
+    #[test]
+    fn malformed_commands_get_an_error_response() {
+        let never_called = |_: ControlCommand| -> Result<Value, String> {
+            panic!("the handler got a malformed command")
+        };
+
+        let lines = [
+            "not json\n",
+            "{\"command\": \"bogus\"}\n",
+            "{\"command\": \"set-hotkey\"}\n",
+        ];
+        for line in lines {
+            let response = respond(line, &never_called);
+            assert_eq!(response["ok"], json!(false), "{}", line);
+            let error = response["error"].as_str().unwrap();
+            assert!(error.starts_with("Invalid command"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn handler_replies_become_the_response_fields() {
+        let status = |_: ControlCommand| Ok(json!({ "status": "recording" }));
+        assert_eq!(
+            respond("{\"command\": \"status\"}\n", &status),
+            json!({ "ok": true, "status": "recording" })
+        );
+
+        // Only objects can carry fields; anything else is a bare success
+        let not_an_object = |_: ControlCommand| Ok(json!("done"));
+        assert_eq!(
+            respond("{\"command\": \"toggle\"}\n", &not_an_object),
+            json!({ "ok": true })
+        );
+
+        let refused = |_: ControlCommand| Err("Not recording".to_string());
+        assert_eq!(
+            respond("{\"command\": \"stop\"}\n", &refused),
+            json!({ "ok": false, "error": "Not recording" })
+        );
+    }
+}
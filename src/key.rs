@@ -0,0 +1,34 @@
+// The key type hotkeys are set with. With the `hotkey` feature it's rdev's, the
+// one the keyboard grab reports; without it, a stand-in with the same names for
+// the keys a hotkey can be, so settings still parse and validate in a build that
+// can't grab the keyboard (e.g. a server-side tool using only the pipeline).
+
+#[cfg(feature = "hotkey")]
+pub use rdev::Key;
+
+/// Stand-in for `rdev::Key` without the `hotkey` feature, see the module docs
+#[cfg(not(feature = "hotkey"))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Key {
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Insert,
+    Pause,
+    ScrollLock,
+    /// A key without a name, by its platform code (e.g. F13 and up)
+    Unknown(u32),
+}
@@ -1,21 +1,815 @@
+pub mod audio;
+pub mod beep;
+pub mod capture;
+pub mod clipboard;
+pub mod command;
+pub mod config;
+pub mod context;
+pub mod crash;
+#[cfg(target_os = "linux")]
+pub mod dbus;
+pub mod dictation;
+pub mod engine;
+pub mod environment;
+pub mod events;
+pub mod focus;
+pub mod icons;
+pub mod key;
+pub mod logging;
+pub mod model;
+pub mod notification;
+pub mod output;
+pub mod paths;
+pub mod permissions;
+pub mod power;
+pub mod recorder;
+pub mod session;
+pub mod settings;
+pub mod stats;
+pub mod sync;
+pub mod testing;
+pub mod text;
+pub mod timing;
+pub mod transcriber;
+pub mod validate;
+pub mod webhook;
+
+pub use context::AppContext;
+pub use dictation::DictationEngine;
+pub use engine::{EngineBuilder, EngineConfig};
+pub use log;
+pub use paths::AppPaths;
+
+use audio::{Normalization, ResampleQuality};
+use beep::SoundCues;
+use key::Key;
+use log::LevelFilter;
 use once_cell::sync::Lazy;
-use rdev::Key as RdevKey;
-use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use sync::lock;
+use text::{FileHeader, Wrap};
+use webhook::Webhook;
+
+// Global flag for --emit-text: stdout carries only transcriptions
+static EMIT_TEXT: AtomicBool = AtomicBool::new(false);
+
+// Global flag for --quiet: nothing on the console but errors
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Log a diagnostic line at info level: on stdout normally, on stderr with --emit-text
+#[macro_export]
+macro_rules! diagln {
+    () => {
+        $crate::log::info!("")
+    };
+    ($($arg:tt)*) => {
+        $crate::log::info!($($arg)*)
+    };
+}
+
+/// Print diagnostic text without a newline, console only (progress and prompts)
+#[macro_export]
+macro_rules! diag {
+    ($($arg:tt)*) => {
+        if $crate::is_quiet() {
+        } else if $crate::is_emit_text() {
+            eprint!($($arg)*)
+        } else {
+            print!($($arg)*)
+        }
+    };
+}
+
+// The app's dictation pipeline, holding the hotkey (F6 by default)
+static APP_CONTEXT: Lazy<Arc<AppContext>> = Lazy::new(|| Arc::new(AppContext::new()));
+
+// Global state for the key that opens the history window (none by default)
+static HISTORY_HOTKEY: Lazy<Arc<Mutex<Option<Key>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for the key that quits the app (none by default)
+static QUIT_HOTKEY: Lazy<Arc<Mutex<Option<Key>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for the key that switches to the next provider (none by default)
+static PROVIDER_HOTKEY: Lazy<Arc<Mutex<Option<Key>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for the key that previews the recording so far (none by default)
+static SNAPSHOT_HOTKEY: Lazy<Arc<Mutex<Option<Key>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for the key that dictates to the clipboard (none by default)
+static CLIPBOARD_HOTKEY: Lazy<Arc<Mutex<Option<Key>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for whether the hotkey toggles recording or records while held
+static HOTKEY_MODE: Lazy<Arc<Mutex<HotkeyMode>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HotkeyMode::Toggle)));
+
+// Global state for swallowing hotkey presses (None: only keys that are safe to block)
+static BLOCK_HOTKEYS: Lazy<Arc<Mutex<Option<bool>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for what Quit does while a dictation is in progress
+static QUIT_BEHAVIOR: Lazy<Arc<Mutex<QuitBehavior>>> =
+    Lazy::new(|| Arc::new(Mutex::new(QuitBehavior::Prompt)));
+
+// Global state for what happens when a transcription comes back empty
+static ON_EMPTY: Lazy<Arc<Mutex<OnEmpty>>> = Lazy::new(|| Arc::new(Mutex::new(OnEmpty::Ignore)));
+
+// Global state for the tones played when a dictation ends
+static SOUND_CUES: Lazy<Arc<Mutex<SoundCues>>> =
+    Lazy::new(|| Arc::new(Mutex::new(SoundCues::default())));
+
+// Global flag for restoring the recording's target window before typing
+static RESTORE_FOCUS: AtomicBool = AtomicBool::new(false);
+
+// Global flag for checking that input can be sent before typing
+static TYPING_PROBE: AtomicBool = AtomicBool::new(true);
+
+// Global state for the apps that are never typed into
+static BLOCKED_APPS: Lazy<Arc<Mutex<Vec<String>>>> = Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+
+// Global state for the URL transcriptions are posted to
+static WEBHOOK: Lazy<Arc<Mutex<Option<Webhook>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Global state for the line written before each transcription appended to a file
+static FILE_HEADER: Lazy<Arc<Mutex<FileHeader>>> =
+    Lazy::new(|| Arc::new(Mutex::new(FileHeader::default())));
+
+// Global flag for showing the last transcription in the tray tooltip
+static TOOLTIP_PREVIEW: AtomicBool = AtomicBool::new(true);
+
+// Global flag for the on-screen status overlay
+static STATUS_OVERLAY: AtomicBool = AtomicBool::new(false);
+
+// Global flag for putting the text in D-Bus TranscriptionCompleted signals
+static DBUS_EMIT_TEXT: AtomicBool = AtomicBool::new(false);
+
+// Global state for how long a transcription may take before it's abandoned
+static TRANSCRIPTION_TIMEOUT_SECS: AtomicU64 =
+    AtomicU64::new(config::DEFAULT_TRANSCRIPTION_TIMEOUT_SECS);
+
+// Global state for the delay before typing, in milliseconds
+static TYPING_DELAY_MS: AtomicU64 = AtomicU64::new(config::DEFAULT_TYPING_DELAY_MS);
 
-// Global state for hotkey configuration
-static HOTKEY: Lazy<Arc<Mutex<RdevKey>>> = Lazy::new(|| {
-    Arc::new(Mutex::new(RdevKey::F6)) // Default hotkey is F6
-});
+// Global state for how long a transcription waits for a reachable window, in seconds
+static UNREACHABLE_HOLD_SECS: AtomicU64 = AtomicU64::new(config::DEFAULT_UNREACHABLE_HOLD_SECS);
+
+// Global flag for printing per-transcription timings
+static TIMINGS: AtomicBool = AtomicBool::new(false);
+
+// Global state for where transcriptions go
+static OUTPUT_MODE: Lazy<Arc<Mutex<OutputMode>>> =
+    Lazy::new(|| Arc::new(Mutex::new(OutputMode::Type)));
+
+// Global state for where files are read and written (standard or portable layout).
+// Not locked with sync::lock: the logger reads it, so logging a recovery would deadlock.
+static APP_PATHS: Lazy<Arc<Mutex<AppPaths>>> =
+    Lazy::new(|| Arc::new(Mutex::new(AppPaths::standard())));
+
+// Global flag for verbose logging, raising the log level to debug
+static VERBOSE_LOGGING: AtomicBool = AtomicBool::new(false);
+
+// Global state for the configured log level (config or --log-level)
+static LOG_LEVEL: Lazy<Arc<Mutex<LevelFilter>>> =
+    Lazy::new(|| Arc::new(Mutex::new(LevelFilter::Info)));
+
+// Global flag for writing transcriptions to the log file at any level
+static LOG_TRANSCRIPTS: AtomicBool = AtomicBool::new(false);
+
+// Global flag for the JSONL event log
+static EVENT_LOG: AtomicBool = AtomicBool::new(false);
+
+// Global state for the size at which a day's event log is rotated
+static EVENT_LOG_MAX_BYTES: AtomicU64 = AtomicU64::new(config::DEFAULT_EVENTS_MAX_MB * 1024 * 1024);
+
+/// Path of the log file when there's no data directory, outside portable mode
+pub const LOG_FILE_PATH: &str = "./esponquen.log";
+
+/// Hotkeys offered in the tray menu and the settings window
+pub const HOTKEY_OPTIONS: [(&str, Key); 12] = [
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+];
+
+/// Keys a hotkey can also be set to in the config, which foot pedals are often
+/// programmed to send; not offered in the menu since keyboards mostly lack them
+pub const EXTRA_HOTKEYS: [(&str, Key); 7] = [
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("Insert", Key::Insert),
+    ("Pause", Key::Pause),
+    ("ScrollLock", Key::ScrollLock),
+];
+
+/// What the keys a hotkey can be are, for error messages
+pub const HOTKEY_HINT: &str =
+    "F1-F24, PageUp, PageDown, Home, End, Insert, Pause, ScrollLock or Unknown(<code>)";
+
+// Codes the keyboard hook reports F13 and up as; rdev has no names for them.
+// Windows virtual-key codes:
+#[cfg(target_os = "windows")]
+const F13_AND_UP: &[u32] = &[
+    0x7C, 0x7D, 0x7E, 0x7F, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+];
+// macOS virtual key codes, which stop at F20:
+#[cfg(target_os = "macos")]
+const F13_AND_UP: &[u32] = &[105, 107, 113, 106, 64, 79, 80, 90];
+// X11 keycodes (the evdev code plus 8):
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const F13_AND_UP: &[u32] = &[191, 192, 193, 194, 195, 196, 197, 198, 199, 200, 201, 202];
+
+/// Look up a hotkey by name (e.g. "F9", "F13", "PageDown"), or a key without a
+/// name by the code the keyboard hook reports for it (e.g. "Unknown(191)")
+pub fn parse_hotkey(name: &str) -> Option<Key> {
+    let name = name.trim();
+    if let Some((_, key)) = HOTKEY_OPTIONS
+        .iter()
+        .chain(&EXTRA_HOTKEYS)
+        .find(|(option, _)| option.eq_ignore_ascii_case(name))
+    {
+        return Some(*key);
+    }
+
+    let lower = name.to_ascii_lowercase();
+    if let Some(number) = lower.strip_prefix('f') {
+        let index = number.parse::<usize>().ok()?.checked_sub(13)?;
+        return F13_AND_UP.get(index).map(|code| Key::Unknown(*code));
+    }
+    let code = lower.strip_prefix("unknown(")?.strip_suffix(')')?;
+    code.trim().parse().ok().map(Key::Unknown)
+}
+
+/// Name of a hotkey as used in settings (e.g. "F9"), which `parse_hotkey` reads back
+pub fn hotkey_name(key: Key) -> String {
+    if let Key::Unknown(code) = key
+        && let Some(index) = F13_AND_UP.iter().position(|f_key| *f_key == code)
+    {
+        return format!("F{}", index + 13);
+    }
+    format!("{:?}", key)
+}
+
+/// The context the app runs its dictation pipeline with
+pub fn app_context() -> &'static Arc<AppContext> {
+    &APP_CONTEXT
+}
 
 /// Set the hotkey for starting/stopping recording
-pub fn set_hotkey(key: RdevKey) {
-    let mut hotkey = HOTKEY.lock().unwrap();
-    *hotkey = key;
-    println!("Hotkey updated to: {:?}", key);
+pub fn set_hotkey(key: Key) {
+    app_context().set_hotkey(key);
+    diagln!("Hotkey updated to: {:?}", key);
 }
 
 /// Get the current hotkey
-pub fn get_hotkey() -> RdevKey {
-    let hotkey = HOTKEY.lock().unwrap();
-    *hotkey
+pub fn get_hotkey() -> Key {
+    app_context().hotkey()
+}
+
+/// Set the hotkey that opens the history window, or None to disable it
+pub fn set_history_hotkey(key: Option<Key>) {
+    let mut history_hotkey = lock(&HISTORY_HOTKEY);
+    *history_hotkey = key;
+}
+
+/// Get the hotkey that opens the history window, if any
+pub fn get_history_hotkey() -> Option<Key> {
+    let history_hotkey = lock(&HISTORY_HOTKEY);
+    *history_hotkey
+}
+
+/// Whether a key can be swallowed without getting in the way of typing: function
+/// keys, Pause, Scroll Lock and keys rdev has no name for (F13 and up, pedal codes)
+pub fn is_safe_to_block(key: Key) -> bool {
+    HOTKEY_OPTIONS.iter().any(|(_, option)| *option == key)
+        || matches!(key, Key::Pause | Key::ScrollLock | Key::Unknown(_))
+}
+
+/// Whether the recording hotkey toggles recording or records while it's held
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HotkeyMode {
+    /// One press starts recording, the next one stops it
+    #[default]
+    Toggle,
+    /// Record from press to release (push-to-talk, e.g. with a foot pedal)
+    Hold,
+}
+
+/// Set whether the recording hotkey toggles recording or records while held
+pub fn set_hotkey_mode(mode: HotkeyMode) {
+    let mut hotkey_mode = lock(&HOTKEY_MODE);
+    *hotkey_mode = mode;
+}
+
+/// Get whether the recording hotkey toggles recording or records while held
+pub fn get_hotkey_mode() -> HotkeyMode {
+    let hotkey_mode = lock(&HOTKEY_MODE);
+    *hotkey_mode
+}
+
+/// Force blocking hotkey presses on or off, or None to block only safe keys
+pub fn set_block_hotkeys(block: Option<bool>) {
+    let mut block_hotkeys = lock(&BLOCK_HOTKEYS);
+    *block_hotkeys = block;
+}
+
+/// Check whether presses of a hotkey are kept from the focused app
+pub fn is_hotkey_blocked(key: Key) -> bool {
+    let block_hotkeys = lock(&BLOCK_HOTKEYS);
+    block_hotkeys.unwrap_or_else(|| is_safe_to_block(key))
+}
+
+/// Set the hotkey that quits the app, or None to disable it
+pub fn set_quit_hotkey(key: Option<Key>) {
+    let mut quit_hotkey = lock(&QUIT_HOTKEY);
+    *quit_hotkey = key;
+}
+
+/// Get the hotkey that quits the app, if any
+pub fn get_quit_hotkey() -> Option<Key> {
+    let quit_hotkey = lock(&QUIT_HOTKEY);
+    *quit_hotkey
+}
+
+/// Set the hotkey that reloads the model with the next provider, or None to disable it
+pub fn set_provider_hotkey(key: Option<Key>) {
+    let mut provider_hotkey = lock(&PROVIDER_HOTKEY);
+    *provider_hotkey = key;
+}
+
+/// Get the hotkey that reloads the model with the next provider, if any
+pub fn get_provider_hotkey() -> Option<Key> {
+    let provider_hotkey = lock(&PROVIDER_HOTKEY);
+    *provider_hotkey
+}
+
+/// Set the hotkey that transcribes the recording so far without stopping, or None
+pub fn set_snapshot_hotkey(key: Option<Key>) {
+    let mut snapshot_hotkey = lock(&SNAPSHOT_HOTKEY);
+    *snapshot_hotkey = key;
+}
+
+/// Get the hotkey that transcribes the recording so far without stopping, if any
+pub fn get_snapshot_hotkey() -> Option<Key> {
+    let snapshot_hotkey = lock(&SNAPSHOT_HOTKEY);
+    *snapshot_hotkey
+}
+
+/// Set the hotkey that records like the main one but copies the text, or None
+pub fn set_clipboard_hotkey(key: Option<Key>) {
+    let mut clipboard_hotkey = lock(&CLIPBOARD_HOTKEY);
+    *clipboard_hotkey = key;
+}
+
+/// Get the hotkey that records like the main one but copies the text, if any
+pub fn get_clipboard_hotkey() -> Option<Key> {
+    let clipboard_hotkey = lock(&CLIPBOARD_HOTKEY);
+    *clipboard_hotkey
+}
+
+/// What happens when quitting while recording or transcribing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuitBehavior {
+    /// Ask whether to finish the dictation first
+    Prompt,
+    /// Stop the recording, finish transcribing and type the text, then quit
+    FinishFirst,
+}
+
+/// Set the behavior of Quit while a dictation is in progress
+pub fn set_quit_behavior(behavior: QuitBehavior) {
+    let mut quit_behavior = lock(&QUIT_BEHAVIOR);
+    *quit_behavior = behavior;
+}
+
+/// Get the behavior of Quit while a dictation is in progress
+pub fn get_quit_behavior() -> QuitBehavior {
+    let quit_behavior = lock(&QUIT_BEHAVIOR);
+    *quit_behavior
+}
+
+/// What happens when a transcription produces no text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnEmpty {
+    /// Do nothing beyond the console message
+    #[default]
+    Ignore,
+    /// Play a short low beep
+    Beep,
+    /// Show a desktop notification
+    Notify,
+    /// Start a new recording right away so the dictation can be repeated
+    Retry,
+}
+
+/// Set what happens when a transcription produces no text
+pub fn set_on_empty(on_empty: OnEmpty) {
+    let mut current = lock(&ON_EMPTY);
+    *current = on_empty;
+}
+
+/// Get what happens when a transcription produces no text
+pub fn get_on_empty() -> OnEmpty {
+    let current = lock(&ON_EMPTY);
+    *current
+}
+
+/// Set how input audio is converted to the model's sample rate
+pub fn set_resample_quality(quality: ResampleQuality) {
+    app_context().update_settings(|settings| settings.resample_quality = quality);
+}
+
+/// Get how input audio is converted to the model's sample rate
+pub fn get_resample_quality() -> ResampleQuality {
+    app_context().settings().resample_quality
+}
+
+/// Set how recordings are normalized before transcription
+pub fn set_normalization(normalization: Normalization) {
+    app_context().update_settings(|settings| settings.normalization = normalization);
+}
+
+/// Get how recordings are normalized before transcription
+pub fn get_normalization() -> Normalization {
+    app_context().settings().normalization
+}
+
+/// Set the tones played when a dictation ends
+pub fn set_sound_cues(cues: SoundCues) {
+    let mut current = lock(&SOUND_CUES);
+    *current = cues;
+}
+
+/// Get the tones played when a dictation ends
+pub fn get_sound_cues() -> SoundCues {
+    let current = lock(&SOUND_CUES);
+    *current
+}
+
+/// Enable or disable re-activating the window that was focused when recording started
+pub fn set_restore_focus(enabled: bool) {
+    RESTORE_FOCUS.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether the target window is re-activated before typing
+pub fn is_restore_focus_enabled() -> bool {
+    RESTORE_FOCUS.load(Ordering::SeqCst)
+}
+
+/// Enable or disable the no-op key event sent to check typing works before typing
+pub fn set_typing_probe(enabled: bool) {
+    TYPING_PROBE.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether typing is probed before the text is typed
+pub fn is_typing_probe_enabled() -> bool {
+    TYPING_PROBE.load(Ordering::SeqCst)
+}
+
+/// Set the apps that are never typed into (see `focus::blocked_foreground_app`)
+pub fn set_blocked_apps(apps: Vec<String>) {
+    *lock(&BLOCKED_APPS) = apps;
+}
+
+/// Get the apps that are never typed into
+pub fn get_blocked_apps() -> Vec<String> {
+    lock(&BLOCKED_APPS).clone()
+}
+
+/// Set where transcriptions are posted, None for nowhere
+pub fn set_webhook(webhook: Option<Webhook>) {
+    *lock(&WEBHOOK) = webhook;
+}
+
+/// Get where transcriptions are posted
+pub fn get_webhook() -> Option<Webhook> {
+    lock(&WEBHOOK).clone()
+}
+
+/// Enable or disable the spacing cleanup of transcriptions (see `text::tidy`)
+pub fn set_tidy(enabled: bool) {
+    app_context().update_settings(|settings| settings.tidy = enabled);
+}
+
+/// Check whether transcriptions get their spacing cleaned up
+pub fn is_tidy_enabled() -> bool {
+    app_context().settings().tidy
+}
+
+/// Set the prefix and suffix put around transcriptions (see `text::Wrap`)
+pub fn set_wrap(wrap: Wrap) {
+    app_context().update_settings(|settings| settings.wrap = wrap);
+}
+
+/// Get the prefix and suffix put around transcriptions
+pub fn get_wrap() -> Wrap {
+    app_context().settings().wrap
+}
+
+/// Set the line written before each transcription appended to a file
+pub fn set_file_header(header: FileHeader) {
+    *lock(&FILE_HEADER) = header;
+}
+
+/// Get the line written before each transcription appended to a file
+pub fn get_file_header() -> FileHeader {
+    lock(&FILE_HEADER).clone()
+}
+
+/// Enable or disable the last-transcription preview in the tray tooltip
+pub fn set_tooltip_preview(enabled: bool) {
+    TOOLTIP_PREVIEW.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether the tray tooltip may show the last transcription
+pub fn is_tooltip_preview_enabled() -> bool {
+    TOOLTIP_PREVIEW.load(Ordering::SeqCst)
+}
+
+/// Enable or disable the on-screen status overlay
+pub fn set_status_overlay(enabled: bool) {
+    STATUS_OVERLAY.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether the on-screen status overlay is shown while busy
+pub fn is_status_overlay_enabled() -> bool {
+    STATUS_OVERLAY.load(Ordering::SeqCst)
+}
+
+/// Enable or disable the text in D-Bus TranscriptionCompleted signals
+pub fn set_dbus_emit_text(enabled: bool) {
+    DBUS_EMIT_TEXT.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether D-Bus TranscriptionCompleted signals carry the text
+pub fn is_dbus_emit_text_enabled() -> bool {
+    DBUS_EMIT_TEXT.load(Ordering::SeqCst)
+}
+
+/// Set the maximum length of a recording in seconds
+pub fn set_max_recording_secs(secs: u64) {
+    app_context().update_settings(|settings| settings.max_recording_secs = secs);
+}
+
+/// Get the maximum length of a recording in seconds
+pub fn get_max_recording_secs() -> u64 {
+    app_context().settings().max_recording_secs
+}
+
+/// Set how many milliseconds of audio are discarded when a recording starts
+pub fn set_start_cooldown_ms(ms: u64) {
+    app_context().update_settings(|settings| settings.start_cooldown_ms = ms);
+}
+
+/// Get how many milliseconds of audio are discarded when a recording starts
+pub fn get_start_cooldown_ms() -> u64 {
+    app_context().settings().start_cooldown_ms
+}
+
+/// Set how many seconds a transcription may take before it's abandoned
+pub fn set_transcription_timeout_secs(secs: u64) {
+    TRANSCRIPTION_TIMEOUT_SECS.store(secs, Ordering::SeqCst);
+}
+
+/// Get how many seconds a transcription may take before it's abandoned
+pub fn get_transcription_timeout_secs() -> u64 {
+    TRANSCRIPTION_TIMEOUT_SECS.load(Ordering::SeqCst)
+}
+
+/// Set the input channel to record (from 1), `None` mixing all channels
+pub fn set_input_channel(channel: Option<usize>) {
+    app_context().set_input_channel(channel);
+}
+
+/// Get the input channel to record, `None` when all channels are mixed
+pub fn get_input_channel() -> Option<usize> {
+    app_context().input_channel()
+}
+
+/// Set how long to wait before typing, giving the target window time to take focus
+pub fn set_typing_delay_ms(delay: u64) {
+    TYPING_DELAY_MS.store(delay, Ordering::SeqCst);
+}
+
+/// Get the delay before typing in milliseconds
+pub fn get_typing_delay_ms() -> u64 {
+    TYPING_DELAY_MS.load(Ordering::SeqCst)
+}
+
+/// Set how many seconds a transcription waits for a window it can be typed into
+pub fn set_unreachable_hold_secs(secs: u64) {
+    UNREACHABLE_HOLD_SECS.store(secs, Ordering::SeqCst);
+}
+
+/// Get how many seconds a transcription waits for a window it can be typed into
+pub fn get_unreachable_hold_secs() -> u64 {
+    UNREACHABLE_HOLD_SECS.load(Ordering::SeqCst)
+}
+
+/// Enable or disable printing per-transcription timings
+pub fn set_timings(enabled: bool) {
+    TIMINGS.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether per-transcription timings are printed
+pub fn is_timings_enabled() -> bool {
+    TIMINGS.load(Ordering::SeqCst)
+}
+
+/// Where transcriptions go
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Type into the focused window
+    #[default]
+    Type,
+    /// Only print them (--dry-run)
+    Print,
+    /// Append each one as a line to a text file
+    File(PathBuf),
+    /// Put each one on the clipboard (only for recordings of the clipboard hotkey)
+    Clipboard,
+}
+
+/// Set where transcriptions go
+pub fn set_output_mode(mode: OutputMode) {
+    let mut output_mode = lock(&OUTPUT_MODE);
+    *output_mode = mode;
+}
+
+/// Get where transcriptions go
+pub fn get_output_mode() -> OutputMode {
+    let output_mode = lock(&OUTPUT_MODE);
+    output_mode.clone()
+}
+
+/// Enable or disable printing transcriptions instead of typing them
+pub fn set_dry_run(enabled: bool) {
+    set_output_mode(if enabled {
+        OutputMode::Print
+    } else {
+        OutputMode::Type
+    });
+}
+
+/// Check whether transcriptions are printed instead of typed
+pub fn is_dry_run() -> bool {
+    get_output_mode() == OutputMode::Print
+}
+
+/// Reserve stdout for transcriptions, sending diagnostics to stderr
+pub fn set_emit_text(enabled: bool) {
+    EMIT_TEXT.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether stdout is reserved for transcriptions
+pub fn is_emit_text() -> bool {
+    EMIT_TEXT.load(Ordering::SeqCst)
+}
+
+/// Print only errors on the console; the log file is unaffected
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether console output is limited to errors
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+/// Write a transcription to stdout as a single line (for --emit-text)
+pub fn emit_text(text: &str) {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.is_empty() {
+        return;
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", line).ok();
+    stdout.flush().ok();
+}
+
+/// Enable or disable verbose logging, which logs at debug level or below
+pub fn set_verbose_logging(enabled: bool) {
+    VERBOSE_LOGGING.store(enabled, Ordering::SeqCst);
+    apply_log_level();
+}
+
+/// Check whether verbose logging is enabled
+pub fn is_verbose_logging() -> bool {
+    VERBOSE_LOGGING.load(Ordering::SeqCst)
+}
+
+/// Set the configured log level
+pub fn set_log_level(level: LevelFilter) {
+    *lock(&LOG_LEVEL) = level;
+    apply_log_level();
+}
+
+/// Get the configured log level
+pub fn get_log_level() -> LevelFilter {
+    *lock(&LOG_LEVEL)
+}
+
+fn apply_log_level() {
+    let level = get_log_level();
+    if is_verbose_logging() {
+        log::set_max_level(level.max(LevelFilter::Debug));
+    } else {
+        log::set_max_level(level);
+    }
+}
+
+/// Enable or disable writing transcriptions to the log file below debug level
+pub fn set_log_transcripts(enabled: bool) {
+    LOG_TRANSCRIPTS.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether transcriptions are written to the log file
+pub fn is_log_transcripts_enabled() -> bool {
+    LOG_TRANSCRIPTS.load(Ordering::SeqCst)
+}
+
+/// Enable or disable the JSONL event log (see `events`)
+pub fn set_event_log(enabled: bool) {
+    EVENT_LOG.store(enabled, Ordering::SeqCst);
+}
+
+/// Check whether events are written to the event log
+pub fn is_event_log_enabled() -> bool {
+    EVENT_LOG.load(Ordering::SeqCst)
+}
+
+/// Set the size in bytes at which a day's event log is rotated
+pub fn set_event_log_max_bytes(bytes: u64) {
+    EVENT_LOG_MAX_BYTES.store(bytes, Ordering::SeqCst);
+}
+
+/// Get the size in bytes at which a day's event log is rotated
+pub fn get_event_log_max_bytes() -> u64 {
+    EVENT_LOG_MAX_BYTES.load(Ordering::SeqCst)
+}
+
+/// Set where the app reads and writes its files
+pub fn set_app_paths(paths: AppPaths) {
+    *APP_PATHS.lock().unwrap_or_else(PoisonError::into_inner) = paths;
+}
+
+/// Get where the app reads and writes its files
+pub fn app_paths() -> AppPaths {
+    APP_PATHS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_pedals_send_parse_and_name_back() {
+        for name in [
+            "F9",
+            "F13",
+            "F19",
+            "PageDown",
+            "PageUp",
+            "Pause",
+            "Unknown(250)",
+        ] {
+            let key = parse_hotkey(name).unwrap_or_else(|| panic!("{} didn't parse", name));
+            assert_eq!(hotkey_name(key), name);
+        }
+        assert_eq!(parse_hotkey(" pagedown "), Some(Key::PageDown));
+        assert_eq!(parse_hotkey("UNKNOWN(250)"), Some(Key::Unknown(250)));
+        assert_eq!(parse_hotkey("F13"), Some(Key::Unknown(F13_AND_UP[0])));
+
+        for name in ["F0", "F25", "Unknown()", "Unknown(x)", "Pedal"] {
+            assert_eq!(parse_hotkey(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_keys_that_type_nothing_are_blocked_by_default() {
+        for name in ["F9", "F13", "Pause", "ScrollLock", "Unknown(250)"] {
+            assert!(is_safe_to_block(parse_hotkey(name).unwrap()), "{}", name);
+        }
+        for name in ["PageUp", "PageDown", "Home", "End", "Insert"] {
+            assert!(!is_safe_to_block(parse_hotkey(name).unwrap()), "{}", name);
+        }
+    }
 }
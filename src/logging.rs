@@ -0,0 +1,145 @@
+// Diagnostics through the `log` crate, mirrored to the console and written to a
+// log file that's rotated when it grows too big.
+//
+// On the console errors and warnings go to stderr marked with ✗ and ⚠, info to
+// stdout (stderr with --emit-text); with --quiet only errors are shown. The file gets every record at or above the
+// configured level. Transcriptions are logged with the `TRANSCRIPT` target and
+// only reach the file when `log.transcripts` is on or the level is debug.
+
+use crate::{app_paths, is_emit_text, is_log_transcripts_enabled, is_quiet, set_log_level};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Log target for transcription text, see the module docs
+pub const TRANSCRIPT: &str = "transcript";
+
+// Size at which the log file is rotated
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+// Rotated files kept next to the log (esponquen.1.log being the newest)
+const KEPT_LOGS: usize = 3;
+
+// Log file currently written to, reopened when the app paths change
+static LOG_FILE: Mutex<Option<OpenLog>> = Mutex::new(None);
+
+static LOGGER: Logger = Logger;
+
+struct OpenLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies (winit, eframe, ureq...) are only heard from when something's wrong
+        let ours = metadata.target().starts_with("esponquen") || metadata.target() == TRANSCRIPT;
+        metadata.level() <= log::max_level() && (ours || metadata.level() <= Level::Warn)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        match record.level() {
+            Level::Error => eprintln!("✗ {}", message),
+            _ if is_quiet() => {}
+            Level::Warn => eprintln!("⚠ {}", message),
+            Level::Info if !is_emit_text() => println!("{}", message),
+            _ => eprintln!("{}", message),
+        }
+
+        let private = record.target() == TRANSCRIPT
+            && !is_log_transcripts_enabled()
+            && log::max_level() < LevelFilter::Debug;
+        if !private {
+            write_to_file(record.level(), &message);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(log) = LOG_FILE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            log.file.flush().ok();
+        }
+    }
+}
+
+/// Parse a level name as written in the config or on the command line
+pub fn parse_level(name: &str) -> Option<LevelFilter> {
+    name.trim().parse().ok()
+}
+
+/// Install the logger at `level`; call once, before anything is logged
+pub fn init(level: LevelFilter) {
+    log::set_logger(&LOGGER).ok();
+    set_log_level(level);
+}
+
+/// Append a message to the log file, one timestamped entry per line
+fn write_to_file(level: Level, message: &str) {
+    let mut log = LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner);
+    let path = app_paths().log_file;
+    if log.as_ref().is_none_or(|log| log.path != path) {
+        *log = open_log(&path);
+    }
+    let Some(open) = log.as_mut() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for line in message.trim_matches('\n').lines() {
+        let entry = format!("[{}] {:<5} {}\n", timestamp, level, line);
+        if open.file.write_all(entry.as_bytes()).is_ok() {
+            open.size += entry.len() as u64;
+        }
+    }
+
+    if open.size > MAX_LOG_BYTES {
+        *log = None;
+        rotate(&path);
+    }
+}
+
+fn open_log(path: &Path) -> Option<OpenLog> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).ok();
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .ok()?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    Some(OpenLog {
+        path: path.to_path_buf(),
+        file,
+        size,
+    })
+}
+
+/// Shift esponquen.log to esponquen.1.log, .1 to .2 and so on, dropping the oldest
+fn rotate(path: &Path) {
+    for n in (1..KEPT_LOGS).rev() {
+        std::fs::rename(rotated_path(path, n), rotated_path(path, n + 1)).ok();
+    }
+    std::fs::rename(path, rotated_path(path, 1)).ok();
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("{}.log", n))
+}
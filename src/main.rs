@@ -1,485 +1,4493 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use enigo::{Enigo, Keyboard, Settings};
-use esponquen::{get_hotkey, set_hotkey};
+use clap::Parser;
+use cli::{Cli, CliCommand, CtlAction};
+use cpal::traits::{DeviceTrait, HostTrait};
+use esponquen::beep::{Cue, beep, play_cue};
+use esponquen::clipboard::copy_text;
+use esponquen::command::{AppCommand, Effect, transition};
+use esponquen::config::{
+    self, Config, IconsConfig, OverlayCorner, StatusDisplay, StatusDisplayConfig, TrayClick,
+};
+use esponquen::context::{AppStatus, ClipId, TakenRecording, run_collector};
+use esponquen::crash;
+#[cfg(target_os = "linux")]
+use esponquen::dbus;
+use esponquen::dictation::transcribe_with;
+use esponquen::environment::{HotkeyCapture, Typing, capabilities};
+use esponquen::events::{self, ErrorCategory, Event as AppEvent};
+use esponquen::focus::{
+    Unreachable, blocked_foreground_app, capture_foreground_window, restore_focus,
+    unreachable_foreground,
+};
+use esponquen::icons::{
+    ERROR_COLOR, ICON_SIZE, IDLE_COLOR, LOADING_COLOR, RECORDING_COLOR, TRANSCRIBING_COLOR,
+    circle_rgba,
+};
+use esponquen::logging::{self, TRANSCRIPT};
+use esponquen::notification::notify;
+use esponquen::output::{FileOutput, Output, TypeOutput};
+use esponquen::permissions::{
+    PermissionIssue, check_input_permissions, check_required_permissions, open_settings,
+};
+use esponquen::power::{SleepInhibitor, spawn_resume_watcher};
+use esponquen::recorder::{Recorder, select_input_device};
+use esponquen::session::SessionType;
+#[cfg(not(target_os = "windows"))]
+use esponquen::set_verbose_logging;
+use esponquen::settings;
+use esponquen::sync::lock;
+use esponquen::text::{WRAP_PRESETS, Wrap, one_line_preview};
+use esponquen::timing::TranscriptionTimings;
+use esponquen::transcriber::{SharedRecognizer, Transcriber};
+use esponquen::validate::{Problem, Severity, has_fatal, validate};
+use esponquen::webhook::{self, Payload, WebhookMode};
+use esponquen::{
+    AppContext, AppPaths, EngineBuilder, EngineConfig, HOTKEY_HINT, HOTKEY_OPTIONS, HotkeyMode,
+    OnEmpty, OutputMode, QuitBehavior, app_context, app_paths, diagln, emit_text,
+    get_clipboard_hotkey, get_file_header, get_history_hotkey, get_hotkey, get_hotkey_mode,
+    get_input_channel, get_on_empty, get_output_mode, get_provider_hotkey, get_quit_behavior,
+    get_quit_hotkey, get_snapshot_hotkey, get_sound_cues, get_transcription_timeout_secs,
+    get_unreachable_hold_secs, get_webhook, get_wrap, hotkey_name, is_dry_run, is_emit_text,
+    is_hotkey_blocked, is_restore_focus_enabled, is_status_overlay_enabled, is_tidy_enabled,
+    is_timings_enabled, is_tooltip_preview_enabled, parse_hotkey, set_app_paths, set_emit_text,
+    set_event_log, set_event_log_max_bytes, set_input_channel, set_log_level, set_log_transcripts,
+    set_output_mode, set_quiet, set_status_overlay, set_tidy, set_timings, set_wrap,
+};
+use instance::ControlCommand;
+use log::{LevelFilter, debug, error, info, warn};
 use once_cell::sync::Lazy;
+use overlay::Overlay;
 use rdev::{Event, EventType, Key as RdevKey, grab};
-use sherpa_rs::transducer::{TransducerConfig, TransducerRecognizer};
-use std::collections::HashMap;
-use std::sync::mpsc::{Receiver, Sender, channel};
+use serde_json::{Value, json};
+use startup::StartupError;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use tray_icon::{
-    Icon, TrayIcon, TrayIconBuilder,
-    menu::{Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
+    Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent,
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
 };
-use winit::event_loop::{ControlFlow, EventLoop};
+use winit::event::{Event as LoopEvent, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+
+mod bench;
+mod cli;
+mod config_error;
+mod devices;
+mod diagnostics;
+#[cfg(target_os = "linux")]
+mod evdev_hotkey;
+mod headless;
+mod history_window;
+mod instance;
+mod metrics_server;
+mod overlay;
+mod self_test;
+mod settings_window;
+mod setup_window;
+mod startup;
+mod wizard;
+
+// Set while the console is a window the app opened itself, which closes with it
+#[cfg(target_os = "windows")]
+static CONSOLE_ALLOCATED: AtomicBool = AtomicBool::new(false);
+
+/// Show the console: the terminal the app was started from if there is one, or
+/// else a window of its own. Returns true if a new window was opened, which
+/// closes with the process.
+#[cfg(target_os = "windows")]
+fn show_console() -> bool {
+    use winapi::um::consoleapi::AllocConsole;
+    use winapi::um::wincon::{
+        ATTACH_PARENT_PROCESS, AttachConsole, GetConsoleWindow, SetConsoleTitleA,
+    };
+
+    unsafe {
+        // Still attached from an earlier call
+        if !GetConsoleWindow().is_null() {
+            return false;
+        }
+
+        // Launched from a terminal: print there
+        if AttachConsole(ATTACH_PARENT_PROCESS) != 0 {
+            attach_std_handles();
+            // The shell has printed its prompt already, start below it
+            eprintln!();
+            return false;
+        }
+
+        let _ = AllocConsole();
+        let _ = SetConsoleTitleA(b"Esponquen Console\0".as_ptr() as *const i8);
+    }
+    attach_std_handles();
+    CONSOLE_ALLOCATED.store(true, Ordering::SeqCst);
+    true
+}
+
+/// Whether a standard handle goes to a pipe or file (e.g. --emit-text | tool),
+/// which the console mustn't take over
+#[cfg(target_os = "windows")]
+unsafe fn is_redirected(handle: winapi::um::winnt::HANDLE) -> bool {
+    use winapi::um::fileapi::GetFileType;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::winbase::{FILE_TYPE_DISK, FILE_TYPE_PIPE};
+
+    !handle.is_null()
+        && handle != INVALID_HANDLE_VALUE
+        && matches!(
+            unsafe { GetFileType(handle) },
+            FILE_TYPE_DISK | FILE_TYPE_PIPE
+        )
+}
+
+/// Point the standard handles at the process console
+#[cfg(target_os = "windows")]
+fn attach_std_handles() {
+    use winapi::um::fileapi::{CreateFileA, OPEN_EXISTING};
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+    use winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE};
+
+    unsafe {
+        // Re-attach stdout/stderr so println! reaches the console
+        let handle = CreateFileA(
+            b"CONOUT$\0".as_ptr() as *const i8,
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        );
+        if handle != INVALID_HANDLE_VALUE {
+            for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+                if !is_redirected(GetStdHandle(std_handle)) {
+                    SetStdHandle(std_handle, handle);
+                }
+            }
+        }
 
+        // And stdin, for the interactive setup wizard
+        let input = CreateFileA(
+            b"CONIN$\0".as_ptr() as *const i8,
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        );
+        if input != INVALID_HANDLE_VALUE && !is_redirected(GetStdHandle(STD_INPUT_HANDLE)) {
+            SetStdHandle(STD_INPUT_HANDLE, input);
+        }
+    }
+}
+
+/// Unset the standard handles pointing at the console, before it's let go of
+///
+/// Writes to a missing handle are dropped, where a handle of a console the
+/// process no longer has fails them.
 #[cfg(target_os = "windows")]
-fn show_console() {
+fn detach_std_handles() {
+    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+
     unsafe {
-        let _ = winapi::um::consoleapi::AllocConsole();
-        let _ = winapi::um::wincon::SetConsoleTitleA(b"Esponquen Console\0".as_ptr() as *const i8);
+        for std_handle in [STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            if !is_redirected(GetStdHandle(std_handle)) {
+                SetStdHandle(std_handle, std::ptr::null_mut());
+            }
+        }
     }
 }
 
 #[cfg(not(target_os = "windows"))]
-fn show_console() {
+fn show_console() -> bool {
     // Console is already visible on non-Windows platforms
+    false
 }
 
-// Global state for recording
-static RECORDING_STATE: Lazy<Arc<Mutex<RecordingState>>> = Lazy::new(|| {
-    Arc::new(Mutex::new(RecordingState {
-        is_recording: false,
-        audio_data: Vec::new(),
-        sample_rate: 16000,
-    }))
-});
+/// Report a startup error where it can be seen and exit
+fn exit_with_error(message: &str) -> ! {
+    let new_console = show_console();
+    error!("{}", message);
+    if new_console {
+        wait_for_enter();
+    }
+    std::process::exit(2);
+}
 
-// Global state for app status
-static APP_STATUS: Lazy<Arc<Mutex<AppStatus>>> =
-    Lazy::new(|| Arc::new(Mutex::new(AppStatus::LoadingModel)));
+/// Keep a console opened by `show_console` until the user has read it
+fn wait_for_enter() {
+    eprintln!("\nPress Enter to close...");
+    std::io::stdin().read_line(&mut String::new()).ok();
+}
 
-// Global state for provider info (for menu display)
-static PROVIDER_INFO: Lazy<Arc<Mutex<String>>> =
-    Lazy::new(|| Arc::new(Mutex::new(String::from("Initializing..."))));
+/// Let go of the console: close the app's own window, or detach from the terminal
+/// it was started from, which stays open
+#[cfg(target_os = "windows")]
+fn hide_console() {
+    detach_std_handles();
+    unsafe {
+        let _ = winapi::um::wincon::FreeConsole();
+    }
+    CONSOLE_ALLOCATED.store(false, Ordering::SeqCst);
+}
 
-struct RecordingState {
-    is_recording: bool,
-    audio_data: Vec<f32>,
-    sample_rate: u32,
+/// Toggle the debug console (Windows) or debug level logging (other platforms)
+#[cfg(target_os = "windows")]
+fn set_console_visible(ctx: &AppContext, visible: bool) {
+    if visible {
+        // Opened to see what's going on, --quiet no longer applies
+        set_quiet(false);
+        show_console();
+        diagln!("{}", state_snapshot(ctx));
+    } else {
+        hide_console();
+    }
 }
 
-#[derive(Clone, Debug)]
-enum AppStatus {
-    LoadingModel,
-    WaitingForHotkey,
-    Recording,
-    Transcribing,
-}
-
-impl AppStatus {
-    fn to_tooltip(&self) -> String {
-        match self {
-            AppStatus::LoadingModel => "Esponquen - Loading model...".to_string(),
-            AppStatus::WaitingForHotkey => {
-                format!("Esponquen - Ready (Press {:?})", get_hotkey())
-            }
-            AppStatus::Recording => format!(
-                "Esponquen - Recording... (Press {:?} to stop)",
-                get_hotkey()
-            ),
-            AppStatus::Transcribing => "Esponquen - Transcribing...".to_string(),
-        }
+#[cfg(not(target_os = "windows"))]
+fn set_console_visible(ctx: &AppContext, visible: bool) {
+    set_verbose_logging(visible);
+    if visible {
+        debug!("{}", state_snapshot(ctx));
+        diagln!(
+            "Verbose logging enabled ({})",
+            app_paths().log_file.display()
+        );
+    } else {
+        diagln!("Verbose logging disabled");
     }
 }
 
-fn set_status(status: AppStatus, tray_icon: &TrayIcon) {
-    let mut app_status = APP_STATUS.lock().unwrap();
-    *app_status = status.clone();
-    let tooltip = status.to_tooltip();
-    drop(app_status);
+/// The providers tried when the model was last loaded and how each went, on one line
+fn providers_tried() -> String {
+    lock(&PROVIDER_ATTEMPTS)
+        .iter()
+        .map(|attempt| attempt.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
 
-    tray_icon.set_tooltip(Some(tooltip)).ok();
+/// What's missing in this session and what's done instead, on one line
+fn limitations() -> String {
+    let degradations = capabilities().degradations();
+    if degradations.is_empty() {
+        "none".to_string()
+    } else {
+        degradations.join("; ")
+    }
+}
 
-    // Set the appropriate icon based on status
-    #[cfg(target_os = "windows")]
-    let icon_path = match status {
-        AppStatus::LoadingModel => "./icons/loading.ico",
-        AppStatus::WaitingForHotkey => "./icons/not-recording.ico",
-        AppStatus::Recording => "./icons/recording.ico",
-        AppStatus::Transcribing => "./icons/not-recording.ico",
+/// Describe the current app state, used when the console is opened mid-session
+fn state_snapshot(ctx: &AppContext) -> String {
+    let status = ctx.status();
+    let status_secs = ctx.status_since().elapsed().as_secs();
+    let device = lock(&DEVICE_INFO).clone();
+    let provider = lock(&ctx.provider_info).clone();
+    let recording = ctx.is_recording();
+    let (buffered_samples, sample_rate) = {
+        let state = ctx.recording_state();
+        (state.audio_data.len(), state.sample_rate)
     };
 
-    #[cfg(not(target_os = "windows"))]
-    let icon_path = match status {
-        AppStatus::LoadingModel => "./icons/loading.png",
-        AppStatus::WaitingForHotkey => "./icons/not-recording.png",
-        AppStatus::Recording => "./icons/recording.png",
-        AppStatus::Transcribing => "./icons/not-recording.png",
-    };
+    format!(
+        "Esponquen state snapshot\n  Status: {:?} (for {}s)\n  Hotkey: {:?}\n  Input device: {}\n  Input channel: {}\n  Sample rate: {} Hz\n  Provider: {}\n  Providers tried: {}\n  Recording: {} ({} samples buffered)\n  Limitations: {}",
+        status,
+        status_secs,
+        ctx.hotkey(),
+        device,
+        recorded_channel().map_or("all mixed".to_string(), |channel| channel.to_string()),
+        sample_rate,
+        provider,
+        providers_tried(),
+        recording,
+        buffered_samples,
+        limitations()
+    )
+}
 
-    if let Ok(icon) = Icon::from_path(icon_path, Some((32, 32))) {
-        tray_icon.set_icon(Some(icon)).ok();
-    }
+// Set when the watchdog gave up on a transcription that's still running in the model
+static TRANSCRIPTION_ABANDONED: AtomicBool = AtomicBool::new(false);
+
+// Set once the keyboard hook has seen the hotkey pressed
+static HOTKEY_SEEN: AtomicBool = AtomicBool::new(false);
+
+// Set while the hotkey is down, so the presses key repeat sends are skipped
+static HOTKEY_HELD: AtomicBool = AtomicBool::new(false);
+
+// The same for the clipboard hotkey
+static CLIPBOARD_HOTKEY_HELD: AtomicBool = AtomicBool::new(false);
+
+// Other key presses the keyboard hook has seen, showing it works
+static KEYS_SEEN: AtomicUsize = AtomicUsize::new(0);
+
+// Set once the user was told the hotkey may never reach the app
+static HOTKEY_UNSEEN_WARNED: AtomicBool = AtomicBool::new(false);
+
+// Key presses the hook must have seen before a missing hotkey is suspicious
+const HOTKEY_UNSEEN_MIN_KEYS: usize = 20;
+
+// Set when a GPU provider was tried but the model ended up on the CPU
+static CPU_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+// Global state for the profile applied on top of the config file (for the tooltip and menu)
+static ACTIVE_PROFILE: Lazy<Arc<Mutex<Option<String>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Outcome of each provider tried when the model was last loaded (for diagnostics)
+static PROVIDER_ATTEMPTS: Lazy<Arc<Mutex<Vec<String>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+
+// Bumped for every model reload so only the latest one is swapped in
+static MODEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Global state for the most recent transcription (for the tooltip preview)
+static LAST_TRANSCRIPTION: Lazy<Arc<Mutex<Option<String>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(None)));
+
+// Recent transcriptions, oldest first (for the history window)
+static HISTORY: Lazy<Arc<Mutex<VecDeque<HistoryEntry>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(VecDeque::new())));
+
+/// A transcription kept for the history window
+#[derive(Clone, Debug)]
+struct HistoryEntry {
+    text: String,
+    /// Set when typing it failed, so the window can point it out
+    untyped: bool,
 }
 
-fn main() {
-    // Check if --console flag is present
-    let args: Vec<String> = std::env::args().collect();
-    let show_console_flag = args.iter().any(|arg| arg == "--console");
+// Number of transcriptions kept in the history
+const HISTORY_CAPACITY: usize = 100;
 
-    if show_console_flag {
-        show_console();
-    }
+// Number of entries listed in the history window unless configured
+const DEFAULT_HISTORY_WINDOW_ENTRIES: usize = 20;
 
-    // Only print if console is visible
-    if show_console_flag {
-        println!("Speech-to-Text Desktop App with Tray Icon");
-        println!("==========================================");
-        println!();
-    }
+// Time for the previous window to regain focus after the history window closes
+const RETYPE_FOCUS_DELAY: Duration = Duration::from_millis(300);
 
-    // Create event loop for tray icon
-    let event_loop = EventLoop::new().unwrap();
-
-    // Create tray icon menu
-    let tray_menu = Menu::new();
-
-    // Define available hotkeys (F1-F12)
-    let hotkey_options = vec![
-        ("F1", RdevKey::F1),
-        ("F2", RdevKey::F2),
-        ("F3", RdevKey::F3),
-        ("F4", RdevKey::F4),
-        ("F5", RdevKey::F5),
-        ("F6", RdevKey::F6),
-        ("F7", RdevKey::F7),
-        ("F8", RdevKey::F8),
-        ("F9", RdevKey::F9),
-        ("F10", RdevKey::F10),
-        ("F11", RdevKey::F11),
-        ("F12", RdevKey::F12),
-    ];
-
-    // Create hotkey submenu and store menu items
-    let hotkey_submenu = Submenu::new("Set Hotkey", true);
-    let mut hotkey_map: HashMap<MenuId, (String, RdevKey)> = HashMap::new();
-
-    for (name, key) in &hotkey_options {
-        let menu_item = MenuItem::new(*name, true, None);
-        hotkey_submenu.append(&menu_item).ok();
-        hotkey_map.insert(menu_item.id().clone(), (name.to_string(), *key));
-    }
-
-    tray_menu.append(&hotkey_submenu).ok();
-    tray_menu.append(&PredefinedMenuItem::separator()).ok();
-
-    // Add provider info menu item (disabled, just for display)
-    let provider_info = PROVIDER_INFO.lock().unwrap().clone();
-    let provider_item = MenuItem::new(format!("Running on: {}", provider_info), false, None);
-    tray_menu.append(&provider_item).ok();
-
-    tray_menu.append(&PredefinedMenuItem::separator()).ok();
-
-    let quit_item = MenuItem::new("Quit", true, None);
-    tray_menu.append(&quit_item).ok();
-    let quit_id = quit_item.id().clone();
-
-    // Load initial icon
-    #[cfg(target_os = "windows")]
-    let loading_icon = Icon::from_path("./icons/loading.ico", Some((32, 32)))
-        .expect("Failed to load loading.ico icon");
+// How often a held transcription checks whether it can be typed yet
+const HOLD_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
-    #[cfg(not(target_os = "windows"))]
-    let loading_icon = Icon::from_path("./icons/loading.png", Some((32, 32)))
-        .expect("Failed to load loading.png icon");
+// The D-Bus service, while it's registered
+#[cfg(target_os = "linux")]
+static DBUS: Mutex<Option<esponquen::dbus::Service>> = Mutex::new(None);
 
-    // Create tray icon
-    let tray_icon = TrayIconBuilder::new()
-        .with_menu(Box::new(tray_menu))
-        .with_tooltip("Esponquen - Loading model...")
-        .with_icon(loading_icon)
-        .build()
-        .expect("Failed to create tray icon");
+// Transcriptions waiting for a window they can be typed into, oldest first
+static HELD_TEXT: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
-    // Load the model with GPU acceleration if available
-    println!("Loading Parakeet model...");
-    set_status(AppStatus::LoadingModel, &tray_icon);
-
-    // Try GPU providers in order of preference
-    let providers_to_try = vec![
-        #[cfg(target_os = "windows")]
-        Some("dml".to_string()), // DirectML - works with any GPU on Windows
-        #[cfg(not(target_os = "windows"))]
-        Some("rocm".to_string()), // ROCm for AMD GPUs on Linux
-        #[cfg(not(target_os = "windows"))]
-        Some("cuda".to_string()), // CUDA for NVIDIA GPUs on Linux/Mac
-        None, // CPU fallback
-    ];
+// Set while a snapshot of the recording is being transcribed, to run one at a time
+static SNAPSHOT_RUNNING: AtomicBool = AtomicBool::new(false);
 
-    let mut recognizer = None;
-    let mut used_provider = String::from("CPU");
+// Maximum number of characters of the last transcription shown in the tooltip
+const TOOLTIP_PREVIEW_CHARS: usize = 40;
 
-    for provider in providers_to_try {
-        println!(
-            "Trying provider: {:?}",
-            provider.as_ref().unwrap_or(&"CPU".to_string())
-        );
+// Set when a setting changed so the event loop refreshes the tooltip
+static REFRESH_TOOLTIP: AtomicBool = AtomicBool::new(false);
 
-        let config = TransducerConfig {
-            decoder: "./model/decoder.int8.onnx".to_string(),
-            encoder: "./model/encoder.int8.onnx".to_string(),
-            joiner: "./model/joiner.int8.onnx".to_string(),
-            tokens: "./model/tokens.txt".to_string(),
-            num_threads: if provider.is_none() { 4 } else { 1 }, // Use more threads for CPU
-            sample_rate: 16_000,
-            feature_dim: 80,
-            debug: false,
-            model_type: "nemo_transducer".to_string(),
-            provider: provider.clone(),
-            ..Default::default()
-        };
+// How often the recording watchdog checks for a stuck recording
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
 
-        match TransducerRecognizer::new(config) {
-            Ok(rec) => {
-                used_provider = provider.unwrap_or_else(|| "CPU".to_string());
-                println!(
-                    "✓ Model loaded successfully with {} provider\n",
-                    used_provider
-                );
-                recognizer = Some(rec);
-                break;
-            }
-            Err(e) => {
-                if provider.is_some() {
-                    println!(
-                        "  ⚠ {} provider not available: {}",
-                        provider.as_ref().unwrap(),
-                        e
-                    );
-                    println!("  Trying next provider...\n");
-                } else {
-                    eprintln!("✗ Failed to initialize recognizer even with CPU: {}", e);
-                    eprintln!("\nMake sure the model files exist:");
-                    eprintln!("  - ./model/encoder.int8.onnx");
-                    eprintln!("  - ./model/decoder.int8.onnx");
-                    eprintln!("  - ./model/joiner.int8.onnx");
-                    eprintln!("  - ./model/tokens.txt");
-                    std::process::exit(1);
-                }
-            }
-        }
-    }
+// Extra time allowed past the maximum recording length before the watchdog steps in
+const WATCHDOG_RECORDING_MARGIN: Duration = Duration::from_secs(30);
 
-    let recognizer = recognizer.unwrap();
+// How long the audio callback may go without delivering data during a recording
+const WATCHDOG_STALL_TIMEOUT: Duration = Duration::from_secs(10);
 
-    // Store provider info globally for menu display
-    {
-        let provider_display = if used_provider != "CPU" {
-            format!("GPU: {}", used_provider.to_uppercase())
-        } else {
-            "CPU (4 threads)".to_string()
-        };
-        let mut provider_info = PROVIDER_INFO.lock().unwrap();
-        *provider_info = provider_display;
+// Icon files configured in the [ui.icons] section
+static ICON_FILES: Lazy<Mutex<IconsConfig>> = Lazy::new(|| Mutex::new(IconsConfig::default()));
+
+// What each status shows in the tray, from the [ui.status_display] section
+static STATUS_DISPLAY: Mutex<StatusDisplayConfig> = Mutex::new(StatusDisplayConfig {
+    loading: None,
+    idle: None,
+    recording: None,
+    transcribing: None,
+});
+
+// Icon files that already failed to load (so the warning is printed once)
+static WARNED_ICON_PATHS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// How often the config file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Phrase labels longer than this are shortened in the tray menu
+const PHRASE_LABEL_CHARS: usize = 30;
+
+// Set by the Ctrl+C handler to request an orderly shutdown
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Set once shutdown has started so the keyboard grab stops intercepting keys
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+// Set by the input stream's error callback so the event loop can switch devices
+static STREAM_FAILED: AtomicBool = AtomicBool::new(false);
+
+// Set when the system woke from sleep, so the event loop reopens what it may have broken
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+// How long a tray click waits for a second one when a double click does something else
+const TRAY_DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+
+// How often to look for a microphone when there's none
+const INPUT_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+// Delay before restarting a keyboard grab that stopped, doubled for each restart in a row
+const GRAB_RESTART_DELAY: Duration = Duration::from_secs(2);
+
+// Restarts in a row before the hotkeys are given up on until a resume or a permission re-check
+const GRAB_MAX_RESTARTS: u32 = 5;
+
+// A grab that ran this long before stopping starts over with the shortest delay
+const GRAB_STABLE_TIME: Duration = Duration::from_secs(60);
+
+// Set while the keyboard grab waits to be restarted, for the tooltip (giving up
+// on it is `AppStatus::Error`, see `status_upkeep`)
+static HOTKEY_TROUBLE: Mutex<Option<String>> = Mutex::new(None);
+
+// Required permissions still missing, see `permissions::check_required_permissions`
+static MISSING_PERMISSIONS: Mutex<Vec<PermissionIssue>> = Mutex::new(Vec::new());
+
+// Wakes the event loop, which sleeps until something needs handling
+static EVENT_LOOP_WAKER: Mutex<Option<EventLoopProxy<()>>> = Mutex::new(None);
+
+// Channel count of the open input device (for the Channel submenu)
+static INPUT_CHANNELS: AtomicUsize = AtomicUsize::new(1);
+
+// Global state for the input device name (for the state snapshot)
+static DEVICE_INFO: Lazy<Arc<Mutex<String>>> =
+    Lazy::new(|| Arc::new(Mutex::new(String::from("Unknown"))));
+
+/// Tray tooltip for a status, naming the hotkey that leaves it
+fn status_tooltip(status: &AppStatus, hotkey: RdevKey) -> String {
+    match status {
+        AppStatus::LoadingModel => "Esponquen - Loading model...".to_string(),
+        AppStatus::WaitingForHotkey => {
+            let ready = format!("Esponquen - Ready (Press {:?})", hotkey);
+            match last_transcription_preview() {
+                Some(preview) => format!("{} — last: '{}'", ready, preview),
+                None => ready,
+            }
+        }
+        AppStatus::Recording { .. } => {
+            format!("Esponquen - Recording... (Press {:?} to stop)", hotkey)
+        }
+        AppStatus::Transcribing { .. } => "Esponquen - Transcribing...".to_string(),
+        AppStatus::Disabled => {
+            "Esponquen - Permissions required (Re-check permissions once granted)".to_string()
+        }
+        AppStatus::Error { reason } => format!("Esponquen - {}", reason),
     }
+}
 
-    if used_provider != "CPU" {
-        println!("🚀 GPU acceleration enabled ({})!", used_provider);
-        println!("   Transcription should be faster and won't freeze the system.\n");
-    } else {
-        println!("ℹ️  Running on CPU (no GPU acceleration available)");
-        println!("   Transcription may cause brief system slowdowns.\n");
+/// Sanitized one-line preview of the last transcription, unless suppressed for privacy
+fn last_transcription_preview() -> Option<String> {
+    if !is_tooltip_preview_enabled() {
+        return None;
     }
 
-    // Recreate menu with updated provider info
-    let updated_menu = Menu::new();
+    let last = lock(&LAST_TRANSCRIPTION);
+    last.as_deref()
+        .map(|text| one_line_preview(text, TOOLTIP_PREVIEW_CHARS))
+}
 
-    // Recreate hotkey submenu
-    let hotkey_submenu_updated = Submenu::new("Set Hotkey", true);
-    let mut hotkey_map_updated: HashMap<MenuId, (String, RdevKey)> = HashMap::new();
+/// Show a status in the tray, or only in the log when running without one
+fn set_status(ctx: &AppContext, status: AppStatus, tray_icon: Option<&TrayIcon>) {
+    ctx.set_status(status.clone());
+    #[cfg(target_os = "linux")]
+    if let Some(service) = lock(&DBUS).as_ref() {
+        service.set_status(status.name());
+    }
+    let display = status_display(&status);
+    let mut tooltip = if display.shows_tooltip() {
+        status_tooltip(&status, ctx.hotkey())
+    } else {
+        "Esponquen".to_string()
+    };
 
-    for (name, key) in &hotkey_options {
-        let menu_item = MenuItem::new(*name, true, None);
-        hotkey_submenu_updated.append(&menu_item).ok();
-        hotkey_map_updated.insert(menu_item.id().clone(), (name.to_string(), *key));
+    if status == AppStatus::WaitingForHotkey
+        && let Some(trouble) = lock(&HOTKEY_TROUBLE).as_ref()
+    {
+        tooltip = format!("Esponquen - {}", trouble);
     }
 
-    updated_menu.append(&hotkey_submenu_updated).ok();
-    updated_menu.append(&PredefinedMenuItem::separator()).ok();
+    // Keep the CPU fallback visible for the whole session
+    if CPU_FALLBACK.load(Ordering::SeqCst) {
+        tooltip.push_str(" [CPU fallback]");
+    }
 
-    // Add provider info with actual value
-    let provider_info_text = PROVIDER_INFO.lock().unwrap().clone();
-    let provider_item_updated =
-        MenuItem::new(format!("Running on: {}", provider_info_text), false, None);
-    updated_menu.append(&provider_item_updated).ok();
+    if let Some(profile) = active_profile() {
+        tooltip.push_str(&format!(" [{}]", profile));
+    }
 
-    updated_menu.append(&PredefinedMenuItem::separator()).ok();
+    let Some(tray_icon) = tray_icon else {
+        debug!("{}", tooltip);
+        return;
+    };
 
-    let quit_item_updated = MenuItem::new("Quit", true, None);
-    updated_menu.append(&quit_item_updated).ok();
-    let quit_id_updated = quit_item_updated.id().clone();
+    tray_icon.set_tooltip(Some(tooltip)).ok();
 
-    // Update the tray icon menu
-    tray_icon.set_menu(Some(Box::new(updated_menu)));
+    let icon_status = if display.shows_icon() {
+        &status
+    } else {
+        &AppStatus::WaitingForHotkey
+    };
+    tray_icon.set_icon(Some(status_icon(icon_status))).ok();
+}
 
-    // Use updated hotkey_map and quit_id
-    let hotkey_map = hotkey_map_updated;
-    let quit_id = quit_id_updated;
+/// Whether a status changes the tray icon, the tooltip, both or neither
+fn status_display(status: &AppStatus) -> StatusDisplay {
+    let display = lock(&STATUS_DISPLAY);
+    match status {
+        AppStatus::LoadingModel => display.loading,
+        AppStatus::WaitingForHotkey => display.idle,
+        AppStatus::Recording { .. } => display.recording,
+        AppStatus::Transcribing { .. } => display.transcribing,
+        // Always shown, nothing works until they're dealt with
+        AppStatus::Disabled | AppStatus::Error { .. } => None,
+    }
+    .unwrap_or_default()
+}
 
-    set_status(AppStatus::WaitingForHotkey, &tray_icon);
+/// Load the icon for a status, falling back to a generated colored circle
+fn status_icon(status: &AppStatus) -> Icon {
+    // Set the appropriate icon based on status
+    #[cfg(target_os = "windows")]
+    let default_path = match status {
+        AppStatus::LoadingModel => "./icons/loading.ico",
+        AppStatus::WaitingForHotkey => "./icons/not-recording.ico",
+        AppStatus::Recording { .. } => "./icons/recording.ico",
+        AppStatus::Transcribing { .. } => "./icons/not-recording.ico",
+        // There are no icon files for these
+        AppStatus::Disabled | AppStatus::Error { .. } => return generated_icon(ERROR_COLOR),
+    };
 
-    println!("Instructions:");
-    println!("  - Press {:?} to start/stop recording", get_hotkey());
-    println!("  - Audio will be recorded from your default microphone");
-    println!("  - After stopping, text will be typed automatically");
-    println!("  - Right-click tray icon to change hotkey or quit");
-    println!("  - Hotkey presses are captured and won't trigger default actions\n");
+    #[cfg(not(target_os = "windows"))]
+    let default_path = match status {
+        AppStatus::LoadingModel => "./icons/loading.png",
+        AppStatus::WaitingForHotkey => "./icons/not-recording.png",
+        AppStatus::Recording { .. } => "./icons/recording.png",
+        AppStatus::Transcribing { .. } => "./icons/not-recording.png",
+        AppStatus::Disabled | AppStatus::Error { .. } => return generated_icon(ERROR_COLOR),
+    };
 
-    // Set up audio recording
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .expect("No input device available");
+    let configured_path = {
+        let icons = lock(&ICON_FILES);
+        match status {
+            AppStatus::LoadingModel => icons.loading.clone(),
+            AppStatus::WaitingForHotkey => icons.idle.clone(),
+            AppStatus::Recording { .. } => icons.recording.clone(),
+            AppStatus::Transcribing { .. } => icons.transcribing.clone(),
+            AppStatus::Disabled | AppStatus::Error { .. } => None,
+        }
+    };
+    let icon_path = app_paths()
+        .resolve(configured_path.unwrap_or_else(|| default_path.to_string()))
+        .display()
+        .to_string();
 
-    println!(
-        "Using input device: {}",
-        device.name().unwrap_or_else(|_| "Unknown".to_string())
-    );
+    let error = match Icon::from_path(&icon_path, Some((ICON_SIZE, ICON_SIZE))) {
+        Ok(icon) => {
+            debug!("Using icon file {}", icon_path);
+            return icon;
+        }
+        Err(e) => e,
+    };
 
-    let config = device
-        .default_input_config()
-        .expect("Failed to get default input config");
-    let sample_rate = config.sample_rate().0;
+    let color = match status {
+        AppStatus::LoadingModel => LOADING_COLOR,
+        AppStatus::WaitingForHotkey => IDLE_COLOR,
+        AppStatus::Recording { .. } => RECORDING_COLOR,
+        AppStatus::Transcribing { .. } => TRANSCRIBING_COLOR,
+        AppStatus::Disabled | AppStatus::Error { .. } => ERROR_COLOR,
+    };
 
-    // Update the recording state sample rate
-    {
-        let mut state = RECORDING_STATE.lock().unwrap();
-        state.sample_rate = sample_rate;
-    }
-
-    println!("Sample rate: {} Hz\n", sample_rate);
-    println!("Ready! Press {:?} to start recording...\n", get_hotkey());
-
-    // Start audio input stream
-    let recording_state = Arc::clone(&RECORDING_STATE);
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::F32 => device.build_input_stream(
-            &config.into(),
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                let mut state = recording_state.lock().unwrap();
-                if state.is_recording {
-                    state.audio_data.extend_from_slice(data);
-                }
-            },
-            |err| eprintln!("Stream error: {}", err),
-            None,
-        ),
-        _ => {
-            eprintln!("Unsupported sample format");
-            std::process::exit(1);
-        }
+    // Warn once per file, the status icon is reloaded on every change
+    if lock(&WARNED_ICON_PATHS).insert(icon_path.clone()) {
+        warn!(
+            "Couldn't load {} ({}), using a generated icon",
+            icon_path, error
+        );
     }
-    .expect("Failed to build input stream");
+    debug!("Using generated icon for {:?}", status);
 
-    stream.play().expect("Failed to play stream");
+    generated_icon(color)
+}
 
-    // Create channel for status updates
-    let (status_tx, status_rx): (Sender<AppStatus>, Receiver<AppStatus>) = channel();
+fn generated_icon(color: [u8; 3]) -> Icon {
+    Icon::from_rgba(circle_rgba(ICON_SIZE, color), ICON_SIZE, ICON_SIZE)
+        .expect("Generated icon has valid dimensions")
+}
 
-    // Listen for keyboard events with grab (blocks default actions)
-    let recognizer = Arc::new(Mutex::new(recognizer));
-    let recognizer_clone = Arc::clone(&recognizer);
+fn main() {
+    crash::install();
 
-    thread::spawn(move || {
-        if let Err(error) =
-            grab(move |event: Event| handle_keyboard_event(event, &recognizer_clone, &status_tx))
-        {
-            eprintln!("Error listening to keyboard events: {:?}", error);
+    // A window opened for the app closes with it, before the panic could be read
+    #[cfg(target_os = "windows")]
+    crash::on_crash(|| {
+        if CONSOLE_ALLOCATED.load(Ordering::SeqCst) {
+            wait_for_enter();
         }
     });
 
-    // Handle menu events
-    let menu_channel = MenuEvent::receiver();
+    // Unknown flags and --help/--version need a console to be seen on Windows
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let new_console = show_console();
+            e.print().ok();
+            if new_console {
+                wait_for_enter();
+            }
+            std::process::exit(e.exit_code());
+        }
+    };
 
-    event_loop
-        .run(move |_event, elwt| {
-            elwt.set_control_flow(ControlFlow::Poll);
+    // Nobody would see the error in the log or a console that isn't there
+    let dialog = !(cli.console || cli.no_tray);
+    if let Err(e) = start(cli) {
+        startup::fail(&e, dialog);
+    }
+}
 
-            // Check for status updates from keyboard thread
-            if let Ok(status) = status_rx.try_recv() {
-                set_status(status, &tray_icon);
-            }
+/// Create the tray icon, showing the loading status until the model is ready
+fn build_tray_icon(menu: Menu, tray_click: TrayClick) -> Result<TrayIcon, String> {
+    TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_menu_on_left_click(tray_click == TrayClick::Menu)
+        .with_tooltip("Esponquen - Loading model...")
+        .with_icon(status_icon(&AppStatus::LoadingModel))
+        .build()
+        .map_err(|e| e.to_string())
+}
 
-            // Check for menu events
-            if let Ok(event) = menu_channel.try_recv() {
-                if event.id == quit_id {
-                    println!("\nQuitting...");
-                    elwt.exit();
-                } else if let Some((name, key)) = hotkey_map.get(&event.id) {
-                    set_hotkey(*key);
-                    set_status(AppStatus::WaitingForHotkey, &tray_icon);
-                    println!("\nHotkey changed to {}", name);
-                }
-            }
-        })
-        .ok();
+/// Log the required permissions that are missing and tell the user, with a
+/// dialog offering to open the settings page of the first when `dialog` is set
+fn report_missing_permissions(missing: &[PermissionIssue], dialog: bool) {
+    for issue in missing {
+        warn!("{}: {}", issue.summary, issue.remedy);
+    }
+    let Some(first) = missing.first() else {
+        return;
+    };
+    if !dialog {
+        notify("Permissions required", &first.remedy);
+        return;
+    }
+
+    let description = missing
+        .iter()
+        .map(|issue| format!("• {}\n  {}", issue.summary, issue.remedy))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let open = "Open System Settings";
+    let result = rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Esponquen needs permissions")
+        .set_description(format!(
+            "The hotkey and recording are off until these are granted:\n\n{}\n\nThen choose Re-check permissions in the tray menu.",
+            description
+        ))
+        .set_buttons(rfd::MessageButtons::OkCancelCustom(
+            open.to_string(),
+            "Later".to_string(),
+        ))
+        .show();
+    let chosen = match result {
+        rfd::MessageDialogResult::Ok => true,
+        rfd::MessageDialogResult::Custom(label) => label == open,
+        _ => false,
+    };
+    if chosen && let Err(e) = open_settings(first) {
+        warn!("Couldn't open the settings for {}: {}", first.summary, e);
+    }
 }
 
-fn handle_keyboard_event(
-    event: Event,
-    recognizer: &Arc<Mutex<TransducerRecognizer>>,
-    status_tx: &Sender<AppStatus>,
-) -> Option<Event> {
-    if let EventType::KeyPress(key) = event.event_type {
-        // Check if the pressed key matches the configured hotkey
-        let configured_hotkey = get_hotkey();
-        if key == configured_hotkey {
-            let mut state = RECORDING_STATE.lock().unwrap();
-
-            if state.is_recording {
-                // Stop recording
-                println!("\n⏹ Recording stopped. Transcribing...");
-                state.is_recording = false;
-                status_tx.send(AppStatus::Transcribing).ok();
-
-                // Take the audio data
-                let audio_data = std::mem::take(&mut state.audio_data);
-                let sample_rate = state.sample_rate;
-                drop(state); // Release the lock
-
-                if audio_data.is_empty() {
-                    println!("✗ No audio recorded");
-                    status_tx.send(AppStatus::WaitingForHotkey).ok();
-                    return None; // Block the key event
-                }
+/// Check the required permissions again, starting the keyboard grab once
+/// they're all granted; returns whether they are
+fn recheck_permissions(keyboard_grab: &mut KeyboardGrab) -> bool {
+    let missing = check_required_permissions();
+    let granted = missing.is_empty();
+    if granted {
+        diagln!("\n✓ Permissions granted");
+        notify(
+            "Permissions granted",
+            "Press the hotkey to start dictating.",
+        );
+        if !keyboard_grab.is_running() {
+            keyboard_grab.start();
+        }
+    } else {
+        report_missing_permissions(&missing, true);
+    }
+    *lock(&MISSING_PERMISSIONS) = missing;
+    granted
+}
 
-                println!(
-                    "  Audio length: {:.2} seconds",
-                    audio_data.len() as f32 / sample_rate as f32
-                );
+/// The keyboard grab of the event loop, which may wait for permissions and is
+/// started again after a resume if it stopped
+///
+/// A grab that stops on its own is restarted after a delay that doubles each
+/// time, until `GRAB_MAX_RESTARTS` in a row failed.
+struct KeyboardGrab {
+    spawn: Box<dyn Fn() -> Receiver<String>>,
+    /// From the running grab (see `spawn_keyboard_grab`), None until started
+    /// and once it stopped
+    failed: Option<Receiver<String>>,
+    started_at: Instant,
+    /// Restarts in a row, each after the last one stopped
+    restarts: u32,
+    /// When the next restart is due, while waiting for one
+    retry_at: Option<Instant>,
+    /// Why the hotkeys are unavailable, once restarting was given up on
+    unavailable: Option<String>,
+}
 
-                // Transcribe
-                let mut rec = recognizer.lock().unwrap();
-                let text = rec.transcribe(sample_rate, &audio_data);
-                drop(rec);
+impl KeyboardGrab {
+    fn new(spawn: Box<dyn Fn() -> Receiver<String>>) -> Self {
+        KeyboardGrab {
+            spawn,
+            failed: None,
+            started_at: Instant::now(),
+            restarts: 0,
+            retry_at: None,
+            unavailable: None,
+        }
+    }
 
-                println!("✓ Transcription: {}", text);
+    /// Start the grab, giving it a full set of restarts
+    fn start(&mut self) {
+        self.restarts = 0;
+        self.retry_at = None;
+        self.spawn_grab();
+    }
 
-                if !text.trim().is_empty() {
-                    println!("⌨ Typing text...");
-                    type_text(&text);
-                    println!("✓ Done!\n");
-                } else {
-                    println!("✗ No text to type\n");
-                }
+    fn spawn_grab(&mut self) {
+        *lock(&HOTKEY_TROUBLE) = None;
+        self.unavailable = None;
+        REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
+        self.started_at = Instant::now();
+        self.failed = Some((self.spawn)());
+    }
 
-                status_tx.send(AppStatus::WaitingForHotkey).ok();
-                println!("Ready! Press {:?} to start recording...", get_hotkey());
-            } else {
-                // Start recording
-                state.audio_data.clear();
-                state.is_recording = true;
-                status_tx.send(AppStatus::Recording).ok();
-                println!("\n🔴 Recording... (Press {:?} to stop)", get_hotkey());
+    /// Notice a grab that stopped and restart it once its delay is over, giving
+    /// up (with a notification) after too many restarts in a row
+    fn watch(&mut self) {
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() >= retry_at {
+                self.retry_at = None;
+                self.restarts += 1;
+                info!(
+                    "Restarting the keyboard grab ({} of {})",
+                    self.restarts, GRAB_MAX_RESTARTS
+                );
+                self.spawn_grab();
             }
+            return;
+        }
 
-            // Return None to block the key event from propagating
-            return None;
+        let Some(failed) = &self.failed else {
+            return;
+        };
+        let error = match failed.try_recv() {
+            Ok(error) => error,
+            Err(TryRecvError::Disconnected) => "it stopped".to_string(),
+            Err(TryRecvError::Empty) => return,
+        };
+        self.failed = None;
+        if self.started_at.elapsed() >= GRAB_STABLE_TIME {
+            self.restarts = 0;
         }
+
+        // Nothing to retry where the keyboard can't be read at all
+        let hopeless = capabilities().hotkey == HotkeyCapture::Unavailable;
+        if let Some(delay) = grab_restart_delay(self.restarts).filter(|_| !hopeless) {
+            warn!(
+                "The keyboard grab failed ({}), restarting it in {}s",
+                error,
+                delay.as_secs()
+            );
+            *lock(&HOTKEY_TROUBLE) = Some("Hotkeys stopped, restarting...".to_string());
+            self.retry_at = Some(Instant::now() + delay);
+        } else {
+            let cause = hotkey_failure_cause();
+            error!(
+                "Hotkeys unavailable: the keyboard grab failed ({}). {}",
+                error, cause
+            );
+            diagln!("\n✗ Hotkeys unavailable: {}", cause);
+            notify(
+                "Hotkeys unavailable",
+                &format!("{} The tray menu and esponquen ctl still work.", cause),
+            );
+            *lock(&HOTKEY_TROUBLE) = None;
+            self.unavailable =
+                Some("Hotkeys unavailable (use the tray menu or esponquen ctl)".to_string());
+        }
+        REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
     }
 
-    // Return Some(event) to allow the key event to propagate
-    Some(event)
+    /// Whether the grab was started and hasn't stopped since
+    fn is_running(&self) -> bool {
+        self.failed
+            .as_ref()
+            .is_some_and(|failed| failed.try_recv() == Err(TryRecvError::Empty))
+    }
 }
 
-fn type_text(text: &str) {
-    // Small delay to ensure focus is on the right window
-    thread::sleep(std::time::Duration::from_millis(100));
+/// How long to wait before restarting a keyboard grab that stopped after
+/// `restarts` restarts in a row, or None once it should be given up on
+fn grab_restart_delay(restarts: u32) -> Option<Duration> {
+    (restarts < GRAB_MAX_RESTARTS).then(|| GRAB_RESTART_DELAY * 2u32.pow(restarts))
+}
 
-    let mut enigo = Enigo::new(&Settings::default()).unwrap();
-    enigo.text(text).ok();
+/// The command that moves an idle app to `Disabled` while permissions are
+/// missing or to `Error` while the hotkeys are unavailable, and back once that's
+/// over; None while the status already says so or a dictation is in flight
+fn status_upkeep(
+    status: &AppStatus,
+    permissions_missing: bool,
+    hotkeys_unavailable: Option<&String>,
+) -> Option<AppCommand> {
+    match (status, hotkeys_unavailable) {
+        (AppStatus::WaitingForHotkey, _) if permissions_missing => Some(AppCommand::Disable),
+        (AppStatus::WaitingForHotkey, Some(reason)) => Some(AppCommand::Fail {
+            reason: reason.clone(),
+        }),
+        (AppStatus::Disabled, _) if !permissions_missing => Some(AppCommand::Recover),
+        (AppStatus::Error { .. }, None) => Some(AppCommand::Recover),
+        _ => None,
+    }
+}
+
+/// The likely reason the keyboard grab keeps failing, and what to do about it
+fn hotkey_failure_cause() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Check that Esponquen (or your terminal) is enabled under System Settings > Privacy & Security > Accessibility and Input Monitoring, then restart the app."
+    } else if capabilities().session == SessionType::Wayland {
+        "On Wayland the keyboard is read from /dev/input: add your user to the 'input' group and log in again."
+    } else if cfg!(target_os = "windows") {
+        "Security software may be blocking keyboard hooks; restarting the app may help."
+    } else {
+        "Another app may be holding the keyboard; restarting the app may help."
+    }
+}
+
+/// Grab keyboard input on a new thread, `callback` returning None for the keys it keeps
+///
+/// The grab runs until the app exits; if it can't be set up, the error is logged
+/// and sent on the returned channel, which disconnects if it stops later on.
+/// Where the keyboard can't be grabbed (Wayland) the keys are read from
+/// /dev/input instead, so none can be kept from the focused app; see
+/// `esponquen::environment`.
+fn spawn_keyboard_grab<F>(callback: F) -> Receiver<String>
+where
+    F: Fn(Event) -> Option<Event> + Send + Sync + 'static,
+{
+    let (failed_tx, failed_rx) = channel();
+    match capabilities().hotkey {
+        HotkeyCapture::Grab => {}
+        #[cfg(target_os = "linux")]
+        HotkeyCapture::Listen => {
+            // Each reader holds a sender, so the channel disconnects once they all stopped
+            let listening = evdev_hotkey::spawn_listener(
+                move |event| {
+                    callback(event);
+                },
+                failed_tx.clone(),
+            );
+            if let Err(error) = listening {
+                error!("Error listening to keyboard events: {}", error);
+                failed_tx.send(error).ok();
+            }
+            return failed_rx;
+        }
+        _ => {
+            let error = "Can't read the keyboard in this session".to_string();
+            error!("{}", error);
+            failed_tx.send(error).ok();
+            return failed_rx;
+        }
+    }
+    thread::spawn(move || {
+        if let Err(error) = grab(callback) {
+            error!("Error listening to keyboard events: {:?}", error);
+            failed_tx.send(format!("{:?}", error)).ok();
+        }
+    });
+    failed_rx
+}
+
+/// Log how each feature works in this session, and what it does without the
+/// ones that are missing
+fn log_environment() {
+    let capabilities = capabilities();
+    info!("{}", capabilities.summary());
+    for degradation in capabilities.degradations() {
+        warn!("{}", degradation);
+    }
+}
+
+/// Run the app, returning what stopped it from starting
+fn start(cli: Cli) -> Result<(), StartupError> {
+    // Config, logs and data next to the executable when running portable
+    let paths = AppPaths::detect(cli.portable);
+    if let Ok(paths) = &paths {
+        set_app_paths(paths.clone());
+    }
+    logging::init(cli.log_level.unwrap_or(LevelFilter::Info));
+    paths.map_err(StartupError::Paths)?;
+
+    if let Some(CliCommand::ListDevices { json }) = cli.command {
+        let new_console = show_console();
+        let code = devices::list_devices(json);
+        if new_console {
+            wait_for_enter();
+        }
+        std::process::exit(code);
+    }
+
+    if let Some(CliCommand::Ctl { action }) = &cli.command {
+        let new_console = show_console();
+        let code = run_ctl(action);
+        if new_console {
+            wait_for_enter();
+        }
+        std::process::exit(code);
+    }
+
+    // Without a tray icon the console is the only place to see what's going on
+    let show_console_flag = cli.console || cli.no_tray;
+
+    // Only transcriptions on stdout, diagnostics on stderr
+    if cli.emit_text {
+        set_emit_text(true);
+    }
+
+    // Asking for the console means asking for its diagnostics, so it wins
+    if cli.quiet && !cli.console {
+        set_quiet(true);
+    }
+
+    // The settings window runs in its own process, see settings_window.rs
+    if cli.settings_window {
+        settings_window::run();
+        return Ok(());
+    }
+
+    if cli.history_window {
+        history_window::run();
+        return Ok(());
+    }
+
+    if cli.setup_window {
+        setup_window::run();
+        return Ok(());
+    }
+
+    // Config file location, overridable with ESPONQUEN_CONFIG and --config
+    let env_config_path = config::env_config_path().map_err(StartupError::Overrides)?;
+    let config_path = cli
+        .config
+        .clone()
+        .or(env_config_path)
+        .or_else(config::config_path);
+
+    if cli.write_default_config {
+        show_console();
+        let Some(path) = config_path else {
+            error!("No config directory on this platform, pass --config <path>");
+            std::process::exit(1);
+        };
+        match config::write_default(&path) {
+            Ok(()) => info!("Default config written to {}", path.display()),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // Print per-transcription timings (always logged at debug level)
+    if cli.timings {
+        set_timings(true);
+    }
+
+    // Transcribe piped audio and exit, see headless.rs
+    if cli.transcribe_pcm {
+        set_emit_text(true);
+        let mut config = load_existing_config(config_path.as_deref());
+        select_startup_profile(&config, &cli);
+        apply_overrides(&mut config, &cli).unwrap_or_else(|e| exit_with_error(&e));
+        if report_problems(&validate(&config)) {
+            std::process::exit(1);
+        }
+        let engine = EngineBuilder::from_config(&config)
+            .build()
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+        std::process::exit(headless::transcribe_pcm(&cli, &engine));
+    }
+
+    // Check each part of the app and exit, see self_test.rs
+    if cli.self_test {
+        let new_console = show_console();
+        let code = self_test::run(&cli, config_path.as_deref());
+        if new_console {
+            wait_for_enter();
+        }
+        std::process::exit(code);
+    }
+
+    // Compare the providers and exit, see bench.rs
+    if cli.bench {
+        let new_console = show_console();
+        // Keep stdout for the JSON
+        set_emit_text(cli.json);
+        let mut config = load_existing_config(config_path.as_deref());
+        select_startup_profile(&config, &cli);
+        apply_overrides(&mut config, &cli).unwrap_or_else(|e| exit_with_error(&e));
+        if report_problems(&validate(&config)) {
+            std::process::exit(1);
+        }
+        let code = match EngineBuilder::from_config(&config).build() {
+            Ok(engine) => bench::run(&cli, &engine),
+            Err(e) => {
+                error!("{}", e);
+                1
+            }
+        };
+        if new_console {
+            wait_for_enter();
+        }
+        std::process::exit(code);
+    }
+
+    // One instance at a time, a second one would fight over the hotkey and microphone.
+    // Later instances can still control this one (--replace, `esponquen ctl`).
+    let ctx = Arc::clone(app_context());
+    // Everything that changes the app's state goes to the event loop over this channel
+    let (commands_tx, commands_rx): (Sender<AppCommand>, Receiver<AppCommand>) = channel();
+    let control_ctx = Arc::clone(&ctx);
+    let control_commands = commands_tx.clone();
+    let on_command = move |command| handle_control(&control_ctx, command, &control_commands);
+    let _instance = if cli.replace {
+        Some(instance::replace(on_command).map_err(StartupError::Replace)?)
+    } else {
+        match instance::acquire(on_command) {
+            Ok(Some(lock)) => Some(lock),
+            Ok(None) => return Err(StartupError::AlreadyRunning),
+            Err(e) => {
+                warn!("Couldn't check for another running instance: {}", e);
+                None
+            }
+        }
+    };
+
+    if show_console_flag {
+        show_console();
+    }
+
+    // Only print if console is visible
+    if show_console_flag {
+        diagln!("Speech-to-Text Desktop App with Tray Icon");
+        diagln!("==========================================");
+        diagln!();
+    }
+
+    // Load the config file, running the setup wizard on first launch. One that
+    // can't be read or parsed is reported with the other problems below.
+    let mut unreadable = None;
+    let mut config = match &config_path {
+        Some(path) if !path.exists() => wizard::run(show_console_flag, path),
+        Some(path) => config::load(path).unwrap_or_else(|e| {
+            unreadable = Some(Problem::unreadable(e));
+            Config::default()
+        }),
+        None => Config::default(),
+    };
+
+    // The profile, environment variables and then command line flags take precedence
+    // over the file, which is kept as is to switch profiles later
+    let mut file_config = config.clone();
+    select_startup_profile(&config, &cli);
+    apply_overrides(&mut config, &cli).map_err(StartupError::Overrides)?;
+
+    // Report every config problem at once. Without a console the tray shows them,
+    // since exiting would look like the app never started.
+    let problems = match unreadable {
+        Some(problem) => vec![problem],
+        None => validate(&config),
+    };
+    if report_problems(&problems) {
+        if cli.no_tray {
+            return Err(StartupError::InvalidConfig);
+        }
+        let recheck_cli = cli.clone();
+        let recheck_path = config_path.clone();
+        config_error::run(problems, _instance, move || {
+            let mut config = match &recheck_path {
+                Some(path) => match config::load(path) {
+                    Ok(config) => config,
+                    Err(e) => return Ok(vec![Problem::unreadable(e)]),
+                },
+                None => Config::default(),
+            };
+            select_startup_profile(&config, &recheck_cli);
+            apply_overrides(&mut config, &recheck_cli)?;
+            Ok(validate(&config))
+        });
+    }
+
+    let mut engine = apply_config(&config, &cli).unwrap_or_else(|e| {
+        error!("{}, using defaults", e);
+        let engine = EngineConfig::default();
+        engine.apply();
+        engine
+    });
+
+    if is_dry_run() {
+        diagln!("Dry run: transcriptions will be printed, not typed");
+    }
+
+    log_environment();
+
+    // Read-only, and only with a port set; a port taken by something else isn't fatal
+    if let Some(port) = config.metrics.port {
+        let metrics_ctx = Arc::clone(&ctx);
+        if let Err(e) = metrics_server::spawn(port, move |path| metrics_page(&metrics_ctx, path)) {
+            warn!("{}", e);
+        }
+    }
+
+    // For shell extensions and scripts; `esponquen ctl` works without a session bus
+    #[cfg(target_os = "linux")]
+    if config.dbus.enabled != Some(false) && esponquen::environment::has_session_bus() {
+        let dbus_ctx = Arc::clone(&ctx);
+        let dbus_commands = commands_tx.clone();
+        match dbus::serve(move |method| handle_dbus(&dbus_ctx, method, &dbus_commands)) {
+            Ok(service) => {
+                service.set_status(ctx.status().name());
+                *lock(&DBUS) = Some(service);
+                debug!("Registered {} on the session bus", dbus::BUS_NAME);
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    // The hotkey and recording wait for these, nothing would work without them
+    let missing_permissions = check_required_permissions();
+    if !missing_permissions.is_empty() {
+        report_missing_permissions(&missing_permissions, !cli.no_tray);
+    }
+    *lock(&MISSING_PERMISSIONS) = missing_permissions;
+
+    let fallback_notification = config.ui.fallback_notification.unwrap_or(true);
+
+    let mut overlay_corner = config.overlay.corner.unwrap_or_default();
+
+    let mut tray_click = config.ui.left_click.unwrap_or_default();
+
+    let mut tray_double_click = config.ui.double_click_action();
+
+    let mut history_window_entries = history_window_limit(&config);
+
+    // Create event loop for tray icon
+    let event_loop = EventLoop::new().map_err(|e| StartupError::EventLoop(e.to_string()))?;
+    *lock(&EVENT_LOOP_WAKER) = Some(event_loop.create_proxy());
+
+    // Create the tray menu once; later changes update its items in place
+    #[cfg(target_os = "windows")]
+    let console_label = "Show console";
+    #[cfg(not(target_os = "windows"))]
+    let console_label = "Verbose logging";
+    let console_checked = cfg!(target_os = "windows") && show_console_flag;
+    let mut menu = MenuHandles::new(&config, &ctx, console_label, console_checked);
+
+    // Create tray icon, carrying on without one if there's no tray to put it in
+    let tray_icon = if cli.no_tray {
+        None
+    } else {
+        let built = if capabilities().tray {
+            build_tray_icon(menu.menu.clone(), tray_click)
+        } else {
+            Err("there's no system tray in this session".to_string())
+        };
+        match built {
+            Ok(tray_icon) => Some(tray_icon),
+            Err(e) => {
+                if !show_console_flag {
+                    show_console();
+                }
+                error!("Failed to create tray icon, running without it: {}", e);
+                notify(
+                    "Esponquen is running without a tray icon",
+                    "Use the hotkeys, or press Ctrl+C in the console to quit.",
+                );
+                None
+            }
+        }
+    };
+    let has_console = show_console_flag || tray_icon.is_none();
+
+    // A crash on this thread shouldn't leave a dead icon in the tray
+    if let Some(tray_icon) = &tray_icon {
+        let tray_icon = tray_icon.clone();
+        crash::on_crash(move || {
+            tray_icon.set_visible(false).ok();
+        });
+    }
+
+    // Load the model with GPU acceleration if available
+    diagln!("Loading Parakeet model...");
+    set_status(&ctx, AppStatus::LoadingModel, tray_icon.as_ref());
+
+    let (recognizer, used_provider, failed_providers) =
+        load_recognizer(&engine).map_err(|error| StartupError::Model {
+            error,
+            model_dir: engine.model_dir.clone(),
+        })?;
+    let cpu_fallback =
+        set_provider_info(&ctx, &used_provider, &failed_providers, engine.cpu_threads);
+
+    if used_provider != "CPU" {
+        diagln!("🚀 GPU acceleration enabled ({})!", used_provider);
+        diagln!("   Transcription should be faster and won't freeze the system.\n");
+    } else {
+        diagln!("ℹ️  Running on CPU (no GPU acceleration available)");
+        diagln!("   Transcription may cause brief system slowdowns.\n");
+    }
+
+    if cpu_fallback {
+        let message = format!(
+            "{} unavailable, using CPU — transcription will be slower",
+            failed_providers.join("/")
+        );
+        warn!("{}", message);
+        if fallback_notification {
+            notify("Esponquen is running on CPU", &message);
+        }
+    }
+
+    menu.sync(&ctx);
+
+    // The event loop takes it from here, this is the first command it sees
+    commands_tx.send(AppCommand::ModelLoaded).ok();
+
+    diagln!("Instructions:");
+    diagln!("  - Press {:?} to start/stop recording", get_hotkey());
+    diagln!("  - Audio will be recorded from your default microphone");
+    diagln!("  - After stopping, text will be typed automatically");
+    if tray_icon.is_some() {
+        diagln!("  - Right-click tray icon to change hotkey or quit");
+    } else if let Some(key) = get_quit_hotkey() {
+        diagln!("  - Press {:?} or Ctrl+C to quit", key);
+    } else {
+        diagln!("  - Press Ctrl+C to quit");
+    }
+    if capabilities().hotkey == HotkeyCapture::Listen {
+        diagln!("  - Hotkey presses also reach the focused window in this session\n");
+    } else {
+        diagln!("  - Hotkey presses are captured and won't trigger default actions\n");
+    }
+
+    // Set up audio recording, trying the configured device first
+    let host = cpal::default_host();
+    let input_settings = input_settings(&config);
+
+    // Without a microphone the app starts disarmed and picks one up once it's plugged in
+    let mut input = match open_input_stream(&ctx, &host, &input_settings, DeviceFallback::Default) {
+        Ok(input) => {
+            diagln!("Sample rate: {} Hz\n", input.sample_rate);
+            Some(input)
+        }
+        Err(e) => {
+            warn!("{}, recording is off until a microphone is connected", e);
+            *lock(&DEVICE_INFO) = "none".to_string();
+            notify("No microphone", "Plug one in to start dictating.");
+            None
+        }
+    };
+    let mut last_input_check = Instant::now();
+
+    menu.channel.rebuild();
+
+    diagln!("Ready! Press {:?} to start recording...\n", get_hotkey());
+
+    let commands_rx = forward_waking("command forwarder", commands_rx);
+
+    // Moves recorded audio out of the ring the audio callback fills
+    let collected = Arc::downgrade(&ctx);
+    crash::spawn_supervised("audio collector", move || run_collector(collected.clone()));
+
+    // Safety net for recordings that never get stopped
+    let watchdog_ctx = Arc::clone(&ctx);
+    let watchdog_commands = commands_tx.clone();
+    crash::spawn_supervised("watchdog", move || {
+        recording_watchdog(&watchdog_ctx, &watchdog_commands)
+    });
+
+    // Listen for keyboard events with grab (blocks default actions)
+    let recognizer = SharedRecognizer::new(Box::new(recognizer));
+    let keyboard_ctx = Arc::clone(&ctx);
+    let keyboard_commands = commands_tx.clone();
+
+    let mut keyboard_grab = KeyboardGrab::new(Box::new(move || {
+        let ctx = Arc::clone(&keyboard_ctx);
+        let commands = keyboard_commands.clone();
+        let failed = spawn_keyboard_grab(move |event: Event| {
+            // A panic in here would leave keyboard input grabbed (or abort on Windows)
+            crash::contain("keyboard hook", || {
+                handle_keyboard_event(&ctx, event.clone(), &commands)
+            })
+            .unwrap_or(Some(event))
+        });
+        // So the event loop notices right away when the grab stops
+        forward_waking("keyboard grab watcher", failed)
+    }));
+    if lock(&MISSING_PERMISSIONS).is_empty() {
+        keyboard_grab.start();
+    }
+
+    // Devices can go away under the input stream and the grab while the system sleeps
+    spawn_resume_watcher(|slept| {
+        info!("System resumed after about {}s asleep", slept.as_secs());
+        RESUMED.store(true, Ordering::SeqCst);
+        wake_event_loop();
+    });
+
+    let coordinator = Coordinator {
+        ctx: Arc::clone(&ctx),
+        recognizer: recognizer.clone(),
+        commands: commands_tx,
+        tray_icon: tray_icon.clone(),
+    };
+
+    // Pick up edits to the config file without restarting
+    let (config_tx, config_rx): (Sender<Config>, Receiver<Config>) = channel();
+    let config_rx = forward_waking("config forwarder", config_rx);
+    if let Some(path) = config_path.clone() {
+        crash::spawn_supervised("config watcher", move || {
+            watch_config(path.clone(), config_tx.clone())
+        });
+    }
+
+    // Shut down cleanly on Ctrl+C (and SIGTERM) when running with a console
+    if has_console {
+        ctrlc::set_handler(|| {
+            if QUIT_REQUESTED.swap(true, Ordering::SeqCst) {
+                warn!("Forced exit");
+                std::process::exit(130);
+            }
+            wake_event_loop();
+        })
+        .ok();
+    }
+
+    // Handle menu events, through handlers so each one wakes the event loop
+    let (menu_tx, menu_channel) = channel();
+    MenuEvent::set_event_handler(Some(move |event| {
+        menu_tx.send(event).ok();
+        wake_event_loop();
+    }));
+    let (tray_tx, tray_channel) = channel();
+    TrayIconEvent::set_event_handler(Some(move |event| {
+        tray_tx.send(event).ok();
+        wake_event_loop();
+    }));
+    let mut stream = input.as_mut().and_then(|input| input.stream.take());
+    let mut quit_pending = false;
+    let mut settings_window: Option<Child> = None;
+    let mut history_window: Option<Child> = None;
+    let mut overlay: Option<Overlay> = None;
+    // Profile to restore if the one picked in the tray can't be applied
+    let mut profile_switch: Option<Option<String>> = None;
+    let mut last_status_check = Instant::now();
+    // A left click waiting to see if it's the first of a double click, and the end
+    // of a double click, whose trailing click isn't a click of its own
+    let mut pending_click: Option<Instant> = None;
+    let mut double_clicked_until = Instant::now();
+    // Held while recording or transcribing, see `SleepInhibitor`
+    let mut sleep_inhibitor: Option<SleepInhibitor> = None;
+    let mut inhibiting = false;
+
+    event_loop
+        .run(move |loop_event, elwt| {
+            // The overlay is the only window in this process
+            if let LoopEvent::WindowEvent {
+                event: WindowEvent::RedrawRequested,
+                ..
+            } = loop_event
+                && let Some(overlay) = &mut overlay
+            {
+                overlay.redraw();
+            }
+
+            // Commands from the keyboard hook, `esponquen ctl` and the worker threads
+            for command in commands_rx.try_iter() {
+                match command {
+                    AppCommand::OpenHistory => {
+                        open_history_window(&mut history_window, history_window_entries)
+                    }
+                    AppCommand::CycleProvider if !quit_pending => {
+                        if let Some(next) = next_provider(&ctx, &engine) {
+                            coordinator.reload_model(&command, &next);
+                        }
+                    }
+                    AppCommand::Quit => QUIT_REQUESTED.store(true, Ordering::SeqCst),
+                    // Nothing new starts while quitting, but running work still finishes
+                    AppCommand::TranscriptionDone { .. } => coordinator.handle(command),
+                    _ if quit_pending => debug!("Ignoring {:?} while quitting", command),
+                    command => coordinator.handle(command),
+                }
+            }
+
+            // The model didn't finish a transcription in time
+            if last_status_check.elapsed() >= WATCHDOG_INTERVAL {
+                last_status_check = Instant::now();
+                if let Some(command) = check_stuck_status(&ctx) {
+                    coordinator.handle(command);
+                }
+            }
+
+            // Left clicks on the tray icon (a click that opens the menu is tray_icon's).
+            // With a double click action, a click waits out the double click time first.
+            let mut tray_action = None;
+            for event in tray_channel.try_iter() {
+                match event {
+                    TrayIconEvent::Click {
+                        button: MouseButton::Left,
+                        button_state: MouseButtonState::Up,
+                        ..
+                    } if Instant::now() >= double_clicked_until => {
+                        if tray_double_click == TrayClick::Nothing {
+                            tray_action = Some(tray_click);
+                        } else {
+                            pending_click = Some(Instant::now() + TRAY_DOUBLE_CLICK_TIME);
+                        }
+                    }
+                    TrayIconEvent::DoubleClick {
+                        button: MouseButton::Left,
+                        ..
+                    } if tray_double_click != TrayClick::Nothing => {
+                        pending_click = None;
+                        double_clicked_until = Instant::now() + TRAY_DOUBLE_CLICK_TIME;
+                        tray_action = Some(tray_double_click);
+                    }
+                    _ => {}
+                }
+            }
+            if pending_click.is_some_and(|at| Instant::now() >= at) {
+                pending_click = None;
+                tray_action = Some(tray_click);
+            }
+            match tray_action.filter(|_| !quit_pending) {
+                Some(TrayClick::Toggle) => coordinator.handle(AppCommand::Toggle),
+                Some(TrayClick::Settings) => open_settings_window(
+                    &ctx,
+                    &mut settings_window,
+                    &engine.model_dir,
+                    &coordinator.commands,
+                ),
+                Some(TrayClick::History) => {
+                    open_history_window(&mut history_window, history_window_entries)
+                }
+                _ => {}
+            }
+
+            // Re-apply the current status after a setting changed
+            if REFRESH_TOOLTIP.swap(false, Ordering::SeqCst) {
+                set_status(&ctx, ctx.status(), tray_icon.as_ref());
+                menu.sync(&ctx);
+            }
+
+            // Config file changed on disk (it already parsed, see watch_config), or a
+            // profile was picked in the tray (carrying the one to go back to on error)
+            let pending = match config_rx.try_recv() {
+                Ok(reloaded) => {
+                    file_config = reloaded.clone();
+                    Some((reloaded, None))
+                }
+                Err(_) => profile_switch
+                    .take()
+                    .map(|previous| (file_config.clone(), Some(previous))),
+            };
+            if let Some((mut reloaded, previous_profile)) = pending {
+                let reload = apply_overrides(&mut reloaded, &cli)
+                    .and_then(|()| reload_config(&config, &reloaded, &cli));
+                match reload {
+                    Ok(reloaded_engine) => {
+                        // Only a different model or provider is worth the load time
+                        if !reloaded.model.loads_same_model(&config.model) {
+                            reload_model(&ctx, &reloaded_engine, &recognizer, None);
+                        }
+                        engine = reloaded_engine;
+
+                        if reloaded.phrases != config.phrases {
+                            menu.set_phrases(&reloaded.phrases);
+                        }
+
+                        let corner = reloaded.overlay.corner.unwrap_or_default();
+                        if corner != overlay_corner {
+                            overlay_corner = corner;
+                            if let Some(overlay) = &overlay {
+                                overlay.set_corner(corner);
+                            }
+                        }
+
+                        let click = reloaded.ui.left_click.unwrap_or_default();
+                        if click != tray_click {
+                            tray_click = click;
+                            if let Some(tray_icon) = &tray_icon {
+                                tray_icon.set_show_menu_on_left_click(click == TrayClick::Menu);
+                            }
+                        }
+                        tray_double_click = reloaded.ui.double_click_action();
+                        pending_click = None;
+
+                        history_window_entries = history_window_limit(&reloaded);
+                        menu.output.sync();
+                        menu.text.sync();
+                        if reloaded.audio.channel != config.audio.channel {
+                            check_input_channel();
+                            menu.channel.sync();
+                        }
+                        if reloaded.profiles != config.profiles {
+                            menu.profile.rebuild(&reloaded);
+                        }
+                        config = reloaded;
+                    }
+                    Err(e) => {
+                        // Keep running with the previous settings
+                        error!("{}, keeping the current settings", e);
+                        if let Some(previous) = previous_profile {
+                            *lock(&ACTIVE_PROFILE) = previous;
+                            menu.profile.sync();
+                            notify("Profile not switched", &e);
+                        } else {
+                            notify("Config file not reloaded", &e);
+                        }
+                    }
+                }
+            }
+
+            sync_overlay(&ctx, &mut overlay, elwt, overlay_corner);
+
+            // The input stream reported an error (e.g. the USB mic was unplugged)
+            if STREAM_FAILED.swap(false, Ordering::SeqCst)
+                && !quit_pending
+                && let Some(input) = &mut input
+            {
+                recover_input_stream(&ctx, &host, &input_settings, input, &mut stream);
+                menu.channel.rebuild();
+            }
+
+            // Woke from sleep: the stream may be silently dead and the grab gone
+            if RESUMED.swap(false, Ordering::SeqCst) && !quit_pending {
+                reopen_input_stream(&ctx, &host, &input_settings, &mut input, &mut stream);
+                menu.channel.rebuild();
+                if lock(&MISSING_PERMISSIONS).is_empty() && !keyboard_grab.is_running() {
+                    keyboard_grab.start();
+                    info!(
+                        "Resume recovery: reopened the input stream and restarted the keyboard grab"
+                    );
+                } else {
+                    info!("Resume recovery: reopened the input stream");
+                }
+            }
+
+            // The grab died (see KeyboardGrab::watch), unless it's waiting for permissions
+            if !quit_pending {
+                keyboard_grab.watch();
+            }
+
+            // No microphone (at startup, or since the last one went away): watch for one
+            if stream.is_none()
+                && !quit_pending
+                && last_input_check.elapsed() >= INPUT_RETRY_INTERVAL
+            {
+                last_input_check = Instant::now();
+                if host.default_input_device().is_some()
+                    && let Ok(mut opened) =
+                        open_input_stream(&ctx, &host, &input_settings, DeviceFallback::Default)
+                {
+                    stream = opened.stream.take();
+                    info!("Microphone connected: {}", opened.device_name);
+                    notify("Microphone connected", &opened.device_name);
+                    input = Some(opened);
+                    menu.channel.rebuild();
+                }
+            }
+
+            // Ctrl+C or the quit hotkey: finish whatever is in progress, then quit
+            if QUIT_REQUESTED.load(Ordering::SeqCst) && !quit_pending {
+                diagln!("\nShutting down (press Ctrl+C again to force)...");
+                coordinator.finish_recording();
+                quit_pending = true;
+            }
+
+            // Check for menu events
+            if let Ok(event) = menu_channel.try_recv() {
+                if &event.id == menu.quit_item.id() {
+                    let status = ctx.status();
+                    if !status.is_busy() {
+                        quit_pending = true;
+                    } else {
+                        match confirm_quit_while_busy(&status) {
+                            QuitChoice::FinishFirst => {
+                                diagln!("\nFinishing current dictation before quitting...");
+                                coordinator.finish_recording();
+                                quit_pending = true;
+                            }
+                            QuitChoice::QuitNow => {
+                                diagln!("\nQuitting and discarding current dictation...");
+                                shutdown(&mut stream, tray_icon.as_ref());
+                                elwt.exit();
+                                return;
+                            }
+                            QuitChoice::Cancel => {}
+                        }
+                    }
+                } else if &event.id == menu.history_item.id() {
+                    open_history_window(&mut history_window, history_window_entries);
+                } else if &event.id == menu.diagnostics_item.id() {
+                    export_diagnostics(&ctx, &config, &engine);
+                } else if menu.is_permissions_item(&event.id) {
+                    if recheck_permissions(&mut keyboard_grab) {
+                        menu.remove_permissions_item();
+                    }
+                } else if &event.id == menu.reload_model_item.id() && !quit_pending {
+                    coordinator.reload_model(&AppCommand::ReloadModel, &engine);
+                } else if &event.id == menu.settings_item.id() {
+                    open_settings_window(
+                        &ctx,
+                        &mut settings_window,
+                        &engine.model_dir,
+                        &coordinator.commands,
+                    );
+                } else if &event.id == menu.output.type_item.id() {
+                    set_output_mode(OutputMode::Type);
+                    menu.output.sync();
+                    diagln!("\nOutput: typing into the focused window");
+                    save_setting(config_path.as_deref(), "output", "mode", "type".into());
+                } else if &event.id == menu.output.file_item.id() {
+                    choose_output_file(config_path.as_deref());
+                    menu.output.sync();
+                } else if &event.id == menu.text.tidy_item.id() {
+                    let enabled = menu.text.tidy_item.is_checked();
+                    set_tidy(enabled);
+                    save_setting(config_path.as_deref(), "text", "tidy", enabled.into());
+                } else if let Some(wrap) = menu.text.wrap_for(&event.id) {
+                    diagln!("\nWrap: {:?} … {:?}", wrap.prefix, wrap.suffix);
+                    save_setting(
+                        config_path.as_deref(),
+                        "text",
+                        "prefix",
+                        wrap.prefix.as_str().into(),
+                    );
+                    save_setting(
+                        config_path.as_deref(),
+                        "text",
+                        "suffix",
+                        wrap.suffix.as_str().into(),
+                    );
+                    set_wrap(wrap);
+                    menu.text.sync();
+                } else if let Some(channel) = menu.channel.channel_for(&event.id) {
+                    set_input_channel(channel);
+                    menu.channel.sync();
+                    match channel {
+                        Some(channel) => diagln!("\nInput: channel {}", channel),
+                        None => diagln!("\nInput: mixing all channels"),
+                    }
+                } else if let Some(profile) = menu.profile.profile_for(&event.id) {
+                    let previous = std::mem::replace(&mut *lock(&ACTIVE_PROFILE), profile.clone());
+                    menu.profile.sync();
+                    profile_switch = Some(previous);
+                    diagln!("\nProfile: {}", profile.as_deref().unwrap_or("none"));
+                } else if &event.id == menu.overlay_item.id() {
+                    set_status_overlay(menu.overlay_item.is_checked());
+                } else if &event.id == menu.console_item.id() {
+                    set_console_visible(&ctx, menu.console_item.is_checked());
+                } else if let Some(key) = menu.hotkey_for(&event.id) {
+                    coordinator.handle(AppCommand::SetHotkey(key));
+                } else if let Some(text) = menu.phrase_map.get(&event.id) {
+                    type_phrase(text.clone());
+                }
+            }
+
+            // Missing permissions and a grab given up on, once nothing is in flight
+            if !quit_pending
+                && let Some(command) = status_upkeep(
+                    &ctx.status(),
+                    !lock(&MISSING_PERMISSIONS).is_empty(),
+                    keyboard_grab.unavailable.as_ref(),
+                )
+            {
+                coordinator.handle(command);
+            }
+
+            // Exit once nothing is left in flight
+            let busy = ctx.status().is_busy();
+
+            // Keep the system awake while there's a dictation to lose
+            if busy != inhibiting {
+                inhibiting = busy;
+                if busy {
+                    sleep_inhibitor = SleepInhibitor::acquire("Dictating")
+                        .map_err(|e| warn!("Can't keep the system awake while dictating: {}", e))
+                        .ok();
+                } else if sleep_inhibitor.take().is_some() {
+                    debug!("Idle, the system may sleep again");
+                }
+            }
+            if quit_pending {
+                SHUTTING_DOWN.store(true, Ordering::SeqCst);
+                if !busy {
+                    diagln!("\nQuitting...");
+                    shutdown(&mut stream, tray_icon.as_ref());
+                    elwt.exit();
+                }
+            }
+
+            // Sleep until woken (see wake_event_loop) or the next timed check is due
+            let mut wake_at = None;
+            if busy {
+                wake_at = Some(last_status_check + WATCHDOG_INTERVAL);
+            }
+            if stream.is_none() && !quit_pending {
+                let retry_at = last_input_check + INPUT_RETRY_INTERVAL;
+                wake_at = Some(wake_at.map_or(retry_at, |at: Instant| at.min(retry_at)));
+            }
+            if let Some(retry_at) = keyboard_grab.retry_at.filter(|_| !quit_pending) {
+                wake_at = Some(wake_at.map_or(retry_at, |at: Instant| at.min(retry_at)));
+            }
+            if let Some(click_at) = pending_click {
+                wake_at = Some(wake_at.map_or(click_at, |at: Instant| at.min(click_at)));
+            }
+            elwt.set_control_flow(match wake_at {
+                Some(at) => ControlFlow::WaitUntil(at),
+                None => ControlFlow::Wait,
+            });
+        })
+        .map_err(|e| StartupError::EventLoop(e.to_string()))
+}
+
+/// Make the event loop run an iteration, to handle a flag set from another thread
+fn wake_event_loop() {
+    if let Some(waker) = lock(&EVENT_LOOP_WAKER).as_ref() {
+        waker.send_event(()).ok();
+    }
+}
+
+/// Pass on everything `receiver` gets, waking the event loop after each message
+/// and once more when it disconnects
+fn forward_waking<T: Send + 'static>(name: &str, receiver: Receiver<T>) -> Receiver<T> {
+    let (sender, forwarded) = channel();
+    let spawned = thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            for message in receiver {
+                if sender.send(message).is_err() {
+                    return;
+                }
+                wake_event_loop();
+            }
+            drop(sender);
+            wake_event_loop();
+        });
+    if let Err(e) = spawned {
+        error!("Couldn't start the {}: {}", name, e);
+    }
+    forwarded
+}
+
+/// Show the overlay dot while recording or transcribing, creating the window on first use
+fn sync_overlay(
+    ctx: &AppContext,
+    overlay: &mut Option<Overlay>,
+    elwt: &ActiveEventLoop,
+    corner: OverlayCorner,
+) {
+    let color = match ctx.status() {
+        AppStatus::Recording { .. } => Some(RECORDING_COLOR),
+        AppStatus::Transcribing { .. } => Some(TRANSCRIBING_COLOR),
+        _ => None,
+    }
+    .filter(|_| is_status_overlay_enabled());
+
+    if overlay.is_none() {
+        if color.is_none() {
+            return;
+        }
+        match Overlay::new(elwt, corner) {
+            Ok(created) => *overlay = Some(created),
+            Err(e) => {
+                error!("Failed to create status overlay, disabling it: {}", e);
+                set_status_overlay(false);
+                return;
+            }
+        }
+    }
+
+    if let Some(overlay) = overlay {
+        overlay.set_color(color);
+    }
+}
+
+/// Whether a window process started earlier is still running
+fn is_window_open(window: &mut Option<Child>) -> bool {
+    window
+        .as_mut()
+        .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+}
+
+/// Start this executable as a window process (e.g. `--settings-window`) with piped stdio
+fn spawn_window_process(flag: &str) -> Option<Child> {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            error!("Failed to locate executable for {}: {}", flag, e);
+            return None;
+        }
+    };
+
+    // The window's own log and files go where this instance's do
+    let mut command = Command::new(exe);
+    command.arg(flag);
+    if app_paths().portable {
+        command.arg("--portable");
+    }
+
+    match command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            error!("Failed to open window ({}): {}", flag, e);
+            None
+        }
+    }
+}
+
+/// Launch the settings window process and apply the changes it reports
+///
+/// A new hotkey goes to the event loop over `commands`, like one from `esponquen ctl`.
+fn open_settings_window(
+    ctx: &AppContext,
+    settings_window: &mut Option<Child>,
+    model_dir: &Path,
+    commands: &Sender<AppCommand>,
+) {
+    if is_window_open(settings_window) {
+        diagln!("Settings window is already open");
+        return;
+    }
+
+    let Some(mut child) = spawn_window_process("--settings-window") else {
+        return;
+    };
+
+    // Send the current values; closing stdin marks the end of the snapshot
+    if let Some(mut stdin) = child.stdin.take() {
+        let mut lines = settings::snapshot_lines();
+        lines.push(format!("info.device={}", lock(&DEVICE_INFO)));
+        lines.push(format!(
+            "info.sample_rate={}",
+            ctx.recording_state().sample_rate
+        ));
+        lines.push(format!("info.provider={}", lock(&ctx.provider_info)));
+        lines.push(format!("info.providers_tried={}", providers_tried()));
+        lines.push(format!("info.limitations={}", limitations()));
+        lines.push(format!("info.model_dir={}", model_dir.display()));
+
+        for line in lines {
+            writeln!(stdin, "{}", line).ok();
+        }
+    }
+
+    // Apply each change reported by the window
+    if let Some(stdout) = child.stdout.take() {
+        let commands = commands.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let applied = match settings::split_line(&line) {
+                    Some((settings::HOTKEY, name)) => request_hotkey(name, &commands).map(drop),
+                    _ => settings::apply_line(&line),
+                };
+                match applied {
+                    Ok(()) => {
+                        diagln!("Setting changed: {}", line);
+                        REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
+                        wake_event_loop();
+                    }
+                    Err(e) => error!("{}", e),
+                }
+            }
+        });
+    }
+
+    *settings_window = Some(child);
+}
+
+/// Launch the history window with the most recent transcriptions
+fn open_history_window(history_window: &mut Option<Child>, max_entries: usize) {
+    if is_window_open(history_window) {
+        diagln!("History window is already open");
+        return;
+    }
+
+    let Some(mut child) = spawn_window_process("--history-window") else {
+        return;
+    };
+
+    // Newest first, one line each
+    let entries: Vec<HistoryEntry> = lock(&HISTORY)
+        .iter()
+        .rev()
+        .take(max_entries)
+        .cloned()
+        .collect();
+
+    if let Some(mut stdin) = child.stdin.take() {
+        for entry in &entries {
+            let key = if entry.untyped {
+                history_window::UNTYPED_ENTRY
+            } else {
+                history_window::ENTRY
+            };
+            writeln!(stdin, "{}={}", key, entry.text.replace('\n', " ")).ok();
+        }
+    }
+
+    // Retype the entries picked in the window
+    if let Some(stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let entry = match settings::split_line(&line) {
+                    Some((history_window::RETYPE, index)) => {
+                        index.parse::<usize>().ok().and_then(|i| entries.get(i))
+                    }
+                    _ => None,
+                };
+                let Some(entry) = entry else {
+                    error!("Unexpected message from history window: {}", line);
+                    continue;
+                };
+
+                diagln!("\n⌨ Retyping from history...");
+                thread::sleep(RETYPE_FOCUS_DELAY);
+                if let Ok(Typed::Now) = type_text(&entry.text) {
+                    diagln!("✓ Done!\n");
+                }
+            }
+        });
+    }
+
+    *history_window = Some(child);
+}
+
+/// Remember a transcription for the history window
+fn add_to_history(text: &str) {
+    let mut history = lock(&HISTORY);
+    history.push_back(HistoryEntry {
+        text: text.to_string(),
+        untyped: false,
+    });
+    if history.len() > HISTORY_CAPACITY {
+        history.pop_front();
+    }
+}
+
+/// Mark the latest history entry for `text` as not typed
+fn mark_untyped_in_history(text: &str) {
+    if let Some(entry) = lock(&HISTORY)
+        .iter_mut()
+        .rev()
+        .find(|entry| entry.text == text)
+    {
+        entry.untyped = true;
+    }
+}
+
+/// Build the Phrases submenu, mapping each item to the text it types
+///
+/// Long labels are shortened; tray menu items can't show a tooltip with the full text.
+fn phrases_submenu(phrases: &BTreeMap<String, String>) -> (Submenu, HashMap<MenuId, String>) {
+    let submenu = Submenu::new("Phrases", true);
+    let mut phrase_map = HashMap::new();
+
+    for (label, text) in phrases {
+        let menu_item = MenuItem::new(one_line_preview(label, PHRASE_LABEL_CHARS), true, None);
+        submenu.append(&menu_item).ok();
+        phrase_map.insert(menu_item.id().clone(), text.clone());
+    }
+
+    (submenu, phrase_map)
+}
+
+/// The tray menu and the items that change while the app runs
+///
+/// Built once; settings, the provider and the hotkey update their items in place,
+/// so an open menu stays open. Only the phrases submenu is replaced when the
+/// config changes which phrases there are.
+struct MenuHandles {
+    menu: Menu,
+    /// Each hotkey entry and the key it selects
+    hotkey_items: Vec<(CheckMenuItem, RdevKey)>,
+    phrases: Option<Submenu>,
+    phrase_map: HashMap<MenuId, String>,
+    output: OutputMenu,
+    text: TextMenu,
+    channel: ChannelMenu,
+    profile: ProfileMenu,
+    provider_item: MenuItem,
+    reload_model_item: MenuItem,
+    history_item: MenuItem,
+    settings_item: MenuItem,
+    diagnostics_item: MenuItem,
+    /// Only in the menu while required permissions are missing
+    permissions_item: Option<MenuItem>,
+    overlay_item: CheckMenuItem,
+    console_item: CheckMenuItem,
+    quit_item: MenuItem,
+}
+
+impl MenuHandles {
+    fn new(config: &Config, ctx: &AppContext, console_label: &str, console_checked: bool) -> Self {
+        let menu = Menu::new();
+
+        let hotkey_submenu = Submenu::new("Set Hotkey", true);
+        let hotkey_items: Vec<(CheckMenuItem, RdevKey)> = HOTKEY_OPTIONS
+            .iter()
+            .map(|(name, key)| {
+                let item = CheckMenuItem::new(*name, true, false, None);
+                hotkey_submenu.append(&item).ok();
+                (item, *key)
+            })
+            .collect();
+        menu.append(&hotkey_submenu).ok();
+
+        // Type into the focused window or append to a file
+        let output = OutputMenu::new();
+        menu.append(&output.submenu).ok();
+
+        // Clean-ups applied to transcriptions
+        let text = TextMenu::new();
+        menu.append(&text.submenu).ok();
+
+        // Mix all input channels or record one, filled in once the device is open
+        let channel = ChannelMenu::new();
+        menu.append(&channel.submenu).ok();
+
+        // Switch between the config's profiles
+        let profile = ProfileMenu::new(config);
+        menu.append(&profile.submenu).ok();
+
+        menu.append(&PredefinedMenuItem::separator()).ok();
+
+        // Provider info (disabled, just for display)
+        let provider_item = MenuItem::new("Running on:", false, None);
+        menu.append(&provider_item).ok();
+
+        let reload_model_item = MenuItem::new("Reload model", true, None);
+        menu.append(&reload_model_item).ok();
+
+        menu.append(&PredefinedMenuItem::separator()).ok();
+
+        let history_item = MenuItem::new("History…", true, None);
+        menu.append(&history_item).ok();
+
+        let settings_item = MenuItem::new("Settings…", true, None);
+        menu.append(&settings_item).ok();
+
+        let diagnostics_item = MenuItem::new("Export diagnostics…", true, None);
+        menu.append(&diagnostics_item).ok();
+
+        let permissions_item = if lock(&MISSING_PERMISSIONS).is_empty() {
+            None
+        } else {
+            let item = MenuItem::new("Re-check permissions", true, None);
+            menu.append(&item).ok();
+            Some(item)
+        };
+
+        let overlay_item =
+            CheckMenuItem::new("Status overlay", true, is_status_overlay_enabled(), None);
+        menu.append(&overlay_item).ok();
+
+        // Console toggle (Windows) or debug level logging (other platforms)
+        let console_item = CheckMenuItem::new(console_label, true, console_checked, None);
+        menu.append(&console_item).ok();
+
+        let quit_item = MenuItem::new("Quit", true, None);
+        menu.append(&quit_item).ok();
+
+        let mut handles = MenuHandles {
+            menu,
+            hotkey_items,
+            phrases: None,
+            phrase_map: HashMap::new(),
+            output,
+            text,
+            channel,
+            profile,
+            provider_item,
+            reload_model_item,
+            history_item,
+            settings_item,
+            diagnostics_item,
+            permissions_item,
+            overlay_item,
+            console_item,
+            quit_item,
+        };
+        handles.set_phrases(&config.phrases);
+        handles.sync(ctx);
+        handles
+    }
+
+    /// Match the provider line and the hotkey check mark to the current state
+    fn sync(&self, ctx: &AppContext) {
+        let provider_info = lock(&ctx.provider_info).clone();
+        self.provider_item
+            .set_text(format!("Running on: {}", provider_info));
+
+        let hotkey = ctx.hotkey();
+        for (item, key) in &self.hotkey_items {
+            item.set_checked(*key == hotkey);
+        }
+    }
+
+    /// Replace the pinned phrases, typed when clicked
+    fn set_phrases(&mut self, phrases: &BTreeMap<String, String>) {
+        if let Some(submenu) = self.phrases.take() {
+            self.menu.remove(&submenu).ok();
+        }
+        let (submenu, map) = phrases_submenu(phrases);
+        if !phrases.is_empty() {
+            // Right after the hotkey submenu
+            self.menu.insert(&submenu, 1).ok();
+            self.phrases = Some(submenu);
+        }
+        self.phrase_map = map;
+    }
+
+    /// Whether `id` is the Re-check permissions entry
+    fn is_permissions_item(&self, id: &MenuId) -> bool {
+        self.permissions_item
+            .as_ref()
+            .is_some_and(|item| item.id() == id)
+    }
+
+    /// Take the Re-check permissions entry out, once there's nothing left to check
+    fn remove_permissions_item(&mut self) {
+        if let Some(item) = self.permissions_item.take() {
+            self.menu.remove(&item).ok();
+        }
+    }
+
+    /// Hotkey selected by a click on `id`, if it's one of the hotkey entries
+    fn hotkey_for(&self, id: &MenuId) -> Option<RdevKey> {
+        self.hotkey_items
+            .iter()
+            .find(|(item, _)| item.id() == id)
+            .map(|(_, key)| *key)
+    }
+}
+
+/// Output submenu, with a check mark on the current output mode
+struct OutputMenu {
+    submenu: Submenu,
+    type_item: CheckMenuItem,
+    file_item: CheckMenuItem,
+}
+
+impl OutputMenu {
+    fn new() -> Self {
+        let submenu = Submenu::new("Output", true);
+        let type_item = CheckMenuItem::new("Type into focused window", true, false, None);
+        let file_item = CheckMenuItem::new("Append to file…", true, false, None);
+        submenu.append(&type_item).ok();
+        submenu.append(&file_item).ok();
+
+        let menu = OutputMenu {
+            submenu,
+            type_item,
+            file_item,
+        };
+        menu.sync();
+        menu
+    }
+
+    /// Match the check marks and file name to the current output mode
+    fn sync(&self) {
+        let mode = get_output_mode();
+        self.type_item.set_checked(mode == OutputMode::Type);
+        self.file_item
+            .set_checked(matches!(mode, OutputMode::File(_)));
+        match &mode {
+            OutputMode::File(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                self.file_item
+                    .set_text(format!("Append to {}", name.to_string_lossy()));
+            }
+            _ => self.file_item.set_text("Append to file…"),
+        }
+    }
+}
+
+/// Text submenu, with the clean-ups applied to transcriptions
+struct TextMenu {
+    submenu: Submenu,
+    tidy_item: CheckMenuItem,
+    /// Each wrap preset and the wrap it selects
+    wrap_items: Vec<(CheckMenuItem, Wrap)>,
+    /// Checked when the config file sets a wrap that isn't a preset
+    custom_wrap_item: CheckMenuItem,
+}
+
+impl TextMenu {
+    fn new() -> Self {
+        let submenu = Submenu::new("Text", true);
+        let tidy_item = CheckMenuItem::new("Tidy spacing", true, is_tidy_enabled(), None);
+        submenu.append(&tidy_item).ok();
+
+        let wrap_submenu = Submenu::new("Wrap", true);
+        let wrap_items: Vec<(CheckMenuItem, Wrap)> = WRAP_PRESETS
+            .iter()
+            .map(|(label, prefix, suffix)| {
+                let item = CheckMenuItem::new(*label, true, false, None);
+                wrap_submenu.append(&item).ok();
+                (item, Wrap::new(prefix, suffix))
+            })
+            .collect();
+        // Only set in the config file, so it can't be picked here
+        let custom_wrap_item = CheckMenuItem::new("Custom (config file)", false, false, None);
+        wrap_submenu.append(&custom_wrap_item).ok();
+        submenu.append(&wrap_submenu).ok();
+
+        let menu = TextMenu {
+            submenu,
+            tidy_item,
+            wrap_items,
+            custom_wrap_item,
+        };
+        menu.sync();
+        menu
+    }
+
+    /// Match the check marks to the current settings
+    fn sync(&self) {
+        self.tidy_item.set_checked(is_tidy_enabled());
+
+        let wrap = get_wrap();
+        for (item, preset) in &self.wrap_items {
+            item.set_checked(*preset == wrap);
+        }
+        self.custom_wrap_item
+            .set_checked(!self.wrap_items.iter().any(|(_, preset)| *preset == wrap));
+    }
+
+    /// Wrap selected by a click on `id`, if it's one of the presets
+    fn wrap_for(&self, id: &MenuId) -> Option<Wrap> {
+        self.wrap_items
+            .iter()
+            .find(|(item, _)| item.id() == id)
+            .map(|(_, wrap)| wrap.clone())
+    }
+}
+
+/// Channel submenu, with a check mark on the channel being recorded
+struct ChannelMenu {
+    submenu: Submenu,
+    /// Each entry and the channel it selects (from 1), None mixing them all
+    items: Vec<(CheckMenuItem, Option<usize>)>,
+}
+
+impl ChannelMenu {
+    fn new() -> Self {
+        let mut menu = ChannelMenu {
+            submenu: Submenu::new("Input Channel", true),
+            items: Vec::new(),
+        };
+        menu.rebuild();
+        menu
+    }
+
+    /// Replace the entries with the channels of the open input device
+    fn rebuild(&mut self) {
+        for (item, _) in self.items.drain(..) {
+            self.submenu.remove(&item).ok();
+        }
+
+        let channels = INPUT_CHANNELS.load(Ordering::SeqCst);
+        let entries = std::iter::once(None).chain((1..=channels).map(Some));
+        for channel in entries {
+            let label = match channel {
+                Some(channel) => format!("Channel {}", channel),
+                None => "Mix all channels".to_string(),
+            };
+            let item = CheckMenuItem::new(label, true, false, None);
+            self.submenu.append(&item).ok();
+            self.items.push((item, channel));
+        }
+
+        // Nothing to pick on a mono device
+        self.submenu.set_enabled(channels > 1);
+        self.sync();
+    }
+
+    /// Match the check marks to the channel being recorded
+    fn sync(&self) {
+        let recorded = recorded_channel();
+        for (item, channel) in &self.items {
+            item.set_checked(*channel == recorded);
+        }
+    }
+
+    /// Channel selected by a click on `id`, if it's one of the entries
+    fn channel_for(&self, id: &MenuId) -> Option<Option<usize>> {
+        self.items
+            .iter()
+            .find(|(item, _)| item.id() == id)
+            .map(|(_, channel)| *channel)
+    }
+}
+
+/// Profile submenu, with a check mark on the active profile
+struct ProfileMenu {
+    submenu: Submenu,
+    /// Each entry and the profile it selects, None being the plain config file
+    items: Vec<(CheckMenuItem, Option<String>)>,
+}
+
+impl ProfileMenu {
+    fn new(config: &Config) -> Self {
+        let mut menu = ProfileMenu {
+            submenu: Submenu::new("Profile", true),
+            items: Vec::new(),
+        };
+        menu.rebuild(config);
+        menu
+    }
+
+    /// Replace the entries with the profiles of `config`
+    fn rebuild(&mut self, config: &Config) {
+        for (item, _) in self.items.drain(..) {
+            self.submenu.remove(&item).ok();
+        }
+
+        let profiles = std::iter::once(None).chain(config.profiles.keys().cloned().map(Some));
+        for profile in profiles {
+            let item = CheckMenuItem::new(profile.as_deref().unwrap_or("None"), true, false, None);
+            self.submenu.append(&item).ok();
+            self.items.push((item, profile));
+        }
+
+        self.submenu.set_enabled(!config.profiles.is_empty());
+        self.sync();
+    }
+
+    /// Match the check marks to the active profile
+    fn sync(&self) {
+        let active = active_profile();
+        for (item, profile) in &self.items {
+            item.set_checked(*profile == active);
+        }
+    }
+
+    /// Profile selected by a click on `id`, if it's one of the entries
+    fn profile_for(&self, id: &MenuId) -> Option<Option<String>> {
+        self.items
+            .iter()
+            .find(|(item, _)| item.id() == id)
+            .map(|(_, profile)| profile.clone())
+    }
+}
+
+/// Write a diagnostics bundle in the background and tell the user where it went
+fn export_diagnostics(ctx: &AppContext, config: &Config, engine: &EngineConfig) {
+    let config = config.clone();
+    let engine = engine.clone();
+    let snapshot = state_snapshot(ctx);
+    let provider_attempts = lock(&PROVIDER_ATTEMPTS).clone();
+    diagln!("\nExporting diagnostics...");
+
+    thread::spawn(move || {
+        match diagnostics::export(&config, &engine, &snapshot, &provider_attempts) {
+            Ok(path) => {
+                diagln!("✓ Diagnostics written to {}", path.display());
+                notify("Diagnostics exported", &path.display().to_string());
+            }
+            Err(e) => {
+                error!("Failed to export diagnostics: {}", e);
+                notify("Diagnostics not exported", &e);
+            }
+        }
+    });
+}
+
+/// Ask for the file transcriptions are appended to; cancelling keeps the current mode
+fn choose_output_file(config_path: Option<&Path>) {
+    let chosen = rfd::FileDialog::new()
+        .set_title("Append transcriptions to")
+        .add_filter("Text", &["txt", "md"])
+        .save_file();
+
+    if let Some(path) = chosen {
+        diagln!("\nOutput: appending to {}", path.display());
+        let file = path.display().to_string();
+        set_output_mode(OutputMode::File(path));
+        save_setting(config_path, "output", "file", file.into());
+        save_setting(config_path, "output", "mode", "file".into());
+    }
+}
+
+/// Remember a change made from the tray in the config file
+///
+/// The file watcher then reloads it, which applies the same value again.
+fn save_setting(config_path: Option<&Path>, section: &str, key: &str, value: toml::Value) {
+    let Some(path) = config_path else {
+        return;
+    };
+    if let Err(e) = config::update_setting(path, section, key, &value) {
+        let message = format!("Couldn't save {}.{}: {}", section, key, e);
+        warn!("{}", message);
+    }
+}
+
+/// Type a pinned phrase on a worker thread, the same way as a transcription
+fn type_phrase(text: String) {
+    thread::spawn(move || {
+        diagln!("\n⌨ Typing phrase...");
+        debug!("Typing phrase ({} chars)", text.len());
+        if let Ok(Typed::Now) = type_text(&text) {
+            diagln!("✓ Done!\n");
+        }
+    });
+}
+
+/// Layer the ESPONQUEN_* environment variables, then the command line, over the file
+fn apply_overrides(config: &mut Config, cli: &Cli) -> Result<(), String> {
+    if let Some(profile) = active_profile() {
+        *config = config.with_profile(&profile)?;
+    }
+    config::apply_env(config)?;
+    cli.apply_to(config);
+    Ok(())
+}
+
+/// Pick the profile to start with: --profile, then the config's `profile`
+fn select_startup_profile(config: &Config, cli: &Cli) {
+    *lock(&ACTIVE_PROFILE) = cli.profile.clone().or_else(|| config.profile.clone());
+}
+
+/// Name of the profile applied on top of the config file, if any
+fn active_profile() -> Option<String> {
+    lock(&ACTIVE_PROFILE).clone()
+}
+
+/// Print and log config problems, returning whether any of them is fatal
+fn report_problems(problems: &[Problem]) -> bool {
+    config_error::log_problems(problems);
+    has_fatal(problems)
+}
+
+/// Validate the settings from the config file and apply them to the running app
+fn apply_config(config: &Config, cli: &Cli) -> Result<EngineConfig, String> {
+    let mut builder = EngineBuilder::from_config(config);
+    if cli.dry_run {
+        builder = builder.output_mode(OutputMode::Print);
+    }
+    let engine = builder.build()?;
+    engine.apply();
+
+    *lock(&ICON_FILES) = config.ui.icons.clone();
+    *lock(&STATUS_DISPLAY) = config.ui.status_display.clone();
+
+    // --log-level wins over the config file
+    let level = config.log.level.as_deref().and_then(logging::parse_level);
+    set_log_level(cli.log_level.or(level).unwrap_or(LevelFilter::Info));
+    set_log_transcripts(config.log.transcripts.unwrap_or(false));
+    set_event_log(config.log.events.unwrap_or(false));
+    let events_max_mb = config
+        .log
+        .events_max_mb
+        .unwrap_or(config::DEFAULT_EVENTS_MAX_MB);
+    set_event_log_max_bytes(events_max_mb * 1024 * 1024);
+
+    Ok(engine)
+}
+
+/// Apply reloaded settings (file edit or profile switch), reporting the ones that need a restart
+fn reload_config(old: &Config, new: &Config, cli: &Cli) -> Result<EngineConfig, String> {
+    let problems = validate(new);
+    if report_problems(&problems) {
+        let fatal: Vec<String> = problems
+            .iter()
+            .filter(|p| p.severity == Severity::Fatal)
+            .map(|p| p.to_string())
+            .collect();
+        return Err(format!("Invalid config: {}", fatal.join("; ")));
+    }
+    let engine = apply_config(new, cli)?;
+    REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
+    wake_event_loop();
+    diagln!("\n✓ Settings reloaded");
+
+    let restart_needed: Vec<&str> = [
+        ("audio.device", old.audio.device != new.audio.device),
+        (
+            "audio.sample_rate",
+            old.audio.sample_rate != new.audio.sample_rate,
+        ),
+        (
+            "audio.buffer_size",
+            old.audio.buffer_size != new.audio.buffer_size,
+        ),
+        ("metrics.port", old.metrics.port != new.metrics.port),
+        ("dbus.enabled", old.dbus.enabled != new.dbus.enabled),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect();
+
+    if !restart_needed.is_empty() {
+        let message = format!("Restart to apply: {}", restart_needed.join(", "));
+        warn!("{}", message);
+    }
+
+    Ok(engine)
+}
+
+/// Poll the config file and send it to the event loop each time it changes and parses
+fn watch_config(path: PathBuf, config_tx: Sender<Config>) {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified(&path);
+
+    loop {
+        thread::sleep(CONFIG_POLL_INTERVAL);
+
+        // Missing while an editor replaces it; wait for the new file
+        let current = modified(&path);
+        if current.is_none() || current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        match config::load(&path) {
+            Ok(config) => {
+                if config_tx.send(config).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                // Keep running with the previous settings
+                error!("{}, keeping the current settings", e);
+                notify("Config file not reloaded", &e);
+            }
+        }
+    }
+}
+
+/// Number of entries listed in the history window
+fn history_window_limit(config: &Config) -> usize {
+    config
+        .history
+        .window_entries
+        .unwrap_or(DEFAULT_HISTORY_WINDOW_ENTRIES)
+        .min(HISTORY_CAPACITY)
+}
+
+/// Load the config file if there is one, without running the setup wizard
+fn load_existing_config(path: Option<&Path>) -> Config {
+    match path {
+        Some(path) if path.exists() => config::load(path).unwrap_or_else(|e| {
+            error!("{}, using defaults", e);
+            Config::default()
+        }),
+        _ => Config::default(),
+    }
+}
+
+/// Load the recognizer, trying GPU providers before falling back to the CPU
+///
+/// Returns the recognizer, the provider it runs on ("CPU" or e.g. "cuda") and the
+/// display names of the GPU providers that failed. Exits if even the CPU fails.
+fn load_recognizer(engine: &EngineConfig) -> Result<(Transcriber, String, Vec<String>), String> {
+    // GPU providers in order of preference, unless the config lists them
+    let providers_to_try = engine.providers.clone();
+
+    let mut recognizer = None;
+    let mut used_provider = String::from("CPU");
+    let mut failed_providers: Vec<String> = Vec::new();
+    lock(&PROVIDER_ATTEMPTS).clear();
+
+    // The preferred GPU provider gets more tries, its driver may not be ready yet
+    let preferred = providers_to_try.iter().flatten().next().cloned();
+
+    for provider in providers_to_try {
+        diagln!(
+            "Trying provider: {:?}",
+            provider.as_ref().unwrap_or(&"CPU".to_string())
+        );
+
+        let retries = if provider.is_some() && provider == preferred {
+            engine.load_retries
+        } else {
+            0
+        };
+        match init_recognizer_with_retries(engine, provider.as_deref(), retries) {
+            Ok(rec) => {
+                used_provider = provider.unwrap_or_else(|| "CPU".to_string());
+                lock(&PROVIDER_ATTEMPTS).push(format!("{}: loaded", used_provider));
+                diagln!(
+                    "✓ Model loaded successfully with {} provider\n",
+                    used_provider
+                );
+                recognizer = Some(rec);
+                break;
+            }
+            Err(message) => {
+                if provider.is_some() {
+                    diagln!(
+                        "  ⚠ {} provider not available: {}",
+                        provider.as_ref().unwrap(),
+                        message
+                    );
+                    diagln!("  Trying next provider...\n");
+                    failed_providers.push(provider_display_name(provider.as_ref().unwrap()));
+                } else {
+                    return Err(format!(
+                        "Failed to initialize recognizer even with CPU: {}",
+                        message
+                    ));
+                }
+            }
+        }
+    }
+
+    // Only reachable when the configured providers don't include "cpu"
+    let Some(recognizer) = recognizer else {
+        return Err(format!(
+            "None of the configured providers could load the model: {}",
+            failed_providers.join(", ")
+        ));
+    };
+
+    Ok((recognizer, used_provider, failed_providers))
+}
+
+/// Load the model with one provider, trying `retries` more times with a doubling
+/// wait in between when it fails
+fn init_recognizer_with_retries(
+    engine: &EngineConfig,
+    provider: Option<&str>,
+    retries: u32,
+) -> Result<Transcriber, String> {
+    let mut delay = engine.load_retry_delay;
+    for retry in 1..=retries {
+        match init_recognizer(engine, provider) {
+            Ok(recognizer) => return Ok(recognizer),
+            Err(message) => {
+                warn!(
+                    "{} provider failed to load ({}), retry {} of {} in {} ms",
+                    provider.unwrap_or("CPU"),
+                    message,
+                    retry,
+                    retries,
+                    delay.as_millis()
+                );
+                thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+    init_recognizer(engine, provider)
+}
+
+/// Load the model with one provider (None for the CPU), explaining the failure if it's a known one
+fn init_recognizer(engine: &EngineConfig, provider: Option<&str>) -> Result<Transcriber, String> {
+    Transcriber::new(engine, provider).map_err(|e| {
+        // The raw error goes to the diagnostics, the console gets the explanation if there is one
+        lock(&PROVIDER_ATTEMPTS).push(format!("{}: {}", provider.unwrap_or("CPU"), e.raw));
+        e.to_string()
+    })
+}
+
+/// Record the provider the model runs on for the menu, returning whether it fell back to the CPU
+fn set_provider_info(
+    ctx: &AppContext,
+    used_provider: &str,
+    failed_providers: &[String],
+    cpu_threads: i32,
+) -> bool {
+    // GPU providers were tried but none of them loaded
+    let cpu_fallback = used_provider == "CPU" && !failed_providers.is_empty();
+    CPU_FALLBACK.store(cpu_fallback, Ordering::SeqCst);
+
+    let provider_display = if used_provider != "CPU" {
+        provider_display_name(used_provider)
+    } else if cpu_fallback {
+        format!(
+            "CPU ({} threads) — {} unavailable",
+            cpu_threads,
+            failed_providers.join("/")
+        )
+    } else {
+        format!("CPU ({} threads)", cpu_threads)
+    };
+    *lock(&ctx.provider_info) = provider_display;
+    *lock(&ctx.provider_name) = used_provider.to_lowercase();
+
+    cpu_fallback
+}
+
+/// Load the model for new settings in the background and swap it in once it's ready
+///
+/// The current model keeps transcribing until then, and stays if loading fails.
+/// A transcription running when the new one is ready finishes on the current
+/// model, and the new one takes over right after. `done` gets `ModelLoaded` when
+/// it's over either way, for a reload that shows the loading status.
+fn reload_model(
+    ctx: &Arc<AppContext>,
+    engine: &EngineConfig,
+    recognizer: &SharedRecognizer,
+    done: Option<Sender<AppCommand>>,
+) {
+    let generation = MODEL_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let engine = engine.clone();
+    let ctx = Arc::clone(ctx);
+    let recognizer = recognizer.clone();
+    diagln!("\nLoading the model for the new settings...");
+
+    thread::spawn(move || {
+        match load_recognizer(&engine) {
+            // A later reload superseded this one
+            Ok(_) if MODEL_GENERATION.load(Ordering::SeqCst) != generation => {}
+            Ok((loaded, used_provider, failed_providers)) => {
+                let now = recognizer.replace(Box::new(loaded));
+                set_provider_info(&ctx, &used_provider, &failed_providers, engine.cpu_threads);
+                REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
+                wake_event_loop();
+                if now {
+                    diagln!("✓ Model reloaded with {} provider", used_provider);
+                } else {
+                    diagln!(
+                        "✓ Model reloaded with {} provider, in use after the current transcription",
+                        used_provider
+                    );
+                }
+            }
+            Err(e) => {
+                error!("{}, keeping the current model", e);
+                events::emit_error(ErrorCategory::Model, e.clone());
+                notify("Model not reloaded", &e);
+            }
+        }
+        if let Some(done) = done {
+            done.send(AppCommand::ModelLoaded).ok();
+        }
+    });
+}
+
+/// `engine` on the provider after the one the model runs on, for comparing
+/// providers; None when there's no other
+///
+/// Goes from the CPU through the GPU providers of `engine` and back; the ones that
+/// fail to load are skipped.
+fn next_provider(ctx: &AppContext, engine: &EngineConfig) -> Option<EngineConfig> {
+    let mut cycle: Vec<Option<String>> = vec![None];
+    cycle.extend(engine.providers.iter().filter(|p| p.is_some()).cloned());
+    let current = lock(&ctx.provider_name).clone();
+    if let Some(position) = cycle
+        .iter()
+        .position(|p| p.as_deref().unwrap_or("cpu") == current)
+    {
+        cycle.rotate_left(position);
+        cycle.remove(0);
+    }
+    if cycle.is_empty() {
+        diagln!("\nNo other provider to switch to");
+        return None;
+    }
+
+    let names: Vec<String> = cycle
+        .iter()
+        .map(|p| provider_display_name(p.as_deref().unwrap_or("cpu")))
+        .collect();
+    diagln!("\nSwitching provider, trying {}", names.join(", then "));
+    let mut next = engine.clone();
+    next.providers = cycle;
+    Some(next)
+}
+
+/// Transcribe what's been recorded so far as a preview, leaving the recording running
+///
+/// Only the copy is made under the recording state lock. The preview goes to the
+/// console, the log and a notification; nothing is typed, the final transcription
+/// covers the whole recording.
+fn transcribe_snapshot(ctx: &AppContext, recognizer: &SharedRecognizer) {
+    let (snapshot, sample_rate) = {
+        let mut state = ctx.recording_state();
+        if !ctx.is_recording() {
+            return;
+        }
+        ctx.collect_audio(&mut state);
+        let mut snapshot = ctx
+            .buffers
+            .hold(ctx.buffers.checkout(state.audio_data.len()));
+        snapshot.extend_from_slice(&state.audio_data);
+        (snapshot, state.sample_rate)
+    };
+
+    // Waiting on a stuck model would hang this thread too
+    if snapshot.is_empty() || TRANSCRIPTION_ABANDONED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let audio_secs = snapshot.len() as f32 / sample_rate as f32;
+    diagln!(
+        "\n📸 Transcribing the first {:.1}s as a preview...",
+        audio_secs
+    );
+    let settings = ctx.settings();
+    let (text, timings) =
+        transcribe_with(&mut *recognizer.lease(), sample_rate, &snapshot, &settings);
+    debug!("Snapshot: {}", timings.summary());
+
+    if text.trim().is_empty() {
+        diagln!("  Nothing recognized yet");
+        return;
+    }
+    info!(target: TRANSCRIPT, "📸 Snapshot: {}", text);
+    notify("Snapshot (still recording)", text.trim());
+}
+
+/// Log the timings of a transcription, and print them with --timings
+fn report_timings(timings: &TranscriptionTimings) {
+    let summary = timings.summary();
+    debug!("Timings: {}", summary);
+    if is_timings_enabled() {
+        diagln!("  ⏱ {}", summary);
+    }
+}
+
+/// Human-readable name of an execution provider
+fn provider_display_name(provider: &str) -> String {
+    match provider {
+        "dml" => "DirectML".to_string(),
+        "cuda" => "CUDA".to_string(),
+        "rocm" => "ROCm".to_string(),
+        "coreml" => "CoreML".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+/// Pick the input config, honoring a requested sample rate when the device supports it
+fn select_input_config(
+    device: &cpal::Device,
+    requested_rate: Option<u32>,
+) -> Result<cpal::SupportedStreamConfig, String> {
+    let default_config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get default input config: {}", e))?;
+
+    let Some(rate) = requested_rate else {
+        return Ok(default_config);
+    };
+
+    let ranges: Vec<cpal::SupportedStreamConfigRange> = match device.supported_input_configs() {
+        Ok(configs) => configs.collect(),
+        Err(e) => {
+            error!(
+                "Could not query supported configs ({}), ignoring --input-rate {}",
+                e, rate
+            );
+            return Ok(default_config);
+        }
+    };
+
+    let supports_rate = |range: &&cpal::SupportedStreamConfigRange| {
+        range.sample_format() == cpal::SampleFormat::F32
+            && range.min_sample_rate().0 <= rate
+            && rate <= range.max_sample_rate().0
+    };
+
+    // Prefer a config with the same channel count as the default one
+    let chosen = ranges
+        .iter()
+        .filter(supports_rate)
+        .find(|range| range.channels() == default_config.channels())
+        .or_else(|| ranges.iter().find(supports_rate));
+
+    match chosen {
+        Some(range) => {
+            diagln!("Using requested input rate: {} Hz", rate);
+            Ok(range.clone().with_sample_rate(cpal::SampleRate(rate)))
+        }
+        None => {
+            error!(
+                "Input device does not support {} Hz, falling back to the default rate of {} Hz",
+                rate,
+                default_config.sample_rate().0
+            );
+            Ok(default_config)
+        }
+    }
+}
+
+/// Check a requested buffer size against the range the device reports
+///
+/// Small buffers lower the latency of anything that reacts to incoming audio but
+/// risk dropouts (xruns) on busy systems; large buffers are more robust but react
+/// later. Returns None (use the device default) when the size is out of range.
+fn validate_buffer_size(config: &cpal::SupportedStreamConfig, frames: u32) -> Option<u32> {
+    match config.buffer_size() {
+        cpal::SupportedBufferSize::Range { min, max } if frames < *min || frames > *max => {
+            error!(
+                "Buffer size {} is outside the device range ({}-{} frames), using the default",
+                frames, min, max
+            );
+            None
+        }
+        _ => {
+            diagln!("Using buffer size: {} frames", frames);
+            Some(frames)
+        }
+    }
+}
+
+/// Input device settings from the config file and command line
+struct InputSettings {
+    device: Option<String>,
+    sample_rate: Option<u32>,
+    buffer_size: Option<u32>,
+}
+
+fn input_settings(config: &Config) -> InputSettings {
+    InputSettings {
+        device: config.audio.device.clone(),
+        // Optional sample rate override for devices whose default config misbehaves
+        sample_rate: config.audio.sample_rate,
+        // Optional buffer size, trading latency (small) against xrun safety (large)
+        buffer_size: config.audio.buffer_size,
+    }
+}
+
+/// What `open_input_stream` does when the configured device can't be opened
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DeviceFallback {
+    /// Try the system default device next
+    Default,
+    /// Only use the system default device
+    DefaultOnly,
+}
+
+/// The input stream currently recording and the device it belongs to
+struct ActiveInput {
+    /// Handed to the event loop once started
+    stream: Option<Recorder>,
+    device_name: String,
+    sample_rate: u32,
+    /// Whether this is the system default rather than the configured device
+    is_default: bool,
+}
+
+/// Open and start an input stream, trying the configured device first unless told otherwise
+fn open_input_stream(
+    ctx: &Arc<AppContext>,
+    host: &cpal::Host,
+    settings: &InputSettings,
+    fallback: DeviceFallback,
+) -> Result<ActiveInput, String> {
+    if fallback == DeviceFallback::Default
+        && let Some(name) = settings.device.as_deref()
+    {
+        match start_input_stream(ctx, host, Some(name), settings) {
+            Ok(input) => return Ok(input),
+            Err(e) => error!("Input device '{}' failed ({}), using the default", name, e),
+        }
+    }
+
+    start_input_stream(ctx, host, None, settings)
+}
+
+/// Open and start an input stream on one device (`None` for the system default)
+fn start_input_stream(
+    ctx: &Arc<AppContext>,
+    host: &cpal::Host,
+    device_name: Option<&str>,
+    settings: &InputSettings,
+) -> Result<ActiveInput, String> {
+    let device =
+        select_input_device(host, device_name).ok_or("No input device available".to_string())?;
+    let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+    let is_default = device_name.is_none()
+        || host
+            .default_input_device()
+            .and_then(|default| default.name().ok())
+            .is_some_and(|default_name| default_name == name);
+    diagln!("Using input device: {}", name);
+
+    let mut input_config = select_input_config(&device, settings.sample_rate)?;
+    let buffer_size = settings
+        .buffer_size
+        .and_then(|frames| validate_buffer_size(&input_config, frames));
+
+    let recorder = match Recorder::start(ctx, &device, &input_config, buffer_size, on_stream_error)
+    {
+        Ok(recorder) => recorder,
+        Err(e) if settings.sample_rate.is_some() || buffer_size.is_some() => {
+            error!(
+                "Device rejected the requested stream settings ({}), falling back to the defaults",
+                e
+            );
+            input_config = device
+                .default_input_config()
+                .map_err(|e| format!("Failed to get default input config: {}", e))?;
+            Recorder::start(ctx, &device, &input_config, None, on_stream_error)?
+        }
+        Err(e) => return Err(e),
+    };
+
+    INPUT_CHANNELS.store(recorder.channels(), Ordering::SeqCst);
+    check_input_channel();
+    *lock(&DEVICE_INFO) = name.clone();
+
+    Ok(ActiveInput {
+        sample_rate: recorder.sample_rate(),
+        stream: Some(recorder),
+        device_name: name,
+        is_default,
+    })
+}
+
+/// Channel actually recorded: the configured one if the device has it, else None (mixed)
+fn recorded_channel() -> Option<usize> {
+    get_input_channel().filter(|channel| *channel <= INPUT_CHANNELS.load(Ordering::SeqCst))
+}
+
+/// Warn when the configured channel isn't on the open device
+fn check_input_channel() {
+    let channels = INPUT_CHANNELS.load(Ordering::SeqCst);
+    if let Some(channel) = get_input_channel()
+        && channel > channels
+    {
+        let message = format!(
+            "Input channel {} isn't on this device ({} channels), mixing all channels",
+            channel, channels
+        );
+        warn!("{}", message);
+    }
+}
+
+/// Replace a failed input stream with one on the system default device
+fn recover_input_stream(
+    ctx: &Arc<AppContext>,
+    host: &cpal::Host,
+    settings: &InputSettings,
+    input: &mut ActiveInput,
+    stream: &mut Option<Recorder>,
+) {
+    let failed_device = input.device_name.clone();
+    let was_default = input.is_default;
+
+    // Release the broken stream before opening a new one, ignoring its late errors
+    drop(stream.take());
+    STREAM_FAILED.store(false, Ordering::SeqCst);
+
+    match open_input_stream(ctx, host, settings, DeviceFallback::DefaultOnly) {
+        Ok(mut recovered) => {
+            *stream = recovered.stream.take();
+            let message = if was_default {
+                format!("Reopened the input device ({})", recovered.device_name)
+            } else {
+                format!(
+                    "'{}' stopped working, switched to the default input device ({})",
+                    failed_device, recovered.device_name
+                )
+            };
+            warn!("{}", message);
+            notify("Microphone changed", &message);
+            *input = recovered;
+        }
+        Err(e) => {
+            let message = format!(
+                "'{}' stopped working and no other input is available: {}",
+                failed_device, e
+            );
+            error!("{}", message);
+            notify("Microphone unavailable", &message);
+        }
+    }
+}
+
+/// Open the input stream again after the system slept, preferring the configured
+/// device; without one, recording is off until the event loop finds a microphone
+fn reopen_input_stream(
+    ctx: &Arc<AppContext>,
+    host: &cpal::Host,
+    settings: &InputSettings,
+    input: &mut Option<ActiveInput>,
+    stream: &mut Option<Recorder>,
+) {
+    // The old stream goes first, both may want the same device
+    drop(stream.take());
+    STREAM_FAILED.store(false, Ordering::SeqCst);
+
+    match open_input_stream(ctx, host, settings, DeviceFallback::Default) {
+        Ok(mut reopened) => {
+            *stream = reopened.stream.take();
+            info!("Reopened the input device ({})", reopened.device_name);
+            *input = Some(reopened);
+        }
+        Err(e) => {
+            warn!("{}, recording is off until a microphone is connected", e);
+            *lock(&DEVICE_INFO) = "none".to_string();
+            *input = None;
+        }
+    }
+}
+
+/// Flag a failed input stream so the event loop can switch devices
+fn on_stream_error(err: cpal::StreamError) {
+    error!("Stream error: {}", err);
+    events::emit_error(ErrorCategory::Audio, format!("Stream error: {}", err));
+    STREAM_FAILED.store(true, Ordering::SeqCst);
+    wake_event_loop();
+}
+
+enum QuitChoice {
+    FinishFirst,
+    QuitNow,
+    Cancel,
+}
+
+/// Decide what to do when Quit is clicked while recording or transcribing
+fn confirm_quit_while_busy(status: &AppStatus) -> QuitChoice {
+    if get_quit_behavior() == QuitBehavior::FinishFirst {
+        return QuitChoice::FinishFirst;
+    }
+
+    let activity = match status {
+        AppStatus::Recording { .. } => "A recording",
+        _ => "A transcription",
+    };
+
+    let result = rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Esponquen")
+        .set_description(format!(
+            "{} is in progress.\n\nFinish it first and type the text before quitting?\n\nYes: finish, then quit\nNo: quit now and discard it\nCancel: keep running",
+            activity
+        ))
+        .set_buttons(rfd::MessageButtons::YesNoCancel)
+        .show();
+
+    match result {
+        rfd::MessageDialogResult::Yes => QuitChoice::FinishFirst,
+        rfd::MessageDialogResult::No => QuitChoice::QuitNow,
+        _ => QuitChoice::Cancel,
+    }
+}
+
+/// Carries out commands on the event loop, the only place the status changes
+///
+/// Which commands are allowed is up to `transition`; this performs the effect it
+/// asks for and logs the commands it refuses. Transcriptions run on worker
+/// threads, which send `TranscriptionDone` back over `commands` when they finish.
+struct Coordinator {
+    ctx: Arc<AppContext>,
+    recognizer: SharedRecognizer,
+    commands: Sender<AppCommand>,
+    tray_icon: Option<TrayIcon>,
+}
+
+impl Coordinator {
+    fn handle(&self, command: AppCommand) {
+        match command {
+            AppCommand::SetHotkey(key) => {
+                self.ctx.set_hotkey(key);
+                HOTKEY_SEEN.store(false, Ordering::SeqCst);
+                HOTKEY_HELD.store(false, Ordering::SeqCst);
+                HOTKEY_UNSEEN_WARNED.store(false, Ordering::SeqCst);
+                diagln!("\nHotkey changed to {}", hotkey_name(key));
+                // The tooltip and the menu's check mark follow on the next iteration
+                REFRESH_TOOLTIP.store(true, Ordering::SeqCst);
+                wake_event_loop();
+            }
+            AppCommand::Snapshot => self.snapshot(),
+            // Refusals are logged by apply
+            command => drop(self.apply(&command)),
+        }
+    }
+
+    /// Move to the status a command leads to, or say why it can't
+    fn apply(&self, command: &AppCommand) -> Result<AppStatus, &'static str> {
+        let (status, effect) = transition(&self.ctx.status(), command, self.ctx.next_clip())
+            .map_err(|invalid| {
+                warn!("{}", invalid);
+                invalid.reason
+            })?;
+        match (&status, effect) {
+            (_, Effect::StartRecording) => {
+                let output = command.to_clipboard().then_some(OutputMode::Clipboard);
+                start_recording(&self.ctx, output)?;
+                warn_if_hotkey_unseen(&self.ctx);
+            }
+            (&AppStatus::Transcribing { clip }, Effect::Transcribe) => {
+                let ctx = Arc::clone(&self.ctx);
+                let recognizer = self.recognizer.clone();
+                let commands = self.commands.clone();
+                thread::spawn(move || finish_dictation(&ctx, &recognizer, &commands, clip));
+            }
+            (_, Effect::Discard) => {
+                let mut state = self.ctx.recording_state();
+                self.ctx.reset_recording(&mut state);
+            }
+            // LoadModel is up to reload_model, which has the settings
+            _ => {}
+        }
+        set_status(&self.ctx, status.clone(), self.tray_icon.as_ref());
+        Ok(status)
+    }
+
+    /// Load the model files again, e.g. after updating them on disk
+    /// (`ReloadModel`) or on the next provider (`CycleProvider`)
+    ///
+    /// Shows the loading status meanwhile; the current model stays if loading fails.
+    fn reload_model(&self, command: &AppCommand, engine: &EngineConfig) {
+        // Swapping it out would wait on the model forever
+        if TRANSCRIPTION_ABANDONED.load(Ordering::SeqCst) {
+            notify(
+                "Model not reloaded",
+                "It's stuck on an abandoned transcription. Restart Esponquen instead.",
+            );
+            return;
+        }
+        match self.apply(command) {
+            Ok(_) => reload_model(
+                &self.ctx,
+                engine,
+                &self.recognizer,
+                Some(self.commands.clone()),
+            ),
+            Err(reason) => diagln!("\n⚠ Model not reloaded: {}", reason),
+        }
+    }
+
+    /// Stop an active recording so it still gets transcribed and typed, e.g. on quit
+    fn finish_recording(&self) {
+        if self.ctx.is_recording() {
+            self.handle(AppCommand::StopRecording);
+        }
+    }
+
+    /// Preview the recording so far, one snapshot at a time on its own thread
+    fn snapshot(&self) {
+        if self.ctx.is_recording() && !SNAPSHOT_RUNNING.swap(true, Ordering::SeqCst) {
+            let ctx = Arc::clone(&self.ctx);
+            let recognizer = self.recognizer.clone();
+            thread::spawn(move || {
+                crash::contain("snapshot", || transcribe_snapshot(&ctx, &recognizer));
+                SNAPSHOT_RUNNING.store(false, Ordering::SeqCst);
+            });
+        }
+    }
+}
+
+/// Send a `ctl` command to the running instance, returning the process exit code
+fn run_ctl(action: &CtlAction) -> i32 {
+    match instance::send(&action.command()) {
+        Ok(response) => {
+            println!("{}", response);
+            if response["ok"] == true { 0 } else { 1 }
+        }
+        Err(e) => {
+            error!("{}", e);
+            1
+        }
+    }
+}
+
+/// The status and counters for `esponquen ctl status` and the metrics endpoint
+fn status_json(ctx: &AppContext) -> Value {
+    let stats = ctx.stats.snapshot();
+    let status = ctx.status();
+    let mut reply = json!({
+        "status": status.name(),
+        "hotkey": hotkey_name(ctx.hotkey()),
+        "provider": lock(&ctx.provider_name).clone(),
+        "cpu_fallback": CPU_FALLBACK.load(Ordering::SeqCst),
+        "transcriptions": stats.transcriptions,
+        "words": stats.words,
+        "audio_secs": stats.audio_secs,
+        "average_rtf": stats.average_rtf,
+        "uptime_secs": stats.uptime_secs,
+    });
+    if let AppStatus::Error { reason } = status {
+        reply["error"] = reason.into();
+    }
+    reply
+}
+
+/// Answer a request to the metrics endpoint
+fn metrics_page(ctx: &AppContext, path: &str) -> Option<metrics_server::Page> {
+    match path {
+        "/status" => Some(metrics_server::Page {
+            content_type: "application/json",
+            body: status_json(ctx).to_string(),
+        }),
+        "/metrics" => Some(metrics_server::Page {
+            content_type: "text/plain; version=0.0.4",
+            body: ctx.stats.snapshot().prometheus(
+                ctx.status().name(),
+                &lock(&ctx.provider_name),
+                CPU_FALLBACK.load(Ordering::SeqCst),
+            ),
+        }),
+        _ => None,
+    }
+}
+
+/// Ask the event loop to make `name` the dictation hotkey, for `esponquen ctl`
+/// and the settings window; refused when it's unknown or bound to another action
+fn request_hotkey(name: &str, commands: &Sender<AppCommand>) -> Result<RdevKey, String> {
+    let key = parse_hotkey(name)
+        .ok_or_else(|| format!("Unknown hotkey '{}', expected {}", name, HOTKEY_HINT))?;
+    if get_history_hotkey() == Some(key)
+        || get_quit_hotkey() == Some(key)
+        || get_provider_hotkey() == Some(key)
+        || get_snapshot_hotkey() == Some(key)
+        || get_clipboard_hotkey() == Some(key)
+    {
+        return Err(format!(
+            "{} is already bound to another action",
+            hotkey_name(key)
+        ));
+    }
+    commands
+        .send(AppCommand::SetHotkey(key))
+        .map_err(|_| "The app is shutting down".to_string())?;
+    Ok(key)
+}
+
+/// Answer a command sent by another instance (called from the instance thread)
+///
+/// Commands are checked here so the reply can say why one is refused, then
+/// carried out by the event loop.
+fn handle_control(
+    ctx: &AppContext,
+    command: ControlCommand,
+    commands: &Sender<AppCommand>,
+) -> Result<Value, String> {
+    let status = ctx.status();
+    let command = match command {
+        ControlCommand::Quit => {
+            diagln!("\nAsked to quit by another instance");
+            AppCommand::Quit
+        }
+        ControlCommand::Status => return Ok(status_json(ctx)),
+        ControlCommand::SetHotkey { key } => {
+            let key = request_hotkey(&key, commands)?;
+            return Ok(json!({ "hotkey": hotkey_name(key) }));
+        }
+        ControlCommand::StartRecording => AppCommand::StartRecording,
+        ControlCommand::Stop => AppCommand::StopRecording,
+        ControlCommand::Toggle => AppCommand::Toggle,
+    };
+    // Only checking, the clip id is the coordinator's to hand out
+    transition(&status, &command, 0).map_err(|invalid| invalid.reason.to_string())?;
+    commands
+        .send(command)
+        .map_err(|_| "The app is shutting down".to_string())?;
+    Ok(json!({}))
+}
+
+/// Answer a D-Bus method call (called from the D-Bus thread) like the same
+/// `esponquen ctl` command
+#[cfg(target_os = "linux")]
+fn handle_dbus(
+    ctx: &AppContext,
+    method: dbus::Method,
+    commands: &Sender<AppCommand>,
+) -> Result<String, String> {
+    let command = match method {
+        dbus::Method::StartRecording => ControlCommand::StartRecording,
+        dbus::Method::StopRecording => ControlCommand::Stop,
+        dbus::Method::Toggle => ControlCommand::Toggle,
+        dbus::Method::GetStatus => ControlCommand::Status,
+        dbus::Method::SetHotkey(key) => ControlCommand::SetHotkey { key },
+    };
+    handle_control(ctx, command, commands).map(|reply| reply.to_string())
+}
+
+/// Release audio, keyboard and tray resources before the process exits
+fn shutdown(stream: &mut Option<Recorder>, tray_icon: Option<&TrayIcon>) {
+    // Make the grab callback pass every event through from now on
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+
+    if let Some(stream) = stream.take() {
+        stream.pause();
+        drop(stream);
+    }
+
+    if let Some(tray_icon) = tray_icon {
+        tray_icon.set_visible(false).ok();
+    }
+
+    debug!("Shutting down");
+    std::io::stdout().flush().ok();
+    std::io::stderr().flush().ok();
+}
+
+/// Turn a hotkey press into a command for the event loop
+///
+/// Runs on the grab thread, so it only translates: it never records, transcribes
+/// or opens anything itself.
+fn handle_keyboard_event(
+    ctx: &AppContext,
+    event: Event,
+    commands: &Sender<AppCommand>,
+) -> Option<Event> {
+    // Once shutdown has started, let every key through untouched
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return Some(event);
+    }
+
+    if let EventType::KeyPress(key) = event.event_type {
+        // The dictation hotkey wins over the others if they're the same key
+        let command = if key == ctx.hotkey() {
+            HOTKEY_SEEN.store(true, Ordering::SeqCst);
+            // Held down, the key (or a pedal) repeats its press
+            if HOTKEY_HELD.swap(true, Ordering::SeqCst) {
+                return blocked(event, key);
+            }
+            match get_hotkey_mode() {
+                HotkeyMode::Toggle => AppCommand::Toggle,
+                HotkeyMode::Hold => AppCommand::StartRecording,
+            }
+        } else if get_history_hotkey() == Some(key) {
+            AppCommand::OpenHistory
+        } else if get_quit_hotkey() == Some(key) {
+            // Same as Ctrl+C; the only way to quit without a tray or console
+            AppCommand::Quit
+        } else if get_provider_hotkey() == Some(key) {
+            AppCommand::CycleProvider
+        } else if get_snapshot_hotkey() == Some(key) {
+            AppCommand::Snapshot
+        } else if get_clipboard_hotkey() == Some(key) {
+            if CLIPBOARD_HOTKEY_HELD.swap(true, Ordering::SeqCst) {
+                return blocked(event, key);
+            }
+            match get_hotkey_mode() {
+                HotkeyMode::Toggle => AppCommand::ToggleToClipboard,
+                HotkeyMode::Hold => AppCommand::StartRecordingToClipboard,
+            }
+        } else {
+            KEYS_SEEN.fetch_add(1, Ordering::Relaxed);
+            // So a pedal's code can be found to set it as the hotkey
+            if let RdevKey::Unknown(_) = key {
+                debug!("Key without a name pressed: {}", hotkey_name(key));
+            }
+            return Some(event);
+        };
+
+        commands.send(command).ok();
+        wake_event_loop();
+        return blocked(event, key);
+    }
+
+    // The release of a swallowed press is swallowed too
+    if let EventType::KeyRelease(key) = event.event_type {
+        let held = if key == ctx.hotkey() {
+            &HOTKEY_HELD
+        } else if get_clipboard_hotkey() == Some(key) {
+            &CLIPBOARD_HOTKEY_HELD
+        } else {
+            return Some(event);
+        };
+        if held.swap(false, Ordering::SeqCst) && get_hotkey_mode() == HotkeyMode::Hold {
+            commands.send(AppCommand::StopRecording).ok();
+            wake_event_loop();
+        }
+        return blocked(event, key);
+    }
+
+    // Return Some(event) to allow the key event to propagate
+    Some(event)
+}
+
+/// Warn once when a recording is started some other way (the tray, `ctl`) while
+/// the keyboard hook works but has never seen the hotkey
+///
+/// Laptops often turn F-keys into media keys in the firmware or the OS, and
+/// those presses never reach any keyboard hook, so the hotkey silently does nothing.
+fn warn_if_hotkey_unseen(ctx: &AppContext) {
+    if HOTKEY_SEEN.load(Ordering::SeqCst)
+        || KEYS_SEEN.load(Ordering::Relaxed) < HOTKEY_UNSEEN_MIN_KEYS
+        || HOTKEY_UNSEEN_WARNED.swap(true, Ordering::SeqCst)
+    {
+        return;
+    }
+
+    let key = hotkey_name(ctx.hotkey());
+    warn!(
+        "No {} press has reached the keyboard hook, though other keys have; it may be taken as a media key",
+        key
+    );
+    diagln!(
+        "\n⚠ {} hasn't been seen since Esponquen started, it may be taken as a media key",
+        key
+    );
+    notify(
+        "Hotkey not seen",
+        &format!(
+            "Esponquen hasn't seen {} since it started. If pressing it does nothing, your keyboard may use it as a media key: try Fn+{}, or pick another hotkey in Settings.",
+            key, key
+        ),
+    );
+}
+
+/// Swallow a hotkey press (None) unless the key shouldn't be blocked
+fn blocked(event: Event, key: RdevKey) -> Option<Event> {
+    if is_hotkey_blocked(key) {
+        None
+    } else {
+        Some(event)
+    }
+}
+
+/// Abandon a transcription that has lasted too long, returning the command to do it
+///
+/// Recordings are left to `recording_watchdog`, which only stops one that is
+/// both past its limit and getting no audio. A hung transcription can't be
+/// interrupted, so it's abandoned: its text isn't typed if it ever finishes.
+fn check_stuck_status(ctx: &AppContext) -> Option<AppCommand> {
+    let status = ctx.status();
+    if !matches!(status, AppStatus::Transcribing { .. }) {
+        return None;
+    }
+    let stuck_for = ctx.status_since().elapsed();
+    let limit = Duration::from_secs(get_transcription_timeout_secs());
+    if stuck_for <= limit {
+        return None;
+    }
+
+    warn!(
+        "Watchdog: {:?} for {:.0}s (limit {:.0}s), going back to ready\n{}",
+        status,
+        stuck_for.as_secs_f32(),
+        limit.as_secs_f32(),
+        state_snapshot(ctx)
+    );
+    TRANSCRIPTION_ABANDONED.store(true, Ordering::SeqCst);
+    events::emit_error(ErrorCategory::Model, "Transcription hung, abandoned");
+    notify(
+        "Transcription abandoned",
+        "The model didn't finish in time. If it happens again, restart Esponquen.",
+    );
+    Some(AppCommand::Abandon)
+}
+
+/// Discard recordings that are stuck on (e.g. a missed stop key after focus loss)
+///
+/// A recording is considered stuck when it has run longer than the maximum
+/// recording length plus a margin and the audio callback has stopped delivering
+/// data. This is a safety net; it doesn't transcribe anything.
+fn recording_watchdog(ctx: &AppContext, commands: &Sender<AppCommand>) {
+    loop {
+        thread::sleep(WATCHDOG_INTERVAL);
+
+        let mut state = ctx.recording_state();
+        let Some(started_at) = state.started_at.filter(|_| ctx.is_recording()) else {
+            continue;
+        };
+
+        let max_duration =
+            Duration::from_secs(ctx.settings().max_recording_secs) + WATCHDOG_RECORDING_MARGIN;
+        let elapsed = started_at.elapsed();
+        let since_data = state.last_data_at.unwrap_or(started_at).elapsed();
+
+        let Some(reason) = stuck_recording(elapsed, since_data, max_duration) else {
+            continue;
+        };
+
+        let discarded_samples = state.audio_data.len();
+        ctx.reset_recording(&mut state);
+        drop(state);
+
+        let message = format!(
+            "Watchdog stopped a stuck recording: {}; discarded {} samples",
+            reason, discarded_samples
+        );
+        warn!("{}", message);
+        events::emit_error(ErrorCategory::Audio, message);
+        notify(
+            "Recording stopped",
+            "It ran past the maximum recording length with no audio and was discarded.",
+        );
+        commands.send(AppCommand::Cancel).ok();
+    }
+}
+
+/// Why a recording is stuck, or None while it's running or only stalled, as a
+/// device hiccup or a Bluetooth mic reconnecting does for a while
+fn stuck_recording(
+    elapsed: Duration,
+    since_data: Duration,
+    max_duration: Duration,
+) -> Option<String> {
+    (elapsed > max_duration && since_data > WATCHDOG_STALL_TIMEOUT).then(|| {
+        format!(
+            "recording active for {:.0}s (limit {:.0}s) with no audio for {:.0}s",
+            elapsed.as_secs_f32(),
+            max_duration.as_secs_f32(),
+            since_data.as_secs_f32()
+        )
+    })
+}
+
+/// Start recording (the status is up to the caller)
+fn start_recording(ctx: &Arc<AppContext>, output: Option<OutputMode>) -> Result<(), &'static str> {
+    if let Some(issue) = lock(&MISSING_PERMISSIONS).first() {
+        warn!("{}, not recording: {}", issue.summary, issue.remedy);
+        events::emit_error(ErrorCategory::Audio, issue.summary.clone());
+        play_cue(Cue::Error);
+        notify("Permissions required", &issue.remedy);
+        return Err("Permissions required");
+    }
+
+    if !ctx.input_available.load(Ordering::SeqCst) {
+        warn!("No microphone, plug one in to record");
+        events::emit_error(ErrorCategory::Audio, "No microphone");
+        play_cue(Cue::Error);
+        notify("No microphone", "Plug one in to start dictating.");
+        return Err("No microphone");
+    }
+
+    let to_clipboard = output == Some(OutputMode::Clipboard);
+    ctx.begin_recording(None, output);
+    if is_restore_focus_enabled() {
+        spawn_focus_capture(ctx);
+    }
+
+    events::emit(AppEvent::RecordingStarted);
+    if to_clipboard {
+        diagln!(
+            "\n🔴 Recording for the clipboard... (Press {:?} to stop)",
+            ctx.hotkey()
+        );
+    } else {
+        diagln!("\n🔴 Recording... (Press {:?} to stop)", ctx.hotkey());
+    }
+    debug!("Recording started");
+    Ok(())
+}
+
+/// Remember where the text should go before anything can steal focus
+///
+/// On its own thread, as it runs xdotool or osascript on Linux and macOS and
+/// the recording has already started; a recording stopped before the window is
+/// known is typed into whichever window has focus then.
+fn spawn_focus_capture(ctx: &Arc<AppContext>) {
+    let Some(started_at) = ctx.recording_state().started_at else {
+        return;
+    };
+    let ctx = Arc::clone(ctx);
+    let spawned = thread::Builder::new()
+        .name("focus capture".to_string())
+        .spawn(move || match capture_foreground_window() {
+            Some(target) => ctx.set_focus_target(started_at, target),
+            None => diagln!("  ⚠ Can't capture the focused window on this platform"),
+        });
+    if let Err(e) = spawned {
+        warn!("Couldn't capture the focused window: {}", e);
+    }
+}
+
+/// Stop the current recording and transcribe it, surviving a panic in the model
+fn finish_dictation(
+    ctx: &AppContext,
+    recognizer: &SharedRecognizer,
+    commands: &Sender<AppCommand>,
+    clip: ClipId,
+) {
+    if crash::contain("transcription", || {
+        stop_recording_and_transcribe(ctx, recognizer, commands, clip)
+    })
+    .is_none()
+    {
+        events::emit_error(ErrorCategory::Model, "Transcription panicked");
+        play_cue(Cue::Error);
+        notify(
+            "Transcription failed",
+            "Something went wrong with that recording, see the log. Esponquen is still running.",
+        );
+        commands.send(AppCommand::TranscriptionDone { clip }).ok();
+    }
+}
+
+/// Stop the current recording, transcribe it and type the result, telling the
+/// event loop with `TranscriptionDone`
+fn stop_recording_and_transcribe(
+    ctx: &AppContext,
+    recognizer: &SharedRecognizer,
+    commands: &Sender<AppCommand>,
+    clip: ClipId,
+) {
+    // The audio buffer goes back to the pool once this transcription is done
+    let stopped = Instant::now();
+    let Some(TakenRecording {
+        audio: audio_data,
+        sample_rate,
+        focus_target,
+        output,
+        recorded_for,
+    }) = ctx.take_recording()
+    else {
+        commands.send(AppCommand::TranscriptionDone { clip }).ok();
+        return;
+    };
+    // Draining what the audio callback still had queued
+    let collected = stopped.elapsed();
+
+    diagln!("\n⏹ Recording stopped. Transcribing...");
+    debug!("Recording stopped");
+
+    events::emit(AppEvent::RecordingStopped {
+        duration_ms: recorded_for.as_millis() as u64,
+        peak_level: audio_data.iter().fold(0.0f32, |peak, s| peak.max(s.abs())),
+    });
+
+    if audio_data.is_empty() {
+        error!("No audio recorded");
+        events::emit_error(ErrorCategory::Audio, "No audio recorded");
+        play_cue(Cue::Error);
+        commands.send(AppCommand::TranscriptionDone { clip }).ok();
+        return;
+    }
+
+    let audio_secs = audio_data.len() as f32 / sample_rate as f32;
+    diagln!("  Audio length: {:.2} seconds", audio_secs);
+    debug!("Transcribing {:.2} seconds of audio", audio_secs);
+
+    // The model is still busy with an abandoned transcription, waiting on it could hang this thread too
+    if TRANSCRIPTION_ABANDONED.load(Ordering::SeqCst) {
+        error!("The model is still stuck on an abandoned transcription");
+        play_cue(Cue::Error);
+        notify(
+            "Model still stuck",
+            "The last recording couldn't be transcribed. Restart Esponquen to dictate again.",
+        );
+        commands.send(AppCommand::TranscriptionDone { clip }).ok();
+        return;
+    }
+
+    // Transcribe
+    events::emit(AppEvent::TranscriptionStarted {
+        audio_ms: (audio_secs * 1000.0) as u64,
+    });
+    let settings = ctx.settings();
+    let (text, mut timings) = transcribe_with(
+        &mut *recognizer.lease(),
+        sample_rate,
+        &audio_data,
+        &settings,
+    );
+    debug!(
+        "Preprocessing {:.2}s at {} Hz: collect {:.1}ms, resample {:.1}ms ({:?})",
+        audio_secs,
+        sample_rate,
+        collected.as_secs_f64() * 1000.0,
+        timings.resample.as_secs_f64() * 1000.0,
+        settings.resample_quality
+    );
+    if TRANSCRIPTION_ABANDONED.swap(false, Ordering::SeqCst) {
+        warn!("An abandoned transcription finished after all, adding it to the history untyped");
+        if !text.trim().is_empty() {
+            add_to_history(&text);
+        }
+        return;
+    }
+    let provider = lock(&ctx.provider_name).clone();
+    events::emit(AppEvent::TranscriptionFinished {
+        elapsed_ms: (timings.resample + timings.inference).as_millis() as u64,
+        provider: provider.clone(),
+        text_length: text.chars().count(),
+    });
+
+    info!(target: TRANSCRIPT, "✓ Transcription: {}", text);
+    if is_emit_text() {
+        emit_text(&text);
+    }
+    debug!("Transcription finished ({} chars)", text.len());
+
+    if !text.trim().is_empty() {
+        add_to_history(&text);
+        ctx.stats.record(&text, &timings);
+        #[cfg(target_os = "linux")]
+        if let Some(service) = lock(&DBUS).as_ref() {
+            let private = !esponquen::is_dbus_emit_text_enabled();
+            service.transcription_completed(if private { "" } else { &text });
+        }
+        if is_tooltip_preview_enabled() {
+            *lock(&LAST_TRANSCRIPTION) = Some(text.clone());
+        }
+
+        // Posted in the background, so a slow endpoint doesn't hold up typing
+        let webhook = get_webhook();
+        if let Some(webhook) = &webhook {
+            webhook::send(
+                webhook,
+                Payload::new(&text, Duration::from_secs_f32(audio_secs), &provider),
+            );
+        }
+        let webhook_only = webhook.is_some_and(|webhook| webhook.mode == WebhookMode::Instead);
+
+        // The clipboard hotkey's recordings skip the configured output
+        let output = output.unwrap_or_else(get_output_mode);
+        let typing_started = Instant::now();
+        let mut typed = true;
+        let mut held = false;
+        if webhook_only {
+            diagln!("🌐 Posting to the webhook instead of typing...");
+        } else if output == OutputMode::Clipboard {
+            diagln!("📋 Copying to the clipboard...");
+            typed = copy_to_clipboard(&text);
+        } else if let OutputMode::File(path) = &output {
+            diagln!("📝 Appending to {}...", path.display());
+            let mut file = FileOutput {
+                path: path.clone(),
+                header: get_file_header().render(
+                    SystemTime::now(),
+                    Duration::from_secs_f32(audio_secs),
+                    &provider,
+                ),
+            };
+            if let Err(e) = file.write(&text) {
+                error!("{}", e);
+                events::emit_error(ErrorCategory::Output, e.clone());
+                notify("Transcription not saved", &e);
+                typed = false;
+            }
+        } else {
+            if let Some(target) = focus_target
+                && !restore_focus(target)
+            {
+                diagln!(
+                    "  ⚠ Couldn't re-activate the original window, typing into the current one"
+                );
+            }
+            diagln!("⌨ Typing text...");
+            match type_text(&text) {
+                Ok(Typed::Now) => {}
+                Ok(Typed::Held) => held = true,
+                Err(_) => {
+                    typed = false;
+                    mark_untyped_in_history(&text);
+                }
+            }
+        }
+        timings.typing = Some(typing_started.elapsed());
+        report_timings(&timings);
+        if held {
+            // Not typed yet, and maybe never: the hold thread reports how it went
+            diagln!("⏸ Held until a window it can be typed into has focus\n");
+        } else {
+            play_cue(if typed { Cue::Success } else { Cue::Error });
+        }
+        if typed && !held {
+            events::emit(AppEvent::TypingFinished {
+                elapsed_ms: typing_started.elapsed().as_millis() as u64,
+                output: match output {
+                    _ if webhook_only => "webhook",
+                    OutputMode::Type => "type",
+                    OutputMode::Print => "print",
+                    OutputMode::File(_) => "file",
+                    OutputMode::Clipboard => "clipboard",
+                }
+                .to_string(),
+                text_length: text.chars().count(),
+            });
+            diagln!("✓ Done!\n");
+        }
+    } else {
+        report_timings(&timings);
+        error!("No text to type");
+        // on_empty = "beep" plays the same tone
+        if get_on_empty() != OnEmpty::Beep {
+            play_cue(Cue::Empty);
+        }
+        if handle_empty_transcription() {
+            let again = if output == Some(OutputMode::Clipboard) {
+                AppCommand::StartRecordingToClipboard
+            } else {
+                AppCommand::StartRecording
+            };
+            commands.send(again).ok();
+            return;
+        }
+    }
+
+    commands.send(AppCommand::TranscriptionDone { clip }).ok();
+    diagln!("Ready! Press {:?} to start recording...", ctx.hotkey());
+}
+
+/// React to an empty transcription, returning true if recording should restart
+fn handle_empty_transcription() -> bool {
+    match get_on_empty() {
+        OnEmpty::Ignore => false,
+        OnEmpty::Beep => {
+            let tone = get_sound_cues().empty;
+            beep(tone.frequency_hz, tone.duration);
+            false
+        }
+        OnEmpty::Notify => {
+            notify(
+                "No speech detected",
+                "Nothing was typed. Try again closer to the microphone.",
+            );
+            false
+        }
+        OnEmpty::Retry => {
+            diagln!("↻ Nothing recognized, recording again...");
+            true
+        }
+    }
+}
+
+/// Put a transcription on the clipboard and say so, returning whether it worked
+fn copy_to_clipboard(text: &str) -> bool {
+    match copy_text(text) {
+        Ok(()) => {
+            notify(
+                "Transcription copied",
+                &one_line_preview(text, TOOLTIP_PREVIEW_CHARS),
+            );
+            true
+        }
+        Err(e) => {
+            error!("Couldn't copy the transcription: {}", e);
+            events::emit_error(ErrorCategory::Output, e.clone());
+            notify(
+                "Transcription not copied",
+                "It's still in the history window.",
+            );
+            false
+        }
+    }
+}
+
+/// How far `type_text` got with a text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Typed {
+    /// Typed into the focused window, or copied where typing isn't available
+    Now,
+    /// Waiting for a window it can be typed into, see `hold_until_reachable`
+    Held,
+}
+
+/// Type text into the focused window, putting it on the clipboard if that fails
+///
+/// Returns why typing failed. A failure can come halfway through the text, so
+/// the whole text is copied.
+fn type_text(text: &str) -> Result<Typed, String> {
+    if is_dry_run() {
+        info!(target: TRANSCRIPT, "  (dry run) {}", text);
+        return Ok(Typed::Now);
+    }
+
+    // Checked now rather than when recording started, focus may have moved since
+    if let Some(app) = blocked_foreground_app() {
+        let message = format!("Typing blocked for {}", app.name());
+        match copy_text(text) {
+            Ok(()) => {
+                warn!("{}, copied the text to the clipboard instead", message);
+                notify(
+                    &format!("{}, copied instead", message),
+                    "Paste it where you wanted it.",
+                );
+            }
+            Err(e) => {
+                error!("{} and the text couldn't be copied: {}", message, e);
+                notify(&message, "It's still in the history window.");
+            }
+        }
+        return Err(message);
+    }
+
+    // Nothing to type with in this session, see log_environment
+    if capabilities().typing == Typing::Clipboard {
+        return if copy_to_clipboard(text) {
+            Ok(Typed::Now)
+        } else {
+            Err("Typing isn't available and the text couldn't be copied".to_string())
+        };
+    }
+
+    // The keys would be dropped without an error, as if they'd been typed
+    if let Some(unreachable) = unreachable_foreground() {
+        hold_until_reachable(text, &unreachable);
+        return Ok(Typed::Held);
+    }
+
+    let typed = TypeOutput::from_settings().write(text);
+    if let Err(e) = &typed {
+        copy_instead_of_typing(text, e);
+    }
+    typed.map(|()| Typed::Now)
+}
+
+/// Keep text that can't reach the focused window, typing it once a window it can
+/// reach has focus, or copying it after `output.unreachable_hold_secs`
+fn hold_until_reachable(text: &str, unreachable: &Unreachable) {
+    let reason = unreachable.describe();
+    let hold = Duration::from_secs(get_unreachable_hold_secs());
+    if hold.is_zero() {
+        copy_held_texts(&[text.to_string()], &reason);
+        return;
+    }
+
+    let mut held = lock(&HELD_TEXT);
+    held.push(text.to_string());
+    // Already waiting: this one is typed after the others
+    if held.len() > 1 {
+        return;
+    }
+    drop(held);
+
+    warn!(
+        "{}, holding the transcription until it can be typed",
+        reason
+    );
+    notify(
+        "Transcription held",
+        &format!(
+            "{}. It's typed once you're back in another window, or copied after {} s.",
+            reason,
+            hold.as_secs()
+        ),
+    );
+    thread::spawn(move || {
+        let deadline = Instant::now() + hold;
+        loop {
+            thread::sleep(HOLD_POLL_INTERVAL);
+            let still = unreachable_foreground();
+            if still.is_some() && Instant::now() < deadline {
+                continue;
+            }
+            // Anything held from here on starts its own wait
+            let texts = std::mem::take(&mut *lock(&HELD_TEXT));
+            match still {
+                None => {
+                    for text in texts {
+                        diagln!("\n⌨ Typing held text...");
+                        if let Ok(Typed::Now) = type_text(&text) {
+                            diagln!("✓ Done!\n");
+                        }
+                    }
+                }
+                Some(unreachable) => copy_held_texts(&texts, &unreachable.describe()),
+            }
+            return;
+        }
+    });
+}
+
+/// Put held transcriptions on the clipboard, a line each, instead of typing them
+fn copy_held_texts(texts: &[String], reason: &str) {
+    match copy_text(&texts.join("\n")) {
+        Ok(()) => {
+            warn!("{}, copied the transcription instead of typing it", reason);
+            notify(
+                "Transcription copied instead",
+                &format!("{}. Paste it where you wanted it.", reason),
+            );
+        }
+        Err(e) => {
+            error!("{} and the transcription couldn't be copied: {}", reason, e);
+            for text in texts {
+                mark_untyped_in_history(text);
+            }
+            notify(
+                "Transcription not typed",
+                "It's still in the history window.",
+            );
+        }
+    }
+}
+
+/// Put text that couldn't be typed on the clipboard so it isn't lost
+fn copy_instead_of_typing(text: &str, error: &str) {
+    events::emit_error(ErrorCategory::Typing, error);
+    // Typing usually fails for want of a permission, say which one if we can tell
+    let hint = check_input_permissions()
+        .into_iter()
+        .next()
+        .map(|issue| issue.remedy);
+
+    match copy_text(text) {
+        Ok(()) => {
+            warn!(
+                "Couldn't type the text ({}), copied it to the clipboard instead",
+                error
+            );
+            notify(
+                "Couldn't type into the focused window — text copied to clipboard",
+                &hint.unwrap_or_else(|| "Paste it where you wanted it typed.".to_string()),
+            );
+        }
+        Err(e) => {
+            error!("Couldn't type the text ({}) or copy it: {}", error, e);
+            let mut body = "It's still in the history window.".to_string();
+            if let Some(hint) = hint {
+                body = format!("{}\n{}", hint, body);
+            }
+            notify("Transcription not typed", &body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use esponquen::testing::MockTranscriber;
+    use esponquen::{set_clipboard_hotkey, set_hotkey_mode};
+    use std::sync::MutexGuard;
+
+    /// A context with a microphone, as if an input stream had opened
+    fn context_with_input() -> Arc<AppContext> {
+        let ctx = Arc::new(AppContext::new());
+        ctx.input_available.store(true, Ordering::SeqCst);
+        ctx
+    }
+
+    // Tests pressing the hotkey share its mode and held flag
+    static HOTKEY_STATE: Mutex<()> = Mutex::new(());
+
+    /// The hotkey and output globals, held for one test and put back when it
+    /// ends, even if an assert failed halfway
+    struct HotkeyGlobals {
+        _lock: MutexGuard<'static, ()>,
+        hotkey_mode: HotkeyMode,
+        clipboard_hotkey: Option<RdevKey>,
+        output_mode: OutputMode,
+    }
+
+    fn hotkey_globals() -> HotkeyGlobals {
+        HotkeyGlobals {
+            _lock: lock(&HOTKEY_STATE),
+            hotkey_mode: get_hotkey_mode(),
+            clipboard_hotkey: get_clipboard_hotkey(),
+            output_mode: get_output_mode(),
+        }
+    }
+
+    impl Drop for HotkeyGlobals {
+        fn drop(&mut self) {
+            set_hotkey_mode(self.hotkey_mode);
+            set_clipboard_hotkey(self.clipboard_hotkey);
+            set_output_mode(self.output_mode.clone());
+            HOTKEY_HELD.store(false, Ordering::SeqCst);
+            CLIPBOARD_HOTKEY_HELD.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn key_press(key: RdevKey) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type: EventType::KeyPress(key),
+        }
+    }
+
+    fn key_release(key: RdevKey) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type: EventType::KeyRelease(key),
+        }
+    }
+
+    #[test]
+    fn a_recording_is_stuck_only_past_the_limit_without_audio() {
+        let limit = Duration::from_secs(630);
+        let secs = Duration::from_secs;
+
+        // A stalled microphone during a normal dictation keeps the recording
+        assert_eq!(stuck_recording(secs(20), secs(15), limit), None);
+        // as does audio still arriving past the limit
+        assert_eq!(stuck_recording(secs(700), secs(1), limit), None);
+
+        let reason = stuck_recording(secs(700), secs(15), limit).unwrap();
+        assert!(reason.contains("no audio for 15s"), "{}", reason);
+    }
+
+    #[test]
+    fn a_stopped_keyboard_grab_is_restarted_with_a_doubling_delay_then_given_up() {
+        let delays: Vec<_> = (0..GRAB_MAX_RESTARTS)
+            .map(|restarts| grab_restart_delay(restarts).unwrap().as_secs())
+            .collect();
+        assert_eq!(delays, [2, 4, 8, 16, 32]);
+
+        assert_eq!(grab_restart_delay(GRAB_MAX_RESTARTS), None);
+        assert_eq!(grab_restart_delay(GRAB_MAX_RESTARTS + 1), None);
+    }
+
+    #[test]
+    fn an_idle_app_follows_the_permissions_and_the_keyboard_grab() {
+        let unavailable = "Hotkeys unavailable".to_string();
+        let failed = AppStatus::Error {
+            reason: unavailable.clone(),
+        };
+        let ready = AppStatus::WaitingForHotkey;
+
+        assert_eq!(status_upkeep(&ready, false, None), None);
+        assert_eq!(status_upkeep(&ready, true, None), Some(AppCommand::Disable));
+        assert_eq!(
+            status_upkeep(&ready, false, Some(&unavailable)),
+            Some(AppCommand::Fail {
+                reason: unavailable.clone()
+            })
+        );
+        assert_eq!(status_upkeep(&failed, false, Some(&unavailable)), None);
+        assert_eq!(
+            status_upkeep(&failed, false, None),
+            Some(AppCommand::Recover)
+        );
+        assert_eq!(status_upkeep(&AppStatus::Disabled, true, None), None);
+        assert_eq!(
+            status_upkeep(&AppStatus::Disabled, false, None),
+            Some(AppCommand::Recover)
+        );
+
+        // A dictation in flight finishes first
+        let recording = AppStatus::Recording { clip: 1 };
+        assert_eq!(status_upkeep(&recording, false, Some(&unavailable)), None);
+    }
+
+    #[test]
+    fn recording_keeps_working_after_a_panic_mid_recording() {
+        let ctx = context_with_input();
+        start_recording(&ctx, None).unwrap();
+        ctx.recording_state().audio_data.extend([0.1; 160]);
+
+        // A thread panics while holding the lock, as a crashing audio callback would
+        let panicking = Arc::clone(&ctx);
+        let result = thread::spawn(move || {
+            let _state = panicking.recording_state();
+            panic!("poisoning the recording state on purpose");
+        })
+        .join();
+        assert!(result.is_err());
+
+        // The next lock recovers it, dropping the half-finished recording
+        {
+            let state = ctx.recording_state();
+            assert!(!ctx.is_recording());
+            assert!(state.audio_data.is_empty());
+        }
+
+        // and the hotkey starts a new recording as usual
+        assert_eq!(start_recording(&ctx, None), Ok(()));
+        assert!(ctx.is_recording());
+    }
+
+    #[test]
+    fn hotkey_records_then_transcribes_with_the_recognizer() {
+        let _globals = hotkey_globals();
+        set_output_mode(OutputMode::Print);
+        let ctx = context_with_input();
+        let transcriber = MockTranscriber::new("hello world");
+        let heard = transcriber.heard();
+        let recognizer = transcriber.shared();
+        ctx.set_status(AppStatus::WaitingForHotkey);
+        let (commands_tx, commands_rx) = channel();
+        let coordinator = Coordinator {
+            ctx: Arc::clone(&ctx),
+            recognizer,
+            commands: commands_tx.clone(),
+            tray_icon: None,
+        };
+
+        // First press: the key is swallowed and becomes a command, which starts recording
+        let press = key_press(ctx.hotkey());
+        let release = key_release(ctx.hotkey());
+        assert!(handle_keyboard_event(&ctx, press.clone(), &commands_tx).is_none());
+        assert!(handle_keyboard_event(&ctx, release.clone(), &commands_tx).is_none());
+        assert!(!ctx.is_recording());
+        let command = commands_rx.try_recv().unwrap();
+        assert_eq!(command, AppCommand::Toggle);
+        assert_eq!(
+            coordinator.apply(&command),
+            Ok(AppStatus::Recording { clip: 1 })
+        );
+        assert!(ctx.is_recording());
+
+        // The audio callback delivers the hotkey's click, which is dropped, then some sound
+        let click = vec![1.0f32; ctx.settings().start_cooldown_ms as usize * 16];
+        ctx.ring.push(click);
+        let samples: Vec<f32> = (0..1600).map(|i| (i as f32 / 100.0).sin()).collect();
+        ctx.ring.push(samples.iter().copied());
+
+        // Second press: the recording goes to a worker and the recognizer
+        assert!(handle_keyboard_event(&ctx, press, &commands_tx).is_none());
+        assert!(handle_keyboard_event(&ctx, release, &commands_tx).is_none());
+        let command = commands_rx.try_recv().unwrap();
+        assert_eq!(
+            coordinator.apply(&command),
+            Ok(AppStatus::Transcribing { clip: 1 })
+        );
+
+        // which reports back when it's done, and the app is ready again
+        let done = commands_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(done, AppCommand::TranscriptionDone { clip: 1 });
+        assert_eq!(coordinator.apply(&done), Ok(AppStatus::WaitingForHotkey));
+        assert!(!ctx.is_recording());
+        assert_eq!(*lock(&heard), [samples]);
+        assert!(ctx.recording_state().audio_data.is_empty());
+    }
+
+    #[test]
+    fn clipboard_hotkey_records_for_the_clipboard_only() {
+        let _globals = hotkey_globals();
+        set_hotkey_mode(HotkeyMode::Toggle);
+        let ctx = context_with_input();
+        ctx.set_status(AppStatus::WaitingForHotkey);
+        let (commands_tx, commands_rx) = channel();
+        let coordinator = Coordinator {
+            ctx: Arc::clone(&ctx),
+            recognizer: MockTranscriber::new("").shared(),
+            commands: commands_tx.clone(),
+            tray_icon: None,
+        };
+        set_clipboard_hotkey(Some(RdevKey::F8));
+
+        assert!(handle_keyboard_event(&ctx, key_press(RdevKey::F8), &commands_tx).is_none());
+        assert!(handle_keyboard_event(&ctx, key_release(RdevKey::F8), &commands_tx).is_none());
+        let command = commands_rx.try_recv().unwrap();
+        assert_eq!(command, AppCommand::ToggleToClipboard);
+        coordinator.apply(&command).unwrap();
+        assert_eq!(ctx.recording_state().output, Some(OutputMode::Clipboard));
+
+        // The next recording of the main hotkey goes to the configured output again
+        coordinator.apply(&AppCommand::Cancel).unwrap();
+        coordinator.apply(&AppCommand::Toggle).unwrap();
+        assert_eq!(ctx.recording_state().output, None);
+    }
+
+    #[test]
+    fn a_held_pedal_records_from_press_to_release() {
+        let _globals = hotkey_globals();
+        set_hotkey_mode(HotkeyMode::Hold);
+        let ctx = context_with_input();
+        let (commands_tx, commands_rx) = channel();
+
+        // Pedals are commonly set to F13-F24, which rdev has no names for
+        let pedal = parse_hotkey("F13").unwrap();
+        ctx.set_hotkey(pedal);
+        assert!(handle_keyboard_event(&ctx, key_press(pedal), &commands_tx).is_none());
+        assert_eq!(commands_rx.try_recv(), Ok(AppCommand::StartRecording));
+
+        // Key repeat while the pedal is held down changes nothing
+        for _ in 0..3 {
+            assert!(handle_keyboard_event(&ctx, key_press(pedal), &commands_tx).is_none());
+        }
+        assert!(commands_rx.try_recv().is_err());
+
+        assert!(handle_keyboard_event(&ctx, key_release(pedal), &commands_tx).is_none());
+        assert_eq!(commands_rx.try_recv(), Ok(AppCommand::StopRecording));
+
+        // or Page Down, the other usual choice, which is still typed in other apps
+        ctx.set_hotkey(RdevKey::PageDown);
+        let press = key_press(RdevKey::PageDown);
+        let release = key_release(RdevKey::PageDown);
+        assert_eq!(
+            handle_keyboard_event(&ctx, press.clone(), &commands_tx),
+            Some(press)
+        );
+        assert_eq!(
+            handle_keyboard_event(&ctx, release.clone(), &commands_tx),
+            Some(release)
+        );
+        assert_eq!(
+            commands_rx.try_iter().collect::<Vec<_>>(),
+            [AppCommand::StartRecording, AppCommand::StopRecording]
+        );
+    }
+
+    #[test]
+    fn a_new_hotkey_goes_to_the_event_loop_unless_taken() {
+        let _globals = hotkey_globals();
+        let (commands_tx, commands_rx) = channel();
+
+        assert_eq!(request_hotkey("F7", &commands_tx), Ok(RdevKey::F7));
+        assert_eq!(
+            commands_rx.try_recv(),
+            Ok(AppCommand::SetHotkey(RdevKey::F7))
+        );
+
+        assert!(request_hotkey("F99", &commands_tx).is_err());
+        set_clipboard_hotkey(Some(RdevKey::F8));
+        assert_eq!(
+            request_hotkey("F8", &commands_tx),
+            Err("F8 is already bound to another action".to_string())
+        );
+        assert!(commands_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn other_keys_pass_through_untouched() {
+        let _globals = hotkey_globals();
+        let ctx = context_with_input();
+        ctx.set_hotkey(RdevKey::F9);
+        let (commands_tx, commands_rx) = channel();
+
+        let press = key_press(RdevKey::KeyA);
+        assert_eq!(
+            handle_keyboard_event(&ctx, press.clone(), &commands_tx),
+            Some(press)
+        );
+        assert!(commands_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn refused_commands_leave_the_status_alone() {
+        let _globals = hotkey_globals();
+        let ctx = context_with_input();
+        ctx.set_status(AppStatus::WaitingForHotkey);
+        let (commands_tx, _commands_rx) = channel();
+        let coordinator = Coordinator {
+            ctx: Arc::clone(&ctx),
+            recognizer: MockTranscriber::new("").shared(),
+            commands: commands_tx,
+            tray_icon: None,
+        };
+
+        assert_eq!(
+            coordinator.apply(&AppCommand::StopRecording),
+            Err("Not recording")
+        );
+        assert_eq!(ctx.status(), AppStatus::WaitingForHotkey);
+
+        ctx.set_status(AppStatus::LoadingModel);
+        assert_eq!(
+            coordinator.apply(&AppCommand::Toggle),
+            Err("The model is still loading")
+        );
+        assert!(!ctx.is_recording());
+    }
 }
@@ -0,0 +1,76 @@
+// Opt-in HTTP endpoint for monitoring, on localhost only.
+//
+// Serves `GET /status` (the JSON `esponquen ctl status` prints) and
+// `GET /metrics` (the same counters in the Prometheus text format). It's a
+// minimal HTTP/1.0 server: one request per connection, answered and closed, no
+// request body. Nothing is written through it, so there's no token as on the
+// control channel; anyone on the machine can read the counters.
+
+use log::{info, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+// A client that doesn't finish its request line within this is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A response body and its content type
+pub struct Page {
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+/// Serve on 127.0.0.1:`port` from a new thread, `route` answering each path it knows
+pub fn spawn(
+    port: u16,
+    route: impl Fn(&str) -> Option<Page> + Send + 'static,
+) -> Result<(), String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| format!("Can't serve metrics on port {}: {}", port, e))?;
+    info!("Serving /status and /metrics on http://127.0.0.1:{}", port);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = answer(stream, &route) {
+                warn!("Metrics request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn answer(stream: TcpStream, route: &dyn Fn(&str) -> Option<Page>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, page) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => match route(path) {
+            Some(page) => ("200 OK", page),
+            None => ("404 Not Found", text_page("Not found\n")),
+        },
+        _ => (
+            "405 Method Not Allowed",
+            text_page("Only GET is supported\n"),
+        ),
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        page.content_type,
+        page.body.len(),
+        page.body
+    )?;
+    stream.flush()
+}
+
+fn text_page(body: &str) -> Page {
+    Page {
+        content_type: "text/plain; charset=utf-8",
+        body: body.to_string(),
+    }
+}
@@ -0,0 +1,154 @@
+// Model files: checking for them and downloading them
+
+use std::io::Read;
+use std::path::Path;
+
+/// Files that make up a Parakeet (NeMo transducer) model
+pub const MODEL_FILES: [&str; 4] = [
+    "encoder.int8.onnx",
+    "decoder.int8.onnx",
+    "joiner.int8.onnx",
+    "tokens.txt",
+];
+
+/// Archive with the recommended multilingual Parakeet model
+pub const MODEL_DOWNLOAD_URL: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/asr-models/sherpa-onnx-nemo-parakeet-tdt-0.6b-v3-int8.tar.bz2";
+
+/// Names of the model files missing from `dir`
+pub fn missing_model_files(dir: &Path) -> Vec<&'static str> {
+    MODEL_FILES
+        .iter()
+        .filter(|file| !dir.join(file).is_file())
+        .copied()
+        .collect()
+}
+
+/// Download the recommended model archive and extract the model files into `dir`
+///
+/// `progress` is called with the number of bytes downloaded so far.
+pub fn download_model(dir: &Path, mut progress: impl FnMut(u64)) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let response = ureq::get(MODEL_DOWNLOAD_URL)
+        .call()
+        .map_err(|e| format!("Download failed: {}", e))?;
+
+    let reader = ProgressReader {
+        inner: response.into_reader(),
+        read: 0,
+        progress: &mut progress,
+    };
+    let mut archive = tar::Archive::new(bzip2::read::BzDecoder::new(reader));
+
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read model archive: {}", e))?;
+
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read model archive: {}", e))?;
+        let file_name = entry.path().ok().and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        });
+
+        // The archive has a top-level directory; keep only the model files
+        if let Some(name) = file_name.filter(|name| MODEL_FILES.contains(&name.as_str())) {
+            entry
+                .unpack(dir.join(&name))
+                .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        }
+    }
+
+    match missing_model_files(dir).as_slice() {
+        [] => Ok(()),
+        missing => Err(format!("Archive did not contain: {}", missing.join(", "))),
+    }
+}
+
+/// Name of the ONNX Runtime library the recognizer loads at runtime
+#[cfg(target_os = "windows")]
+const ONNXRUNTIME_LIBRARY: &str = "onnxruntime.dll";
+#[cfg(target_os = "macos")]
+const ONNXRUNTIME_LIBRARY: &str = "libonnxruntime.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const ONNXRUNTIME_LIBRARY: &str = "libonnxruntime.so";
+
+/// Turn a recognizer initialization error into an actionable message, if it's a known failure
+///
+/// `files` are the model files the recognizer was given and `provider` the execution
+/// provider it was tried with (None for the CPU). The raw error is left to the caller to log.
+pub fn describe_init_error(error: &str, files: &[&Path], provider: Option<&str>) -> Option<String> {
+    let lower = error.to_lowercase();
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+
+    let missing: Vec<String> = files
+        .iter()
+        .filter(|file| !file.is_file())
+        .map(|file| file.display().to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Some(format!(
+            "Model file not found: {} — check model.dir or run the setup again",
+            missing.join(", ")
+        ));
+    }
+
+    if mentions(&["onnxruntime", "ort_"])
+        && mentions(&[
+            "cannot open shared object",
+            "not found",
+            "loadlibrary",
+            "no such file",
+            "image not found",
+            "error loading",
+        ])
+    {
+        return Some(format!(
+            "ONNX Runtime library not found — ensure {} is next to the executable",
+            ONNXRUNTIME_LIBRARY
+        ));
+    }
+
+    if let Some(provider) = provider
+        && mentions(&[
+            "provider", "cuda", "cudnn", "cublas", "directml", "coreml", "tensorrt",
+        ])
+    {
+        return Some(format!(
+            "The {} provider can't be used with this build or system — install its runtime \
+             (e.g. CUDA and cuDNN for cuda) or list \"cpu\" in model.providers",
+            provider
+        ));
+    }
+
+    if mentions(&[
+        "protobuf",
+        "invalid_protobuf",
+        "invalid model",
+        "modelproto",
+        "corrupt",
+        "unexpected end of file",
+    ]) {
+        return Some(
+            "A model file is damaged or isn't an ONNX model — download the model again".to_string(),
+        );
+    }
+
+    None
+}
+
+struct ProgressReader<'a, R, F: FnMut(u64)> {
+    inner: R,
+    read: u64,
+    progress: &'a mut F,
+}
+
+impl<R: Read, F: FnMut(u64)> Read for ProgressReader<'_, R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.read += count as u64;
+        (self.progress)(self.read);
+        Ok(count)
+    }
+}
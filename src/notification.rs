@@ -0,0 +1,24 @@
+// Desktop notifications
+
+use crate::environment::capabilities;
+use log::{info, warn};
+
+/// Show a desktop notification, logging the failure if it can't be displayed
+///
+/// Without a notification service it only goes to the log.
+pub fn notify(summary: &str, body: &str) {
+    if !capabilities().notifications {
+        info!("{}: {}", summary, body);
+        return;
+    }
+
+    let result = notify_rust::Notification::new()
+        .appname("Esponquen")
+        .summary(summary)
+        .body(body)
+        .show();
+
+    if let Err(e) = result {
+        warn!("Failed to show notification: {}", e);
+    }
+}
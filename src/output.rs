@@ -0,0 +1,129 @@
+// Where transcribed text goes: typed into the focused window, put on the
+// clipboard or appended to a file. The app picks one from `output.mode`;
+// embedders can hand a `DictationEngine` any `Output`.
+//
+// Typing needs the `typing` feature; without it `TypeOutput` refuses every text.
+// On Wayland it's done by an external tool instead of enigo, see `session`.
+
+use crate::clipboard::copy_text;
+#[cfg(feature = "typing")]
+use crate::session::{TypingTool, typing_tools};
+use crate::{get_typing_delay_ms, is_typing_probe_enabled};
+#[cfg(feature = "typing")]
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::io::Write;
+use std::path::PathBuf;
+#[cfg(feature = "typing")]
+use std::thread;
+use std::time::Duration;
+
+/// Receives the text of each dictation
+pub trait Output: Send {
+    fn write(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// Type into the focused window with simulated key presses
+pub struct TypeOutput {
+    /// Wait before typing, for focus to settle on the right window
+    pub delay: Duration,
+    /// Check the window accepts input before typing, see `output.typing_probe`
+    pub probe: bool,
+}
+
+impl TypeOutput {
+    /// With the delay and probe of the current settings
+    pub fn from_settings() -> Self {
+        TypeOutput {
+            delay: Duration::from_millis(get_typing_delay_ms()),
+            probe: is_typing_probe_enabled(),
+        }
+    }
+}
+
+#[cfg(feature = "typing")]
+impl Output for TypeOutput {
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        thread::sleep(self.delay);
+
+        if !typing_tools().is_empty() {
+            return type_with_tools(typing_tools(), text);
+        }
+
+        let mut enigo = new_enigo()?;
+        // Blocked input (elevated window, secure desktop, anti-cheat) refuses even
+        // a no-op, where typing itself may fail only after part of the text
+        if self.probe {
+            enigo
+                .key(Key::Shift, Direction::Release)
+                .map_err(|e| format!("the focused window doesn't accept input ({})", e))?;
+        }
+        enigo.text(text).map_err(|e| e.to_string())
+    }
+}
+
+/// Type `text` with the first of `tools` that manages to
+#[cfg(feature = "typing")]
+fn type_with_tools(tools: &[TypingTool], text: &str) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for tool in tools {
+        let output = std::process::Command::new(tool.program())
+            .args(tool.args(text))
+            .output();
+        match output {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => errors.push(format!(
+                "{} failed: {}",
+                tool.program(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => errors.push(format!("{} failed: {}", tool.program(), e)),
+        }
+    }
+    Err(errors.join("; "))
+}
+
+#[cfg(not(feature = "typing"))]
+impl Output for TypeOutput {
+    fn write(&mut self, _text: &str) -> Result<(), String> {
+        Err("built without the typing feature".to_string())
+    }
+}
+
+/// Put the text on the clipboard, replacing what was there
+pub struct ClipboardOutput;
+
+impl Output for ClipboardOutput {
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        copy_text(text)
+    }
+}
+
+/// Append each text as a line to a file, creating it if needed
+pub struct FileOutput {
+    pub path: PathBuf,
+    /// Line written before the text, see `text::FileHeader`
+    pub header: Option<String>,
+}
+
+impl Output for FileOutput {
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        let path = &self.path;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let entry = match &self.header {
+            Some(header) => format!("{}\n{}", header, text.trim()),
+            None => text.trim().to_string(),
+        };
+        writeln!(file, "{}", entry)
+            .map_err(|e| format!("Failed to write to {}: {}", path.display(), e))
+    }
+}
+
+/// Set up simulated keyboard input
+#[cfg(feature = "typing")]
+pub fn new_enigo() -> Result<Enigo, String> {
+    Enigo::new(&Settings::default()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,147 @@
+// Small always-on-top status dot, for when the tray icon is out of sight (e.g.
+// in fullscreen apps). The window is frameless, click-through and never takes
+// focus; it's anchored to a corner of the primary display.
+
+use esponquen::config::OverlayCorner;
+use log::warn;
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event_loop::ActiveEventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{Window, WindowLevel};
+
+// Size of the dot in logical pixels
+const OVERLAY_SIZE: f64 = 16.0;
+
+// Distance from the screen edges in logical pixels
+const OVERLAY_MARGIN: f64 = 24.0;
+
+pub struct Overlay {
+    window: Rc<Window>,
+    surface: softbuffer::Surface<Rc<Window>, Rc<Window>>,
+    color: Option<[u8; 3]>,
+}
+
+impl Overlay {
+    /// Create the overlay window, hidden until a color is set
+    pub fn new(event_loop: &ActiveEventLoop, corner: OverlayCorner) -> Result<Overlay, String> {
+        let monitor = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next());
+        let scale = monitor
+            .as_ref()
+            .map_or(1.0, |monitor| monitor.scale_factor());
+        let size = ((OVERLAY_SIZE * scale).round() as u32).max(1);
+
+        let mut attributes = Window::default_attributes()
+            .with_title("Esponquen status")
+            .with_decorations(false)
+            .with_resizable(false)
+            .with_window_level(WindowLevel::AlwaysOnTop)
+            .with_inner_size(PhysicalSize::new(size, size))
+            .with_visible(false)
+            .with_active(false);
+
+        // Without a monitor (e.g. Wayland) the compositor places the window
+        if let Some(monitor) = &monitor {
+            attributes = attributes.with_position(corner_position(monitor, corner, size));
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            use winit::platform::windows::WindowAttributesExtWindows;
+            attributes = attributes.with_skip_taskbar(true);
+        }
+
+        let window = Rc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(|e| e.to_string())?,
+        );
+
+        // Let clicks go through to whatever is underneath
+        if let Err(e) = window.set_cursor_hittest(false) {
+            warn!("Status overlay can't be made click-through: {}", e);
+        }
+
+        let context = softbuffer::Context::new(Rc::clone(&window)).map_err(|e| e.to_string())?;
+        let mut surface =
+            softbuffer::Surface::new(&context, Rc::clone(&window)).map_err(|e| e.to_string())?;
+        let side = NonZeroU32::new(size).expect("Overlay size is at least 1");
+        surface.resize(side, side).map_err(|e| e.to_string())?;
+
+        Ok(Overlay {
+            window,
+            surface,
+            color: None,
+        })
+    }
+
+    /// Show the dot in `color`, or hide it with None
+    pub fn set_color(&mut self, color: Option<[u8; 3]>) {
+        if color == self.color {
+            return;
+        }
+
+        self.color = color;
+        if color.is_some() {
+            self.window.set_visible(true);
+            self.redraw();
+        } else {
+            self.window.set_visible(false);
+        }
+    }
+
+    /// Move the dot to another corner of the display it's on
+    pub fn set_corner(&self, corner: OverlayCorner) {
+        let Some(monitor) = self.window.current_monitor() else {
+            return;
+        };
+        let size = self.window.inner_size().width;
+        self.window
+            .set_outer_position(corner_position(&monitor, corner, size));
+    }
+
+    /// Paint the dot, also called when the platform asks for a redraw
+    pub fn redraw(&mut self) {
+        let Some([r, g, b]) = self.color else {
+            return;
+        };
+
+        // softbuffer pixels are 0RGB
+        let pixel = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        match self.surface.buffer_mut() {
+            Ok(mut buffer) => {
+                buffer.fill(pixel);
+                buffer.present().ok();
+            }
+            Err(e) => warn!("Failed to draw status overlay: {}", e),
+        }
+    }
+}
+
+/// Top-left position of the dot in the given corner of `monitor`
+fn corner_position(
+    monitor: &MonitorHandle,
+    corner: OverlayCorner,
+    size: u32,
+) -> PhysicalPosition<i32> {
+    let origin = monitor.position();
+    let screen = monitor.size();
+    let margin = (OVERLAY_MARGIN * monitor.scale_factor()).round() as i32;
+    let size = size as i32;
+
+    let x = match corner {
+        OverlayCorner::TopLeft | OverlayCorner::BottomLeft => margin,
+        OverlayCorner::TopRight | OverlayCorner::BottomRight => screen.width as i32 - size - margin,
+    };
+    let y = match corner {
+        OverlayCorner::TopLeft | OverlayCorner::TopRight => margin,
+        OverlayCorner::BottomLeft | OverlayCorner::BottomRight => {
+            screen.height as i32 - size - margin
+        }
+    };
+
+    PhysicalPosition::new(origin.x + x, origin.y + y)
+}
@@ -0,0 +1,184 @@
+// Where the app reads and writes its files.
+//
+// Normally the config lives in the platform's config directory, the log in the
+// data directory, and relative paths (model, icons) resolve against the working
+// directory. In portable mode, enabled with `--portable` or a `portable.flag`
+// file next to the executable, everything lives in subdirectories of the
+// executable's folder so the app can run from a USB stick without touching
+// AppData or XDG directories.
+
+use crate::LOG_FILE_PATH;
+use crate::config::CONFIG_FILE_NAME;
+use std::path::{Path, PathBuf};
+
+/// File next to the executable that turns on portable mode
+pub const PORTABLE_FLAG_FILE: &str = "portable.flag";
+
+/// Every location the app writes to, see the module docs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppPaths {
+    /// Whether everything lives next to the executable
+    pub portable: bool,
+    /// Config file unless --config or ESPONQUEN_CONFIG name another one
+    pub config_file: Option<PathBuf>,
+    /// Log file, rotated next to itself
+    pub log_file: PathBuf,
+    /// Saved history and recordings
+    pub data_dir: Option<PathBuf>,
+    /// Where diagnostics bundles are exported
+    pub export_dir: PathBuf,
+    /// Directory relative paths resolve against, None for the working directory
+    pub base_dir: Option<PathBuf>,
+}
+
+impl Default for AppPaths {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl AppPaths {
+    /// Platform config and data directories, relative paths left as they are
+    pub fn standard() -> Self {
+        AppPaths {
+            portable: false,
+            config_file: dirs::config_dir().map(|dir| dir.join("esponquen").join(CONFIG_FILE_NAME)),
+            log_file: dirs::data_dir()
+                .map(|dir| dir.join("esponquen").join("logs").join("esponquen.log"))
+                .unwrap_or_else(|| PathBuf::from(LOG_FILE_PATH)),
+            data_dir: dirs::data_dir().map(|dir| dir.join("esponquen")),
+            export_dir: dirs::download_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_else(std::env::temp_dir),
+            base_dir: None,
+        }
+    }
+
+    /// Everything in subdirectories of `exe_dir`
+    pub fn portable(exe_dir: &Path) -> Self {
+        AppPaths {
+            portable: true,
+            config_file: Some(exe_dir.join("config").join(CONFIG_FILE_NAME)),
+            log_file: exe_dir.join("logs").join("esponquen.log"),
+            data_dir: Some(exe_dir.join("data")),
+            export_dir: exe_dir.join("diagnostics"),
+            base_dir: Some(exe_dir.to_path_buf()),
+        }
+    }
+
+    /// Portable layout if `force` is set or the executable's folder has the flag file
+    pub fn detect(force: bool) -> Result<Self, String> {
+        let exe_dir = std::env::current_exe()
+            .map_err(|e| e.to_string())
+            .and_then(|exe| {
+                exe.parent()
+                    .map(Path::to_path_buf)
+                    .ok_or_else(|| format!("{} has no parent directory", exe.display()))
+            });
+
+        match exe_dir {
+            Ok(dir) => Ok(Self::for_exe_dir(&dir, force)),
+            Err(e) if force => Err(format!(
+                "Portable mode needs the executable's folder: {}",
+                e
+            )),
+            Err(_) => Ok(Self::standard()),
+        }
+    }
+
+    /// Layout for an executable in `exe_dir`, see [`AppPaths::detect`]
+    pub fn for_exe_dir(exe_dir: &Path, force: bool) -> Self {
+        if force || exe_dir.join(PORTABLE_FLAG_FILE).is_file() {
+            Self::portable(exe_dir)
+        } else {
+            Self::standard()
+        }
+    }
+
+    /// Resolve a path from the config; relative ones are taken from the base directory
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        match &self.base_dir {
+            Some(base) if path.is_relative() => base.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_exe_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("esponquen-paths-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn portable_layout_lives_next_to_the_executable() {
+        let exe_dir = Path::new("/media/usb/esponquen");
+        let paths = AppPaths::portable(exe_dir);
+
+        assert!(paths.portable);
+        assert_eq!(
+            paths.config_file,
+            Some(exe_dir.join("config").join("config.toml"))
+        );
+        assert_eq!(paths.log_file, exe_dir.join("logs").join("esponquen.log"));
+        assert_eq!(paths.data_dir, Some(exe_dir.join("data")));
+        assert_eq!(paths.export_dir, exe_dir.join("diagnostics"));
+        for path in [&paths.log_file, &paths.export_dir] {
+            assert!(path.starts_with(exe_dir));
+        }
+    }
+
+    #[test]
+    fn portable_layout_resolves_relative_paths_against_the_executable() {
+        let exe_dir = Path::new("/media/usb/esponquen");
+        let paths = AppPaths::portable(exe_dir);
+
+        assert_eq!(paths.resolve("./model"), exe_dir.join("./model"));
+        assert_eq!(
+            paths.resolve("icons/idle.png"),
+            exe_dir.join("icons/idle.png")
+        );
+        let absolute = std::env::temp_dir().join("model");
+        assert_eq!(paths.resolve(&absolute), absolute);
+    }
+
+    #[test]
+    fn standard_layout_keeps_relative_paths() {
+        let paths = AppPaths::standard();
+
+        assert!(!paths.portable);
+        assert_eq!(paths.base_dir, None);
+        match &paths.data_dir {
+            Some(data_dir) => assert!(paths.log_file.starts_with(data_dir)),
+            None => assert_eq!(paths.log_file, PathBuf::from(LOG_FILE_PATH)),
+        }
+        assert_eq!(paths.resolve("./model"), PathBuf::from("./model"));
+        if let Some(config_file) = &paths.config_file {
+            assert!(config_file.ends_with(Path::new("esponquen").join("config.toml")));
+        }
+    }
+
+    #[test]
+    fn flag_file_enables_portable_mode() {
+        let exe_dir = temp_exe_dir("flag");
+        let flag = exe_dir.join(PORTABLE_FLAG_FILE);
+        std::fs::remove_file(&flag).ok();
+
+        assert!(!AppPaths::for_exe_dir(&exe_dir, false).portable);
+        assert!(AppPaths::for_exe_dir(&exe_dir, true).portable);
+
+        std::fs::write(&flag, "").unwrap();
+        assert_eq!(
+            AppPaths::for_exe_dir(&exe_dir, false),
+            AppPaths::portable(&exe_dir)
+        );
+
+        std::fs::remove_dir_all(&exe_dir).ok();
+    }
+}
@@ -0,0 +1,216 @@
+// Checks for the platform permissions needed to grab the hotkey (rdev) and
+// type text (enigo), or on Wayland to read the hotkey from /dev/input and type
+// with an external tool (see `session`)
+//
+// On macOS nothing works without the Accessibility, Input Monitoring and
+// Microphone permissions, and nothing says so either: the grab and the input
+// stream fail quietly. So there they're required, and the app waits for them.
+
+#[cfg(target_os = "linux")]
+use crate::session::{SessionType, TypingTool, is_on_path};
+
+/// A permission problem and how to fix it
+#[derive(Clone, Debug)]
+pub struct PermissionIssue {
+    pub summary: String,
+    pub remedy: String,
+    /// System settings page where it's granted, if it can be opened directly
+    pub settings_url: Option<&'static str>,
+}
+
+#[cfg(target_os = "macos")]
+const ACCESSIBILITY_SETTINGS: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility";
+#[cfg(target_os = "macos")]
+const INPUT_MONITORING_SETTINGS: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_ListenEvent";
+#[cfg(target_os = "macos")]
+const MICROPHONE_SETTINGS: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone";
+
+/// Check the permissions needed for the hotkey and typing on this platform
+#[cfg(target_os = "linux")]
+pub fn check_input_permissions() -> Vec<PermissionIssue> {
+    let mut issues = Vec::new();
+    let wayland = SessionType::detect() == SessionType::Wayland;
+
+    let tools = [TypingTool::Wtype, TypingTool::Ydotool];
+    if wayland && !tools.iter().any(|tool| is_on_path(tool.program())) {
+        issues.push(PermissionIssue {
+            summary: "No tool to type with on Wayland".to_string(),
+            remedy: "Install wtype (wlroots compositors, KDE) or ydotool (any compositor, with ydotoold running)".to_string(),
+            settings_url: None,
+        });
+    }
+
+    if !wayland && std::env::var_os("DISPLAY").is_none() {
+        issues.push(PermissionIssue {
+            summary: "No X11 display found (DISPLAY is not set)".to_string(),
+            remedy: "Typing the transcription requires an X11 display".to_string(),
+            settings_url: None,
+        });
+    }
+
+    // The keyboard grab (and on Wayland the hotkey listener) reads /dev/input
+    let can_read_input = std::fs::read_dir("/dev/input")
+        .map(|entries| {
+            entries.filter_map(Result::ok).any(|entry| {
+                entry.file_name().to_string_lossy().starts_with("event")
+                    && std::fs::File::open(entry.path()).is_ok()
+            })
+        })
+        .unwrap_or(false);
+    if !can_read_input {
+        issues.push(PermissionIssue {
+            summary: "Can't read keyboard devices in /dev/input".to_string(),
+            remedy:
+                "Add your user to the 'input' group (sudo usermod -aG input $USER) and log in again"
+                    .to_string(),
+            settings_url: None,
+        });
+    }
+
+    // Off Wayland, the grab re-emits the keys it lets through in /dev/uinput
+    let can_write_uinput = wayland
+        || std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .is_ok();
+    if !can_write_uinput {
+        issues.push(PermissionIssue {
+            summary: "Can't write to /dev/uinput".to_string(),
+            remedy: "Allow the 'input' group to use /dev/uinput with a udev rule: KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\"".to_string(),
+            settings_url: None,
+        });
+    }
+
+    issues
+}
+
+/// Check the permissions needed for the hotkey and typing on this platform
+#[cfg(target_os = "macos")]
+pub fn check_input_permissions() -> Vec<PermissionIssue> {
+    #[link(name = "ApplicationServices", kind = "framework")]
+    unsafe extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+    #[link(name = "IOKit", kind = "framework")]
+    unsafe extern "C" {
+        fn IOHIDCheckAccess(request: u32) -> u32;
+    }
+    // kIOHIDRequestTypeListenEvent and kIOHIDAccessTypeGranted
+    const LISTEN_EVENT: u32 = 1;
+    const GRANTED: u32 = 0;
+
+    let mut issues = Vec::new();
+
+    // Typing, and the grab keeping the hotkey from the focused app
+    if !unsafe { AXIsProcessTrusted() } {
+        issues.push(PermissionIssue {
+            summary: "Accessibility permission not granted".to_string(),
+            remedy: "Open System Settings > Privacy & Security > Accessibility and enable Esponquen (or your terminal)".to_string(),
+            settings_url: Some(ACCESSIBILITY_SETTINGS),
+        });
+    }
+
+    // Seeing the hotkey at all
+    if unsafe { IOHIDCheckAccess(LISTEN_EVENT) } != GRANTED {
+        issues.push(PermissionIssue {
+            summary: "Input Monitoring permission not granted".to_string(),
+            remedy: "Open System Settings > Privacy & Security > Input Monitoring and enable Esponquen (or your terminal)".to_string(),
+            settings_url: Some(INPUT_MONITORING_SETTINGS),
+        });
+    }
+
+    issues
+}
+
+/// Check the permissions needed for the hotkey and typing on this platform
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn check_input_permissions() -> Vec<PermissionIssue> {
+    Vec::new()
+}
+
+/// Check the app may record from the microphone, None if it may or hasn't been
+/// asked yet (opening the input stream asks)
+#[cfg(target_os = "macos")]
+fn check_microphone_permission() -> Option<PermissionIssue> {
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    unsafe extern "C" {
+        static AVMediaTypeAudio: *const c_void;
+    }
+    #[link(name = "objc")]
+    unsafe extern "C" {
+        fn objc_getClass(name: *const c_char) -> *const c_void;
+        fn sel_registerName(name: *const c_char) -> *const c_void;
+        fn objc_msgSend();
+    }
+    // AVAuthorizationStatusRestricted and AVAuthorizationStatusDenied
+    const RESTRICTED: isize = 1;
+    const DENIED: isize = 2;
+
+    // [AVCaptureDevice authorizationStatusForMediaType:AVMediaTypeAudio]
+    let status = unsafe {
+        let send = std::mem::transmute::<
+            unsafe extern "C" fn(),
+            unsafe extern "C" fn(*const c_void, *const c_void, *const c_void) -> isize,
+        >(objc_msgSend);
+        send(
+            objc_getClass(c"AVCaptureDevice".as_ptr()),
+            sel_registerName(c"authorizationStatusForMediaType:".as_ptr()),
+            AVMediaTypeAudio,
+        )
+    };
+
+    matches!(status, RESTRICTED | DENIED).then(|| PermissionIssue {
+        summary: "Microphone access denied".to_string(),
+        remedy: "Open System Settings > Privacy & Security > Microphone and enable Esponquen (or your terminal)".to_string(),
+        settings_url: Some(MICROPHONE_SETTINGS),
+    })
+}
+
+/// Check the permissions the app can't work without, which the hotkey and
+/// recording wait for
+///
+/// Only macOS has such; elsewhere a missing permission only shows once it's
+/// needed, see `check_input_permissions`.
+#[cfg(target_os = "macos")]
+pub fn check_required_permissions() -> Vec<PermissionIssue> {
+    let mut issues = check_input_permissions();
+    issues.extend(check_microphone_permission());
+    issues
+}
+
+/// Check the permissions the app can't work without, which the hotkey and
+/// recording wait for
+#[cfg(not(target_os = "macos"))]
+pub fn check_required_permissions() -> Vec<PermissionIssue> {
+    Vec::new()
+}
+
+/// Open the system settings page of `issue`
+#[cfg(target_os = "macos")]
+pub fn open_settings(issue: &PermissionIssue) -> Result<(), String> {
+    let url = issue
+        .settings_url
+        .ok_or("No settings page for this permission")?;
+    std::process::Command::new("open")
+        .arg(url)
+        .status()
+        .map_err(|e| e.to_string())
+        .and_then(|status| {
+            if status.success() {
+                Ok(())
+            } else {
+                Err(format!("open exited with {}", status))
+            }
+        })
+}
+
+/// Open the system settings page of `issue`
+#[cfg(not(target_os = "macos"))]
+pub fn open_settings(_issue: &PermissionIssue) -> Result<(), String> {
+    Err("No settings page for this permission".to_string())
+}
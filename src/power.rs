@@ -0,0 +1,139 @@
+// Keeping the system awake while dictating, and noticing when it wakes from sleep.
+//
+// A `SleepInhibitor` keeps the system from sleeping on idle for as long as it's
+// held: SetThreadExecutionState on Windows, a `systemd-inhibit` child on Linux
+// (logind's inhibitor lock, taken over D-Bus and held while the child runs) and
+// a `caffeinate` child on macOS (an IOPMAssertion, likewise). The display may
+// still turn off, and closing the lid still suspends.
+//
+// Resume is noticed without power events: the wall clock keeps counting while
+// the system is suspended, so it jumps far ahead of the time a thread meant to
+// wait. A clock change can look the same, which costs a harmless recovery.
+
+use std::marker::PhantomData;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the wall clock is compared with the time waited
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The wall clock getting this far ahead of the time waited means the system slept
+const RESUME_GAP: Duration = Duration::from_secs(30);
+
+/// Keeps the system awake until dropped, on the thread that acquired it
+pub struct SleepInhibitor {
+    #[cfg(not(target_os = "windows"))]
+    child: std::process::Child,
+    // SetThreadExecutionState is per thread, so it's released where it was set
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl SleepInhibitor {
+    /// Keep the system from sleeping on idle, `reason` naming why where the
+    /// system lists inhibitors (`systemd-inhibit --list`)
+    #[cfg(target_os = "windows")]
+    pub fn acquire(_reason: &str) -> Result<SleepInhibitor, String> {
+        use winapi::um::winbase::SetThreadExecutionState;
+        use winapi::um::winnt::{ES_CONTINUOUS, ES_SYSTEM_REQUIRED};
+
+        if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) } == 0 {
+            return Err("SetThreadExecutionState failed".to_string());
+        }
+        Ok(SleepInhibitor {
+            _thread_bound: PhantomData,
+        })
+    }
+
+    /// Keep the system from sleeping on idle, `reason` naming why where the
+    /// system lists inhibitors (`systemd-inhibit --list`)
+    #[cfg(not(target_os = "windows"))]
+    pub fn acquire(reason: &str) -> Result<SleepInhibitor, String> {
+        use std::process::{Command, Stdio};
+
+        // Both exit with this process too, should it die without dropping this
+        let pid = std::process::id().to_string();
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("caffeinate");
+            command.args(["-i", "-w", &pid]);
+            command
+        } else {
+            let mut command = Command::new("systemd-inhibit");
+            command
+                .arg("--what=sleep:idle")
+                .arg("--who=Esponquen")
+                .arg(format!("--why={}", reason))
+                .arg("--mode=block")
+                .args(["tail", &format!("--pid={}", pid), "-f", "/dev/null"]);
+            command
+        };
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Can't run {:?}: {}", command.get_program(), e))?;
+        Ok(SleepInhibitor {
+            child,
+            _thread_bound: PhantomData,
+        })
+    }
+}
+
+impl Drop for SleepInhibitor {
+    #[cfg(target_os = "windows")]
+    fn drop(&mut self) {
+        use winapi::um::winbase::SetThreadExecutionState;
+        use winapi::um::winnt::ES_CONTINUOUS;
+
+        unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Call `on_resume` from a new thread each time the system wakes from sleep,
+/// with roughly how long it slept
+pub fn spawn_resume_watcher(on_resume: impl Fn(Duration) + Send + 'static) {
+    thread::spawn(move || {
+        let mut last = SystemTime::now();
+        loop {
+            thread::sleep(CLOCK_CHECK_INTERVAL);
+            let now = SystemTime::now();
+            // A clock set back is no sleep
+            let wall = now.duration_since(last).unwrap_or_default();
+            last = now;
+            if let Some(slept) = slept_for(CLOCK_CHECK_INTERVAL, wall) {
+                on_resume(slept);
+            }
+        }
+    });
+}
+
+/// How long the system slept, if the wall clock moved on by `wall` while a
+/// thread waited `waited`
+fn slept_for(waited: Duration, wall: Duration) -> Option<Duration> {
+    let gap = wall.saturating_sub(waited);
+    (gap >= RESUME_GAP).then_some(gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_large_clock_jump_is_a_resume() {
+        let waited = Duration::from_secs(5);
+        assert_eq!(slept_for(waited, Duration::from_secs(5)), None);
+        // A busy system oversleeping a little
+        assert_eq!(slept_for(waited, Duration::from_secs(12)), None);
+        assert_eq!(slept_for(waited, Duration::ZERO), None);
+        assert_eq!(
+            slept_for(waited, Duration::from_secs(3605)),
+            Some(Duration::from_secs(3600))
+        );
+    }
+}
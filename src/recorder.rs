@@ -0,0 +1,126 @@
+// Recording from an input device into an `AppContext`.
+//
+// A `Recorder` owns the cpal stream. Its callback pushes samples into the
+// context's ring while the context is recording (see capture.rs), so starting
+// and stopping a dictation never touches the stream. Dropping the recorder
+// closes the stream and marks the context as having no input.
+
+use crate::audio::mono_samples;
+use crate::context::AppContext;
+use crate::crash;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use log::error;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+/// An open input stream feeding a context
+pub struct Recorder {
+    stream: cpal::Stream,
+    ctx: Arc<AppContext>,
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl Recorder {
+    /// Record from a device (None for the default) at its default config
+    pub fn open(ctx: &Arc<AppContext>, device_name: Option<&str>) -> Result<Recorder, String> {
+        let host = cpal::default_host();
+        let device = select_input_device(&host, device_name).ok_or("No input device available")?;
+        let config = device
+            .default_input_config()
+            .map_err(|e| format!("Failed to get default input config: {}", e))?;
+        Recorder::start(ctx, &device, &config, None, |err| {
+            error!("Stream error: {}", err)
+        })
+    }
+
+    /// Build and start a stream on `device`, calling `on_error` if it fails later
+    /// (e.g. the device is unplugged)
+    pub fn start<E>(
+        ctx: &Arc<AppContext>,
+        device: &cpal::Device,
+        config: &cpal::SupportedStreamConfig,
+        buffer_size: Option<u32>,
+        on_error: E,
+    ) -> Result<Recorder, String>
+    where
+        E: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        let mut stream_config = config.config();
+        if let Some(frames) = buffer_size {
+            stream_config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+
+        // Cloned here, not on the first callback, which mustn't allocate
+        let callback_ctx = Arc::clone(ctx);
+        let channels = config.channels() as usize;
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &stream_config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    // Realtime: no locks, no allocations, see capture.rs
+                    crash::contain("audio callback", || {
+                        if callback_ctx.recording.load(Ordering::Relaxed) {
+                            callback_ctx.ring.push(mono_samples(
+                                data,
+                                channels,
+                                callback_ctx.input_channel(),
+                            ));
+                        }
+                    });
+                },
+                on_error,
+                None,
+            ),
+            _ => Err(cpal::BuildStreamError::StreamConfigNotSupported),
+        }
+        .map_err(|e| format!("Failed to build input stream: {}", e))?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start input stream: {}", e))?;
+
+        let sample_rate = config.sample_rate().0;
+        ctx.set_sample_rate(sample_rate);
+        ctx.input_available.store(true, Ordering::SeqCst);
+        Ok(Recorder {
+            stream,
+            ctx: Arc::clone(ctx),
+            sample_rate,
+            channels,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Stop the stream ahead of dropping it, e.g. on shutdown
+    pub fn pause(&self) {
+        self.stream.pause().ok();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.ctx.input_available.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Find the configured input device by name, falling back to the system default
+pub fn select_input_device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    if let Some(name) = name {
+        let found = host.input_devices().ok().and_then(|mut devices| {
+            devices.find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        });
+        if found.is_some() {
+            return found;
+        }
+        error!("Input device '{}' not found, using the default", name);
+    }
+
+    host.default_input_device()
+}
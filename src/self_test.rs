@@ -0,0 +1,262 @@
+// `--self-test`: check each part the app needs, one after the other, and print
+// PASS or FAIL for each with the details, for bug reports.
+//
+// The checks go through the same functions as a normal start (config overrides,
+// model loading, opening the input stream, the keyboard grab, enigo, the tray
+// icon), so a pass here means the app gets past that step. The exit code is 1 if
+// any check failed.
+
+use crate::DeviceFallback;
+use crate::cli::Cli;
+use esponquen::config::{self, Config};
+use esponquen::validate::{Severity, validate};
+use esponquen::{AppContext, EngineBuilder, EngineConfig};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+use tray_icon::menu::Menu;
+use winit::event_loop::EventLoop;
+
+// Audio recorded from the default input to check it delivers samples
+const MICROPHONE_SECS: u64 = 2;
+
+// Time the keyboard grab has to fail in before it counts as established
+const GRAB_CHECK_TIME: Duration = Duration::from_secs(1);
+
+/// Result of one check, with one line per detail worth attaching to a report
+struct Check {
+    name: &'static str,
+    passed: bool,
+    summary: String,
+    details: Vec<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, summary: impl Into<String>) -> Self {
+        Check {
+            name,
+            passed: true,
+            summary: summary.into(),
+            details: Vec::new(),
+        }
+    }
+
+    fn fail(name: &'static str, summary: impl Into<String>) -> Self {
+        Check {
+            name,
+            passed: false,
+            summary: summary.into(),
+            details: Vec::new(),
+        }
+    }
+
+    fn print(&self) {
+        let verdict = if self.passed { "PASS" } else { "FAIL" };
+        println!("{}  {:<10} {}", verdict, self.name, self.summary);
+        for detail in &self.details {
+            println!("      {:<10} {}", "", detail);
+        }
+    }
+}
+
+/// Run every check, returning the process exit code
+pub fn run(cli: &Cli, config_path: Option<&Path>) -> i32 {
+    println!("Esponquen {} self-test\n", env!("CARGO_PKG_VERSION"));
+
+    let (check, config, engine) = check_config(cli, config_path);
+    let mut passed = report(check);
+    passed &= report(check_model(&engine));
+    passed &= report(check_microphone(&config));
+    passed &= report(check_keyboard_grab());
+    passed &= report(check_typing());
+    passed &= report(check_tray_icon(&config));
+
+    if passed {
+        println!("\nAll checks passed");
+        0
+    } else {
+        println!("\nSome checks failed, see the details above");
+        1
+    }
+}
+
+fn report(check: Check) -> bool {
+    check.print();
+    check.passed
+}
+
+/// Parse the config file and validate it with the profile, environment and flags applied
+///
+/// Carries on with the defaults when the file can't be used, so the other checks still run.
+fn check_config(cli: &Cli, path: Option<&Path>) -> (Check, Config, EngineConfig) {
+    let name = "Config";
+    let (file_config, source) = match path {
+        Some(path) if path.exists() => match config::load(path) {
+            Ok(config) => (config, path.display().to_string()),
+            Err(e) => {
+                let mut check = Check::fail(name, e);
+                check
+                    .details
+                    .push("Checking the rest with the defaults".to_string());
+                return (check, Config::default(), EngineConfig::default());
+            }
+        },
+        Some(path) => (
+            Config::default(),
+            format!("defaults ({} doesn't exist)", path.display()),
+        ),
+        None => (
+            Config::default(),
+            "defaults (no config directory)".to_string(),
+        ),
+    };
+
+    let mut config = file_config.clone();
+    crate::select_startup_profile(&file_config, cli);
+    if let Err(e) = crate::apply_overrides(&mut config, cli) {
+        let mut check = Check::fail(name, e);
+        check.details.push(format!("Loaded from {}", source));
+        return (check, file_config, EngineConfig::default());
+    }
+
+    let problems = validate(&config);
+    let engine = EngineBuilder::from_config(&config).build();
+    let fatal = problems.iter().any(|p| p.severity == Severity::Fatal);
+
+    let mut check = match &engine {
+        Ok(_) if !fatal => Check::pass(name, source),
+        Ok(_) => Check::fail(name, format!("{} has invalid settings", source)),
+        Err(e) => Check::fail(name, format!("{}: {}", source, e)),
+    };
+    if let Some(profile) = crate::active_profile() {
+        check.details.push(format!("Profile: {}", profile));
+    }
+    for problem in &problems {
+        check
+            .details
+            .push(format!("{:?}: {}", problem.severity, problem));
+    }
+    (check, config, engine.unwrap_or_default())
+}
+
+/// Load the model with every configured provider, passing if any of them can
+fn check_model(engine: &EngineConfig) -> Check {
+    let mut loaded = Vec::new();
+    let mut details = Vec::new();
+    for provider in &engine.providers {
+        let display_name = crate::provider_display_name(provider.as_deref().unwrap_or("cpu"));
+        let started = Instant::now();
+        match crate::init_recognizer(engine, provider.as_deref()) {
+            Ok(_) => {
+                let secs = started.elapsed().as_secs_f64();
+                details.push(format!("{}: loaded in {:.2}s", display_name, secs));
+                loaded.push(display_name);
+            }
+            Err(e) => details.push(format!("{}: {}", display_name, e)),
+        }
+    }
+
+    let mut check = if loaded.is_empty() {
+        Check::fail(
+            "Model",
+            format!("no provider could load {}", engine.model_dir.display()),
+        )
+    } else {
+        Check::pass("Model", format!("loads with {}", loaded.join(", ")))
+    };
+    check.details = details;
+    check
+}
+
+/// Open the input the app would record from and check it delivers sound
+fn check_microphone(config: &Config) -> Check {
+    let name = "Microphone";
+    let host = cpal::default_host();
+    // A context of its own, the check doesn't go through the hotkey
+    let ctx = Arc::new(AppContext::new());
+    let mut input = match crate::open_input_stream(
+        &ctx,
+        &host,
+        &crate::input_settings(config),
+        DeviceFallback::Default,
+    ) {
+        Ok(input) => input,
+        Err(e) => return Check::fail(name, e),
+    };
+
+    // The ring holds more than the check records, so no collector is needed
+    ctx.recording.store(true, Ordering::SeqCst);
+    thread::sleep(Duration::from_secs(MICROPHONE_SECS));
+    let samples = {
+        let mut state = ctx.recording_state();
+        ctx.collect_audio(&mut state);
+        let samples = std::mem::take(&mut state.audio_data);
+        ctx.reset_recording(&mut state);
+        samples
+    };
+    drop(input.stream.take());
+
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let mut check = if samples.is_empty() {
+        Check::fail(name, format!("{} delivered no audio", input.device_name))
+    } else if peak == 0.0 {
+        Check::fail(
+            name,
+            format!("{} delivered only silence (muted?)", input.device_name),
+        )
+    } else {
+        Check::pass(name, input.device_name.clone())
+    };
+    check.details.push(format!(
+        "{} samples in {}s at {} Hz, peak level {:.3}",
+        samples.len(),
+        MICROPHONE_SECS,
+        input.sample_rate,
+        peak
+    ));
+    check
+}
+
+/// Grab the keyboard like the hotkey listener does, letting every key through
+fn check_keyboard_grab() -> Check {
+    let name = "Keyboard";
+    let failed = crate::spawn_keyboard_grab(Some);
+    match failed.recv_timeout(GRAB_CHECK_TIME) {
+        Err(RecvTimeoutError::Timeout) => Check::pass(name, "hotkeys can be captured"),
+        Ok(e) => Check::fail(name, format!("can't capture hotkeys: {}", e)),
+        Err(RecvTimeoutError::Disconnected) => {
+            Check::fail(name, "the keyboard grab stopped right away")
+        }
+    }
+}
+
+/// Set up the keyboard simulation used to type transcriptions, typing nothing
+fn check_typing() -> Check {
+    let tools = esponquen::session::typing_tools();
+    if !tools.is_empty() {
+        let names: Vec<&str> = tools.iter().map(|tool| tool.program()).collect();
+        return Check::pass("Typing", format!("typing with {}", names.join(", then ")));
+    }
+    match esponquen::output::new_enigo() {
+        Ok(_) => Check::pass("Typing", "keyboard input can be simulated"),
+        Err(e) => Check::fail("Typing", format!("can't simulate keyboard input: {}", e)),
+    }
+}
+
+/// Create the tray icon and remove it again
+fn check_tray_icon(config: &Config) -> Check {
+    let name = "Tray icon";
+    // The tray needs the event loop of the main thread, as when running
+    let _event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(e) => return Check::fail(name, format!("no event loop: {}", e)),
+    };
+    let tray_click = config.ui.left_click.unwrap_or_default();
+    match crate::build_tray_icon(Menu::new(), tray_click) {
+        Ok(_) => Check::pass(name, "created"),
+        Err(e) => Check::fail(name, e),
+    }
+}
@@ -0,0 +1,87 @@
+// The kind of desktop session, which decides how the hotkey is read and how text
+// is typed on Linux.
+//
+// On X11 the keyboard grab (rdev) and simulated typing (enigo) work as on the
+// other platforms. Wayland allows neither, so there the hotkey is read from the
+// keyboard devices in /dev/input (which can't keep it from the focused app), and
+// text is typed by `wtype` (the virtual keyboard protocol: wlroots compositors
+// and KDE) or else `ydotool` (through /dev/uinput, with `ydotoold` running),
+// whichever are installed.
+
+use once_cell::sync::Lazy;
+
+// Found on first use, installing a tool takes a restart
+static TYPING_TOOLS: Lazy<Vec<TypingTool>> = Lazy::new(find_typing_tools);
+
+/// Desktop sessions that need their own way to read the hotkey or type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionType {
+    X11,
+    Wayland,
+    /// Windows, macOS, or a Linux session that's neither (e.g. a text console)
+    Other,
+}
+
+impl SessionType {
+    /// The session this process runs in
+    pub fn detect() -> SessionType {
+        if !cfg!(target_os = "linux") {
+            return SessionType::Other;
+        }
+        match std::env::var("XDG_SESSION_TYPE").as_deref() {
+            Ok("wayland") => SessionType::Wayland,
+            Ok("x11") => SessionType::X11,
+            // Not set everywhere, e.g. when started by a window manager's autostart
+            _ if std::env::var_os("WAYLAND_DISPLAY").is_some() => SessionType::Wayland,
+            _ if std::env::var_os("DISPLAY").is_some() => SessionType::X11,
+            _ => SessionType::Other,
+        }
+    }
+}
+
+/// Command line tools that type text on Wayland, in order of preference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypingTool {
+    Wtype,
+    Ydotool,
+}
+
+impl TypingTool {
+    /// Name of the executable
+    pub fn program(self) -> &'static str {
+        match self {
+            TypingTool::Wtype => "wtype",
+            TypingTool::Ydotool => "ydotool",
+        }
+    }
+
+    /// Arguments that make the tool type `text`
+    pub fn args(self, text: &str) -> Vec<&str> {
+        match self {
+            TypingTool::Wtype => vec!["--", text],
+            TypingTool::Ydotool => vec!["type", "--", text],
+        }
+    }
+}
+
+/// The tools to type text with, each one tried when the one before fails; empty
+/// where enigo types it
+pub fn typing_tools() -> &'static [TypingTool] {
+    &TYPING_TOOLS
+}
+
+fn find_typing_tools() -> Vec<TypingTool> {
+    if SessionType::detect() != SessionType::Wayland {
+        return Vec::new();
+    }
+    [TypingTool::Wtype, TypingTool::Ydotool]
+        .into_iter()
+        .filter(|tool| is_on_path(tool.program()))
+        .collect()
+}
+
+/// Whether an executable named `program` is in one of the PATH directories
+pub fn is_on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
@@ -0,0 +1,116 @@
+// Line-based exchange of live settings between the app and the settings window.
+//
+// The settings window runs as a child process; the app sends it the current
+// values as `key=value` lines on stdin and applies each `key=value` line the
+// window prints on stdout.
+
+use crate::{
+    QuitBehavior, get_hotkey, get_normalization, get_quit_behavior, hotkey_name,
+    is_restore_focus_enabled, is_tooltip_preview_enabled, set_normalization, set_quit_behavior,
+    set_restore_focus, set_tooltip_preview,
+};
+
+/// Settings key for the recording hotkey
+pub const HOTKEY: &str = "hotkey";
+/// Settings key for restoring the target window before typing
+pub const RESTORE_FOCUS: &str = "restore_focus";
+/// Settings key for the last-transcription tooltip preview
+pub const TOOLTIP_PREVIEW: &str = "tooltip_preview";
+/// Settings key for finishing the current dictation on Quit
+pub const FINISH_ON_QUIT: &str = "finish_on_quit";
+/// Settings key for normalizing recordings before transcription
+pub const NORMALIZE: &str = "normalize";
+/// Settings key for the peak level normalization aims for, in dBFS
+pub const NORMALIZE_TARGET_DB: &str = "normalize_target_db";
+/// Settings key for the most normalization turns a recording down, in dB
+pub const NORMALIZE_MIN_GAIN_DB: &str = "normalize_min_gain_db";
+/// Settings key for the most normalization turns a recording up, in dB
+pub const NORMALIZE_MAX_GAIN_DB: &str = "normalize_max_gain_db";
+
+/// Current values of the live-editable settings as `key=value` lines
+pub fn snapshot_lines() -> Vec<String> {
+    let normalization = get_normalization();
+    vec![
+        format!("{}={}", HOTKEY, hotkey_name(get_hotkey())),
+        format!("{}={}", RESTORE_FOCUS, is_restore_focus_enabled()),
+        format!("{}={}", TOOLTIP_PREVIEW, is_tooltip_preview_enabled()),
+        format!(
+            "{}={}",
+            FINISH_ON_QUIT,
+            get_quit_behavior() == QuitBehavior::FinishFirst
+        ),
+        format!("{}={}", NORMALIZE, normalization.enabled),
+        format!("{}={}", NORMALIZE_TARGET_DB, normalization.target_db),
+        format!("{}={}", NORMALIZE_MIN_GAIN_DB, normalization.min_gain_db),
+        format!("{}={}", NORMALIZE_MAX_GAIN_DB, normalization.max_gain_db),
+    ]
+}
+
+/// Split a `key=value` line into its trimmed parts
+pub fn split_line(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+/// Apply one `key=value` line to the running app
+///
+/// Except the hotkey, which the app changes on its event loop (see
+/// `crate::command::AppCommand::SetHotkey`) after checking it isn't taken.
+pub fn apply_line(line: &str) -> Result<(), String> {
+    let (key, value) = split_line(line).ok_or_else(|| format!("Malformed setting: {}", line))?;
+
+    match key {
+        HOTKEY => return Err("The hotkey is changed through AppCommand::SetHotkey".to_string()),
+        RESTORE_FOCUS => set_restore_focus(parse_bool(value)?),
+        TOOLTIP_PREVIEW => set_tooltip_preview(parse_bool(value)?),
+        FINISH_ON_QUIT => set_quit_behavior(if parse_bool(value)? {
+            QuitBehavior::FinishFirst
+        } else {
+            QuitBehavior::Prompt
+        }),
+        NORMALIZE => {
+            let mut normalization = get_normalization();
+            normalization.enabled = parse_bool(value)?;
+            set_normalization(normalization);
+        }
+        NORMALIZE_TARGET_DB => {
+            let mut normalization = get_normalization();
+            normalization.target_db = parse_db(value, -60.0..=0.0)?;
+            set_normalization(normalization);
+        }
+        NORMALIZE_MIN_GAIN_DB => {
+            let mut normalization = get_normalization();
+            normalization.min_gain_db = parse_db(value, -60.0..=0.0)?;
+            set_normalization(normalization);
+        }
+        NORMALIZE_MAX_GAIN_DB => {
+            let mut normalization = get_normalization();
+            normalization.max_gain_db = parse_db(value, 0.0..=60.0)?;
+            set_normalization(normalization);
+        }
+        _ => return Err(format!("Unknown setting: {}", key)),
+    }
+
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Expected true or false, got: {}", value))
+}
+
+fn parse_db(value: &str, range: std::ops::RangeInclusive<f32>) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|db| range.contains(db))
+        .ok_or_else(|| {
+            format!(
+                "Expected a level from {} to {} dB, got: {}",
+                range.start(),
+                range.end(),
+                value
+            )
+        })
+}
@@ -0,0 +1,286 @@
+// Settings window, run in its own process (`--settings-window`) because winit
+// only allows one event loop per process. Current values arrive as `key=value`
+// lines on stdin and every change is written back as a `key=value` line on
+// stdout, which the tray app applies live.
+
+use eframe::egui;
+use esponquen::config::{
+    DEFAULT_NORMALIZE_MAX_GAIN_DB, DEFAULT_NORMALIZE_MIN_GAIN_DB, DEFAULT_NORMALIZE_TARGET_DB,
+};
+use esponquen::{HOTKEY_OPTIONS, settings};
+use log::error;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// Prefix of the read-only values sent by the app for display
+const INFO_PREFIX: &str = "info.";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Hotkeys,
+    Audio,
+    Model,
+    Output,
+}
+
+struct SettingsApp {
+    tab: Tab,
+    hotkey: String,
+    restore_focus: bool,
+    tooltip_preview: bool,
+    finish_on_quit: bool,
+    normalize: bool,
+    normalize_target_db: f32,
+    normalize_min_gain_db: f32,
+    normalize_max_gain_db: f32,
+    info: HashMap<String, String>,
+}
+
+impl SettingsApp {
+    fn new() -> Self {
+        SettingsApp {
+            tab: Tab::Hotkeys,
+            hotkey: String::from("F6"),
+            restore_focus: false,
+            tooltip_preview: true,
+            finish_on_quit: false,
+            normalize: false,
+            normalize_target_db: DEFAULT_NORMALIZE_TARGET_DB,
+            normalize_min_gain_db: DEFAULT_NORMALIZE_MIN_GAIN_DB,
+            normalize_max_gain_db: DEFAULT_NORMALIZE_MAX_GAIN_DB,
+            info: HashMap::new(),
+        }
+    }
+
+    fn load_line(&mut self, line: &str) {
+        let Some((key, value)) = settings::split_line(line) else {
+            return;
+        };
+
+        match key {
+            settings::HOTKEY => self.hotkey = value.to_string(),
+            settings::RESTORE_FOCUS => self.restore_focus = value == "true",
+            settings::TOOLTIP_PREVIEW => self.tooltip_preview = value == "true",
+            settings::FINISH_ON_QUIT => self.finish_on_quit = value == "true",
+            settings::NORMALIZE => self.normalize = value == "true",
+            settings::NORMALIZE_TARGET_DB => load_db(&mut self.normalize_target_db, value),
+            settings::NORMALIZE_MIN_GAIN_DB => load_db(&mut self.normalize_min_gain_db, value),
+            settings::NORMALIZE_MAX_GAIN_DB => load_db(&mut self.normalize_max_gain_db, value),
+            _ => {
+                if let Some(info_key) = key.strip_prefix(INFO_PREFIX) {
+                    self.info.insert(info_key.to_string(), value.to_string());
+                }
+            }
+        }
+    }
+
+    fn info(&self, key: &str) -> &str {
+        self.info.get(key).map(String::as_str).unwrap_or("Unknown")
+    }
+
+    fn hotkeys_tab(&mut self, ui: &mut egui::Ui) {
+        let previous = self.hotkey.clone();
+
+        egui::ComboBox::from_label("Start/stop recording")
+            .selected_text(self.hotkey.as_str())
+            .show_ui(ui, |ui| {
+                for (name, _) in &HOTKEY_OPTIONS {
+                    ui.selectable_value(&mut self.hotkey, name.to_string(), *name);
+                }
+            });
+
+        if self.hotkey != previous {
+            emit(settings::HOTKEY, &self.hotkey);
+        }
+
+        ui.add_space(8.0);
+        ui.label("Changes apply immediately.");
+    }
+
+    fn audio_tab(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("audio_info").num_columns(2).show(ui, |ui| {
+            ui.label("Input device");
+            ui.label(self.info("device"));
+            ui.end_row();
+
+            ui.label("Sample rate");
+            ui.label(format!("{} Hz", self.info("sample_rate")));
+            ui.end_row();
+        });
+
+        ui.add_space(8.0);
+        restart_required(ui, "Use --input-rate to change the sample rate.");
+
+        ui.separator();
+        if ui
+            .checkbox(&mut self.normalize, "Normalize the level of each recording")
+            .changed()
+        {
+            emit(settings::NORMALIZE, &self.normalize.to_string());
+        }
+
+        ui.add_enabled_ui(self.normalize, |ui| {
+            egui::Grid::new("normalization")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    db_slider(
+                        ui,
+                        "Target peak",
+                        &mut self.normalize_target_db,
+                        -30.0..=0.0,
+                        settings::NORMALIZE_TARGET_DB,
+                    );
+                    db_slider(
+                        ui,
+                        "Most turned down",
+                        &mut self.normalize_min_gain_db,
+                        -30.0..=0.0,
+                        settings::NORMALIZE_MIN_GAIN_DB,
+                    );
+                    db_slider(
+                        ui,
+                        "Most turned up",
+                        &mut self.normalize_max_gain_db,
+                        0.0..=40.0,
+                        settings::NORMALIZE_MAX_GAIN_DB,
+                    );
+                });
+        });
+        ui.label("Changes apply from the next recording.");
+    }
+
+    fn model_tab(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("model_info").num_columns(2).show(ui, |ui| {
+            ui.label("Model directory");
+            ui.label(self.info("model_dir"));
+            ui.end_row();
+
+            ui.label("Provider");
+            ui.label(self.info("provider"));
+            ui.end_row();
+
+            ui.label("Providers tried");
+            ui.label(self.info("providers_tried"));
+            ui.end_row();
+
+            ui.label("Limitations");
+            ui.label(self.info("limitations"));
+            ui.end_row();
+        });
+
+        ui.add_space(8.0);
+        restart_required(ui, "The model and provider are chosen at startup.");
+    }
+
+    fn output_tab(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(
+                &mut self.restore_focus,
+                "Re-activate the original window before typing",
+            )
+            .changed()
+        {
+            emit(settings::RESTORE_FOCUS, &self.restore_focus.to_string());
+        }
+
+        if ui
+            .checkbox(
+                &mut self.tooltip_preview,
+                "Show the last transcription in the tray tooltip",
+            )
+            .changed()
+        {
+            emit(settings::TOOLTIP_PREVIEW, &self.tooltip_preview.to_string());
+        }
+
+        if ui
+            .checkbox(
+                &mut self.finish_on_quit,
+                "Finish the current dictation when quitting",
+            )
+            .changed()
+        {
+            emit(settings::FINISH_ON_QUIT, &self.finish_on_quit.to_string());
+        }
+
+        ui.add_space(8.0);
+        ui.label("Changes apply immediately.");
+    }
+}
+
+impl eframe::App for SettingsApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Hotkeys, "Hotkeys");
+                ui.selectable_value(&mut self.tab, Tab::Audio, "Audio");
+                ui.selectable_value(&mut self.tab, Tab::Model, "Model");
+                ui.selectable_value(&mut self.tab, Tab::Output, "Output");
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Hotkeys => self.hotkeys_tab(ui),
+            Tab::Audio => self.audio_tab(ui),
+            Tab::Model => self.model_tab(ui),
+            Tab::Output => self.output_tab(ui),
+        });
+    }
+}
+
+/// A row of a settings grid with a slider for a level in dB, reporting changes
+fn db_slider(
+    ui: &mut egui::Ui,
+    label: &str,
+    db: &mut f32,
+    range: std::ops::RangeInclusive<f32>,
+    key: &str,
+) {
+    ui.label(label);
+    let slider = egui::Slider::new(db, range).step_by(0.5).suffix(" dB");
+    if ui.add(slider).changed() {
+        emit(key, &db.to_string());
+    }
+    ui.end_row();
+}
+
+fn load_db(db: &mut f32, value: &str) {
+    if let Ok(value) = value.parse() {
+        *db = value;
+    }
+}
+
+fn restart_required(ui: &mut egui::Ui, hint: &str) {
+    ui.colored_label(egui::Color32::from_rgb(220, 140, 0), "Restart required");
+    ui.label(hint);
+}
+
+/// Report a changed setting (or other `key=value` message) to the tray app
+pub fn emit(key: &str, value: &str) {
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}={}", key, value).ok();
+    stdout.flush().ok();
+}
+
+/// Read the current settings from stdin and show the settings window
+pub fn run() {
+    let mut app = SettingsApp::new();
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        app.load_line(&line);
+    }
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Esponquen Settings")
+            .with_inner_size([420.0, 320.0]),
+        ..Default::default()
+    };
+
+    if let Err(e) = eframe::run_native(
+        "Esponquen Settings",
+        options,
+        Box::new(|_cc| Ok(Box::new(app))),
+    ) {
+        error!("Failed to open settings window: {}", e);
+    }
+}
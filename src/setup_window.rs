@@ -0,0 +1,215 @@
+// First-run setup window, run in its own process (`--setup-window`) like the
+// settings window, for the wizard when there's no console. The defaults arrive as
+// `key=value` lines on stdin; "Finish" writes the choices back the same way on
+// stdout, and closing the window otherwise keeps the defaults.
+
+use crate::settings_window::emit;
+use eframe::egui;
+use esponquen::model::missing_model_files;
+use esponquen::{HOTKEY_OPTIONS, settings};
+use log::error;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+use std::time::Duration;
+
+/// Key of the line carrying the model directory
+pub const MODEL_DIR: &str = "model_dir";
+
+// How long the microphone test listens
+const MIC_TEST_DURATION: Duration = Duration::from_secs(3);
+
+// Peak level below which the microphone test counts as hearing nothing
+const SILENCE_LEVEL: f32 = 0.01;
+
+/// Progress of the microphone test, sent by the thread running it
+enum MeterUpdate {
+    Level(f32),
+    Finished(Result<f32, String>),
+}
+
+enum MicTest {
+    NotRun,
+    /// Updates from the test and the latest level
+    Running(Receiver<MeterUpdate>, f32),
+    /// Peak level heard, or why the microphone couldn't be opened
+    Done(Result<f32, String>),
+}
+
+struct SetupApp {
+    model_dir: String,
+    /// Model files missing from `model_dir`, checked again when it changes
+    missing: Vec<&'static str>,
+    hotkey: String,
+    mic_test: MicTest,
+}
+
+impl SetupApp {
+    fn new() -> Self {
+        SetupApp {
+            model_dir: String::new(),
+            missing: Vec::new(),
+            hotkey: String::from("F6"),
+            mic_test: MicTest::NotRun,
+        }
+    }
+
+    fn load_line(&mut self, line: &str) {
+        match settings::split_line(line) {
+            Some((MODEL_DIR, value)) => self.set_model_dir(value.to_string()),
+            Some((settings::HOTKEY, value)) => self.hotkey = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn set_model_dir(&mut self, dir: String) {
+        self.missing = missing_model_files(Path::new(&dir));
+        self.model_dir = dir;
+    }
+
+    fn model_section(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Speech model");
+        ui.horizontal(|ui| {
+            let mut dir = self.model_dir.clone();
+            if ui.text_edit_singleline(&mut dir).changed() {
+                self.set_model_dir(dir);
+            }
+            if ui.button("Browse…").clicked()
+                && let Some(picked) = rfd::FileDialog::new()
+                    .set_title("Model directory")
+                    .set_directory(&self.model_dir)
+                    .pick_folder()
+            {
+                self.set_model_dir(picked.display().to_string());
+            }
+        });
+
+        if self.missing.is_empty() {
+            ui.colored_label(egui::Color32::from_rgb(0, 160, 60), "✓ Model files found");
+        } else {
+            ui.colored_label(
+                egui::Color32::from_rgb(220, 140, 0),
+                format!("Missing: {}", self.missing.join(", ")),
+            );
+            ui.label("Pick the folder holding them, or download the model after this window.");
+        }
+    }
+
+    fn hotkey_section(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Hotkey");
+        egui::ComboBox::from_label("Start/stop recording")
+            .selected_text(self.hotkey.as_str())
+            .show_ui(ui, |ui| {
+                for (name, _) in &HOTKEY_OPTIONS {
+                    ui.selectable_value(&mut self.hotkey, name.to_string(), *name);
+                }
+            });
+    }
+
+    fn microphone_section(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Microphone");
+
+        if let MicTest::Running(updates, level) = &mut self.mic_test {
+            let mut finished = None;
+            for update in updates.try_iter() {
+                match update {
+                    MeterUpdate::Level(latest) => *level = latest,
+                    MeterUpdate::Finished(result) => finished = Some(result),
+                }
+            }
+            ui.add(egui::ProgressBar::new(level.clamp(0.0, 1.0)).text("Speak now…"));
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+            if let Some(result) = finished {
+                self.mic_test = MicTest::Done(result);
+            }
+            return;
+        }
+
+        match &self.mic_test {
+            MicTest::Done(Ok(peak)) if *peak < SILENCE_LEVEL => {
+                ui.colored_label(
+                    egui::Color32::from_rgb(220, 140, 0),
+                    "Nothing heard, check the microphone isn't muted",
+                );
+            }
+            MicTest::Done(Ok(peak)) => {
+                ui.colored_label(
+                    egui::Color32::from_rgb(0, 160, 60),
+                    format!("✓ The default microphone works (peak level {:.2})", peak),
+                );
+            }
+            MicTest::Done(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("✗ {}", e));
+            }
+            _ => {}
+        }
+        if ui.button("Test the microphone").clicked() {
+            self.mic_test = MicTest::Running(start_mic_test(), 0.0);
+        }
+    }
+}
+
+impl eframe::App for SetupApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::bottom("finish").show(ctx, |ui| {
+            ui.add_space(4.0);
+            if ui.button("Finish").clicked() {
+                emit(MODEL_DIR, &self.model_dir);
+                emit(settings::HOTKEY, &self.hotkey);
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            ui.add_space(4.0);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Welcome to Esponquen");
+            ui.label("A few things to check before the first dictation.");
+            ui.add_space(8.0);
+            self.model_section(ui);
+            ui.add_space(8.0);
+            self.hotkey_section(ui);
+            ui.add_space(8.0);
+            self.microphone_section(ui);
+        });
+    }
+}
+
+/// Listen to the default input on a thread, reporting the level as it goes
+fn start_mic_test() -> Receiver<MeterUpdate> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let host = cpal::default_host();
+        let result = match esponquen::recorder::select_input_device(&host, None) {
+            Some(device) => crate::wizard::measure_level(&device, MIC_TEST_DURATION, |level| {
+                sender.send(MeterUpdate::Level(level)).ok();
+            }),
+            None => Err("No microphone found".to_string()),
+        };
+        sender.send(MeterUpdate::Finished(result)).ok();
+    });
+    receiver
+}
+
+/// Read the defaults from stdin and show the setup window
+pub fn run() {
+    let mut app = SetupApp::new();
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        app.load_line(&line);
+    }
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Esponquen Setup")
+            .with_inner_size([440.0, 320.0]),
+        ..Default::default()
+    };
+
+    if let Err(e) = eframe::run_native(
+        "Esponquen Setup",
+        options,
+        Box::new(|_cc| Ok(Box::new(app))),
+    ) {
+        error!("Failed to open setup window: {}", e);
+    }
+}
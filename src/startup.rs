@@ -0,0 +1,105 @@
+// Problems that stop the app from starting, and how they're shown.
+//
+// Built with `windows_subsystem = "windows"`, a failed start has no console to
+// print to, so besides the log the error is shown in a native dialog along with
+// the likely fix. Problems the app can live with (no tray, no microphone) aren't
+// startup errors, see `main` for how it degrades.
+
+use esponquen::model::MODEL_FILES;
+use esponquen::notification::notify;
+use log::error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why the app couldn't start
+#[derive(Debug)]
+pub enum StartupError {
+    /// Portable mode without a usable executable folder
+    Paths(String),
+    /// An environment variable or flag has an invalid value
+    Overrides(String),
+    /// The config file has problems the app can't run with (already reported one by one)
+    InvalidConfig,
+    /// Another instance holds the lock
+    AlreadyRunning,
+    /// Taking over from the running instance (--replace) failed
+    Replace(String),
+    /// No event loop, usually because there's no graphical session
+    EventLoop(String),
+    /// The model files are missing or no provider could load them
+    Model { error: String, model_dir: PathBuf },
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartupError::Paths(e) | StartupError::Overrides(e) => write!(f, "{}", e),
+            StartupError::InvalidConfig => write!(f, "The config file has problems"),
+            StartupError::AlreadyRunning => write!(f, "Esponquen is already running"),
+            StartupError::Replace(e) => write!(f, "Couldn't replace the running instance: {}", e),
+            StartupError::EventLoop(e) => write!(f, "Couldn't start the event loop: {}", e),
+            StartupError::Model { error, .. } => write!(f, "Couldn't load the model: {}", error),
+        }
+    }
+}
+
+impl StartupError {
+    /// What the user can do about it
+    pub fn fix(&self) -> String {
+        match self {
+            StartupError::Paths(_) => {
+                "Start without --portable, or move the app to a folder it can read.".to_string()
+            }
+            StartupError::Overrides(_) => {
+                "Check the ESPONQUEN_* environment variables and the command line flags."
+                    .to_string()
+            }
+            StartupError::InvalidConfig => {
+                "Fix the settings listed in the log and start again.".to_string()
+            }
+            StartupError::AlreadyRunning => {
+                "Use its tray icon, or start with --replace to take over.".to_string()
+            }
+            StartupError::Replace(_) => {
+                "Quit the running instance from its tray icon and start again.".to_string()
+            }
+            StartupError::EventLoop(_) => {
+                "Start it from a desktop session, or check DISPLAY / WAYLAND_DISPLAY.".to_string()
+            }
+            StartupError::Model { model_dir, .. } => format!(
+                "Make sure {} are in {}, or set model.dir in the config file.",
+                MODEL_FILES.join(", "),
+                model_dir.display()
+            ),
+        }
+    }
+
+    /// Exit code of the process
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StartupError::AlreadyRunning | StartupError::Model { .. } => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Report a startup error and exit
+///
+/// `dialog` shows a native error dialog, for when no console is watching; otherwise
+/// a notification is enough next to the console output.
+pub fn fail(error: &StartupError, dialog: bool) -> ! {
+    error!("{}\n  → {}", error, error.fix());
+
+    let message = format!("{}\n\n{}", error, error.fix());
+    if dialog {
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Error)
+            .set_title("Esponquen couldn't start")
+            .set_description(&message)
+            .set_buttons(rfd::MessageButtons::Ok)
+            .show();
+    } else {
+        notify("Esponquen couldn't start", &message);
+    }
+    std::process::exit(error.exit_code());
+}
@@ -0,0 +1,190 @@
+// Usage counters of a dictation pipeline, for `esponquen ctl status` and the
+// metrics endpoint.
+//
+// Only transcriptions that produced text are counted. The average real-time
+// factor is the processing time of all of them over their total audio length, so
+// a long dictation weighs more than a short one.
+
+use crate::timing::TranscriptionTimings;
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Counters since the pipeline was created
+pub struct Stats {
+    started: Instant,
+    transcriptions: AtomicU64,
+    words: AtomicU64,
+    audio_us: AtomicU64,
+    /// Resampling and inference
+    processing_us: AtomicU64,
+}
+
+/// The counters at one point in time
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatsSnapshot {
+    pub transcriptions: u64,
+    pub words: u64,
+    pub audio_secs: f64,
+    /// 0 until something was transcribed
+    pub average_rtf: f64,
+    pub uptime_secs: u64,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            started: Instant::now(),
+            transcriptions: AtomicU64::new(0),
+            words: AtomicU64::new(0),
+            audio_us: AtomicU64::new(0),
+            processing_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a transcription that produced `text`
+    pub fn record(&self, text: &str, timings: &TranscriptionTimings) {
+        let processing = timings.resample + timings.inference;
+        self.transcriptions.fetch_add(1, Ordering::Relaxed);
+        self.words
+            .fetch_add(text.split_whitespace().count() as u64, Ordering::Relaxed);
+        self.audio_us
+            .fetch_add(timings.audio.as_micros() as u64, Ordering::Relaxed);
+        self.processing_us
+            .fetch_add(processing.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let audio_us = self.audio_us.load(Ordering::Relaxed);
+        let processing_us = self.processing_us.load(Ordering::Relaxed);
+        StatsSnapshot {
+            transcriptions: self.transcriptions.load(Ordering::Relaxed),
+            words: self.words.load(Ordering::Relaxed),
+            audio_secs: audio_us as f64 / 1_000_000.0,
+            average_rtf: if audio_us == 0 {
+                0.0
+            } else {
+                processing_us as f64 / audio_us as f64
+            },
+            uptime_secs: self.started.elapsed().as_secs(),
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsSnapshot {
+    /// The counters in the Prometheus text format, with the status and provider
+    /// as labels of info-style gauges
+    pub fn prometheus(&self, status: &str, provider: &str, cpu_fallback: bool) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, sample: String| {
+            writeln!(text, "# HELP esponquen_{} {}", name, help).ok();
+            writeln!(text, "# TYPE esponquen_{} {}", name, kind).ok();
+            writeln!(text, "esponquen_{}{}", name, sample).ok();
+        };
+        metric(
+            "transcriptions_total",
+            "counter",
+            "Transcriptions that produced text",
+            format!(" {}", self.transcriptions),
+        );
+        metric(
+            "words_total",
+            "counter",
+            "Words transcribed",
+            format!(" {}", self.words),
+        );
+        metric(
+            "audio_seconds_total",
+            "counter",
+            "Length of the audio transcribed",
+            format!(" {}", self.audio_secs),
+        );
+        metric(
+            "real_time_factor",
+            "gauge",
+            "Processing time over audio length, averaged over all transcriptions",
+            format!(" {}", self.average_rtf),
+        );
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Time since the app started",
+            format!(" {}", self.uptime_secs),
+        );
+        metric(
+            "status",
+            "gauge",
+            "What the app is doing, as the status label",
+            format!("{{status=\"{}\"}} 1", label_value(status)),
+        );
+        metric(
+            "provider_info",
+            "gauge",
+            "Execution provider the model runs on",
+            format!(
+                "{{provider=\"{}\",cpu_fallback=\"{}\"}} 1",
+                label_value(provider),
+                cpu_fallback
+            ),
+        );
+        text
+    }
+}
+
+/// Escape a label value for the Prometheus text format
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn timings(audio_ms: u64, inference_ms: u64) -> TranscriptionTimings {
+        TranscriptionTimings {
+            audio: Duration::from_millis(audio_ms),
+            inference: Duration::from_millis(inference_ms),
+            ..TranscriptionTimings::default()
+        }
+    }
+
+    #[test]
+    fn long_dictations_weigh_more_in_the_average_rtf() {
+        let stats = Stats::new();
+        assert_eq!(stats.snapshot().average_rtf, 0.0);
+
+        stats.record("hello world", &timings(1_000, 500));
+        stats.record("a much longer dictation", &timings(9_000, 500));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.transcriptions, 2);
+        assert_eq!(snapshot.words, 6);
+        assert_eq!(snapshot.audio_secs, 10.0);
+        assert_eq!(snapshot.average_rtf, 0.1);
+    }
+
+    #[test]
+    fn prometheus_text_has_every_metric_once() {
+        let stats = Stats::new();
+        stats.record("one two three", &timings(2_000, 200));
+        let text = stats.snapshot().prometheus("ready", "CUDA \"gpu\"", false);
+
+        assert!(text.contains("\nesponquen_transcriptions_total 1\n"));
+        assert!(text.contains("\nesponquen_words_total 3\n"));
+        assert!(text.contains("\nesponquen_status{status=\"ready\"} 1\n"));
+        assert!(text.contains(
+            "\nesponquen_provider_info{provider=\"CUDA \\\"gpu\\\"\",cpu_fallback=\"false\"} 1\n"
+        ));
+        assert_eq!(text.matches("# TYPE").count(), 7);
+    }
+}
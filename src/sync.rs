@@ -0,0 +1,71 @@
+// Mutex locking that survives a panic on another thread.
+//
+// A thread that panics while holding a std Mutex poisons it, and every later
+// `lock().unwrap()` panics as well. In the keyboard hook that would leave
+// system-wide input frozen until the process is killed, so the app's locks go
+// through these helpers, which log the poisoning and carry on with the data.
+
+use log::error;
+use std::sync::{Mutex, MutexGuard};
+
+/// Lock `mutex`, recovering it if a thread panicked while holding it
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    lock_or_reset(mutex, |_| {})
+}
+
+/// Lock `mutex`, calling `reset` on the data if a thread panicked while holding it
+///
+/// For state a panic can leave half-updated, e.g. a recording that was never stopped.
+pub fn lock_or_reset<T>(mutex: &Mutex<T>, reset: impl FnOnce(&mut T)) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            error!(
+                "A thread panicked while holding the {} lock, recovering it",
+                std::any::type_name::<T>()
+            );
+            mutex.clear_poison();
+            let mut guard = poisoned.into_inner();
+            reset(&mut guard);
+            guard
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poison<T: Send + 'static>(mutex: &'static Mutex<T>) {
+        let result = std::thread::spawn(move || {
+            let _guard = mutex.lock().unwrap();
+            panic!("poisoning the lock on purpose");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+    }
+
+    #[test]
+    fn lock_recovers_a_poisoned_mutex() {
+        static VALUE: Mutex<u32> = Mutex::new(7);
+        poison(&VALUE);
+
+        assert_eq!(*lock(&VALUE), 7);
+        assert!(!VALUE.is_poisoned());
+        *lock(&VALUE) += 1;
+        assert_eq!(*lock(&VALUE), 8);
+    }
+
+    #[test]
+    fn lock_or_reset_only_resets_after_a_panic() {
+        static SAMPLES: Mutex<Vec<f32>> = Mutex::new(Vec::new());
+        lock(&SAMPLES).push(0.5);
+
+        assert_eq!(lock_or_reset(&SAMPLES, Vec::clear).len(), 1);
+
+        poison(&SAMPLES);
+        assert!(lock_or_reset(&SAMPLES, Vec::clear).is_empty());
+        assert!(!SAMPLES.is_poisoned());
+    }
+}
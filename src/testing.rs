@@ -0,0 +1,129 @@
+// Stand-ins for the model and the microphone, for testing the pipeline headless.
+//
+// A `MockTranscriber` answers with canned text, optionally after a delay or by
+// failing the way a crashing model does, and keeps every clip it was given. A
+// `SyntheticRecorder` takes the place of a `Recorder`: it marks the context as
+// having an input and plays sample buffers into it as the audio callback would.
+//
+// ```ignore
+// let ctx = Arc::new(AppContext::new());
+// let mic = SyntheticRecorder::open(&ctx, 16_000);
+// let model = MockTranscriber::new("hello world");
+// let dictation = DictationEngine::new(Arc::clone(&ctx), model.shared(), output);
+// dictation.start()?;
+// mic.play(&samples);
+// dictation.stop()?;
+// ```
+
+use crate::context::AppContext;
+use crate::sync::lock;
+use crate::transcriber::{Recognize, SharedRecognizer};
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Samples per push, about what an audio callback gets at 16 kHz
+const CHUNK: usize = 512;
+
+/// Recognizer returning canned text instead of running a model
+#[derive(Clone)]
+pub struct MockTranscriber {
+    /// Answers still to give; the last one repeats
+    texts: VecDeque<String>,
+    delay: Duration,
+    fail: bool,
+    heard: Arc<Mutex<Vec<Vec<f32>>>>,
+}
+
+impl MockTranscriber {
+    /// Always answer `text`
+    pub fn new(text: impl Into<String>) -> Self {
+        Self::in_turn([text])
+    }
+
+    /// Answer each of `texts` in turn, then keep answering the last one
+    pub fn in_turn(texts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        MockTranscriber {
+            texts: texts.into_iter().map(Into::into).collect(),
+            delay: Duration::ZERO,
+            fail: false,
+            heard: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Take `delay` over every transcription, like a slow model
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Panic on every transcription, like a model that crashes
+    pub fn failing(mut self) -> Self {
+        self.fail = true;
+        self
+    }
+
+    /// Every clip transcribed so far, as handed to the model
+    pub fn heard(&self) -> Arc<Mutex<Vec<Vec<f32>>>> {
+        Arc::clone(&self.heard)
+    }
+
+    /// Share the transcriber the way the app shares a loaded model
+    pub fn shared(self) -> SharedRecognizer {
+        SharedRecognizer::new(Box::new(self))
+    }
+}
+
+impl Recognize for MockTranscriber {
+    fn transcribe_samples(&mut self, samples: &[f32], _sample_rate: u32) -> String {
+        lock(&self.heard).push(samples.to_vec());
+        thread::sleep(self.delay);
+        if self.fail {
+            panic!("Mock transcriber failed");
+        }
+        if self.texts.len() > 1 {
+            self.texts.pop_front().unwrap_or_default()
+        } else {
+            self.texts.front().cloned().unwrap_or_default()
+        }
+    }
+}
+
+/// An input that plays sample buffers into a context instead of recording
+pub struct SyntheticRecorder {
+    ctx: Arc<AppContext>,
+}
+
+impl SyntheticRecorder {
+    /// Feed `ctx` as an input running at `sample_rate`
+    pub fn open(ctx: &Arc<AppContext>, sample_rate: u32) -> SyntheticRecorder {
+        ctx.set_sample_rate(sample_rate);
+        ctx.input_available.store(true, Ordering::SeqCst);
+        SyntheticRecorder {
+            ctx: Arc::clone(ctx),
+        }
+    }
+
+    /// Play mono `samples` in callback-sized chunks, returning how many were
+    /// recorded; like a microphone, nothing is kept while the context isn't recording
+    pub fn play(&self, samples: &[f32]) -> usize {
+        samples
+            .chunks(CHUNK)
+            .map(|chunk| {
+                if self.ctx.recording.load(Ordering::Relaxed) {
+                    self.ctx.ring.push(chunk.iter().copied())
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+}
+
+impl Drop for SyntheticRecorder {
+    fn drop(&mut self) {
+        self.ctx.input_available.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,184 @@
+// Helpers for presenting transcribed text
+
+use crate::events::civil_date;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Collapse text to a single line and truncate it to `max_chars` characters
+///
+/// Newlines, tabs and repeated spaces become a single space, and an ellipsis is
+/// appended when the text had to be cut.
+pub fn one_line_preview(text: &str, max_chars: usize) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if collapsed.chars().count() <= max_chars {
+        return collapsed;
+    }
+
+    let truncated: String = collapsed.chars().take(max_chars).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+/// Fix spacing the model sometimes gets wrong
+///
+/// Removes spaces before `,.;:!?`, collapses runs of whitespace into a single
+/// space and trims both ends: "hello ,  world !" becomes "hello, world!".
+pub fn tidy(text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let mut tidied = String::with_capacity(collapsed.len());
+    for c in collapsed.chars() {
+        if matches!(c, ',' | '.' | ';' | ':' | '!' | '?') && tidied.ends_with(' ') {
+            tidied.pop();
+        }
+        tidied.push(c);
+    }
+    tidied
+}
+
+/// Prefix and suffix put around every transcription, e.g. "// " to dictate code comments
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Wrap {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+/// Wraps offered in the tray: label, prefix and suffix
+pub const WRAP_PRESETS: [(&str, &str, &str); 5] = [
+    ("None", "", ""),
+    ("Code comment (// )", "// ", ""),
+    ("Shell comment (# )", "# ", ""),
+    ("Inline code (`…`)", "`", "`"),
+    ("Quote (> )", "> ", ""),
+];
+
+impl Wrap {
+    pub fn new(prefix: &str, suffix: &str) -> Self {
+        Wrap {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+        }
+    }
+
+    /// Put the prefix and suffix around `text`
+    ///
+    /// This is the last clean-up, after `tidy`, so the others only ever touch the
+    /// dictated words. Empty text stays empty so it still counts as an empty
+    /// transcription.
+    pub fn apply(&self, text: &str) -> String {
+        if text.trim().is_empty() {
+            return text.to_string();
+        }
+        format!("{}{}{}", self.prefix, text, self.suffix)
+    }
+}
+
+/// Template of the line written before each entry appended to the output file,
+/// e.g. "## {timestamp} ({secs} s)"; empty for plain appends
+///
+/// `{timestamp}` is when the dictation finished, in UTC ("2024-05-01 14:03:22 UTC"),
+/// `{secs}` how long the recording was and `{provider}` what transcribed it.
+/// Anything else in braces is written as is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileHeader(pub String);
+
+impl FileHeader {
+    /// The header of an entry, None when there's no template
+    pub fn render(
+        &self,
+        finished: SystemTime,
+        recorded: Duration,
+        provider: &str,
+    ) -> Option<String> {
+        if self.0.trim().is_empty() {
+            return None;
+        }
+        Some(
+            self.0
+                .replace("{timestamp}", &utc_timestamp(finished))
+                .replace("{secs}", &format!("{:.1}", recorded.as_secs_f32()))
+                .replace("{provider}", provider),
+        )
+    }
+}
+
+/// `time` as "2024-05-01 14:03:22 UTC"
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_date(secs / 86_400);
+    let of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        of_day / 3600,
+        of_day / 60 % 60,
+        of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tidy_removes_spaces_before_punctuation() {
+        assert_eq!(tidy("hello , world ."), "hello, world.");
+        assert_eq!(tidy("wait ; what ? really !"), "wait; what? really!");
+        assert_eq!(tidy("note : this"), "note: this");
+    }
+
+    #[test]
+    fn tidy_collapses_repeated_spaces() {
+        assert_eq!(tidy("one  two   three"), "one two three");
+        assert_eq!(tidy("tabs\tand\n newlines"), "tabs and newlines");
+    }
+
+    #[test]
+    fn tidy_trims_the_ends() {
+        assert_eq!(tidy("  padded text  "), "padded text");
+        assert_eq!(tidy(" , leading comma"), ", leading comma");
+        assert_eq!(tidy("   "), "");
+    }
+
+    #[test]
+    fn tidy_handles_runs_of_punctuation() {
+        assert_eq!(tidy("what  ?!"), "what?!");
+        assert_eq!(tidy("and so on . . ."), "and so on...");
+    }
+
+    #[test]
+    fn tidy_keeps_clean_text_unchanged() {
+        let clean = "Hello, world. This is fine: really? Yes!";
+        assert_eq!(tidy(clean), clean);
+        assert_eq!(tidy("3.14 and 10:30"), "3.14 and 10:30");
+    }
+
+    #[test]
+    fn wrap_surrounds_text_but_not_empty_text() {
+        let wrap = Wrap::new("`", "`");
+        assert_eq!(wrap.apply("ls -la"), "`ls -la`");
+        assert_eq!(Wrap::new("// ", "").apply("todo: fix"), "// todo: fix");
+        assert_eq!(wrap.apply(""), "");
+        assert_eq!(wrap.apply("  "), "  ");
+        assert_eq!(Wrap::default().apply("as is"), "as is");
+    }
+
+    #[test]
+    fn file_header_fills_in_the_variables() {
+        // 2024-05-01 14:03:22 UTC
+        let finished = UNIX_EPOCH + Duration::from_secs(1_714_572_202);
+        let header = FileHeader("## {timestamp} ({secs} s, {provider}) {other}".to_string());
+        assert_eq!(
+            header.render(finished, Duration::from_millis(4_300), "CUDA"),
+            Some("## 2024-05-01 14:03:22 UTC (4.3 s, CUDA) {other}".to_string())
+        );
+        assert_eq!(
+            FileHeader::default().render(finished, Duration::ZERO, "CPU"),
+            None
+        );
+    }
+}
@@ -0,0 +1,39 @@
+// Per-transcription latency measurements
+
+use std::time::Duration;
+
+/// Wall-clock time spent on each stage of one transcription
+#[derive(Clone, Debug, Default)]
+pub struct TranscriptionTimings {
+    /// Length of the recorded audio
+    pub audio: Duration,
+    pub resample: Duration,
+    pub inference: Duration,
+    /// Time to type the text, once it has been typed
+    pub typing: Option<Duration>,
+}
+
+impl TranscriptionTimings {
+    /// Processing time (resampling and inference) divided by the audio length
+    pub fn real_time_factor(&self) -> f64 {
+        if self.audio.is_zero() {
+            return 0.0;
+        }
+        (self.resample + self.inference).as_secs_f64() / self.audio.as_secs_f64()
+    }
+
+    /// One-line summary, e.g. "audio 3.20s, resample 2ms, inference 410ms, RTF 0.13"
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "audio {:.2}s, resample {}ms, inference {}ms",
+            self.audio.as_secs_f64(),
+            self.resample.as_millis(),
+            self.inference.as_millis()
+        );
+        if let Some(typing) = self.typing {
+            summary.push_str(&format!(", typing {}ms", typing.as_millis()));
+        }
+        summary.push_str(&format!(", RTF {:.2}", self.real_time_factor()));
+        summary
+    }
+}
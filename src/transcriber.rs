@@ -0,0 +1,249 @@
+// The speech recognizer behind a small API, used by the app and open to embedders
+// that capture audio themselves.
+//
+// ```ignore
+// let engine = EngineBuilder::new().model_dir("/opt/parakeet").build()?;
+// let mut transcriber = Transcriber::new(&engine, None)?;
+// let text = transcriber.transcribe_samples(&samples, 44_100);
+// ```
+//
+// Without the `local-model` feature there's no model to load: `Transcriber::new`
+// always fails, and embedders bring their own `Recognize`.
+
+use crate::EngineConfig;
+#[cfg(feature = "local-model")]
+use crate::audio::{MODEL_SAMPLE_RATE, resample};
+#[cfg(feature = "local-model")]
+use crate::model::describe_init_error;
+use crate::sync::lock;
+use log::debug;
+#[cfg(feature = "local-model")]
+use sherpa_rs::transducer::TransducerRecognizer;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+/// Why the model couldn't be loaded
+#[derive(Clone, Debug)]
+pub struct LoadError {
+    /// Error from sherpa-onnx, as is
+    pub raw: String,
+    /// What it most likely means, when it's one of the known causes
+    pub explanation: Option<String>,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.explanation.as_deref().unwrap_or(&self.raw))
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Turns mono audio into text; the `Transcriber`, or a stand-in in tests
+pub trait Recognize: Send {
+    /// See [`Transcriber::transcribe_samples`]
+    fn transcribe_samples(&mut self, samples: &[f32], sample_rate: u32) -> String;
+}
+
+/// A recognizer shared between threads, swapped out when the settings need another model
+///
+/// A transcription takes the recognizer out with `lease` and it goes back when
+/// the lease is dropped, so no lock is held while the model runs. A replacement
+/// loaded meanwhile (a reload, a provider switch) doesn't wait for it: it's kept
+/// aside and takes over as the lease comes back.
+#[derive(Clone)]
+pub struct SharedRecognizer(Arc<Slot>);
+
+struct Slot {
+    state: Mutex<SlotState>,
+    returned: Condvar,
+}
+
+struct SlotState {
+    /// None while leased
+    recognizer: Option<Box<dyn Recognize>>,
+    /// Replacement waiting for the lease to come back
+    pending: Option<Box<dyn Recognize>>,
+}
+
+impl SharedRecognizer {
+    pub fn new(recognizer: Box<dyn Recognize>) -> Self {
+        SharedRecognizer(Arc::new(Slot {
+            state: Mutex::new(SlotState {
+                recognizer: Some(recognizer),
+                pending: None,
+            }),
+            returned: Condvar::new(),
+        }))
+    }
+
+    /// Take the recognizer for a transcription, waiting for the one that has it
+    pub fn lease(&self) -> RecognizerLease<'_> {
+        let mut state = lock(&self.0.state);
+        loop {
+            if let Some(recognizer) = state.recognizer.take() {
+                return RecognizerLease {
+                    slot: &self.0,
+                    recognizer: Some(recognizer),
+                };
+            }
+            state = self
+                .0
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Whether a transcription has the recognizer
+    pub fn is_busy(&self) -> bool {
+        lock(&self.0.state).recognizer.is_none()
+    }
+
+    /// Swap in `recognizer`, right away if it's free or else when the lease
+    /// comes back, replacing any replacement still waiting
+    ///
+    /// Returns whether it took over right away.
+    pub fn replace(&self, recognizer: Box<dyn Recognize>) -> bool {
+        let mut state = lock(&self.0.state);
+        let now = state.recognizer.is_some();
+        let previous = if now {
+            state.recognizer.replace(recognizer)
+        } else {
+            state.pending.replace(recognizer)
+        };
+        // Dropped after unlocking, freeing a model can take a while
+        drop(state);
+        drop(previous);
+        now
+    }
+}
+
+/// The recognizer, lent to one transcription until dropped
+pub struct RecognizerLease<'a> {
+    slot: &'a Slot,
+    /// Only None while being returned
+    recognizer: Option<Box<dyn Recognize>>,
+}
+
+impl Deref for RecognizerLease<'_> {
+    type Target = dyn Recognize;
+
+    fn deref(&self) -> &Self::Target {
+        self.recognizer
+            .as_deref()
+            .expect("lease holds the recognizer")
+    }
+}
+
+impl DerefMut for RecognizerLease<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.recognizer
+            .as_deref_mut()
+            .expect("lease holds the recognizer")
+    }
+}
+
+impl Drop for RecognizerLease<'_> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.slot.state);
+        let leased = self.recognizer.take();
+        // A replacement loaded during the transcription takes over now
+        let replaced = match state.pending.take() {
+            Some(pending) => {
+                state.recognizer = Some(pending);
+                leased
+            }
+            None => {
+                state.recognizer = leased;
+                None
+            }
+        };
+        drop(state);
+        self.slot.returned.notify_one();
+        drop(replaced);
+    }
+}
+
+/// A loaded model, ready to turn audio into text
+pub struct Transcriber {
+    #[cfg(feature = "local-model")]
+    recognizer: TransducerRecognizer,
+    /// Can't be built without a model to run
+    #[cfg(not(feature = "local-model"))]
+    never: std::convert::Infallible,
+}
+
+impl Transcriber {
+    /// Load the model files of `engine` with `provider` ("cuda", "dml"...), None for the CPU
+    #[cfg(feature = "local-model")]
+    pub fn new(engine: &EngineConfig, provider: Option<&str>) -> Result<Self, LoadError> {
+        let config = engine.transducer_config(provider.map(String::from));
+        match TransducerRecognizer::new(config) {
+            Ok(recognizer) => Ok(Transcriber { recognizer }),
+            Err(e) => {
+                let raw = e.to_string();
+                debug!(
+                    "Recognizer init failed ({}: {})",
+                    provider.unwrap_or("CPU"),
+                    raw
+                );
+                let files = [
+                    engine.encoder.as_path(),
+                    engine.decoder.as_path(),
+                    engine.joiner.as_path(),
+                    engine.tokens.as_path(),
+                ];
+                let explanation = describe_init_error(&raw, &files, provider);
+                Err(LoadError { raw, explanation })
+            }
+        }
+    }
+
+    /// Always fails, this build has no model runtime
+    #[cfg(not(feature = "local-model"))]
+    pub fn new(_engine: &EngineConfig, provider: Option<&str>) -> Result<Self, LoadError> {
+        debug!(
+            "Recognizer init skipped ({}): no local-model feature",
+            provider.unwrap_or("CPU")
+        );
+        Err(LoadError {
+            raw: "built without the local-model feature".to_string(),
+            explanation: Some(
+                "This build can't run the model, rebuild with `--features local-model`".to_string(),
+            ),
+        })
+    }
+
+    /// Transcribe mono audio, returning the text as the model produced it
+    ///
+    /// `samples` are mono f32 in -1.0..1.0 (average or pick the channels of
+    /// multi-channel audio first, see `audio::append_mono`). Audio at any other
+    /// rate than the model's 16 kHz is resampled first, see `audio::resample`.
+    /// No clean-ups (`text::tidy`, wraps) are applied; empty or silent audio
+    /// gives an empty string.
+    #[cfg(feature = "local-model")]
+    pub fn transcribe_samples(&mut self, samples: &[f32], sample_rate: u32) -> String {
+        if samples.is_empty() {
+            return String::new();
+        }
+        if sample_rate == MODEL_SAMPLE_RATE {
+            return self.recognizer.transcribe(MODEL_SAMPLE_RATE, samples);
+        }
+        let samples = resample(samples, sample_rate, MODEL_SAMPLE_RATE);
+        self.recognizer.transcribe(MODEL_SAMPLE_RATE, &samples)
+    }
+
+    /// Transcribe mono audio, see the `local-model` version
+    #[cfg(not(feature = "local-model"))]
+    pub fn transcribe_samples(&mut self, _samples: &[f32], _sample_rate: u32) -> String {
+        match self.never {}
+    }
+}
+
+impl Recognize for Transcriber {
+    fn transcribe_samples(&mut self, samples: &[f32], sample_rate: u32) -> String {
+        Transcriber::transcribe_samples(self, samples, sample_rate)
+    }
+}
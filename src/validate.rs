@@ -0,0 +1,644 @@
+// Checks a loaded config for mistakes before it's used, reporting all of them at once
+// instead of failing on the first one (or deep inside sherpa).
+
+use crate::config::{Config, OutputTarget, TrayClick};
+use crate::engine::KNOWN_PROVIDERS;
+use crate::logging::parse_level;
+use crate::model::MODEL_FILES;
+use crate::webhook;
+use crate::{HOTKEY_HINT, parse_hotkey};
+use std::fmt;
+use std::path::Path;
+
+// Input sample rates any real device could run at
+const SAMPLE_RATE_RANGE: std::ops::RangeInclusive<u32> = 8_000..=192_000;
+
+// Buffer sizes outside this range are almost certainly a typo
+const BUFFER_SIZE_RANGE: std::ops::RangeInclusive<u32> = 32..=16_384;
+
+// More recognizer threads than this only adds overhead
+const MAX_THREADS: i32 = 64;
+
+// Typing delays above this make the app look broken
+const MAX_TYPING_DELAY_MS: u64 = 5_000;
+
+// More retries than this hold up startup for minutes without a GPU
+const MAX_LOAD_RETRIES: u32 = 8;
+
+// Longer start cooldowns cut off the first word of people who talk right away
+const MAX_START_COOLDOWN_MS: u64 = 200;
+
+// More gain than this turns the room's hiss into something the model transcribes
+const MAX_NORMALIZE_GAIN_DB: f32 = 30.0;
+
+// Audible pitches for the sound cues, in Hz
+const TONE_FREQUENCY_RANGE: std::ops::RangeInclusive<f32> = 20.0..=20_000.0;
+
+// Longest sound cue, in milliseconds
+const MAX_TONE_DURATION_MS: u64 = 2_000;
+
+/// How serious a problem is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting is odd but the app can run with it
+    Warning,
+    /// The app can't run with this setting
+    Fatal,
+}
+
+/// One problem found in the config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Setting the problem is about, as written in the file (e.g. "audio.sample_rate")
+    pub setting: &'static str,
+    pub message: String,
+}
+
+impl Problem {
+    /// The config file couldn't be read or parsed, so none of it could be checked
+    pub fn unreadable(message: String) -> Problem {
+        Problem {
+            severity: Severity::Fatal,
+            setting: "config file",
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.setting, self.message)
+    }
+}
+
+/// Whether any of the problems stops the app from running
+pub fn has_fatal(problems: &[Problem]) -> bool {
+    problems.iter().any(|p| p.severity == Severity::Fatal)
+}
+
+/// Check every setting of `config`, returning all the problems found
+pub fn validate(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut report = |severity, setting, message: String| {
+        problems.push(Problem {
+            severity,
+            setting,
+            message,
+        })
+    };
+
+    // Model files
+    let dir = config.model_dir();
+    if !dir.is_dir() {
+        report(
+            Severity::Fatal,
+            "model.dir",
+            format!("{} is not a directory", dir.display()),
+        );
+    } else {
+        let configured = [
+            ("model.encoder", &config.model.encoder),
+            ("model.decoder", &config.model.decoder),
+            ("model.joiner", &config.model.joiner),
+            ("model.tokens", &config.model.tokens),
+        ];
+        for ((setting, file), default) in configured.into_iter().zip(MODEL_FILES) {
+            let path = dir.join(file.as_deref().unwrap_or(default));
+            if let Err(e) = std::fs::File::open(&path) {
+                report(
+                    Severity::Fatal,
+                    setting,
+                    format!("can't read {}: {}", path.display(), e),
+                );
+            }
+        }
+    }
+
+    // Providers
+    if let Some(providers) = &config.model.providers {
+        if providers.is_empty() {
+            report(
+                Severity::Fatal,
+                "model.providers",
+                "lists no provider; use [\"cpu\"] for the CPU".to_string(),
+            );
+        }
+        for provider in providers {
+            if !KNOWN_PROVIDERS.contains(&provider.trim().to_lowercase().as_str()) {
+                report(
+                    Severity::Fatal,
+                    "model.providers",
+                    format!(
+                        "unknown provider '{}', expected one of {}",
+                        provider,
+                        KNOWN_PROVIDERS.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    // Threads
+    for (setting, threads) in [
+        ("model.cpu_threads", config.model.cpu_threads),
+        ("model.gpu_threads", config.model.gpu_threads),
+    ] {
+        match threads {
+            Some(threads) if threads < 1 => report(
+                Severity::Fatal,
+                setting,
+                format!("{} is less than 1", threads),
+            ),
+            Some(threads) if threads > MAX_THREADS => report(
+                Severity::Warning,
+                setting,
+                format!(
+                    "{} threads is more than {} and won't help",
+                    threads, MAX_THREADS
+                ),
+            ),
+            _ => {}
+        }
+    }
+
+    // Hotkeys
+    let hotkeys = [
+        ("hotkey.key", &config.hotkey.key),
+        ("hotkey.history", &config.hotkey.history),
+        ("hotkey.quit", &config.hotkey.quit),
+        ("hotkey.provider", &config.hotkey.provider),
+        ("hotkey.snapshot", &config.hotkey.snapshot),
+        ("hotkey.clipboard", &config.hotkey.clipboard),
+    ];
+    let mut bound = Vec::new();
+    for (setting, name) in hotkeys {
+        let Some(name) = name else {
+            continue;
+        };
+        match parse_hotkey(name) {
+            None => report(
+                Severity::Fatal,
+                setting,
+                format!("unknown key '{}', expected {}", name, HOTKEY_HINT),
+            ),
+            Some(key) => {
+                if let Some((other, _)) = bound.iter().find(|(_, bound_key)| *bound_key == key) {
+                    report(
+                        Severity::Fatal,
+                        setting,
+                        format!("{} is already used by {}", name, other),
+                    );
+                }
+                bound.push((setting, key));
+            }
+        }
+    }
+
+    // Audio
+    if let Some(rate) = config.audio.sample_rate
+        && !SAMPLE_RATE_RANGE.contains(&rate)
+    {
+        report(
+            Severity::Fatal,
+            "audio.sample_rate",
+            format!(
+                "{} Hz is outside {}-{} Hz",
+                rate,
+                SAMPLE_RATE_RANGE.start(),
+                SAMPLE_RATE_RANGE.end()
+            ),
+        );
+    }
+    if let Some(frames) = config.audio.buffer_size
+        && !BUFFER_SIZE_RANGE.contains(&frames)
+    {
+        report(
+            Severity::Warning,
+            "audio.buffer_size",
+            format!(
+                "{} frames is outside {}-{}, the device may reject it",
+                frames,
+                BUFFER_SIZE_RANGE.start(),
+                BUFFER_SIZE_RANGE.end()
+            ),
+        );
+    }
+    if config.audio.channel == Some(0) {
+        report(
+            Severity::Fatal,
+            "audio.channel",
+            "channels are numbered from 1".to_string(),
+        );
+    }
+    if config.model.transcription_timeout_secs == Some(0) {
+        report(
+            Severity::Fatal,
+            "model.transcription_timeout_secs",
+            "must be at least 1".to_string(),
+        );
+    }
+    if let Some(retries) = config.model.load_retries
+        && retries > MAX_LOAD_RETRIES
+    {
+        report(
+            Severity::Warning,
+            "model.load_retries",
+            format!("{} retries can hold up startup for a long time", retries),
+        );
+    }
+    if config.audio.max_recording_secs == Some(0) {
+        report(
+            Severity::Fatal,
+            "audio.max_recording_secs",
+            "must be at least 1".to_string(),
+        );
+    }
+    if let Some(ms) = config.audio.start_cooldown_ms
+        && ms > MAX_START_COOLDOWN_MS
+    {
+        report(
+            Severity::Warning,
+            "audio.start_cooldown_ms",
+            format!("{} ms can cut off the first word", ms),
+        );
+    }
+    if let Some(db) = config.audio.normalize_target_db
+        && (db.is_nan() || db > 0.0)
+    {
+        report(
+            Severity::Fatal,
+            "audio.normalize_target_db",
+            format!("{} is above full scale, it must be 0 or below", db),
+        );
+    }
+    if let Some(db) = config.audio.normalize_min_gain_db
+        && (db.is_nan() || db > 0.0)
+    {
+        report(
+            Severity::Fatal,
+            "audio.normalize_min_gain_db",
+            format!("{} would turn recordings up, it must be 0 or below", db),
+        );
+    }
+    if let Some(db) = config.audio.normalize_max_gain_db {
+        if db.is_nan() || db < 0.0 {
+            report(
+                Severity::Fatal,
+                "audio.normalize_max_gain_db",
+                format!("{} would turn recordings down, it must be 0 or above", db),
+            );
+        } else if db > MAX_NORMALIZE_GAIN_DB {
+            report(
+                Severity::Warning,
+                "audio.normalize_max_gain_db",
+                format!("{} dB can blow background noise up into words", db),
+            );
+        }
+    }
+
+    // Output
+    if let Some(delay) = config.output.typing_delay_ms
+        && delay > MAX_TYPING_DELAY_MS
+    {
+        report(
+            Severity::Warning,
+            "output.typing_delay_ms",
+            format!("{} ms is a long wait before typing", delay),
+        );
+    }
+    if let Some(apps) = &config.output.blocked_apps
+        && apps.iter().any(|app| app.trim().is_empty())
+    {
+        report(
+            Severity::Fatal,
+            "output.blocked_apps",
+            "can't contain an empty name".to_string(),
+        );
+    }
+    let file_mode = config.output.mode == Some(OutputTarget::File);
+    match config.output.file.as_deref() {
+        None | Some("") if file_mode => report(
+            Severity::Fatal,
+            "output.file",
+            "is required with mode = \"file\"".to_string(),
+        ),
+        Some(file) if !file.is_empty() => {
+            if let Err(e) = check_creatable(Path::new(file)) {
+                // Only stops the app when transcriptions go there
+                let severity = if file_mode {
+                    Severity::Fatal
+                } else {
+                    Severity::Warning
+                };
+                report(severity, "output.file", e);
+            }
+        }
+        _ => {}
+    }
+
+    // Sounds
+    for (frequency_setting, duration_setting, tone) in [
+        (
+            "sounds.success.frequency_hz",
+            "sounds.success.duration_ms",
+            &config.sounds.success,
+        ),
+        (
+            "sounds.empty.frequency_hz",
+            "sounds.empty.duration_ms",
+            &config.sounds.empty,
+        ),
+        (
+            "sounds.error.frequency_hz",
+            "sounds.error.duration_ms",
+            &config.sounds.error,
+        ),
+    ] {
+        if let Some(frequency) = tone.frequency_hz
+            && !TONE_FREQUENCY_RANGE.contains(&frequency)
+        {
+            report(
+                Severity::Fatal,
+                frequency_setting,
+                format!(
+                    "{} Hz is outside {}-{} Hz",
+                    frequency,
+                    TONE_FREQUENCY_RANGE.start(),
+                    TONE_FREQUENCY_RANGE.end()
+                ),
+            );
+        }
+        match tone.duration_ms {
+            Some(0) => report(
+                Severity::Fatal,
+                duration_setting,
+                "must be at least 1".to_string(),
+            ),
+            Some(ms) if ms > MAX_TONE_DURATION_MS => report(
+                Severity::Fatal,
+                duration_setting,
+                format!("{} ms is longer than {} ms", ms, MAX_TONE_DURATION_MS),
+            ),
+            _ => {}
+        }
+    }
+
+    // Webhook
+    match config.webhook.url.as_deref() {
+        // A disabled webhook isn't posted to, so its URL can wait
+        Some(_) if config.webhook.enabled == Some(false) => {}
+        Some(url) => {
+            if let Err(e) = webhook::check_url(url) {
+                report(Severity::Fatal, "webhook.url", e);
+            }
+        }
+        None if config.webhook.enabled == Some(true) => report(
+            Severity::Warning,
+            "webhook.enabled",
+            "is on but no webhook.url is set, nothing will be posted".to_string(),
+        ),
+        None => {}
+    }
+    if config.webhook.timeout_secs == Some(0) {
+        report(
+            Severity::Fatal,
+            "webhook.timeout_secs",
+            "must be at least 1".to_string(),
+        );
+    }
+
+    // Metrics
+    if config.metrics.port == Some(0) {
+        report(
+            Severity::Fatal,
+            "metrics.port",
+            "must be a port number from 1".to_string(),
+        );
+    }
+
+    // Tray
+    match config.ui.double_click {
+        Some(TrayClick::Menu) => report(
+            Severity::Fatal,
+            "ui.double_click",
+            "can't open the menu, only left_click can".to_string(),
+        ),
+        Some(action)
+            if action != TrayClick::Nothing
+                && config.ui.left_click.unwrap_or_default() == TrayClick::Menu =>
+        {
+            report(
+                Severity::Warning,
+                "ui.double_click",
+                "is ignored while left_click = \"menu\", the first click opens the menu"
+                    .to_string(),
+            )
+        }
+        _ => {}
+    }
+
+    // History
+    if config.history.window_entries == Some(0) {
+        report(
+            Severity::Warning,
+            "history.window_entries",
+            "is 0, the history window will be empty".to_string(),
+        );
+    }
+
+    // Log
+    if let Some(level) = &config.log.level
+        && parse_level(level).is_none()
+    {
+        report(
+            Severity::Fatal,
+            "log.level",
+            format!(
+                "unknown level '{}', expected error, warn, info, debug, trace or off",
+                level
+            ),
+        );
+    }
+    if config.log.events_max_mb == Some(0) {
+        report(
+            Severity::Fatal,
+            "log.events_max_mb",
+            "must be at least 1".to_string(),
+        );
+    }
+
+    problems
+}
+
+/// Check that `path` can be written to, or created along with its directories
+fn check_creatable(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()));
+    }
+
+    // The file itself, or the closest directory that already exists
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    if existing != path && !existing.is_dir() {
+        return Err(format!(
+            "can't create {}: {} is not a directory",
+            path.display(),
+            existing.display()
+        ));
+    }
+
+    match std::fs::metadata(existing) {
+        Ok(metadata) if metadata.permissions().readonly() => Err(format!(
+            "can't write {}: {} is read-only",
+            path.display(),
+            existing.display()
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("can't write {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A model directory with every model file in it, so only the settings under
+    /// test are reported
+    fn model_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "esponquen-validate-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in MODEL_FILES {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn config_with_model(name: &str) -> Config {
+        let mut config = Config::default();
+        config.model.dir = Some(model_dir(name).display().to_string());
+        config
+    }
+
+    fn severity_of(problems: &[Problem], setting: &str) -> Option<Severity> {
+        problems
+            .iter()
+            .find(|problem| problem.setting == setting)
+            .map(|problem| problem.severity)
+    }
+
+    #[test]
+    fn a_valid_config_has_no_problems() {
+        assert_eq!(validate(&config_with_model("valid")), Vec::new());
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once_with_its_severity() {
+        let dir = model_dir("many");
+        // A file where a directory is needed
+        let not_a_dir = dir.join("encoder.int8.onnx");
+
+        let mut config = config_with_model("many");
+        config.model.providers = Some(vec!["dmI".to_string()]);
+        config.hotkey.key = Some("F99".to_string());
+        config.audio.sample_rate = Some(1_000_000);
+        config.audio.buffer_size = Some(1_000_000);
+        config.output.file = Some(not_a_dir.join("notes.txt").display().to_string());
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert_eq!(
+            severity_of(&problems, "model.providers"),
+            Some(Severity::Fatal)
+        );
+        assert_eq!(severity_of(&problems, "hotkey.key"), Some(Severity::Fatal));
+        assert_eq!(
+            severity_of(&problems, "audio.sample_rate"),
+            Some(Severity::Fatal)
+        );
+        assert_eq!(
+            severity_of(&problems, "audio.buffer_size"),
+            Some(Severity::Warning)
+        );
+        // Only a warning while transcriptions aren't written there
+        assert_eq!(
+            severity_of(&problems, "output.file"),
+            Some(Severity::Warning)
+        );
+        assert!(has_fatal(&problems));
+    }
+
+    #[test]
+    fn warnings_alone_dont_stop_the_app() {
+        let mut config = config_with_model("warnings");
+        config.audio.buffer_size = Some(1_000_000);
+        config.model.cpu_threads = Some(MAX_THREADS + 1);
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(
+            problems
+                .iter()
+                .all(|problem| problem.severity == Severity::Warning)
+        );
+        assert!(!has_fatal(&problems));
+    }
+
+    #[test]
+    fn an_uncreatable_output_file_is_fatal_once_transcriptions_go_there() {
+        let dir = model_dir("file");
+        let mut config = config_with_model("file");
+        config.output.mode = Some(OutputTarget::File);
+        config.output.file = Some(
+            dir.join("tokens.txt")
+                .join("notes.txt")
+                .display()
+                .to_string(),
+        );
+
+        let problems = validate(&config);
+        assert_eq!(severity_of(&problems, "output.file"), Some(Severity::Fatal));
+        assert!(
+            problems[0].message.contains("is not a directory"),
+            "{}",
+            problems[0]
+        );
+    }
+
+    #[test]
+    fn a_config_file_that_doesnt_parse_is_a_fatal_problem() {
+        let path = model_dir("unparsed").join("config.toml");
+        std::fs::write(&path, "[model\nproviders = [\"cpu\"]\n").unwrap();
+
+        let problem = Problem::unreadable(crate::config::load(&path).unwrap_err());
+        assert_eq!(problem.severity, Severity::Fatal);
+        assert!(
+            problem.to_string().starts_with("config file: "),
+            "{}",
+            problem
+        );
+    }
+
+    #[test]
+    fn a_hotkey_bound_twice_names_the_other_setting() {
+        let mut config = config_with_model("twice");
+        config.hotkey.key = Some("F9".to_string());
+        config.hotkey.quit = Some("F9".to_string());
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].setting, "hotkey.quit");
+        assert!(
+            problems[0].message.contains("hotkey.key"),
+            "{}",
+            problems[0]
+        );
+    }
+}
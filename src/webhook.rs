@@ -0,0 +1,133 @@
+// Posting each transcription to a URL, e.g. to file it in a note app.
+//
+// The body is a JSON object: `text`, `timestamp` (Unix seconds), `duration_secs`
+// (length of the recording) and `provider` (what the model ran on).
+//
+// `send` only hands the transcription to a channel: a dedicated thread makes the
+// requests, one at a time and each with a timeout, so a slow or unreachable
+// endpoint never holds up typing. Failures are logged and otherwise ignored.
+
+use crate::sync::lock;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds a request may take when the config doesn't say
+pub const DEFAULT_TIMEOUT_SECS: u64 = 5;
+
+// Channel to the sender thread, started by the first transcription
+static SENDER: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+
+/// Whether the webhook comes on top of the usual output or replaces it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookMode {
+    /// Type (or write to the output file) as usual, and post too
+    #[default]
+    Also,
+    /// Only post, nothing is typed
+    Instead,
+}
+
+/// Where transcriptions are posted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub mode: WebhookMode,
+    pub timeout: Duration,
+}
+
+/// What's posted for a transcription
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Payload {
+    pub text: String,
+    /// When the transcription finished, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Length of the recording
+    pub duration_secs: f32,
+    pub provider: String,
+}
+
+impl Payload {
+    /// A transcription that finished just now
+    pub fn new(text: &str, duration: Duration, provider: &str) -> Self {
+        Payload {
+            text: text.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            duration_secs: duration.as_secs_f32(),
+            provider: provider.to_string(),
+        }
+    }
+}
+
+struct Request {
+    webhook: Webhook,
+    payload: Payload,
+}
+
+/// Post `payload` to `webhook` in the background
+pub fn send(webhook: &Webhook, payload: Payload) {
+    let mut sender = lock(&SENDER);
+    let request = Request {
+        webhook: webhook.clone(),
+        payload,
+    };
+    if sender
+        .get_or_insert_with(start_sender)
+        .send(request)
+        .is_err()
+    {
+        // The sender thread is gone, the next transcription starts a new one
+        *sender = None;
+    }
+}
+
+fn start_sender() -> Sender<Request> {
+    let (sender, receiver) = channel();
+    let spawned = thread::Builder::new()
+        .name("webhook".to_string())
+        .spawn(move || post_requests(receiver));
+    if let Err(e) = spawned {
+        warn!("Couldn't start the webhook thread: {}", e);
+    }
+    sender
+}
+
+/// Post every request received, until the app exits
+fn post_requests(requests: Receiver<Request>) {
+    for Request { webhook, payload } in requests {
+        match post(&webhook, &payload) {
+            Ok(()) => debug!("Transcription posted to {}", webhook.url),
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+fn post(webhook: &Webhook, payload: &Payload) -> Result<(), String> {
+    let body = serde_json::to_string(payload).map_err(|e| e.to_string())?;
+    ureq::post(&webhook.url)
+        .timeout(webhook.timeout)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map(|_| ())
+        .map_err(|e| format!("Webhook post to {} failed: {}", webhook.url, e))
+}
+
+/// Check that `url` is something the webhook can post to
+pub fn check_url(url: &str) -> Result<(), String> {
+    let url = url.trim();
+    let host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| format!("'{}' isn't an http:// or https:// URL", url))?;
+    if host.is_empty() || host.starts_with('/') {
+        return Err(format!("'{}' has no host", url));
+    }
+    Ok(())
+}
@@ -0,0 +1,416 @@
+// First-run setup: model download, microphone selection with a level meter,
+// a test dictation, permission checks, and writing the initial config file.
+// Interactive on the console with --console; otherwise a setup window (see
+// setup_window.rs) picks the model folder and hotkey, and dialogs do the rest.
+
+use crate::setup_window;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use esponquen::config::{self, Config, DEFAULT_MODEL_DIR};
+use esponquen::logging::TRANSCRIPT;
+use esponquen::model::{download_model, missing_model_files};
+use esponquen::notification::notify;
+use esponquen::permissions::check_input_permissions;
+use esponquen::settings;
+use esponquen::sync::lock;
+use esponquen::transcriber::Transcriber;
+use esponquen::{EngineBuilder, HOTKEY_OPTIONS, diag, diagln, parse_hotkey};
+use log::{error, info};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long each device's level meter runs
+const METER_DURATION: Duration = Duration::from_secs(2);
+
+// Length of the test dictation
+const TEST_DICTATION_DURATION: Duration = Duration::from_secs(3);
+
+/// Run the setup wizard and write the resulting config to `config_path`
+pub fn run(interactive: bool, config_path: &Path) -> Config {
+    let mut config = Config::default();
+    config.model.dir = Some(DEFAULT_MODEL_DIR.to_string());
+
+    if interactive {
+        run_console(&mut config);
+    } else {
+        run_dialogs(&mut config);
+    }
+
+    match config::save(&config, config_path) {
+        Ok(()) => {
+            diagln!("✓ Config written to {}\n", config_path.display());
+            if !interactive {
+                notify(
+                    "Esponquen is set up",
+                    &format!(
+                        "Settings saved to {}. Press {} to start dictating.",
+                        config_path.display(),
+                        config.hotkey.key.as_deref().unwrap_or("F6")
+                    ),
+                );
+            }
+        }
+        Err(e) => error!("{}", e),
+    }
+
+    config
+}
+
+fn run_console(config: &mut Config) {
+    diagln!("Welcome to Esponquen! Let's get you set up.");
+    diagln!("===========================================\n");
+
+    // Step 1: model files
+    diagln!("[1/5] Model files");
+    let model_dir = config.model_dir();
+    let missing = missing_model_files(&model_dir);
+    if missing.is_empty() {
+        diagln!("✓ Model files found in {}\n", model_dir.display());
+    } else {
+        diagln!(
+            "Missing from {}: {}",
+            model_dir.display(),
+            missing.join(", ")
+        );
+        if ask_yes_no(
+            "Download the recommended Parakeet model now (~640 MB)?",
+            true,
+        ) {
+            download_with_progress(&model_dir);
+        } else {
+            diagln!(
+                "Place the model files in {} before dictating (see model/README.md).\n",
+                model_dir.display()
+            );
+        }
+    }
+
+    // Step 2: microphone
+    diagln!("[2/5] Microphone");
+    let host = cpal::default_host();
+    let default_name = host
+        .default_input_device()
+        .and_then(|device| device.name().ok());
+    let devices: Vec<cpal::Device> = host
+        .input_devices()
+        .map(|devices| devices.collect())
+        .unwrap_or_default();
+
+    if devices.is_empty() {
+        diagln!("✗ No input devices found, plug in a microphone and restart\n");
+    } else {
+        diagln!("Speak while each device is tested to see which one picks you up:\n");
+        for (index, device) in devices.iter().enumerate() {
+            let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+            let marker = if Some(&name) == default_name.as_ref() {
+                " (default)"
+            } else {
+                ""
+            };
+            diagln!("  [{}] {}{}", index + 1, name, marker);
+            match measure_level(device, METER_DURATION, print_meter) {
+                Ok(peak) => diagln!("\r      peak {}", meter_bar(peak)),
+                Err(e) => diagln!("\r      ✗ {}", e),
+            }
+        }
+
+        let answer = ask("Device number (Enter for the default)");
+        if let Some(device) = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| devices.get(number.wrapping_sub(1)))
+        {
+            config.audio.device = device.name().ok();
+        }
+        diagln!(
+            "✓ Using {}\n",
+            config
+                .audio
+                .device
+                .as_deref()
+                .unwrap_or("the default input device")
+        );
+    }
+
+    // Step 3: test dictation
+    diagln!("[3/5] Test dictation");
+    if !missing_model_files(&model_dir).is_empty() {
+        diagln!("Skipped, the model files are not available yet\n");
+    } else if ask_yes_no("Run a 3-second test dictation?", true) {
+        test_dictation(config);
+    } else {
+        diagln!();
+    }
+
+    // Step 4: permissions
+    diagln!("[4/5] Permissions");
+    let issues = check_input_permissions();
+    if issues.is_empty() {
+        diagln!("✓ No permission problems detected\n");
+    } else {
+        for issue in issues {
+            diagln!("⚠ {}", issue.summary);
+            diagln!("  → {}", issue.remedy);
+        }
+        diagln!();
+    }
+
+    // Step 5: hotkey
+    diagln!("[5/5] Hotkey");
+    let names: Vec<&str> = HOTKEY_OPTIONS.iter().map(|(name, _)| *name).collect();
+    loop {
+        let answer = ask(&format!(
+            "Hotkey to start/stop recording ({}, Enter for F6)",
+            names.join("/")
+        ));
+        if answer.is_empty() {
+            break;
+        }
+        if parse_hotkey(&answer).is_some() {
+            config.hotkey.key = Some(answer.to_uppercase());
+            break;
+        }
+        diagln!("✗ Unknown hotkey: {}", answer);
+    }
+    diagln!();
+}
+
+fn run_dialogs(config: &mut Config) {
+    run_setup_window(config);
+
+    let model_dir = config.model_dir();
+    let missing = missing_model_files(&model_dir);
+
+    if !missing.is_empty() {
+        let download = rfd::MessageDialog::new()
+            .set_title("Esponquen setup")
+            .set_description(format!(
+                "The speech recognition model is missing from {}.\n\nDownload the recommended Parakeet model now (~640 MB)?",
+                model_dir.display()
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+
+        if download == rfd::MessageDialogResult::Yes {
+            notify(
+                "Downloading model",
+                "Esponquen will start when the download finishes.",
+            );
+            match download_model(&model_dir, |_| {}) {
+                Ok(()) => notify("Model downloaded", "The speech model is ready."),
+                Err(e) => notify("Model download failed", &e),
+            }
+        }
+    }
+
+    let issues = check_input_permissions();
+    if !issues.is_empty() {
+        let description = issues
+            .iter()
+            .map(|issue| format!("• {}\n  {}", issue.summary, issue.remedy))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title("Esponquen needs permissions")
+            .set_description(format!(
+                "The hotkey or typing may not work until these are fixed:\n\n{}",
+                description
+            ))
+            .set_buttons(rfd::MessageButtons::Ok)
+            .show();
+    }
+}
+
+/// Let the setup window pick the model directory and hotkey, keeping the defaults without it
+fn run_setup_window(config: &mut Config) {
+    let Some(mut child) = crate::spawn_window_process("--setup-window") else {
+        return;
+    };
+
+    // Closing stdin marks the end of the defaults
+    if let Some(mut stdin) = child.stdin.take() {
+        let model_dir = config.model_dir();
+        let hotkey = config.hotkey.key.as_deref().unwrap_or("F6");
+        writeln!(stdin, "{}={}", setup_window::MODEL_DIR, model_dir.display()).ok();
+        writeln!(stdin, "{}={}", settings::HOTKEY, hotkey).ok();
+    }
+
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            match settings::split_line(&line) {
+                // Only a different folder is written, so the default stays relative
+                Some((setup_window::MODEL_DIR, dir)) if Path::new(dir) != config.model_dir() => {
+                    config.model.dir = Some(dir.to_string());
+                }
+                Some((settings::HOTKEY, key)) if parse_hotkey(key).is_some() => {
+                    config.hotkey.key = Some(key.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+    child.wait().ok();
+}
+
+fn download_with_progress(model_dir: &Path) {
+    let mut last_reported = 0;
+    let result = download_model(model_dir, |bytes| {
+        let megabytes = bytes / 1_000_000;
+        if megabytes >= last_reported + 5 {
+            last_reported = megabytes;
+            diag!("\r  Downloaded {} MB", megabytes);
+            std::io::stdout().flush().ok();
+        }
+    });
+
+    match result {
+        Ok(()) => diagln!("\n✓ Model downloaded to {}\n", model_dir.display()),
+        Err(e) => diagln!("\n✗ {}\n", e),
+    }
+}
+
+fn test_dictation(config: &Config) {
+    let host = cpal::default_host();
+    let Some(device) =
+        esponquen::recorder::select_input_device(&host, config.audio.device.as_deref())
+    else {
+        diagln!("✗ No input device available\n");
+        return;
+    };
+
+    diagln!("🔴 Speak now...");
+    let (samples, sample_rate) = match record(&device, TEST_DICTATION_DURATION) {
+        Ok(recording) => recording,
+        Err(e) => {
+            diagln!("✗ {}\n", e);
+            return;
+        }
+    };
+
+    diagln!("Transcribing...");
+    let engine = match EngineBuilder::from_config(config).build() {
+        Ok(engine) => engine,
+        Err(e) => {
+            diagln!("✗ {}\n", e);
+            return;
+        }
+    };
+
+    match Transcriber::new(&engine, None) {
+        Ok(mut transcriber) => {
+            let text = transcriber.transcribe_samples(&samples, sample_rate);
+            if text.trim().is_empty() {
+                diagln!("✗ No speech recognized, check the microphone choice\n");
+            } else {
+                info!(target: TRANSCRIPT, "✓ You said: {}\n", text.trim());
+            }
+        }
+        Err(e) => diagln!("✗ Failed to load the model: {}\n", e),
+    }
+}
+
+/// Watch the input level of a device, reporting the peak of each 100 ms window
+pub fn measure_level(
+    device: &cpal::Device,
+    duration: Duration,
+    mut on_level: impl FnMut(f32),
+) -> Result<f32, String> {
+    let window_peak = Arc::new(Mutex::new(0.0f32));
+    let callback_peak = Arc::clone(&window_peak);
+
+    let stream = build_f32_stream(device, move |data, _| {
+        let peak = data
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let mut window_peak = lock(&callback_peak);
+        *window_peak = window_peak.max(peak);
+    })?;
+    stream.play().map_err(|e| e.to_string())?;
+
+    let started = Instant::now();
+    let mut overall_peak = 0.0f32;
+    while started.elapsed() < duration {
+        thread::sleep(Duration::from_millis(100));
+        let peak = std::mem::take(&mut *lock(&window_peak));
+        overall_peak = overall_peak.max(peak);
+        on_level(peak);
+    }
+
+    Ok(overall_peak)
+}
+
+/// Record mono audio from a device for a fixed duration
+fn record(device: &cpal::Device, duration: Duration) -> Result<(Vec<f32>, u32), String> {
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let callback_samples = Arc::clone(&samples);
+
+    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+
+    let stream = build_f32_stream(device, move |data, _| {
+        // Downmix interleaved frames to mono
+        let mut samples = lock(&callback_samples);
+        samples.extend(
+            data.chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+        );
+    })?;
+    stream.play().map_err(|e| e.to_string())?;
+    thread::sleep(duration);
+    drop(stream);
+
+    let samples = std::mem::take(&mut *lock(&samples));
+    Ok((samples, sample_rate))
+}
+
+fn build_f32_stream(
+    device: &cpal::Device,
+    callback: impl FnMut(&[f32], &cpal::InputCallbackInfo) + Send + 'static,
+) -> Result<cpal::Stream, String> {
+    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    if config.sample_format() != cpal::SampleFormat::F32 {
+        return Err(format!(
+            "Unsupported sample format {:?}",
+            config.sample_format()
+        ));
+    }
+
+    device
+        .build_input_stream(&config.config(), callback, |_| {}, None)
+        .map_err(|e| e.to_string())
+}
+
+fn meter_bar(level: f32) -> String {
+    let filled = ((level.clamp(0.0, 1.0) * 30.0).round()) as usize;
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(30 - filled))
+}
+
+fn print_meter(level: f32) {
+    diag!("\r      {}", meter_bar(level));
+    std::io::stdout().flush().ok();
+}
+
+fn ask(question: &str) -> String {
+    diag!("{}: ", question);
+    std::io::stdout().flush().ok();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok();
+    answer.trim().to_string()
+}
+
+fn ask_yes_no(question: &str, default: bool) -> bool {
+    let hint = if default { "Y/n" } else { "y/N" };
+    match ask(&format!("{} [{}]", question, hint))
+        .to_lowercase()
+        .as_str()
+    {
+        "" => default,
+        answer => answer.starts_with('y'),
+    }
+}
@@ -0,0 +1,109 @@
+// Checks the capture path's hot spots never allocate, with an allocator that
+// counts per thread. It replaces the allocator of the whole test binary, so
+// these tests have a file of their own.
+
+use esponquen::audio::mono_samples;
+use esponquen::capture::{BufferPool, SampleRing};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS
+            .try_with(|count| count.set(count.get() + 1))
+            .ok();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations made on this thread so far
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn callback_never_allocates_or_waits_on_the_consumer() {
+    // 10 ms of stereo 48 kHz per callback, like a typical driver buffer
+    const CALLBACKS: usize = 100;
+    let data = vec![0.25f32; 960];
+    let ring = Arc::new(SampleRing::new(48_000 * 2));
+    let recording = Arc::new(Mutex::new(Vec::new()));
+
+    // The consumer holds the recording lock for a long stretch, as a slow
+    // transcription or a stuck thread would
+    let consumer = {
+        let ring = Arc::clone(&ring);
+        let recording = Arc::clone(&recording);
+        thread::spawn(move || {
+            for _ in 0..5 {
+                let mut recording = recording.lock().unwrap();
+                thread::sleep(Duration::from_millis(50));
+                ring.drain_into(&mut recording);
+            }
+        })
+    };
+
+    let before = allocations();
+    let mut slowest = Duration::ZERO;
+    for _ in 0..CALLBACKS {
+        let started = Instant::now();
+        ring.push(mono_samples(&data, 2, None));
+        slowest = slowest.max(started.elapsed());
+        thread::sleep(Duration::from_millis(2));
+    }
+    let allocated = allocations() - before;
+    consumer.join().unwrap();
+
+    assert_eq!(allocated, 0, "the callback allocated");
+    assert!(
+        slowest < Duration::from_millis(10),
+        "a callback took {:?}",
+        slowest
+    );
+
+    let mut recording = recording.lock().unwrap();
+    ring.drain_into(&mut recording);
+    assert_eq!(recording.len() + ring.take_dropped(), CALLBACKS * 480);
+    assert!(recording.iter().all(|sample| *sample == 0.25));
+}
+
+#[test]
+fn recordings_after_the_first_dont_allocate() {
+    const CAPACITY: usize = 48_000 * 5;
+    let pool = BufferPool::new(2);
+    let ring = SampleRing::new(4096);
+    let data = vec![0.1f32; 2048];
+
+    // One full recording, drained from the ring as the collector does
+    let record = |pool: &BufferPool| {
+        let mut buffer = pool.checkout(CAPACITY);
+        while buffer.len() + data.len() <= CAPACITY {
+            ring.push(data.iter().copied());
+            ring.drain_into(&mut buffer);
+        }
+        pool.give_back(buffer);
+    };
+
+    record(&pool);
+    let before = allocations();
+    for _ in 0..5 {
+        record(&pool);
+    }
+    assert_eq!(allocations() - before, 0);
+}
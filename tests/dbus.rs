@@ -0,0 +1,116 @@
+// Talks to the D-Bus service the way a shell extension would, on a private bus
+// started for the test so nothing on the desktop's session bus is touched.
+// Ignored by default since it needs dbus-daemon; run with
+// `cargo test --test dbus -- --ignored`.
+
+#![cfg(target_os = "linux")]
+
+use esponquen::dbus::{self, BUS_NAME, Method, OBJECT_PATH};
+use esponquen::sync::lock;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use zbus::blocking::{Proxy, connection};
+
+/// A bus daemon of the test's own, stopped when dropped
+struct PrivateBus {
+    daemon: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    fn start() -> Option<PrivateBus> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(PrivateBus {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        self.daemon.kill().ok();
+        self.daemon.wait().ok();
+    }
+}
+
+/// Wait up to 5 seconds for `done`
+fn wait_for(mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if done() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn methods_properties_and_signals_reach_a_client() {
+    let bus = PrivateBus::start().expect("Failed to start dbus-daemon, is it installed?");
+
+    let called = Arc::new(Mutex::new(Vec::new()));
+    let handler_calls = Arc::clone(&called);
+    let service = dbus::serve_at(&bus.address, move |method| {
+        lock(&handler_calls).push(method.clone());
+        match method {
+            Method::GetStatus => Ok(r#"{"status":"ready"}"#.to_string()),
+            Method::SetHotkey(key) if key != "F9" => Err(format!("Unknown hotkey '{}'", key)),
+            _ => Ok(String::new()),
+        }
+    })
+    .unwrap();
+
+    let client = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .build()
+        .unwrap();
+    let proxy = Proxy::new(&client, BUS_NAME, OBJECT_PATH, BUS_NAME).unwrap();
+
+    // Methods go to the handler, and its refusals come back as D-Bus errors
+    proxy.call::<_, _, ()>("Toggle", &()).unwrap();
+    proxy.call::<_, _, ()>("SetHotkey", &("F9",)).unwrap();
+    let refused = proxy.call::<_, _, ()>("SetHotkey", &("F99",)).unwrap_err();
+    assert!(
+        refused.to_string().contains("Unknown hotkey 'F99'"),
+        "{}",
+        refused
+    );
+    let status: String = proxy.call("GetStatus", &()).unwrap();
+    assert_eq!(status, r#"{"status":"ready"}"#);
+    assert_eq!(
+        *lock(&called),
+        vec![
+            Method::Toggle,
+            Method::SetHotkey("F9".to_string()),
+            Method::SetHotkey("F99".to_string()),
+            Method::GetStatus,
+        ]
+    );
+
+    // The Status property follows the app
+    service.set_status("recording");
+    assert!(wait_for(|| {
+        proxy.get_property::<String>("Status").ok().as_deref() == Some("recording")
+    }));
+
+    // Subscribed before it's emitted, so it can't be missed
+    let mut completed = proxy.receive_signal("TranscriptionCompleted").unwrap();
+    service.transcription_completed("hello world");
+    let message = completed.next().unwrap();
+    let text: String = message.body().deserialize().unwrap();
+    assert_eq!(text, "hello world");
+}
@@ -0,0 +1,315 @@
+// Runs the dictation engine the way an embedder would, with no tray, hotkey or
+// microphone: synthetic audio is played into the context and a mock
+// transcriber returns canned text.
+
+use esponquen::AppContext;
+use esponquen::context::AppStatus;
+use esponquen::dictation::{Dictation, DictationEngine};
+use esponquen::output::Output;
+use esponquen::sync::lock;
+use esponquen::testing::{MockTranscriber, SyntheticRecorder};
+use esponquen::text::Wrap;
+use esponquen::transcriber::SharedRecognizer;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Output keeping every text it's given
+struct Collected(Arc<Mutex<Vec<String>>>);
+
+impl Output for Collected {
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        lock(&self.0).push(text.to_string());
+        Ok(())
+    }
+}
+
+struct Harness {
+    engine: DictationEngine,
+    mic: SyntheticRecorder,
+    heard: Arc<Mutex<Vec<Vec<f32>>>>,
+    written: Arc<Mutex<Vec<String>>>,
+    recognizer: SharedRecognizer,
+}
+
+/// An engine over a fresh context with a 16 kHz synthetic input
+fn harness(transcriber: MockTranscriber) -> Harness {
+    let ctx = Arc::new(AppContext::new());
+    // Synthetic audio has no hotkey click to skip
+    ctx.update_settings(|settings| settings.start_cooldown_ms = 0);
+    let mic = SyntheticRecorder::open(&ctx, 16_000);
+    let heard = transcriber.heard();
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let recognizer = transcriber.shared();
+    let engine = DictationEngine::new(
+        ctx,
+        recognizer.clone(),
+        Box::new(Collected(Arc::clone(&written))),
+    );
+    Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        recognizer,
+    }
+}
+
+/// A second of a 440 Hz tone
+fn tone(sample_rate: u32) -> Vec<f32> {
+    (0..sample_rate)
+        .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin() * 0.5)
+        .collect()
+}
+
+#[test]
+fn dictation_goes_from_the_ring_to_the_output() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        ..
+    } = harness(MockTranscriber::new("hello world"));
+    let results: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&results);
+    let engine = engine.on_result(move |dictation: &Dictation| {
+        lock(&seen).push(dictation.text.clone());
+    });
+
+    engine.start().unwrap();
+    assert!(engine.context().is_recording());
+    let audio = tone(16_000);
+    assert_eq!(mic.play(&audio), audio.len());
+
+    let dictation = engine.stop().unwrap().expect("text was recognized");
+    assert_eq!(dictation.text, "hello world");
+    assert_eq!(dictation.timings.audio.as_secs_f32(), 1.0);
+    assert_eq!(*lock(&heard), [audio]);
+    assert_eq!(*lock(&written), ["hello world"]);
+    assert_eq!(*lock(&results), ["hello world"]);
+    assert!(!engine.context().is_recording());
+}
+
+#[test]
+fn audio_is_resampled_for_the_model() {
+    let Harness {
+        engine, mic, heard, ..
+    } = harness(MockTranscriber::new("resampled"));
+    engine.context().set_sample_rate(48_000);
+
+    engine.start().unwrap();
+    mic.play(&tone(48_000));
+    engine.stop().unwrap();
+
+    assert_eq!(lock(&heard)[0].len(), 16_000);
+}
+
+#[test]
+fn each_context_transcribes_with_its_own_settings() {
+    let plain = harness(MockTranscriber::new("hello"));
+    let wrapped = harness(MockTranscriber::new("hello"));
+    wrapped
+        .engine
+        .context()
+        .update_settings(|settings| settings.wrap = Wrap::new("// ", ""));
+
+    for Harness { engine, mic, .. } in [&plain, &wrapped] {
+        engine.start().unwrap();
+        mic.play(&tone(16_000));
+        engine.stop().unwrap();
+    }
+
+    assert_eq!(*lock(&plain.written), ["hello"]);
+    assert_eq!(*lock(&wrapped.written), ["// hello"]);
+}
+
+#[test]
+fn nothing_recognized_writes_nothing() {
+    let Harness {
+        engine,
+        mic,
+        written,
+        ..
+    } = harness(MockTranscriber::new("  "));
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+
+    assert!(engine.stop().unwrap().is_none());
+    assert!(lock(&written).is_empty());
+}
+
+#[test]
+fn start_and_stop_are_refused_out_of_turn() {
+    let Harness {
+        engine, mic, heard, ..
+    } = harness(MockTranscriber::new("unused"));
+
+    assert_eq!(engine.stop().unwrap_err(), "Not recording");
+    engine.start().unwrap();
+    assert!(engine.start().is_err());
+    assert_eq!(engine.stop().unwrap_err(), "No audio recorded");
+    assert!(lock(&heard).is_empty());
+
+    drop(mic);
+    assert_eq!(engine.start().unwrap_err(), "No microphone");
+}
+
+#[test]
+fn toggling_on_and_off_writes_once() {
+    let Harness {
+        engine,
+        mic,
+        written,
+        ..
+    } = harness(MockTranscriber::new("once"));
+
+    assert!(engine.toggle().unwrap().is_none());
+    mic.play(&tone(16_000));
+    let dictation = engine.toggle().unwrap().expect("text was recognized");
+    assert_eq!(dictation.text, "once");
+    assert_eq!(engine.context().status(), AppStatus::WaitingForHotkey);
+
+    // The next toggle starts another recording rather than writing again
+    assert!(engine.toggle().unwrap().is_none());
+    assert!(engine.context().is_recording());
+    assert_eq!(*lock(&written), ["once"]);
+}
+
+#[test]
+fn a_cancelled_recording_is_discarded() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        ..
+    } = harness(MockTranscriber::new("kept"));
+
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    engine.cancel().unwrap();
+    assert_eq!(engine.context().status(), AppStatus::WaitingForHotkey);
+    assert_eq!(mic.play(&tone(16_000)), 0);
+    assert_eq!(engine.stop().unwrap_err(), "Not recording");
+    assert_eq!(engine.cancel().unwrap_err(), "Not recording");
+    assert!(lock(&heard).is_empty());
+
+    // None of the cancelled audio ends up in the next recording
+    engine.start().unwrap();
+    mic.play(&tone(16_000)[..8_000]);
+    engine.stop().unwrap();
+    assert_eq!(lock(&heard)[0].len(), 8_000);
+    assert_eq!(*lock(&written), ["kept"]);
+}
+
+#[test]
+fn queued_dictations_come_out_in_order() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        ..
+    } = harness(
+        MockTranscriber::in_turn(["first", "second"]).with_delay(Duration::from_millis(200)),
+    );
+    let engine = Arc::new(engine);
+
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    let first = {
+        let engine = Arc::clone(&engine);
+        thread::spawn(move || engine.stop())
+    };
+
+    // The second recording starts and stops while the first is transcribing
+    while lock(&heard).is_empty() {
+        thread::sleep(Duration::from_millis(5));
+    }
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    let second = engine.stop().unwrap().expect("text was recognized");
+
+    assert_eq!(first.join().unwrap().unwrap().unwrap().text, "first");
+    assert_eq!(second.text, "second");
+    assert_eq!(*lock(&written), ["first", "second"]);
+    assert_eq!(engine.context().status(), AppStatus::WaitingForHotkey);
+}
+
+#[test]
+fn empty_recordings_are_skipped() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        ..
+    } = harness(MockTranscriber::new("unused"));
+
+    // Audio from before the recording started isn't part of it
+    assert_eq!(mic.play(&tone(16_000)), 0);
+    engine.start().unwrap();
+    assert_eq!(engine.stop().unwrap_err(), "No audio recorded");
+
+    assert!(lock(&heard).is_empty());
+    assert!(lock(&written).is_empty());
+    assert_eq!(engine.context().status(), AppStatus::WaitingForHotkey);
+}
+
+#[test]
+fn a_failing_transcriber_is_an_error_not_a_crash() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        ..
+    } = harness(MockTranscriber::new("unused").failing());
+
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    assert_eq!(engine.stop().unwrap_err(), "Transcription failed");
+    assert_eq!(lock(&heard).len(), 1);
+    assert!(lock(&written).is_empty());
+
+    // Back to ready, so the next dictation can go ahead
+    assert_eq!(engine.context().status(), AppStatus::WaitingForHotkey);
+    engine.start().unwrap();
+    assert!(engine.context().is_recording());
+}
+
+#[test]
+fn a_reload_mid_transcription_takes_over_right_after() {
+    let Harness {
+        engine,
+        mic,
+        heard,
+        written,
+        recognizer,
+    } = harness(MockTranscriber::new("old model").with_delay(Duration::from_millis(300)));
+    let engine = Arc::new(engine);
+
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    let first = {
+        let engine = Arc::clone(&engine);
+        thread::spawn(move || engine.stop())
+    };
+    while lock(&heard).is_empty() {
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    // The reload doesn't wait for the transcription, it's queued behind it
+    assert!(recognizer.is_busy());
+    let reloading = Instant::now();
+    assert!(!recognizer.replace(Box::new(MockTranscriber::new("new model"))));
+    assert!(reloading.elapsed() < Duration::from_millis(100));
+
+    assert_eq!(first.join().unwrap().unwrap().unwrap().text, "old model");
+    assert!(!recognizer.is_busy());
+    engine.start().unwrap();
+    mic.play(&tone(16_000));
+    assert_eq!(engine.stop().unwrap().unwrap().text, "new model");
+    assert_eq!(*lock(&written), ["old model", "new model"]);
+}